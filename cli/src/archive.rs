@@ -0,0 +1,162 @@
+//! Copies of the database file kept in `backup.dir`, named after the
+//! database and the moment they were taken, like `finiate-20261018-093000.db`.
+//! The daily ones end in `-daily`, and only those are ever rotated away.
+
+use crate::config::Config;
+use jiff::{ToSpan, Zoned, civil::DateTime};
+use std::error::Error;
+use std::path::PathBuf;
+
+const STAMP: &str = "%Y%m%d-%H%M%S";
+const DAILY: &str = "-daily";
+
+/// Backs the open database up into a new file in `backup.dir`.
+pub async fn take(
+    pool: &storage::SqlitePool,
+    config: &Config,
+    options: &storage::DbOptions,
+) -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+    backup(pool, config, options, "").await
+}
+
+async fn backup(
+    pool: &storage::SqlitePool,
+    config: &Config,
+    options: &storage::DbOptions,
+    suffix: &str,
+) -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+    let path = next_path(config, suffix)?;
+    storage::backup(pool, &path, options).await?;
+    Ok(path)
+}
+
+/// Takes the day's backup unless one was taken already, then deletes the
+/// oldest daily ones beyond `backup.keep`. Returns the new backup, if any.
+pub async fn daily(
+    pool: &storage::SqlitePool,
+    config: &Config,
+    options: &storage::DbOptions,
+) -> Result<Option<PathBuf>, Box<dyn Error + Send + Sync>> {
+    if config.backup.keep == 0 {
+        return Ok(None);
+    }
+    let today = Zoned::now().with_time_zone(config.time_zone.clone()).date();
+    if list(config, DAILY)?
+        .iter()
+        .any(|(taken, _)| taken.date() == today)
+    {
+        return Ok(None);
+    }
+    let path = backup(pool, config, options, DAILY).await?;
+    let backups = list(config, DAILY)?;
+    let excess = backups.len().saturating_sub(config.backup.keep);
+    for (_, old) in &backups[..excess] {
+        std::fs::remove_file(old)?;
+    }
+    Ok(Some(path))
+}
+
+/// Copies the database file aside as it is, for when it is about to be
+/// replaced and may not even open.
+pub fn save_file(config: &Config) -> std::io::Result<PathBuf> {
+    let path = next_path(config, "")?;
+    std::fs::copy(&config.database, &path)?;
+    Ok(path)
+}
+
+fn next_path(config: &Config, suffix: &str) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(&config.backup.dir)?;
+    let mut at = Zoned::now().with_time_zone(config.time_zone.clone());
+    loop {
+        let path = config.backup.dir.join(format!(
+            "{}-{}{suffix}.db",
+            stem(config),
+            at.strftime(STAMP)
+        ));
+        // Two backups within a second must not overwrite each other.
+        if !path.exists() {
+            return Ok(path);
+        }
+        at = at.saturating_add(1.second());
+    }
+}
+
+/// The database's backups in `backup.dir` whose names end in `suffix`,
+/// oldest first. Files not named like one are never touched.
+fn list(config: &Config, suffix: &str) -> std::io::Result<Vec<(DateTime, PathBuf)>> {
+    let entries = match std::fs::read_dir(&config.backup.dir) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+    let prefix = format!("{}-", stem(config));
+    let suffix = format!("{suffix}.db");
+    let mut backups = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if let Some(stamp) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix(&prefix))
+            .and_then(|rest| rest.strip_suffix(&suffix))
+            && let Ok(taken) = DateTime::strptime(STAMP, stamp)
+        {
+            backups.push((taken, path));
+        }
+    }
+    backups.sort();
+    Ok(backups)
+}
+
+fn stem(config: &Config) -> String {
+    config
+        .database
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn daily_backup_runs_once_a_day_and_rotates() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(
+            &config_path,
+            "database = \"finiate.db\"\n[backup]\nkeep = 2\n",
+        )
+        .unwrap();
+        let config = Config::load(Some(&config_path), None).expect("config");
+        let backups = dir.path().join("finiate-backups");
+        std::fs::create_dir_all(&backups).unwrap();
+        for name in [
+            "finiate-20250101-000000-daily.db",
+            "finiate-20250102-000000-daily.db",
+            "finiate-20241231-000000.db",
+            "notes.txt",
+        ] {
+            std::fs::write(backups.join(name), "").unwrap();
+        }
+
+        let pool = storage::init_db(&config.database).await.expect("init db");
+        let options = storage::DbOptions::default();
+        let taken = daily(&pool, &config, &options)
+            .await
+            .unwrap()
+            .expect("first command of the day");
+        assert!(taken.starts_with(&backups));
+        assert!(taken.to_string_lossy().ends_with("-daily.db"));
+        assert!(!backups.join("finiate-20250101-000000-daily.db").exists());
+        assert!(backups.join("finiate-20250102-000000-daily.db").exists());
+        // Backups taken by hand are never rotated away.
+        assert!(backups.join("finiate-20241231-000000.db").exists());
+        assert!(backups.join("notes.txt").exists());
+
+        assert_eq!(daily(&pool, &config, &options).await.unwrap(), None);
+        assert_eq!(list(&config, DAILY).unwrap().len(), 2);
+    }
+}
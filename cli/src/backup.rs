@@ -0,0 +1,329 @@
+//! Versioned JSON backups of every agenda and log.
+//!
+//! Records keep their ids and timestamps, so a backup restored on another
+//! machine is indistinguishable from the original database.
+
+use clap::ValueEnum;
+use domain::*;
+use jiff::Timestamp;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use storage::{AgendaStore, LogStore};
+use uuid::Uuid;
+
+/// Bumped whenever the document layout changes incompatibly.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BackupError {
+    #[error("not a finiate backup: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("backup has no schema version")]
+    MissingVersion,
+    #[error("backup schema version {found} is not supported (expected {SCHEMA_VERSION})")]
+    Version { found: u64 },
+    #[error("log {log} refers to agenda {agenda}, which is not in the backup")]
+    DanglingLog { log: Uuid, agenda: Uuid },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum RestoreMode {
+    /// Add records whose ids are new, keep local ones on conflict
+    #[default]
+    Merge,
+    /// Delete every local agenda and log first
+    Replace,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Backup {
+    pub version: u32,
+    pub exported_at: Timestamp,
+    pub agendas: Vec<Agenda>,
+    pub logs: Vec<Log>,
+}
+
+/// A record present on both sides with different contents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", content = "id", rename_all = "snake_case")]
+pub enum Conflict {
+    Agenda(Uuid),
+    Log(Uuid),
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct RestoreSummary {
+    pub removed_agendas: usize,
+    pub removed_logs: usize,
+    pub added_agendas: usize,
+    pub added_logs: usize,
+    pub unchanged: usize,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl Backup {
+    pub async fn collect<A, L>(
+        agendas: &A,
+        logs: &L,
+    ) -> Result<Backup, Box<dyn Error + Send + Sync>>
+    where
+        A: AgendaRepo + Sync,
+        L: LogRepo + Sync,
+    {
+        let mut agendas = agendas.get_agendas_by_status(None).await?;
+        agendas.sort_by_key(|agenda| agenda.id);
+        let mut logs = logs
+            .get_logs_by_time_range(Timestamp::MIN, Timestamp::MAX)
+            .await?;
+        logs.sort_by_key(|log| log.id);
+        Ok(Backup {
+            version: SCHEMA_VERSION,
+            exported_at: Timestamp::now(),
+            agendas,
+            logs,
+        })
+    }
+
+    /// Parses a backup, checking the schema version before anything else so
+    /// documents from newer releases get a clear error.
+    pub fn parse(text: &str) -> Result<Backup, BackupError> {
+        let value: serde_json::Value = serde_json::from_str(text)?;
+        match value.get("version").and_then(|version| version.as_u64()) {
+            Some(version) if version == u64::from(SCHEMA_VERSION) => {}
+            Some(found) => return Err(BackupError::Version { found }),
+            None => return Err(BackupError::MissingVersion),
+        }
+        let backup: Backup = serde_json::from_value(value)?;
+
+        let agenda_ids: HashSet<Uuid> = backup.agendas.iter().map(|agenda| agenda.id).collect();
+        if let Some(log) = backup
+            .logs
+            .iter()
+            .find(|log| !agenda_ids.contains(&log.agenda_id))
+        {
+            return Err(BackupError::DanglingLog {
+                log: log.id,
+                agenda: log.agenda_id,
+            });
+        }
+        Ok(backup)
+    }
+
+    /// Restores the backup. Replacing swaps every local record for the
+    /// backup's and merging adds the new ones, either at once, so a failure
+    /// leaves the database untouched.
+    pub async fn restore(
+        &self,
+        agendas: &AgendaStore,
+        logs: &LogStore,
+        mode: RestoreMode,
+    ) -> Result<RestoreSummary, Box<dyn Error + Send + Sync>> {
+        if mode == RestoreMode::Replace {
+            let removed = agendas.replace_all(&self.agendas, &self.logs).await?;
+            return Ok(RestoreSummary {
+                removed_agendas: removed.agendas as usize,
+                removed_logs: removed.logs as usize,
+                added_agendas: self.agendas.len(),
+                added_logs: self.logs.len(),
+                ..RestoreSummary::default()
+            });
+        }
+
+        let mut summary = RestoreSummary::default();
+        let (mut new_agendas, mut new_logs) = (Vec::new(), Vec::new());
+        let local_agendas: HashMap<Uuid, Agenda> = agendas
+            .get_agendas_by_status(None)
+            .await?
+            .into_iter()
+            .map(|agenda| (agenda.id, agenda))
+            .collect();
+        for agenda in &self.agendas {
+            match local_agendas.get(&agenda.id) {
+                None => new_agendas.push(agenda.clone()),
+                Some(local) if local == agenda => summary.unchanged += 1,
+                Some(_) => summary.conflicts.push(Conflict::Agenda(agenda.id)),
+            }
+        }
+
+        let local_logs: HashMap<Uuid, Log> = logs
+            .get_logs_by_time_range(Timestamp::MIN, Timestamp::MAX)
+            .await?
+            .into_iter()
+            .map(|log| (log.id, log))
+            .collect();
+        for log in &self.logs {
+            match local_logs.get(&log.id) {
+                None => new_logs.push(log.clone()),
+                Some(local) if local == log => summary.unchanged += 1,
+                Some(_) => summary.conflicts.push(Conflict::Log(log.id)),
+            }
+        }
+        agendas.merge_all(&new_agendas, &new_logs).await?;
+        summary.added_agendas = new_agendas.len();
+        summary.added_logs = new_logs.len();
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn setup_repos() -> (tempfile::TempDir, AgendaStore, LogStore) {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let pool = storage::init_db(&dir.path().join("finiate.db"))
+            .await
+            .expect("init db");
+        let (agenda_repo, log_repo) =
+            storage::open_repos(&pool, storage::Backend::Relational, dir.path())
+                .expect("open repos");
+        (dir, agenda_repo, log_repo)
+    }
+
+    async fn seed(agendas: &AgendaStore, logs: &LogStore, title: &str) -> Uuid {
+        let id = agendas
+            .create_agenda(&AgendaCreate {
+                title: title.to_string(),
+                agenda_status: AgendaStatus::Pending,
+                terminate_at: Timestamp::now(),
+            })
+            .await
+            .expect("create agenda");
+        logs.create_log(&LogCreate {
+            agenda_id: id,
+            content: format!("note on {title}"),
+            log_type: LogType::CommonLog,
+        })
+        .await
+        .expect("create log");
+        id
+    }
+
+    #[tokio::test]
+    async fn round_trip_preserves_records() {
+        let (_source_dir, agendas, logs) = setup_repos().await;
+        seed(&agendas, &logs, "Write backup").await;
+        let backup = Backup::collect(&agendas, &logs).await.expect("collect");
+        let text = serde_json::to_string(&backup).expect("serialize");
+
+        let (_target_dir, target_agendas, target_logs) = setup_repos().await;
+        let restored = Backup::parse(&text).expect("parse");
+        let summary = restored
+            .restore(&target_agendas, &target_logs, RestoreMode::Merge)
+            .await
+            .expect("restore");
+
+        assert_eq!((summary.added_agendas, summary.added_logs), (1, 1));
+        let copy = Backup::collect(&target_agendas, &target_logs)
+            .await
+            .expect("collect");
+        assert_eq!(copy.agendas, backup.agendas);
+        assert_eq!(copy.logs, backup.logs);
+    }
+
+    #[tokio::test]
+    async fn merge_reports_conflicts_and_replace_overwrites() {
+        let (_dir, agendas, logs) = setup_repos().await;
+        let id = seed(&agendas, &logs, "Original").await;
+        let mut backup = Backup::collect(&agendas, &logs).await.expect("collect");
+        backup.agendas[0].title = "Edited elsewhere".to_string();
+        let local_only = seed(&agendas, &logs, "Local only").await;
+
+        let merged = backup
+            .restore(&agendas, &logs, RestoreMode::Merge)
+            .await
+            .expect("merge");
+        assert_eq!(merged.conflicts, [Conflict::Agenda(id)]);
+        assert_eq!(merged.unchanged, 1);
+        let kept = agendas
+            .get_agenda_by_id(id)
+            .await
+            .expect("get")
+            .expect("agenda");
+        assert_eq!(kept.title, "Original");
+
+        let replaced = backup
+            .restore(&agendas, &logs, RestoreMode::Replace)
+            .await
+            .expect("replace");
+        assert_eq!((replaced.removed_agendas, replaced.removed_logs), (2, 2));
+        assert!(replaced.conflicts.is_empty());
+        let agenda = agendas
+            .get_agenda_by_id(id)
+            .await
+            .expect("get")
+            .expect("agenda");
+        assert_eq!(agenda.title, "Edited elsewhere");
+        assert!(
+            agendas
+                .get_agenda_by_id(local_only)
+                .await
+                .expect("get")
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_replace_leaves_the_database_as_it_was() {
+        let (_dir, agendas, logs) = setup_repos().await;
+        let id = seed(&agendas, &logs, "Kept").await;
+        let mut backup = Backup::collect(&agendas, &logs).await.expect("collect");
+        // A log the database refuses, after every local record was trashed.
+        let mut bad = backup.logs[0].clone();
+        bad.agenda_id = Uuid::now_v7();
+        backup.logs.push(bad);
+
+        let result = backup.restore(&agendas, &logs, RestoreMode::Replace).await;
+        assert!(result.is_err());
+        let kept = agendas.get_agenda_by_id(id).await.expect("get");
+        assert_eq!(kept.map(|agenda| agenda.title).as_deref(), Some("Kept"));
+        assert_eq!(logs.get_logs_by_agenda_id(id).await.expect("logs").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn failed_merge_leaves_the_database_as_it_was() {
+        let (_source_dir, agendas, logs) = setup_repos().await;
+        seed(&agendas, &logs, "New").await;
+        let mut backup = Backup::collect(&agendas, &logs).await.expect("collect");
+        // A log the database refuses, after the new agenda and its log.
+        let mut bad = backup.logs[0].clone();
+        bad.id = Uuid::now_v7();
+        bad.agenda_id = Uuid::now_v7();
+        backup.logs.push(bad);
+
+        let (_target_dir, target_agendas, target_logs) = setup_repos().await;
+        let kept = seed(&target_agendas, &target_logs, "Kept").await;
+        let result = backup
+            .restore(&target_agendas, &target_logs, RestoreMode::Merge)
+            .await;
+        assert!(result.is_err());
+        let all = target_agendas
+            .get_agendas_by_status(None)
+            .await
+            .expect("agendas");
+        assert_eq!(
+            all.iter().map(|agenda| agenda.id).collect::<Vec<_>>(),
+            [kept]
+        );
+        let all_logs = target_logs
+            .get_logs_by_time_range(Timestamp::MIN, Timestamp::MAX)
+            .await
+            .expect("logs");
+        assert_eq!(all_logs.len(), 1);
+    }
+
+    #[test]
+    fn rejects_other_schema_versions() {
+        let text =
+            r#"{"version": 2, "exported_at": "2026-01-01T00:00:00Z", "agendas": [], "logs": []}"#;
+        assert!(matches!(
+            Backup::parse(text),
+            Err(BackupError::Version { found: 2 })
+        ));
+        assert!(matches!(
+            Backup::parse(r#"{"agendas": []}"#),
+            Err(BackupError::MissingVersion)
+        ));
+    }
+}
@@ -0,0 +1,184 @@
+use crate::config::{Config, Encryption};
+use crate::slot::Slots;
+use clap::CommandFactory;
+use clap_complete::{CompletionCandidate, Shell, env::Shells};
+use domain::{Agenda, AgendaStatus};
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Environment variable the shell sets when asking `finiate` for candidates.
+pub const COMPLETE_VAR: &str = "COMPLETE";
+
+/// Writes the registration script for `shell`.
+///
+/// The script calls back into `finiate` on every completion request, so
+/// candidates such as slot numbers always reflect the current database.
+pub fn write_registration(shell: Shell, out: &mut dyn Write) -> std::io::Result<()> {
+    let name = shell.to_string();
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(&name)
+        .expect("every clap_complete shell has a dynamic completer");
+    let bin = std::env::args()
+        .next()
+        .unwrap_or_else(|| "finiate".to_string());
+    completer.write_registration(COMPLETE_VAR, "finiate", "finiate", &bin, out)
+}
+
+/// Candidates for `slot set <slot>`: the occupied slot numbers.
+pub fn slot_numbers() -> Vec<CompletionCandidate> {
+    occupied_slots()
+        .into_iter()
+        .zip(1u8..)
+        .map(|(agenda, slot)| {
+            CompletionCandidate::new(slot.to_string()).help(Some(describe(&agenda).into()))
+        })
+        .collect()
+}
+
+/// Candidates for commands taking an agenda title.
+pub fn slot_titles() -> Vec<CompletionCandidate> {
+    occupied_slots()
+        .into_iter()
+        .zip(1u8..)
+        .map(|(agenda, slot)| {
+            CompletionCandidate::new(agenda.title.clone()).help(Some(
+                format!("slot {slot}, {}", agenda.agenda_status).into(),
+            ))
+        })
+        .collect()
+}
+
+fn describe(agenda: &Agenda) -> String {
+    match agenda.agenda_status {
+        AgendaStatus::Ongoing => format!("{} (current)", agenda.title),
+        _ => agenda.title.clone(),
+    }
+}
+
+/// Reads the occupied slots of the profile selected on the command line
+/// being completed.
+///
+/// Completion must never fail loudly or create files, so any problem simply
+/// yields no candidates.
+fn occupied_slots() -> Vec<Agenda> {
+    // The shell passes the words typed so far after `--`.
+    let words = std::env::args_os().skip_while(|arg| arg != "--").skip(1);
+    let (config_path, profile) = selection(words);
+    let Ok(config) = Config::load(config_path.as_deref(), profile.as_deref()) else {
+        return Vec::new();
+    };
+    // Opening a git store initialises the repository when it is missing.
+    if !config.database.exists()
+        || (config.backend == storage::Backend::Git && !config.git_dir.exists())
+    {
+        return Vec::new();
+    }
+    let Ok(runtime) = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    else {
+        return Vec::new();
+    };
+    runtime.block_on(async {
+        // Completion cannot stop to ask for a passphrase.
+        if config.encryption == Some(Encryption::Prompt) {
+            return Vec::new();
+        }
+        let Ok(options) = crate::db_options(&config, false) else {
+            return Vec::new();
+        };
+        let Ok(pool) = storage::open_read_only(&config.database, &options).await else {
+            return Vec::new();
+        };
+        let Ok((agenda_repo, log_repo)) =
+            storage::open_repos(&pool, config.backend, &config.git_dir)
+        else {
+            return Vec::new();
+        };
+        Slots::new(&agenda_repo, &log_repo, config.max_slots)
+            .list()
+            .await
+            .unwrap_or_default()
+    })
+}
+
+/// The `--config` and `--profile` given on a partial command line, the
+/// profile falling back to `FINIATE_PROFILE` as it does when running.
+fn selection(words: impl IntoIterator<Item = OsString>) -> (Option<PathBuf>, Option<String>) {
+    let Ok(matches) = crate::Args::command()
+        .ignore_errors(true)
+        .try_get_matches_from(words)
+    else {
+        return (None, std::env::var("FINIATE_PROFILE").ok());
+    };
+    (
+        matches.get_one::<PathBuf>("config").cloned(),
+        matches.get_one::<String>("profile").cloned(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::ValueEnum;
+
+    #[test]
+    fn registration_is_written_for_every_shell() {
+        for shell in Shell::value_variants() {
+            let mut script = Vec::new();
+            write_registration(*shell, &mut script).expect("write registration");
+            let script = String::from_utf8(script).expect("utf-8 script");
+            assert!(script.contains(COMPLETE_VAR), "{shell} script");
+        }
+    }
+
+    #[test]
+    fn selection_reads_config_and_profile_from_the_partial_line() {
+        let words = [
+            "finiate",
+            "--config",
+            "work.toml",
+            "-p",
+            "job",
+            "slot",
+            "set",
+            "",
+        ];
+        let (config, profile) = selection(words.map(OsString::from));
+        assert_eq!(config, Some(PathBuf::from("work.toml")));
+        assert_eq!(profile.as_deref(), Some("job"));
+
+        let words = ["finiate", "slot", "set", "--config=other.toml", ""];
+        let (config, _) = selection(words.map(OsString::from));
+        assert_eq!(config, Some(PathBuf::from("other.toml")));
+    }
+
+    #[tokio::test]
+    async fn completion_opens_the_database_without_migrating() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("finiate.db");
+        let options = storage::DbOptions::default();
+        assert!(storage::open_read_only(&path, &options).await.is_err());
+        assert!(!path.exists());
+
+        storage::init_db(&path)
+            .await
+            .expect("init db")
+            .close()
+            .await;
+        let pool = storage::open_read_only(&path, &options)
+            .await
+            .expect("open");
+        let (agenda_repo, log_repo) = storage::create_repos(&pool);
+        let slots = Slots::new(&agenda_repo, &log_repo, 5);
+        assert!(slots.list().await.expect("list").is_empty());
+        assert!(
+            slots
+                .add(Some("a".into()), jiff::Timestamp::now())
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,1051 @@
+use jiff::{SignedDuration, Span, tz::TimeZone};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_DB_PATH: &str = "finiate.db";
+const DEFAULT_MAX_SLOTS: u8 = 5;
+const DEFAULT_DEADLINE: &str = "1d";
+const DEFAULT_SERVER_BIND: &str = "127.0.0.1:7077";
+const DEFAULT_DAEMON_INTERVAL: &str = "1m";
+const DEFAULT_REMIND_BEFORE: [&str; 2] = ["1d", "1h"];
+const DEFAULT_BACKUP_KEEP: usize = 7;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("failed to read config file {path}: {source}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("failed to parse config file {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("profile `{0}` is not defined in the config file")]
+    UnknownProfile(String),
+    #[error("invalid time zone `{name}`: {source}")]
+    TimeZone { name: String, source: jiff::Error },
+    #[error("invalid default deadline `{value}`: {source}")]
+    Deadline { value: String, source: jiff::Error },
+    #[error("max_slots must be at least 1")]
+    NoSlots,
+    #[error("invalid server bind address `{value}`: {source}")]
+    Bind {
+        value: String,
+        source: std::net::AddrParseError,
+    },
+    #[error("server.token must be set to serve the API")]
+    NoServerToken,
+    #[error("invalid daemon interval `{value}`: {reason}")]
+    Interval { value: String, reason: String },
+    #[error("invalid reminder threshold `{value}`: {source}")]
+    Threshold { value: String, source: jiff::Error },
+    #[error("daemon.command must be set to use the `command` notifier")]
+    NoNotifyCommand,
+    #[error("invalid webhook url `{value}`: {reason}")]
+    WebhookUrl { value: String, reason: String },
+    #[error("unknown webhook event `{0}`")]
+    WebhookEvent(String),
+    #[error("invalid backend: {0}")]
+    Backend(String),
+    #[error("encryption.passphrase must be set when encryption.source is `config`")]
+    NoPassphrase,
+    #[error("invalid sqlite.{key} `{value}`: {reason}")]
+    Sqlite {
+        key: &'static str,
+        value: String,
+        reason: String,
+    },
+    #[error("sync.dir must be set to sync")]
+    NoSyncDir,
+    #[error(
+        "sync.device must be set to sync, since this device's host name is unknown or not a valid device name"
+    )]
+    NoSyncDevice,
+    #[error("invalid sync device name `{0}`: use letters, digits, `.`, `_` and `-`")]
+    SyncDevice(String),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// How the daemon delivers reminders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifierKind {
+    /// A desktop notification through `notify-send`.
+    Desktop,
+    /// A terminal bell and a line on stdout.
+    Bell,
+    /// Runs `daemon.command` with the reminder in its environment.
+    Command,
+}
+
+/// Where the passphrase of an encrypted database comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PassphraseSource {
+    /// `encryption.passphrase` in the config file.
+    Config,
+    /// The OS keyring, under the database's path.
+    Keyring,
+    /// Asked for on the terminal.
+    Prompt,
+}
+
+/// The settings that may appear both at the top level of the config file and
+/// inside a `[profiles.<name>]` table. Profile values take precedence.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Layer {
+    database: Option<PathBuf>,
+    time_zone: Option<String>,
+    max_slots: Option<u8>,
+    default_deadline: Option<String>,
+    backend: Option<String>,
+    output: OutputLayer,
+    server: ServerLayer,
+    daemon: DaemonLayer,
+    webhooks: Option<Vec<WebhookLayer>>,
+    sync: SyncLayer,
+    git: GitLayer,
+    encryption: EncryptionLayer,
+    backup: BackupLayer,
+    sqlite: SqliteLayer,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct OutputLayer {
+    format: Option<OutputFormat>,
+    color: Option<ColorChoice>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ServerLayer {
+    bind: Option<String>,
+    token: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct DaemonLayer {
+    interval: Option<String>,
+    remind_before: Option<Vec<String>>,
+    notifiers: Option<Vec<NotifierKind>>,
+    command: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SyncLayer {
+    dir: Option<PathBuf>,
+    device: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct EncryptionLayer {
+    source: Option<PassphraseSource>,
+    passphrase: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct GitLayer {
+    dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct BackupLayer {
+    dir: Option<PathBuf>,
+    keep: Option<usize>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SqliteLayer {
+    journal_mode: Option<String>,
+    synchronous: Option<String>,
+    busy_timeout: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WebhookLayer {
+    url: String,
+    secret: String,
+    #[serde(default)]
+    events: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    database: Option<PathBuf>,
+    time_zone: Option<String>,
+    max_slots: Option<u8>,
+    default_deadline: Option<String>,
+    backend: Option<String>,
+    output: OutputLayer,
+    server: ServerLayer,
+    daemon: DaemonLayer,
+    webhooks: Option<Vec<WebhookLayer>>,
+    sync: SyncLayer,
+    git: GitLayer,
+    encryption: EncryptionLayer,
+    backup: BackupLayer,
+    sqlite: SqliteLayer,
+    default_profile: Option<String>,
+    profiles: BTreeMap<String, Layer>,
+}
+
+/// Fully resolved configuration for one invocation of the CLI.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub database: PathBuf,
+    pub time_zone: TimeZone,
+    pub max_slots: u8,
+    pub default_deadline: Span,
+    pub backend: storage::Backend,
+    pub format: OutputFormat,
+    pub color: ColorChoice,
+    pub server: ServerConfig,
+    pub daemon: DaemonConfig,
+    pub webhooks: Vec<WebhookConfig>,
+    pub sync: SyncConfig,
+    /// The repository the git backend keeps its documents in.
+    pub git_dir: PathBuf,
+    /// Set when the database is encrypted.
+    pub encryption: Option<Encryption>,
+    pub backup: BackupConfig,
+    pub sqlite: SqliteConfig,
+}
+
+/// Settings for `finiate serve`.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub bind: SocketAddr,
+    /// The bearer token API clients must present; serving requires one.
+    pub token: Option<String>,
+}
+
+/// Settings for `finiate daemon`.
+#[derive(Debug, Clone)]
+pub struct DaemonConfig {
+    /// How long to wait between two scans.
+    pub interval: std::time::Duration,
+    /// How long before a deadline to remind; overdue agendas are always
+    /// reminded about as well.
+    pub remind_before: Vec<Span>,
+    pub notifiers: Vec<NotifierKind>,
+    pub command: Option<String>,
+}
+
+/// One `[[webhooks]]` endpoint.
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// The HMAC key payloads are signed with.
+    pub secret: String,
+    /// The events to send; all of them when empty.
+    pub events: Vec<String>,
+}
+
+/// Settings for `finiate sync`.
+#[derive(Debug, Clone)]
+pub struct SyncConfig {
+    /// The folder shared between devices; syncing requires one.
+    pub dir: Option<PathBuf>,
+    /// This device's name, which its change file is named after; the host
+    /// name unless set. `None` when neither is known, which only `sync`
+    /// refuses.
+    pub device: Option<String>,
+}
+
+/// Copies of the database file taken by `finiate db backup` and, once a
+/// day, by the first command that opens the database.
+#[derive(Debug, Clone)]
+pub struct BackupConfig {
+    pub dir: PathBuf,
+    /// How many daily copies to keep; 0 turns the daily copy off.
+    pub keep: usize,
+}
+
+/// How connections to the database are set up; see [`storage::DbOptions`].
+#[derive(Debug, Clone)]
+pub struct SqliteConfig {
+    pub journal_mode: storage::SqliteJournalMode,
+    pub synchronous: storage::SqliteSynchronous,
+    pub busy_timeout: std::time::Duration,
+}
+
+/// How to unlock an encrypted database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Encryption {
+    Passphrase(String),
+    Keyring,
+    Prompt,
+}
+
+impl Config {
+    /// Loads the config file and resolves the selected profile.
+    ///
+    /// When `path` is `None` the default location is used and a missing file
+    /// simply yields the built-in defaults.
+    pub fn load(path: Option<&Path>, profile: Option<&str>) -> Result<Config, ConfigError> {
+        let (path, required) = match path {
+            Some(path) => (Some(path.to_path_buf()), true),
+            None => (default_config_path(), false),
+        };
+
+        let Some(path) = path else {
+            return Config::resolve(ConfigFile::default(), None, profile);
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                let file = parse_file(&text, &path)?;
+                Config::resolve(file, path.parent(), profile)
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound && !required => {
+                Config::resolve(ConfigFile::default(), None, profile)
+            }
+            Err(source) => Err(ConfigError::Read { path, source }),
+        }
+    }
+
+    fn resolve(
+        file: ConfigFile,
+        base_dir: Option<&Path>,
+        profile: Option<&str>,
+    ) -> Result<Config, ConfigError> {
+        let profile = profile
+            .map(str::to_string)
+            .or_else(|| file.default_profile.clone());
+        let base = Layer {
+            database: file.database,
+            time_zone: file.time_zone,
+            max_slots: file.max_slots,
+            default_deadline: file.default_deadline,
+            backend: file.backend,
+            output: file.output,
+            server: file.server,
+            daemon: file.daemon,
+            webhooks: file.webhooks,
+            sync: file.sync,
+            git: file.git,
+            encryption: file.encryption,
+            backup: file.backup,
+            sqlite: file.sqlite,
+        };
+        let base_database = base
+            .database
+            .as_deref()
+            .map(|path| resolve_path(path, base_dir))
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_DB_PATH));
+
+        let layer = match &profile {
+            Some(name) => {
+                let layer = file
+                    .profiles
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| ConfigError::UnknownProfile(name.clone()))?;
+                Some(layer)
+            }
+            None => None,
+        };
+
+        // Each profile points at its own database; if it does not name one, it
+        // gets a sibling of the base database named after the profile.
+        let database = match (&profile, &layer) {
+            (Some(name), Some(layer)) => match &layer.database {
+                Some(path) => resolve_path(path, base_dir),
+                None => base_database.with_file_name(format!("finiate-{name}.db")),
+            },
+            _ => base_database,
+        };
+
+        let layer = layer.unwrap_or_default();
+        let time_zone = match layer.time_zone.or(base.time_zone) {
+            Some(name) => {
+                TimeZone::get(&name).map_err(|source| ConfigError::TimeZone { name, source })?
+            }
+            None => TimeZone::system(),
+        };
+        let max_slots = layer
+            .max_slots
+            .or(base.max_slots)
+            .unwrap_or(DEFAULT_MAX_SLOTS);
+        if max_slots == 0 {
+            return Err(ConfigError::NoSlots);
+        }
+        let deadline = layer
+            .default_deadline
+            .or(base.default_deadline)
+            .unwrap_or_else(|| DEFAULT_DEADLINE.to_string());
+        let default_deadline =
+            deadline
+                .parse::<Span>()
+                .map_err(|source| ConfigError::Deadline {
+                    value: deadline,
+                    source,
+                })?;
+        let backend = match layer.backend.or(base.backend) {
+            Some(name) => name.parse().map_err(ConfigError::Backend)?,
+            None => storage::Backend::default(),
+        };
+        let bind = layer
+            .server
+            .bind
+            .or(base.server.bind)
+            .unwrap_or_else(|| DEFAULT_SERVER_BIND.to_string());
+        let server = ServerConfig {
+            bind: bind.parse().map_err(|source| ConfigError::Bind {
+                value: bind,
+                source,
+            })?,
+            token: layer.server.token.or(base.server.token),
+        };
+        let daemon = resolve_daemon(layer.daemon, base.daemon)?;
+        let webhooks = resolve_webhooks(layer.webhooks.or(base.webhooks).unwrap_or_default())?;
+        let sync = resolve_sync(layer.sync, base.sync, base_dir, hostname)?;
+        let encryption = resolve_encryption(layer.encryption, base.encryption)?;
+        let sqlite = resolve_sqlite(layer.sqlite, base.sqlite)?;
+        // Like the database, the repository defaults to one per profile.
+        let git_dir = match layer.git.dir.or(base.git.dir) {
+            Some(dir) => resolve_path(&dir, base_dir),
+            None => {
+                let stem = database.file_stem().unwrap_or_default().to_string_lossy();
+                database.with_file_name(format!("{stem}-agendas"))
+            }
+        };
+
+        let backup = BackupConfig {
+            dir: match layer.backup.dir.or(base.backup.dir) {
+                Some(dir) => resolve_path(&dir, base_dir),
+                None => {
+                    let stem = database.file_stem().unwrap_or_default().to_string_lossy();
+                    database.with_file_name(format!("{stem}-backups"))
+                }
+            },
+            keep: layer
+                .backup
+                .keep
+                .or(base.backup.keep)
+                .unwrap_or(DEFAULT_BACKUP_KEEP),
+        };
+
+        Ok(Config {
+            database,
+            time_zone,
+            max_slots,
+            default_deadline,
+            backend,
+            format: layer
+                .output
+                .format
+                .or(base.output.format)
+                .unwrap_or_default(),
+            color: layer.output.color.or(base.output.color).unwrap_or_default(),
+            server,
+            daemon,
+            webhooks,
+            sync,
+            git_dir,
+            encryption,
+            backup,
+            sqlite,
+        })
+    }
+}
+
+fn resolve_daemon(layer: DaemonLayer, base: DaemonLayer) -> Result<DaemonConfig, ConfigError> {
+    let interval = layer
+        .interval
+        .or(base.interval)
+        .unwrap_or_else(|| DEFAULT_DAEMON_INTERVAL.to_string());
+    let invalid = |reason: String| ConfigError::Interval {
+        value: interval.clone(),
+        reason,
+    };
+    let duration = interval
+        .parse::<SignedDuration>()
+        .map_err(|e| invalid(e.to_string()))?;
+    let interval = std::time::Duration::try_from(duration)
+        .ok()
+        .filter(|interval| !interval.is_zero())
+        .ok_or_else(|| invalid("must be positive".to_string()))?;
+
+    let remind_before = layer
+        .remind_before
+        .or(base.remind_before)
+        .unwrap_or_else(|| DEFAULT_REMIND_BEFORE.map(str::to_string).to_vec())
+        .into_iter()
+        .map(|value| {
+            value
+                .parse::<Span>()
+                .map_err(|source| ConfigError::Threshold { value, source })
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(DaemonConfig {
+        interval,
+        remind_before,
+        notifiers: layer
+            .notifiers
+            .or(base.notifiers)
+            .unwrap_or_else(|| vec![NotifierKind::Desktop]),
+        command: layer.command.or(base.command),
+    })
+}
+
+fn resolve_sqlite(layer: SqliteLayer, base: SqliteLayer) -> Result<SqliteConfig, ConfigError> {
+    let defaults = storage::DbOptions::default();
+    let invalid = |key, value: String, reason: String| ConfigError::Sqlite { key, value, reason };
+    let journal_mode = match layer.journal_mode.or(base.journal_mode) {
+        Some(value) => value.parse().map_err(|_| {
+            invalid(
+                "journal_mode",
+                value,
+                "use wal, delete, truncate, persist, memory or off".to_string(),
+            )
+        })?,
+        None => defaults.journal_mode,
+    };
+    let synchronous = match layer.synchronous.or(base.synchronous) {
+        Some(value) => value.parse().map_err(|_| {
+            invalid(
+                "synchronous",
+                value,
+                "use off, normal, full or extra".to_string(),
+            )
+        })?,
+        None => defaults.synchronous,
+    };
+    let busy_timeout = match layer.busy_timeout.or(base.busy_timeout) {
+        Some(value) => value
+            .parse::<SignedDuration>()
+            .map_err(|e| e.to_string())
+            .and_then(|duration| {
+                std::time::Duration::try_from(duration)
+                    .map_err(|_| "must not be negative".to_string())
+            })
+            .map_err(|reason| invalid("busy_timeout", value, reason))?,
+        None => defaults.busy_timeout,
+    };
+    Ok(SqliteConfig {
+        journal_mode,
+        synchronous,
+        busy_timeout,
+    })
+}
+
+fn resolve_encryption(
+    layer: EncryptionLayer,
+    base: EncryptionLayer,
+) -> Result<Option<Encryption>, ConfigError> {
+    let passphrase = layer.passphrase.or(base.passphrase);
+    let source = layer
+        .source
+        .or(base.source)
+        .or(passphrase.as_ref().map(|_| PassphraseSource::Config));
+    Ok(match source {
+        None => None,
+        Some(PassphraseSource::Config) => Some(Encryption::Passphrase(
+            passphrase.ok_or(ConfigError::NoPassphrase)?,
+        )),
+        Some(PassphraseSource::Keyring) => Some(Encryption::Keyring),
+        Some(PassphraseSource::Prompt) => Some(Encryption::Prompt),
+    })
+}
+
+/// The host name, which devices are named after unless configured.
+fn hostname() -> Option<String> {
+    ["HOSTNAME", "COMPUTERNAME"]
+        .into_iter()
+        .find_map(|name| std::env::var(name).ok())
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|host| host.trim().to_string())
+        .filter(|host| !host.is_empty())
+}
+
+fn resolve_sync(
+    layer: SyncLayer,
+    base: SyncLayer,
+    base_dir: Option<&Path>,
+    hostname: fn() -> Option<String>,
+) -> Result<SyncConfig, ConfigError> {
+    let dir = layer
+        .dir
+        .or(base.dir)
+        .map(|dir| resolve_path(&dir, base_dir));
+    // Two devices sharing a name would overwrite each other's changes, so
+    // there is no made-up default.
+    let device = match layer.device.or(base.device) {
+        Some(device) if valid_device(&device) => Some(device),
+        Some(device) => return Err(ConfigError::SyncDevice(device)),
+        None if dir.is_some() => hostname().filter(|host| valid_device(host)),
+        None => None,
+    };
+    Ok(SyncConfig { dir, device })
+}
+
+fn valid_device(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+fn resolve_webhooks(layers: Vec<WebhookLayer>) -> Result<Vec<WebhookConfig>, ConfigError> {
+    layers
+        .into_iter()
+        .map(|layer| {
+            reqwest::Url::parse(&layer.url)
+                .map_err(|e| e.to_string())
+                .and_then(|url| match url.scheme() {
+                    "http" | "https" => Ok(()),
+                    scheme => Err(format!("unsupported scheme `{scheme}`")),
+                })
+                .map_err(|reason| ConfigError::WebhookUrl {
+                    value: layer.url.clone(),
+                    reason,
+                })?;
+            if let Some(event) = layer
+                .events
+                .iter()
+                .find(|event| !crate::webhook::EVENTS.contains(&event.as_str()))
+            {
+                return Err(ConfigError::WebhookEvent(event.clone()));
+            }
+            Ok(WebhookConfig {
+                url: layer.url,
+                secret: layer.secret,
+                events: layer.events,
+            })
+        })
+        .collect()
+}
+
+fn parse_file(text: &str, path: &Path) -> Result<ConfigFile, ConfigError> {
+    toml::from_str(text).map_err(|source| ConfigError::Parse {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// `$XDG_CONFIG_HOME/finiate/config.toml`, falling back to `~/.config`.
+pub fn default_config_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home_dir().map(|home| home.join(".config")))?;
+    Some(config_home.join("finiate").join("config.toml"))
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Expands a leading `~` and anchors relative paths at the config directory.
+fn resolve_path(path: &Path, base_dir: Option<&Path>) -> PathBuf {
+    if let Ok(rest) = path.strip_prefix("~")
+        && let Some(home) = home_dir()
+    {
+        return home.join(rest);
+    }
+    match base_dir {
+        Some(dir) if path.is_relative() => dir.join(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_toml(
+        text: &str,
+        base_dir: Option<&Path>,
+        profile: Option<&str>,
+    ) -> Result<Config, ConfigError> {
+        Config::resolve(parse_file(text, Path::new(""))?, base_dir, profile)
+    }
+
+    #[test]
+    fn defaults_without_config_file() {
+        let config = from_toml("", None, None).expect("empty config");
+
+        assert_eq!(config.database, PathBuf::from(DEFAULT_DB_PATH));
+        assert_eq!(config.max_slots, DEFAULT_MAX_SLOTS);
+        assert_eq!(config.format, OutputFormat::Text);
+        assert_eq!(config.color, ColorChoice::Auto);
+    }
+
+    #[test]
+    fn top_level_settings_apply() {
+        let text = r#"
+            database = "data/main.db"
+            time_zone = "Asia/Tokyo"
+            max_slots = 3
+            default_deadline = "2d"
+
+            [output]
+            format = "json"
+            color = "never"
+        "#;
+        let config = from_toml(text, Some(Path::new("/cfg")), None).expect("config");
+
+        assert_eq!(config.database, PathBuf::from("/cfg/data/main.db"));
+        assert_eq!(config.time_zone.iana_name(), Some("Asia/Tokyo"));
+        assert_eq!(config.max_slots, 3);
+        assert_eq!(config.default_deadline.get_days(), 2);
+        assert_eq!(config.format, OutputFormat::Json);
+        assert_eq!(config.color, ColorChoice::Never);
+    }
+
+    #[test]
+    fn profile_overrides_base_settings() {
+        let text = r#"
+            database = "/data/main.db"
+            max_slots = 3
+
+            [profiles.work]
+            database = "/data/work.db"
+            max_slots = 7
+
+            [profiles.work.output]
+            format = "json"
+        "#;
+        let config = from_toml(text, None, Some("work")).expect("config");
+
+        assert_eq!(config.database, PathBuf::from("/data/work.db"));
+        assert_eq!(config.max_slots, 7);
+        assert_eq!(config.format, OutputFormat::Json);
+    }
+
+    #[test]
+    fn default_profile_is_used_when_none_selected() {
+        let text = r#"
+            database = "/data/main.db"
+            default_profile = "personal"
+
+            [profiles.personal]
+        "#;
+        let config = from_toml(text, None, None).expect("config");
+
+        assert_eq!(config.database, PathBuf::from("/data/finiate-personal.db"));
+    }
+
+    #[test]
+    fn server_settings_layer_like_the_rest() {
+        let text = r#"
+            [server]
+            token = "secret"
+
+            [profiles.work.server]
+            bind = "127.0.0.1:9000"
+        "#;
+        let base = from_toml(text, None, None).expect("config");
+        assert_eq!(base.server.bind.to_string(), DEFAULT_SERVER_BIND);
+        assert_eq!(base.server.token.as_deref(), Some("secret"));
+
+        let work = from_toml(text, None, Some("work")).expect("config");
+        assert_eq!(work.server.bind.port(), 9000);
+        assert_eq!(work.server.token.as_deref(), Some("secret"));
+
+        assert!(matches!(
+            from_toml("[server]\nbind = \"localhost\"", None, None),
+            Err(ConfigError::Bind { .. })
+        ));
+    }
+
+    #[test]
+    fn backend_defaults_to_relational_and_profiles_choose() {
+        let text = r#"
+            [profiles.audit]
+            backend = "event_sourced"
+        "#;
+        let base = from_toml(text, None, None).expect("config");
+        assert_eq!(base.backend, storage::Backend::Relational);
+        let audit = from_toml(text, None, Some("audit")).expect("config");
+        assert_eq!(audit.backend, storage::Backend::EventSourced);
+
+        assert!(matches!(
+            from_toml("backend = \"ledger\"", None, None),
+            Err(ConfigError::Backend(_))
+        ));
+    }
+
+    #[test]
+    fn encryption_is_off_unless_a_source_is_given() {
+        assert_eq!(from_toml("", None, None).expect("config").encryption, None);
+
+        let text = r#"
+            [encryption]
+            passphrase = "hunter2"
+
+            [profiles.laptop.encryption]
+            source = "keyring"
+        "#;
+        let base = from_toml(text, None, None).expect("config");
+        assert_eq!(
+            base.encryption,
+            Some(Encryption::Passphrase("hunter2".to_string()))
+        );
+        let laptop = from_toml(text, None, Some("laptop")).expect("config");
+        assert_eq!(laptop.encryption, Some(Encryption::Keyring));
+
+        assert!(matches!(
+            from_toml("[encryption]\nsource = \"config\"", None, None),
+            Err(ConfigError::NoPassphrase)
+        ));
+    }
+
+    #[test]
+    fn git_dir_defaults_next_to_the_database() {
+        let text = r#"
+            database = "/data/main.db"
+            backend = "git"
+
+            [profiles.notes.git]
+            dir = "notes"
+        "#;
+        let base = from_toml(text, Some(Path::new("/cfg")), None).expect("config");
+        assert_eq!(base.backend, storage::Backend::Git);
+        assert_eq!(base.git_dir, PathBuf::from("/data/main-agendas"));
+        let notes = from_toml(text, Some(Path::new("/cfg")), Some("notes")).expect("config");
+        assert_eq!(notes.git_dir, PathBuf::from("/cfg/notes"));
+    }
+
+    #[test]
+    fn backups_default_next_to_the_database() {
+        let text = r#"
+            database = "/data/main.db"
+
+            [profiles.work.backup]
+            dir = "backups"
+            keep = 0
+        "#;
+        let base = from_toml(text, Some(Path::new("/cfg")), None).expect("config");
+        assert_eq!(base.backup.dir, PathBuf::from("/data/main-backups"));
+        assert_eq!(base.backup.keep, DEFAULT_BACKUP_KEEP);
+        let work = from_toml(text, Some(Path::new("/cfg")), Some("work")).expect("config");
+        assert_eq!(work.backup.keep, 0);
+        assert_eq!(work.backup.dir, PathBuf::from("/cfg/backups"));
+    }
+
+    #[test]
+    fn sqlite_settings_default_to_wal_and_validate() {
+        let defaults = from_toml("", None, None).expect("config");
+        assert!(matches!(
+            defaults.sqlite.journal_mode,
+            storage::SqliteJournalMode::Wal
+        ));
+        assert_eq!(defaults.sqlite.busy_timeout.as_secs(), 5);
+
+        let text = r#"
+            [sqlite]
+            journal_mode = "DELETE"
+            synchronous = "full"
+            busy_timeout = "30s"
+        "#;
+        let config = from_toml(text, None, None).expect("config");
+        assert!(matches!(
+            config.sqlite.journal_mode,
+            storage::SqliteJournalMode::Delete
+        ));
+        assert!(matches!(
+            config.sqlite.synchronous,
+            storage::SqliteSynchronous::Full
+        ));
+        assert_eq!(config.sqlite.busy_timeout.as_secs(), 30);
+
+        assert!(matches!(
+            from_toml("[sqlite]\njournal_mode = \"fast\"", None, None),
+            Err(ConfigError::Sqlite {
+                key: "journal_mode",
+                ..
+            })
+        ));
+        assert!(matches!(
+            from_toml("[sqlite]\nbusy_timeout = \"-1s\"", None, None),
+            Err(ConfigError::Sqlite {
+                key: "busy_timeout",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn daemon_settings_have_defaults_and_validate() {
+        let defaults = from_toml("", None, None).expect("config");
+        assert_eq!(defaults.daemon.interval.as_secs(), 60);
+        assert_eq!(defaults.daemon.remind_before.len(), 2);
+        assert_eq!(defaults.daemon.notifiers, [NotifierKind::Desktop]);
+
+        let text = r#"
+            [daemon]
+            interval = "30s"
+            remind_before = ["15m"]
+            notifiers = ["bell", "command"]
+            command = "logger finiate"
+        "#;
+        let config = from_toml(text, None, None).expect("config");
+        assert_eq!(config.daemon.interval.as_secs(), 30);
+        assert_eq!(config.daemon.remind_before[0].get_minutes(), 15);
+        assert_eq!(
+            config.daemon.notifiers,
+            [NotifierKind::Bell, NotifierKind::Command]
+        );
+        assert_eq!(config.daemon.command.as_deref(), Some("logger finiate"));
+
+        assert!(matches!(
+            from_toml("[daemon]\ninterval = \"0s\"", None, None),
+            Err(ConfigError::Interval { .. })
+        ));
+        assert!(matches!(
+            from_toml("[daemon]\nremind_before = [\"soon\"]", None, None),
+            Err(ConfigError::Threshold { .. })
+        ));
+    }
+
+    #[test]
+    fn webhooks_are_validated_and_profiles_replace_them() {
+        let text = r#"
+            [[webhooks]]
+            url = "https://chat.example/hook"
+            secret = "s3cret"
+            events = ["agenda.terminated"]
+
+            [profiles.quiet]
+            webhooks = []
+        "#;
+        let config = from_toml(text, None, None).expect("config");
+        assert_eq!(config.webhooks.len(), 1);
+        assert_eq!(config.webhooks[0].events, ["agenda.terminated"]);
+        let quiet = from_toml(text, None, Some("quiet")).expect("config");
+        assert!(quiet.webhooks.is_empty());
+
+        assert!(matches!(
+            from_toml(
+                "[[webhooks]]\nurl = \"ftp://x\"\nsecret = \"s\"",
+                None,
+                None
+            ),
+            Err(ConfigError::WebhookUrl { .. })
+        ));
+        assert!(matches!(
+            from_toml(
+                "[[webhooks]]\nurl = \"http://x\"\nsecret = \"s\"\nevents = [\"agenda.deleted\"]",
+                None,
+                None
+            ),
+            Err(ConfigError::WebhookEvent(event)) if event == "agenda.deleted"
+        ));
+    }
+
+    #[test]
+    fn sync_dir_is_resolved_and_device_names_validated() {
+        let text = r#"
+            [sync]
+            dir = "shared"
+            device = "laptop"
+
+            [profiles.desk.sync]
+            device = "desk-01"
+        "#;
+        let config = from_toml(text, Some(Path::new("/cfg")), None).expect("config");
+        assert_eq!(config.sync.dir, Some(PathBuf::from("/cfg/shared")));
+        assert_eq!(config.sync.device.as_deref(), Some("laptop"));
+        let desk = from_toml(text, Some(Path::new("/cfg")), Some("desk")).expect("config");
+        assert_eq!(desk.sync.dir, Some(PathBuf::from("/cfg/shared")));
+        assert_eq!(desk.sync.device.as_deref(), Some("desk-01"));
+
+        assert!(
+            from_toml("", None, None)
+                .expect("config")
+                .sync
+                .dir
+                .is_none()
+        );
+        assert!(matches!(
+            from_toml("[sync]\ndevice = \"../up\"", None, None),
+            Err(ConfigError::SyncDevice(name)) if name == "../up"
+        ));
+    }
+
+    #[test]
+    fn sync_device_falls_back_to_the_host_name_or_stays_unknown() {
+        let shared = || SyncLayer {
+            dir: Some(PathBuf::from("/shared")),
+            device: None,
+        };
+        let named = resolve_sync(shared(), SyncLayer::default(), None, || {
+            Some("desk".to_string())
+        })
+        .expect("sync config");
+        assert_eq!(named.device.as_deref(), Some("desk"));
+        // Loading still works; only `sync` needs the name.
+        for hostname in [(|| None) as fn() -> Option<String>, || {
+            Some("my desk".into())
+        }] {
+            let unknown =
+                resolve_sync(shared(), SyncLayer::default(), None, hostname).expect("sync config");
+            assert_eq!(unknown.dir, Some(PathBuf::from("/shared")));
+            assert!(unknown.device.is_none());
+        }
+        let unused = resolve_sync(SyncLayer::default(), SyncLayer::default(), None, || None)
+            .expect("sync config");
+        assert!(unused.device.is_none());
+    }
+
+    #[test]
+    fn unknown_profile_is_an_error() {
+        let result = from_toml("", None, Some("missing"));
+        assert!(matches!(result, Err(ConfigError::UnknownProfile(name)) if name == "missing"));
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        assert!(matches!(
+            from_toml("max_slots = 0", None, None),
+            Err(ConfigError::NoSlots)
+        ));
+        assert!(matches!(
+            from_toml("time_zone = \"Mars/Olympus\"", None, None),
+            Err(ConfigError::TimeZone { .. })
+        ));
+        assert!(matches!(
+            from_toml("default_deadline = \"soon\"", None, None),
+            Err(ConfigError::Deadline { .. })
+        ));
+        assert!(matches!(
+            from_toml("colour = \"red\"", None, None),
+            Err(ConfigError::Parse { .. })
+        ));
+    }
+}
@@ -0,0 +1,498 @@
+//! Deadline reminders for `finiate daemon`.
+//!
+//! Every scan looks up the open agendas whose `terminate_at` falls within the
+//! widest reminder threshold and fires at most one reminder per agenda: the
+//! tightest threshold it has crossed. Fired reminders are remembered in a
+//! small state file next to the database, keyed by agenda, deadline and
+//! threshold, so each threshold fires once even across restarts, and moving
+//! a deadline arms its reminders again.
+
+use crate::config::{ConfigError, DaemonConfig, NotifierKind};
+use crate::time;
+use crate::webhook::Dispatcher;
+use async_trait::async_trait;
+use domain::*;
+use jiff::{Span, Timestamp, Zoned, tz::TimeZone};
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::process::Command;
+
+/// A point before (or at) a deadline at which to remind.
+#[derive(Debug, Clone, Copy)]
+pub enum Threshold {
+    Before(Span),
+    Overdue,
+}
+
+impl fmt::Display for Threshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Threshold::Before(span) => write!(f, "{span:#}"),
+            Threshold::Overdue => f.write_str("overdue"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Reminder {
+    pub agenda: Agenda,
+    pub threshold: Threshold,
+}
+
+impl Reminder {
+    /// The state-file key: changing the deadline yields a new key.
+    fn key(&self) -> String {
+        key(&self.agenda, self.threshold)
+    }
+
+    pub fn summary(&self) -> String {
+        match self.threshold {
+            Threshold::Before(span) => format!("Due in {span:#}: {}", self.agenda.title),
+            Threshold::Overdue => format!("Overdue: {}", self.agenda.title),
+        }
+    }
+
+    pub fn body(&self, tz: &TimeZone) -> String {
+        format!("deadline {}", time::display(self.agenda.terminate_at, tz))
+    }
+}
+
+fn key(agenda: &Agenda, threshold: Threshold) -> String {
+    format!("{}@{}/{threshold}", agenda.id, agenda.terminate_at)
+}
+
+/// Delivers reminders somewhere the user will notice them.
+#[async_trait]
+pub trait Notifier: Sync {
+    async fn notify(&self, reminder: &Reminder, tz: &TimeZone) -> std::io::Result<()>;
+}
+
+/// A desktop notification sent over D-Bus by `notify-send`.
+pub struct Desktop;
+
+#[async_trait]
+impl Notifier for Desktop {
+    async fn notify(&self, reminder: &Reminder, tz: &TimeZone) -> std::io::Result<()> {
+        let urgency = match reminder.threshold {
+            Threshold::Overdue => "critical",
+            Threshold::Before(_) => "normal",
+        };
+        run_command(
+            Command::new("notify-send")
+                .args(["--app-name=finiate", "--urgency", urgency])
+                .arg(reminder.summary())
+                .arg(reminder.body(tz)),
+        )
+        .await
+    }
+}
+
+/// Rings the terminal bell and prints the reminder.
+pub struct Bell;
+
+#[async_trait]
+impl Notifier for Bell {
+    async fn notify(&self, reminder: &Reminder, tz: &TimeZone) -> std::io::Result<()> {
+        let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "\x07{} ({})", reminder.summary(), reminder.body(tz))?;
+        stdout.flush()
+    }
+}
+
+/// Runs a shell command with the reminder in `FINIATE_*` variables.
+pub struct UserCommand(pub String);
+
+#[async_trait]
+impl Notifier for UserCommand {
+    async fn notify(&self, reminder: &Reminder, tz: &TimeZone) -> std::io::Result<()> {
+        run_command(
+            Command::new("sh")
+                .arg("-c")
+                .arg(&self.0)
+                .env("FINIATE_AGENDA_ID", reminder.agenda.id.to_string())
+                .env("FINIATE_TITLE", &reminder.agenda.title)
+                .env("FINIATE_DEADLINE", reminder.agenda.terminate_at.to_string())
+                .env("FINIATE_THRESHOLD", reminder.threshold.to_string())
+                .env("FINIATE_MESSAGE", reminder.summary())
+                .env("FINIATE_BODY", reminder.body(tz)),
+        )
+        .await
+    }
+}
+
+/// Waits for the command without blocking the runtime.
+async fn run_command(command: &mut Command) -> std::io::Result<()> {
+    let status = command.stdin(Stdio::null()).status().await?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!(
+            "{:?} exited with {status}",
+            command.as_std().get_program()
+        )))
+    }
+}
+
+/// Builds the notifiers selected in the config.
+pub fn notifiers(config: &DaemonConfig) -> Result<Vec<Box<dyn Notifier>>, ConfigError> {
+    config
+        .notifiers
+        .iter()
+        .map(|kind| -> Result<Box<dyn Notifier>, _> {
+            Ok(match kind {
+                NotifierKind::Desktop => Box::new(Desktop),
+                NotifierKind::Bell => Box::new(Bell),
+                NotifierKind::Command => Box::new(UserCommand(
+                    config.command.clone().ok_or(ConfigError::NoNotifyCommand)?,
+                )),
+            })
+        })
+        .collect()
+}
+
+/// The reminders that already fired, persisted as a JSON array of keys.
+#[derive(Debug, Default)]
+pub struct Fired {
+    path: Option<PathBuf>,
+    keys: BTreeSet<String>,
+}
+
+impl Fired {
+    /// The state file that belongs to `database`.
+    pub fn path_for(database: &Path) -> PathBuf {
+        database.with_extension("reminders.json")
+    }
+
+    /// Loads the state file; a missing file means nothing fired yet.
+    pub fn load(path: PathBuf) -> Result<Fired, Box<dyn Error + Send + Sync>> {
+        let keys = match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeSet::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Fired {
+            path: Some(path),
+            keys,
+        })
+    }
+
+    fn save(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if let Some(path) = &self.path {
+            std::fs::write(path, serde_json::to_string_pretty(&self.keys)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// Finds the reminders due at `now` and records them as fired.
+///
+/// Crossing several thresholds at once (say the daemon was not running)
+/// yields a single reminder for the tightest one.
+pub async fn scan<A: AgendaRepo + Sync>(
+    agendas: &A,
+    remind_before: &[Span],
+    now: &Zoned,
+    fired: &mut Fired,
+) -> Result<Vec<Reminder>, Box<dyn Error + Send + Sync>> {
+    // Tightest threshold first, each with the instant it starts to apply.
+    let mut thresholds = vec![(Threshold::Overdue, now.timestamp())];
+    for span in remind_before {
+        thresholds.push((
+            Threshold::Before(*span),
+            now.checked_add(*span)?.timestamp(),
+        ));
+    }
+    thresholds.sort_by_key(|(_, horizon)| *horizon);
+    let widest = thresholds.last().map_or(now.timestamp(), |(_, at)| *at);
+
+    let mut due = agendas
+        .get_agendas_by_terminate_time_range(Timestamp::MIN, widest)
+        .await?;
+    due.retain(|agenda| agenda.agenda_status != AgendaStatus::Terminated);
+    due.sort_by_key(|agenda| (agenda.terminate_at, agenda.id));
+
+    // Only keys of agendas still open and due at their current deadline can
+    // matter again; the rest would pile up forever.
+    let live: BTreeSet<String> = due
+        .iter()
+        .map(|agenda| format!("{}@{}", agenda.id, agenda.terminate_at))
+        .collect();
+    let before = fired.keys.len();
+    fired.keys.retain(|key| {
+        key.split_once('/')
+            .is_some_and(|(agenda, _)| live.contains(agenda))
+    });
+    let pruned = fired.keys.len() != before;
+
+    let mut reminders = Vec::new();
+    for agenda in due {
+        let crossed: Vec<Threshold> = thresholds
+            .iter()
+            .filter(|(_, horizon)| agenda.terminate_at <= *horizon)
+            .map(|(threshold, _)| *threshold)
+            .collect();
+        let Some(&tightest) = crossed.first() else {
+            continue;
+        };
+        if fired.keys.contains(&key(&agenda, tightest)) {
+            continue;
+        }
+        for threshold in &crossed {
+            fired.keys.insert(key(&agenda, *threshold));
+        }
+        reminders.push(Reminder {
+            agenda,
+            threshold: tightest,
+        });
+    }
+    if pruned || !reminders.is_empty() {
+        fired.save()?;
+    }
+    Ok(reminders)
+}
+
+/// Scans every `config.interval` until interrupted, or once with `once`,
+/// delivering due webhooks after each scan.
+pub async fn run<A: AgendaRepo + Sync>(
+    agendas: &A,
+    config: &DaemonConfig,
+    tz: &TimeZone,
+    notifiers: &[Box<dyn Notifier>],
+    fired: &mut Fired,
+    webhooks: Option<&Dispatcher<'_>>,
+    once: bool,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    loop {
+        let now = Zoned::now().with_time_zone(tz.clone());
+        for reminder in scan(agendas, &config.remind_before, &now, fired).await? {
+            for notifier in notifiers {
+                // One broken notifier must not silence the others.
+                if let Err(e) = notifier.notify(&reminder, tz).await {
+                    eprintln!("finiate daemon: {} not delivered: {e}", reminder.key());
+                }
+            }
+        }
+        if let Some(dispatcher) = webhooks
+            && let Err(e) = dispatcher.deliver_due(Timestamp::now()).await
+        {
+            eprintln!("finiate daemon: webhook delivery failed: {e}");
+        }
+        if once {
+            return Ok(());
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(config.interval) => {}
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jiff::ToSpan;
+    use storage::SqliteAgendaRepo;
+
+    async fn setup_repo() -> (tempfile::TempDir, SqliteAgendaRepo) {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let pool = storage::init_db(&dir.path().join("finiate.db"))
+            .await
+            .expect("init db");
+        let (agenda_repo, _) = storage::create_repos(&pool);
+        (dir, agenda_repo)
+    }
+
+    fn now() -> Zoned {
+        "2026-03-01T09:00:00+00:00[UTC]".parse().expect("zoned")
+    }
+
+    async fn create(agendas: &SqliteAgendaRepo, title: &str, due_in: Span) -> uuid::Uuid {
+        agendas
+            .create_agenda(&AgendaCreate {
+                title: title.to_string(),
+                agenda_status: AgendaStatus::Pending,
+                terminate_at: now().checked_add(due_in).unwrap().timestamp(),
+            })
+            .await
+            .expect("create agenda")
+    }
+
+    #[tokio::test]
+    async fn fires_tightest_threshold_once() {
+        let (_dir, agendas) = setup_repo().await;
+        let thresholds = [1.day(), 1.hour()];
+        create(&agendas, "Far", 3.days()).await;
+        create(&agendas, "Tomorrow", 20.hours()).await;
+        create(&agendas, "Soon", 30.minutes()).await;
+        create(&agendas, "Late", -(5.minutes())).await;
+        let mut fired = Fired::default();
+
+        let reminders = scan(&agendas, &thresholds, &now(), &mut fired)
+            .await
+            .unwrap();
+        let got: Vec<_> = reminders
+            .iter()
+            .map(|r| (r.agenda.title.as_str(), r.threshold.to_string()))
+            .collect();
+        assert_eq!(
+            got,
+            [
+                ("Late", "overdue".to_string()),
+                ("Soon", "1h".to_string()),
+                ("Tomorrow", "1d".to_string()),
+            ]
+        );
+        assert_eq!(reminders[1].summary(), "Due in 1h: Soon");
+
+        let again = scan(&agendas, &thresholds, &now(), &mut fired)
+            .await
+            .unwrap();
+        assert!(again.is_empty());
+
+        // "Tomorrow" moves into the next threshold an hour before it is due.
+        let later = now().checked_add(19.hours().minutes(30)).unwrap();
+        let reminders = scan(&agendas, &thresholds, &later, &mut fired)
+            .await
+            .unwrap();
+        let got: Vec<_> = reminders
+            .iter()
+            .map(|r| (r.agenda.title.as_str(), r.threshold.to_string()))
+            .collect();
+        assert_eq!(
+            got,
+            [
+                ("Soon", "overdue".to_string()),
+                ("Tomorrow", "1h".to_string())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn moved_deadlines_and_terminated_agendas() {
+        let (_dir, agendas) = setup_repo().await;
+        let id = create(&agendas, "Moving", 10.minutes()).await;
+        let done = create(&agendas, "Done", 10.minutes()).await;
+        agendas
+            .update_agenda(
+                done,
+                &AgendaUpdate {
+                    title: None,
+                    agenda_status: Some(AgendaStatus::Terminated),
+                    terminate_at: None,
+                    actor: None,
+                },
+            )
+            .await
+            .unwrap();
+        let mut fired = Fired::default();
+
+        let reminders = scan(&agendas, &[1.hour()], &now(), &mut fired)
+            .await
+            .unwrap();
+        assert_eq!(reminders.len(), 1);
+        assert_eq!(reminders[0].agenda.id, id);
+
+        agendas
+            .update_agenda(
+                id,
+                &AgendaUpdate {
+                    title: None,
+                    agenda_status: None,
+                    terminate_at: Some(now().checked_add(20.minutes()).unwrap().timestamp()),
+                    actor: None,
+                },
+            )
+            .await
+            .unwrap();
+        let reminders = scan(&agendas, &[1.hour()], &now(), &mut fired)
+            .await
+            .unwrap();
+        assert_eq!(reminders.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn keys_of_finished_agendas_are_dropped() {
+        let (_dir, agendas) = setup_repo().await;
+        let done = create(&agendas, "Done", 10.minutes()).await;
+        let deleted = create(&agendas, "Deleted", 10.minutes()).await;
+        let open = create(&agendas, "Open", 10.minutes()).await;
+        let mut fired = Fired::default();
+        scan(&agendas, &[1.hour()], &now(), &mut fired)
+            .await
+            .unwrap();
+        assert_eq!(fired.keys.len(), 3);
+
+        let terminate = AgendaUpdate {
+            title: None,
+            agenda_status: Some(AgendaStatus::Terminated),
+            terminate_at: None,
+            actor: None,
+        };
+        agendas.update_agenda(done, &terminate).await.unwrap();
+        agendas.delete_agenda_by_id(deleted).await.unwrap();
+        let reminders = scan(&agendas, &[1.hour()], &now(), &mut fired)
+            .await
+            .unwrap();
+        assert!(reminders.is_empty());
+        assert_eq!(fired.keys.len(), 1);
+        assert!(
+            fired
+                .keys
+                .iter()
+                .all(|key| key.starts_with(&open.to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn fired_reminders_survive_restarts() {
+        let (dir, agendas) = setup_repo().await;
+        create(&agendas, "Soon", 30.minutes()).await;
+        let path = Fired::path_for(&dir.path().join("finiate.db"));
+        assert_eq!(path.file_name().unwrap(), "finiate.reminders.json");
+
+        let mut fired = Fired::load(path.clone()).unwrap();
+        let reminders = scan(&agendas, &[1.hour()], &now(), &mut fired)
+            .await
+            .unwrap();
+        assert_eq!(reminders.len(), 1);
+
+        let mut reloaded = Fired::load(path).unwrap();
+        let reminders = scan(&agendas, &[1.hour()], &now(), &mut reloaded)
+            .await
+            .unwrap();
+        assert!(reminders.is_empty());
+    }
+
+    #[tokio::test]
+    async fn command_notifier_sees_the_reminder() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out");
+        let command = UserCommand(format!(
+            "printf '%s|%s' \"$FINIATE_THRESHOLD\" \"$FINIATE_TITLE\" > '{}'",
+            out.display()
+        ));
+        let reminder = Reminder {
+            agenda: Agenda {
+                id: uuid::Uuid::now_v7(),
+                title: "Ship".to_string(),
+                agenda_status: AgendaStatus::Pending,
+                initiate_at: now().timestamp(),
+                terminate_at: now().timestamp(),
+            },
+            threshold: Threshold::Overdue,
+        };
+        command.notify(&reminder, &TimeZone::UTC).await.unwrap();
+        assert_eq!(std::fs::read_to_string(out).unwrap(), "overdue|Ship");
+
+        assert!(
+            UserCommand("exit 3".to_string())
+                .notify(&reminder, &TimeZone::UTC)
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,32 @@
+//! Handlers for the domain events the long-running front ends publish.
+//!
+//! `serve`, `rpc` and `mcp` wrap their repositories with [`publishing`], so
+//! every change a client makes goes through one [`EventBus`].
+
+use async_trait::async_trait;
+use domain::{DomainEvent, EventBus, EventHandler, PublishingAgendaRepo, PublishingLogRepo};
+
+/// Writes every event to stderr as one line of JSON, so whoever runs the
+/// front end can follow what its clients change.
+pub struct EventLog;
+
+#[async_trait]
+impl EventHandler for EventLog {
+    async fn handle(&self, event: &DomainEvent) {
+        match serde_json::to_string(event) {
+            Ok(line) => eprintln!("{line}"),
+            Err(e) => eprintln!("warning: could not log event: {e}"),
+        }
+    }
+}
+
+/// Wraps both repositories to publish to a bus with the [`EventLog`]
+/// subscribed.
+pub fn publishing<A, L>(agendas: A, logs: L) -> (PublishingAgendaRepo<A>, PublishingLogRepo<L>) {
+    let bus = EventBus::new();
+    bus.subscribe(EventLog);
+    (
+        PublishingAgendaRepo::new(agendas, bus.clone()),
+        PublishingLogRepo::new(logs, bus),
+    )
+}
@@ -0,0 +1,263 @@
+use domain::*;
+use jiff::{ToSpan, civil::Date, tz::TimeZone};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+const LEVELS: [&str; 5] = ["·", "░", "▒", "▓", "█"];
+/// 256-color palette indices for the five activity levels.
+const COLORS: [u8; 5] = [237, 22, 28, 34, 40];
+
+/// Log activity for one local day.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Activity {
+    /// All logs written that day.
+    pub logs: usize,
+    /// Whether a terminate log or a mark was written, which keeps a streak alive.
+    pub counts_for_streak: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Streaks {
+    /// Consecutive streak days ending today, or yesterday if today has none yet.
+    pub current: usize,
+    pub longest: usize,
+}
+
+/// Buckets logs by the local date they were written on.
+pub fn daily_activity(logs: &[Log], tz: &TimeZone) -> BTreeMap<Date, Activity> {
+    let mut days: BTreeMap<Date, Activity> = BTreeMap::new();
+    for log in logs {
+        let day = days
+            .entry(log.create_at.to_zoned(tz.clone()).date())
+            .or_default();
+        day.logs += 1;
+        if matches!(log.log_type, LogType::Terminate | LogType::CommonLog) {
+            day.counts_for_streak = true;
+        }
+    }
+    days
+}
+
+/// Computes streaks over `days`; the longest one only counts days in `range`.
+pub fn streaks(days: &BTreeMap<Date, Activity>, range: (Date, Date), today: Date) -> Streaks {
+    let streak_days: BTreeSet<Date> = days
+        .iter()
+        .filter(|(_, activity)| activity.counts_for_streak)
+        .map(|(date, _)| *date)
+        .collect();
+
+    let mut longest = 0;
+    let mut run = 0;
+    let mut previous: Option<Date> = None;
+    for date in streak_days.range(range.0..=range.1) {
+        run = match previous {
+            Some(previous) if previous.tomorrow().ok() == Some(*date) => run + 1,
+            _ => 1,
+        };
+        longest = longest.max(run);
+        previous = Some(*date);
+    }
+
+    let mut current = 0;
+    let mut cursor = if streak_days.contains(&today) {
+        Some(today)
+    } else {
+        today.yesterday().ok()
+    };
+    while let Some(date) = cursor.filter(|date| streak_days.contains(date)) {
+        current += 1;
+        cursor = date.yesterday().ok();
+    }
+
+    Streaks { current, longest }
+}
+
+/// Renders a contribution grid for `year`: one row per weekday, one column
+/// per week starting on Monday, with month labels on top.
+pub fn render(year: i16, days: &BTreeMap<Date, Activity>, color: bool) -> String {
+    let Ok(first) = Date::new(year, 1, 1) else {
+        return String::new();
+    };
+    let last = first.last_of_year();
+    let grid_start = first
+        .checked_sub(i64::from(first.weekday().to_monday_zero_offset()).days())
+        .unwrap_or(first);
+    let weeks = (grid_start
+        .until(last)
+        .map(|span| span.get_days())
+        .unwrap_or(0)
+        / 7
+        + 1) as usize;
+    let max = days
+        .range(first..=last)
+        .map(|(_, activity)| activity.logs)
+        .max()
+        .unwrap_or(0);
+
+    let mut out = String::new();
+    let mut labels = vec![' '; weeks * 2];
+    let mut month_start = Some(first);
+    while let Some(start) = month_start.filter(|date| date.year() == year) {
+        let week = (grid_start
+            .until(start)
+            .map(|span| span.get_days())
+            .unwrap_or(0)
+            / 7) as usize;
+        let label = start.strftime("%b").to_string();
+        for (offset, ch) in label.chars().enumerate() {
+            if let Some(slot) = labels.get_mut(week * 2 + offset) {
+                *slot = ch;
+            }
+        }
+        month_start = start.checked_add(1.month()).ok();
+    }
+    writeln!(
+        out,
+        "    {}",
+        labels.into_iter().collect::<String>().trim_end()
+    )
+    .unwrap();
+
+    for (row, label) in ["Mon", "", "Wed", "", "Fri", "", ""]
+        .into_iter()
+        .enumerate()
+    {
+        write!(out, "{label:<4}").unwrap();
+        for week in 0..weeks {
+            let Ok(date) = grid_start.checked_add((week * 7 + row) as i64 * 1.day()) else {
+                continue;
+            };
+            if date < first || date > last {
+                out.push_str("  ");
+                continue;
+            }
+            let logs = days.get(&date).map_or(0, |activity| activity.logs);
+            let level = level(logs, max);
+            if color {
+                write!(out, "\x1b[38;5;{}m■\x1b[0m ", COLORS[level]).unwrap();
+            } else {
+                write!(out, "{} ", LEVELS[level]).unwrap();
+            }
+        }
+        let trimmed = out.trim_end_matches(' ').len();
+        out.truncate(trimmed);
+        out.push('\n');
+    }
+    out
+}
+
+/// Maps a day's log count onto one of five levels relative to the busiest day.
+fn level(logs: usize, max: usize) -> usize {
+    if logs == 0 || max == 0 {
+        0
+    } else {
+        (logs * 4).div_ceil(max).clamp(1, 4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jiff::civil::date;
+
+    fn streak_days(dates: &[Date]) -> BTreeMap<Date, Activity> {
+        dates
+            .iter()
+            .map(|date| {
+                (
+                    *date,
+                    Activity {
+                        logs: 1,
+                        counts_for_streak: true,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn only_terminations_and_marks_count_for_streaks() {
+        let tz = TimeZone::UTC;
+        let log = |log_type, at: &str| Log {
+            id: uuid::Uuid::now_v7(),
+            agenda_id: uuid::Uuid::now_v7(),
+            content: String::new(),
+            create_at: at.parse().expect("timestamp"),
+            log_type,
+        };
+        let logs = [
+            log(LogType::Activate, "2026-01-01T10:00:00Z"),
+            log(LogType::PutOff, "2026-01-01T11:00:00Z"),
+            log(LogType::CommonLog, "2026-01-02T10:00:00Z"),
+        ];
+
+        let days = daily_activity(&logs, &tz);
+
+        assert_eq!(days[&date(2026, 1, 1)].logs, 2);
+        assert!(!days[&date(2026, 1, 1)].counts_for_streak);
+        assert!(days[&date(2026, 1, 2)].counts_for_streak);
+    }
+
+    #[test]
+    fn longest_and_current_streaks() {
+        let days = streak_days(&[
+            date(2026, 3, 1),
+            date(2026, 3, 2),
+            date(2026, 3, 3),
+            date(2026, 3, 10),
+            date(2026, 3, 11),
+        ]);
+        let year = (date(2026, 1, 1), date(2026, 12, 31));
+
+        let today = streaks(&days, year, date(2026, 3, 11));
+        assert_eq!(
+            today,
+            Streaks {
+                current: 2,
+                longest: 3
+            }
+        );
+
+        // A streak stays current until the end of the following day.
+        assert_eq!(streaks(&days, year, date(2026, 3, 12)).current, 2);
+        assert_eq!(streaks(&days, year, date(2026, 3, 13)).current, 0);
+    }
+
+    #[test]
+    fn current_streak_crosses_year_boundary() {
+        let days = streak_days(&[date(2025, 12, 31), date(2026, 1, 1)]);
+        let year = (date(2026, 1, 1), date(2026, 12, 31));
+
+        let streaks = streaks(&days, year, date(2026, 1, 1));
+        assert_eq!(
+            streaks,
+            Streaks {
+                current: 2,
+                longest: 1
+            }
+        );
+    }
+
+    #[test]
+    fn grid_has_a_row_per_weekday() {
+        let days = streak_days(&[date(2026, 1, 1)]);
+        let grid = render(2026, &days, false);
+        let lines: Vec<_> = grid.lines().collect();
+
+        assert_eq!(lines.len(), 8);
+        assert!(lines[0].trim_start().starts_with("Jan"));
+        assert!(lines[0].contains("Dec"));
+        // 2026-01-01 is a Thursday, the first cell of the first column.
+        assert!(lines[4].starts_with("    █"));
+        assert!(lines[1].starts_with("Mon   ·"));
+    }
+
+    #[test]
+    fn levels_scale_with_busiest_day() {
+        assert_eq!(level(0, 10), 0);
+        assert_eq!(level(1, 10), 1);
+        assert_eq!(level(5, 10), 2);
+        assert_eq!(level(10, 10), 4);
+    }
+}
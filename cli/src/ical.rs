@@ -0,0 +1,536 @@
+//! iCalendar (RFC 5545) export and import.
+//!
+//! Agendas map onto VTODO components whose UID is the agenda id, and logs
+//! onto VJOURNAL entries related to their agenda.
+
+use domain::*;
+use jiff::{
+    Timestamp,
+    civil::{Date, DateTime},
+    tz::TimeZone,
+};
+use std::collections::HashMap;
+use std::error::Error;
+use uuid::Uuid;
+
+const PRODID: &str = "-//finiate//finiate//EN";
+/// Namespace for deriving agenda ids from UIDs that are not UUIDs.
+const UID_NAMESPACE: Uuid = Uuid::from_u128(0x5c1f_4a3e_8d2b_4e7a_9f61_0b3c_d4e5_f607);
+const MAX_LINE_OCTETS: usize = 75;
+
+#[derive(Debug, thiserror::Error)]
+pub enum IcalError {
+    #[error("line {line}: {message}")]
+    Syntax { line: usize, message: String },
+    #[error("invalid date-time `{value}`: {source}")]
+    DateTime { value: String, source: jiff::Error },
+}
+
+/// A VTODO read from an iCalendar file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Todo {
+    pub uid: String,
+    pub summary: String,
+    pub status: AgendaStatus,
+    pub due: Option<Timestamp>,
+    pub created: Option<Timestamp>,
+}
+
+impl Todo {
+    /// The agenda id for this entry: the UID itself when it is a UUID, a
+    /// stable name-based UUID otherwise, so re-imports hit the same agenda.
+    pub fn agenda_id(&self) -> Uuid {
+        Uuid::parse_str(&self.uid)
+            .unwrap_or_else(|_| Uuid::new_v5(&UID_NAMESPACE, self.uid.as_bytes()))
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub created: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+}
+
+/// Renders agendas as VTODO and logs as VJOURNAL components.
+pub fn export(agendas: &[Agenda], logs: &[Log], now: Timestamp) -> String {
+    let titles: HashMap<Uuid, &str> = agendas
+        .iter()
+        .map(|agenda| (agenda.id, agenda.title.as_str()))
+        .collect();
+    let stamp = format_utc(now);
+
+    let mut out = String::new();
+    let mut line = |text: String| write_folded(&mut out, &text);
+    line("BEGIN:VCALENDAR".into());
+    line("VERSION:2.0".into());
+    line(format!("PRODID:{PRODID}"));
+
+    for agenda in agendas {
+        line("BEGIN:VTODO".into());
+        line(format!("UID:{}", agenda.id));
+        line(format!("DTSTAMP:{stamp}"));
+        line(format!("CREATED:{}", format_utc(agenda.initiate_at)));
+        line(format!("SUMMARY:{}", escape(&agenda.title)));
+        line(format!("STATUS:{}", status_to_ical(agenda.agenda_status)));
+        line(format!("DUE:{}", format_utc(agenda.terminate_at)));
+        line("END:VTODO".into());
+    }
+
+    for log in logs {
+        let title = titles.get(&log.agenda_id).copied().unwrap_or_default();
+        line("BEGIN:VJOURNAL".into());
+        line(format!("UID:{}", log.id));
+        line(format!("DTSTAMP:{stamp}"));
+        line(format!("DTSTART:{}", format_utc(log.create_at)));
+        line(format!(
+            "SUMMARY:{}",
+            escape(&format!("{}: {title}", log.log_type))
+        ));
+        if !log.content.is_empty() {
+            line(format!("DESCRIPTION:{}", escape(&log.content)));
+        }
+        line(format!(
+            "CATEGORIES:{}",
+            log.log_type.to_string().to_uppercase()
+        ));
+        line(format!("RELATED-TO:{}", log.agenda_id));
+        line("END:VJOURNAL".into());
+    }
+
+    line("END:VCALENDAR".into());
+    out
+}
+
+/// Reads the VTODO components of an iCalendar document.
+///
+/// Floating and date-only values are interpreted in `tz`; a date-only DUE
+/// means the end of that day.
+pub fn parse_todos(text: &str, tz: &TimeZone) -> Result<Vec<Todo>, IcalError> {
+    let mut todos = Vec::new();
+    let mut current: Option<Todo> = None;
+    let mut depth = 0usize;
+
+    for (line_no, line) in unfold(text) {
+        let property = Property::parse(&line).ok_or_else(|| IcalError::Syntax {
+            line: line_no,
+            message: format!("malformed content line `{line}`"),
+        })?;
+        match property.name.as_str() {
+            "BEGIN" => {
+                depth += 1;
+                if property.value.eq_ignore_ascii_case("VTODO") {
+                    current = Some(Todo {
+                        uid: String::new(),
+                        summary: String::new(),
+                        status: AgendaStatus::Pending,
+                        due: None,
+                        created: None,
+                    });
+                }
+            }
+            "END" => {
+                depth = depth.checked_sub(1).ok_or_else(|| IcalError::Syntax {
+                    line: line_no,
+                    message: "END without BEGIN".to_string(),
+                })?;
+                if property.value.eq_ignore_ascii_case("VTODO") {
+                    let todo = current.take().ok_or_else(|| IcalError::Syntax {
+                        line: line_no,
+                        message: "END:VTODO without BEGIN:VTODO".to_string(),
+                    })?;
+                    if todo.uid.is_empty() {
+                        return Err(IcalError::Syntax {
+                            line: line_no,
+                            message: "VTODO without UID".to_string(),
+                        });
+                    }
+                    todos.push(todo);
+                }
+            }
+            name => {
+                let Some(todo) = current.as_mut() else {
+                    continue;
+                };
+                match name {
+                    "UID" => todo.uid = property.value.trim().to_string(),
+                    "SUMMARY" => todo.summary = unescape(&property.value),
+                    "STATUS" => todo.status = status_from_ical(&property.value),
+                    "DUE" => todo.due = Some(property.timestamp(tz, true)?),
+                    "CREATED" => todo.created = Some(property.timestamp(tz, false)?),
+                    _ => {}
+                }
+            }
+        }
+    }
+    Ok(todos)
+}
+
+/// Creates or updates one agenda per VTODO; importing the same file twice
+/// leaves the database unchanged.
+pub async fn import<A>(
+    agendas: &A,
+    todos: &[Todo],
+    default_due: Timestamp,
+) -> Result<ImportSummary, Box<dyn Error + Send + Sync>>
+where
+    A: AgendaRepo + Sync,
+{
+    let mut summary = ImportSummary::default();
+    for todo in todos {
+        let id = todo.agenda_id();
+        let terminate_at = todo.due.unwrap_or(default_due);
+        match agendas.get_agenda_by_id(id).await? {
+            None => {
+                agendas
+                    .insert_agenda(&Agenda {
+                        id,
+                        title: todo.summary.clone(),
+                        agenda_status: todo.status,
+                        initiate_at: todo.created.unwrap_or_else(Timestamp::now),
+                        terminate_at,
+                    })
+                    .await?;
+                summary.created += 1;
+            }
+            Some(existing) => {
+                let update = AgendaUpdate {
+                    title: (existing.title != todo.summary).then(|| todo.summary.clone()),
+                    agenda_status: (existing.agenda_status != todo.status).then_some(todo.status),
+                    // iCalendar only carries whole seconds.
+                    terminate_at: (todo.due.is_some()
+                        && existing.terminate_at.as_second() != terminate_at.as_second())
+                    .then_some(terminate_at),
+                    actor: Some("import".to_string()),
+                };
+                if update.title.is_none()
+                    && update.agenda_status.is_none()
+                    && update.terminate_at.is_none()
+                {
+                    summary.unchanged += 1;
+                } else {
+                    agendas.update_agenda(id, &update).await?;
+                    summary.updated += 1;
+                }
+            }
+        }
+    }
+    Ok(summary)
+}
+
+fn status_to_ical(status: AgendaStatus) -> &'static str {
+    match status {
+        AgendaStatus::Pending => "NEEDS-ACTION",
+        AgendaStatus::Ongoing => "IN-PROCESS",
+        AgendaStatus::Terminated => "COMPLETED",
+    }
+}
+
+fn status_from_ical(value: &str) -> AgendaStatus {
+    match value.trim().to_ascii_uppercase().as_str() {
+        "IN-PROCESS" => AgendaStatus::Ongoing,
+        "COMPLETED" | "CANCELLED" => AgendaStatus::Terminated,
+        _ => AgendaStatus::Pending,
+    }
+}
+
+fn format_utc(timestamp: Timestamp) -> String {
+    timestamp.strftime("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '\\' => out.push_str("\\\\"),
+            ';' => out.push_str("\\;"),
+            ',' => out.push_str("\\,"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            ch => out.push(ch),
+        }
+    }
+    out
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            out.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Writes one content line, folding it at 75 octets as RFC 5545 requires.
+fn write_folded(out: &mut String, line: &str) {
+    let mut budget = MAX_LINE_OCTETS;
+    let mut used = 0;
+    for ch in line.chars() {
+        if used + ch.len_utf8() > budget {
+            out.push_str("\r\n ");
+            // The leading space of a continuation line counts toward its length.
+            budget = MAX_LINE_OCTETS - 1;
+            used = 0;
+        }
+        out.push(ch);
+        used += ch.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+/// Joins folded lines, yielding each logical line with its starting line number.
+fn unfold(text: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (index, raw) in text.lines().enumerate() {
+        let raw = raw.strip_suffix('\r').unwrap_or(raw);
+        if let Some(rest) = raw.strip_prefix([' ', '\t'])
+            && let Some((_, last)) = lines.last_mut()
+        {
+            last.push_str(rest);
+        } else if !raw.is_empty() {
+            lines.push((index + 1, raw.to_string()));
+        }
+    }
+    lines
+}
+
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn parse(line: &str) -> Option<Property> {
+        let mut in_quotes = false;
+        let mut value_start = None;
+        for (index, ch) in line.char_indices() {
+            match ch {
+                '"' => in_quotes = !in_quotes,
+                ':' if !in_quotes => {
+                    value_start = Some(index);
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let value_start = value_start?;
+        let mut head = line[..value_start].split(';');
+        let name = head.next()?.trim().to_ascii_uppercase();
+        if name.is_empty() {
+            return None;
+        }
+        let params = head
+            .filter_map(|param| {
+                let (key, value) = param.split_once('=')?;
+                Some((
+                    key.trim().to_ascii_uppercase(),
+                    value.trim_matches('"').to_string(),
+                ))
+            })
+            .collect();
+        Some(Property {
+            name,
+            params,
+            value: line[value_start + 1..].to_string(),
+        })
+    }
+
+    fn param(&self, key: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn timestamp(&self, tz: &TimeZone, end_of_day: bool) -> Result<Timestamp, IcalError> {
+        let value = self.value.trim();
+        let error = |source| IcalError::DateTime {
+            value: value.to_string(),
+            source,
+        };
+        let zone = match self.param("TZID") {
+            Some(name) => TimeZone::get(name).unwrap_or_else(|_| tz.clone()),
+            None => tz.clone(),
+        };
+
+        if self.param("VALUE") == Some("DATE") || value.len() == "YYYYMMDD".len() {
+            let date = Date::strptime("%Y%m%d", value).map_err(error)?;
+            let time = if end_of_day {
+                date.at(23, 59, 59, 999_000_000)
+            } else {
+                date.at(0, 0, 0, 0)
+            };
+            return Ok(time.to_zoned(zone).map_err(error)?.timestamp());
+        }
+        if let Some(utc) = value.strip_suffix('Z') {
+            let datetime = DateTime::strptime("%Y%m%dT%H%M%S", utc).map_err(error)?;
+            return Ok(datetime.to_zoned(TimeZone::UTC).map_err(error)?.timestamp());
+        }
+        let datetime = DateTime::strptime("%Y%m%dT%H%M%S", value).map_err(error)?;
+        Ok(datetime.to_zoned(zone).map_err(error)?.timestamp())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use storage::SqliteAgendaRepo;
+
+    fn agenda(title: &str, status: AgendaStatus) -> Agenda {
+        Agenda {
+            id: Uuid::now_v7(),
+            title: title.to_string(),
+            agenda_status: status,
+            initiate_at: "2026-01-02T03:04:05Z".parse().unwrap(),
+            terminate_at: "2026-01-09T18:00:00Z".parse().unwrap(),
+        }
+    }
+
+    async fn setup_repo() -> (tempfile::TempDir, SqliteAgendaRepo) {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let pool = storage::init_db(&dir.path().join("finiate.db"))
+            .await
+            .expect("init db");
+        let (agenda_repo, _) = storage::create_repos(&pool);
+        (dir, agenda_repo)
+    }
+
+    #[test]
+    fn export_maps_agendas_and_logs() {
+        let agenda = agenda("Plan, then; ship", AgendaStatus::Ongoing);
+        let log = Log {
+            id: Uuid::now_v7(),
+            agenda_id: agenda.id,
+            content: "line one\nline two".to_string(),
+            create_at: "2026-01-03T10:00:00Z".parse().unwrap(),
+            log_type: LogType::CommonLog,
+        };
+
+        let ics = export(
+            std::slice::from_ref(&agenda),
+            std::slice::from_ref(&log),
+            Timestamp::UNIX_EPOCH,
+        );
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.contains(&format!("UID:{}\r\n", agenda.id)));
+        assert!(ics.contains("SUMMARY:Plan\\, then\\; ship\r\n"));
+        assert!(ics.contains("STATUS:IN-PROCESS\r\n"));
+        assert!(ics.contains("CREATED:20260102T030405Z\r\n"));
+        assert!(ics.contains("DUE:20260109T180000Z\r\n"));
+        assert!(ics.contains("BEGIN:VJOURNAL\r\n"));
+        assert!(ics.contains("DESCRIPTION:line one\\nline two\r\n"));
+        assert!(ics.contains(&format!("RELATED-TO:{}\r\n", agenda.id)));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+    }
+
+    #[test]
+    fn long_lines_are_folded_and_unfolded() {
+        let title = "é".repeat(60);
+        let agenda = agenda(&title, AgendaStatus::Pending);
+        let ics = export(&[agenda], &[], Timestamp::UNIX_EPOCH);
+
+        assert!(ics.split("\r\n").all(|line| line.len() <= MAX_LINE_OCTETS));
+        let todos = parse_todos(&ics, &TimeZone::UTC).expect("parse");
+        assert_eq!(todos[0].summary, title);
+    }
+
+    #[test]
+    fn round_trips_through_export() {
+        let agendas = [
+            agenda("Pending", AgendaStatus::Pending),
+            agenda("Ongoing", AgendaStatus::Ongoing),
+            agenda("Terminated", AgendaStatus::Terminated),
+        ];
+        let ics = export(&agendas, &[], Timestamp::UNIX_EPOCH);
+        let todos = parse_todos(&ics, &TimeZone::UTC).expect("parse");
+
+        assert_eq!(todos.len(), 3);
+        for (todo, agenda) in todos.iter().zip(&agendas) {
+            assert_eq!(todo.agenda_id(), agenda.id);
+            assert_eq!(todo.summary, agenda.title);
+            assert_eq!(todo.status, agenda.agenda_status);
+            assert_eq!(todo.due, Some(agenda.terminate_at));
+            assert_eq!(todo.created, Some(agenda.initiate_at));
+        }
+    }
+
+    #[test]
+    fn parses_foreign_date_forms() {
+        let ics = "BEGIN:VCALENDAR\r\n\
+            BEGIN:VTODO\r\n\
+            UID:task-1@example.com\r\n\
+            SUMMARY:Date only\r\n\
+            DUE;VALUE=DATE:20260301\r\n\
+            STATUS:CANCELLED\r\n\
+            END:VTODO\r\n\
+            BEGIN:VTODO\r\n\
+            UID:task-2@example.com\r\n\
+            SUMMARY:Zoned\r\n\
+            DUE;TZID=Asia/Tokyo:20260301T090000\r\n\
+            END:VTODO\r\n\
+            END:VCALENDAR\r\n";
+        let todos = parse_todos(ics, &TimeZone::UTC).expect("parse");
+
+        assert_eq!(
+            todos[0].due,
+            Some("2026-03-01T23:59:59.999Z".parse().unwrap())
+        );
+        assert_eq!(todos[0].status, AgendaStatus::Terminated);
+        assert_eq!(todos[1].due, Some("2026-03-01T00:00:00Z".parse().unwrap()));
+        assert_eq!(todos[1].status, AgendaStatus::Pending);
+        assert_ne!(todos[0].agenda_id(), todos[1].agenda_id());
+    }
+
+    #[test]
+    fn rejects_todo_without_uid() {
+        let ics = "BEGIN:VCALENDAR\nBEGIN:VTODO\nSUMMARY:x\nEND:VTODO\nEND:VCALENDAR\n";
+        assert!(matches!(
+            parse_todos(ics, &TimeZone::UTC),
+            Err(IcalError::Syntax { line: 4, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn import_is_idempotent_by_uid() {
+        let (_dir, repo) = setup_repo().await;
+        let source = agenda("Write docs", AgendaStatus::Pending);
+        let ics = export(std::slice::from_ref(&source), &[], Timestamp::UNIX_EPOCH);
+        let todos = parse_todos(&ics, &TimeZone::UTC).expect("parse");
+        let default_due = Timestamp::now();
+
+        let first = import(&repo, &todos, default_due).await.expect("import");
+        let second = import(&repo, &todos, default_due).await.expect("re-import");
+
+        assert_eq!(first.created, 1);
+        assert_eq!(
+            second,
+            ImportSummary {
+                unchanged: 1,
+                ..Default::default()
+            }
+        );
+        let stored = repo
+            .get_agenda_by_id(source.id)
+            .await
+            .expect("get")
+            .expect("exists");
+        assert_eq!(stored.initiate_at, source.initiate_at);
+        assert_eq!(repo.count_agendas_by_status(None).await.expect("count"), 1);
+
+        let mut changed = todos.clone();
+        changed[0].status = AgendaStatus::Terminated;
+        let third = import(&repo, &changed, default_due).await.expect("update");
+        assert_eq!(third.updated, 1);
+    }
+}
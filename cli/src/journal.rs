@@ -0,0 +1,337 @@
+//! Markdown and Org-mode journals rendered from the log table.
+//!
+//! Each document starts with metadata (YAML front matter or Org keywords) so
+//! it can be committed to a notes repository as-is.
+
+use clap::ValueEnum;
+use domain::*;
+use jiff::{Timestamp, civil::Date, tz::TimeZone};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum JournalFormat {
+    #[default]
+    Markdown,
+    Org,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum JournalGrouping {
+    /// One document per local day, with a heading per agenda
+    #[default]
+    Day,
+    /// One document per agenda, with a heading per local day
+    Agenda,
+}
+
+/// A rendered document and the file name it should be written to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Document {
+    pub file_name: String,
+    pub contents: String,
+}
+
+pub fn render(
+    agendas: &[Agenda],
+    logs: &[Log],
+    format: JournalFormat,
+    grouping: JournalGrouping,
+    tz: &TimeZone,
+) -> Vec<Document> {
+    let agendas: HashMap<Uuid, &Agenda> =
+        agendas.iter().map(|agenda| (agenda.id, agenda)).collect();
+    let mut logs: Vec<&Log> = logs.iter().collect();
+    logs.sort_by_key(|log| (log.create_at, log.id));
+    let writer = Writer { format, tz };
+
+    match grouping {
+        JournalGrouping::Day => {
+            let mut days: BTreeMap<Date, Vec<&Log>> = BTreeMap::new();
+            for log in logs {
+                days.entry(writer.date(log.create_at))
+                    .or_default()
+                    .push(log);
+            }
+            days.into_iter()
+                .map(|(date, logs)| writer.day(date, &logs, &agendas))
+                .collect()
+        }
+        JournalGrouping::Agenda => group_by_agenda(&logs)
+            .into_iter()
+            .map(|(id, logs)| writer.agenda(id, agendas.get(&id).copied(), &logs))
+            .collect(),
+    }
+}
+
+struct Writer<'a> {
+    format: JournalFormat,
+    tz: &'a TimeZone,
+}
+
+impl Writer<'_> {
+    fn day(&self, date: Date, logs: &[&Log], agendas: &HashMap<Uuid, &Agenda>) -> Document {
+        let groups = group_by_agenda(logs);
+
+        let mut out = String::new();
+        self.metadata(
+            &mut out,
+            &[
+                ("title", format!("Journal {date}")),
+                ("date", date.to_string()),
+                ("agendas", groups.len().to_string()),
+                ("logs", logs.len().to_string()),
+            ],
+        );
+        self.heading(
+            &mut out,
+            1,
+            &format!("Journal {}", date.strftime("%Y-%m-%d (%a)")),
+        );
+        for (id, logs) in groups {
+            let title = agendas
+                .get(&id)
+                .map_or(DELETED, |agenda| agenda.title.as_str());
+            self.heading(&mut out, 2, title);
+            for log in logs {
+                self.entry(&mut out, log);
+            }
+        }
+        Document {
+            file_name: format!("{date}.{}", self.extension()),
+            contents: out,
+        }
+    }
+
+    fn agenda(&self, id: Uuid, agenda: Option<&Agenda>, logs: &[&Log]) -> Document {
+        let title = agenda.map_or(DELETED, |agenda| agenda.title.as_str());
+        let mut metadata = vec![("title", title.to_string()), ("id", id.to_string())];
+        if let Some(agenda) = agenda {
+            metadata.extend([
+                ("status", agenda.agenda_status.to_string()),
+                (
+                    "initiated",
+                    self.local(agenda.initiate_at, "%Y-%m-%d %H:%M"),
+                ),
+                (
+                    "deadline",
+                    self.local(agenda.terminate_at, "%Y-%m-%d %H:%M"),
+                ),
+            ]);
+        }
+        metadata.push(("logs", logs.len().to_string()));
+
+        let mut out = String::new();
+        self.metadata(&mut out, &metadata);
+        self.heading(&mut out, 1, title);
+        let mut current: Option<Date> = None;
+        for log in logs {
+            let date = self.date(log.create_at);
+            if current != Some(date) {
+                self.heading(&mut out, 2, &date.strftime("%Y-%m-%d (%a)").to_string());
+                current = Some(date);
+            }
+            self.entry(&mut out, log);
+        }
+        let short_id = id.simple().to_string();
+        Document {
+            file_name: format!("{}-{}.{}", slug(title), &short_id[..8], self.extension()),
+            contents: out,
+        }
+    }
+
+    fn metadata(&self, out: &mut String, fields: &[(&str, String)]) {
+        match self.format {
+            JournalFormat::Markdown => {
+                out.push_str("---\n");
+                for (key, value) in fields {
+                    // Counts stay numbers; JSON strings are valid YAML scalars
+                    // and need no extra escaping.
+                    let value = match value.parse::<u64>() {
+                        Ok(_) => value.clone(),
+                        Err(_) => serde_json::to_string(value).expect("serialize string"),
+                    };
+                    writeln!(out, "{key}: {value}").unwrap();
+                }
+                out.push_str("---\n\n");
+            }
+            JournalFormat::Org => {
+                for (key, value) in fields {
+                    writeln!(out, "#+{}: {value}", key.to_uppercase()).unwrap();
+                }
+                out.push('\n');
+            }
+        }
+    }
+
+    fn heading(&self, out: &mut String, level: usize, text: &str) {
+        let marker = match self.format {
+            JournalFormat::Markdown => "#",
+            JournalFormat::Org => "*",
+        };
+        if level > 1 {
+            out.push('\n');
+        }
+        writeln!(out, "{} {text}", marker.repeat(level)).unwrap();
+    }
+
+    fn entry(&self, out: &mut String, log: &Log) {
+        let at = match self.format {
+            JournalFormat::Markdown => format!("`{}`", self.local(log.create_at, "%H:%M")),
+            JournalFormat::Org => self.local(log.create_at, "[%Y-%m-%d %a %H:%M]"),
+        };
+        let text = if log.content.is_empty() {
+            label(log.log_type)
+        } else {
+            log.content.as_str()
+        };
+        writeln!(out, "- {at} {} {text}", icon(log.log_type)).unwrap();
+    }
+
+    fn extension(&self) -> &'static str {
+        match self.format {
+            JournalFormat::Markdown => "md",
+            JournalFormat::Org => "org",
+        }
+    }
+
+    fn date(&self, at: Timestamp) -> Date {
+        at.to_zoned(self.tz.clone()).date()
+    }
+
+    fn local(&self, at: Timestamp, format: &str) -> String {
+        at.to_zoned(self.tz.clone()).strftime(format).to_string()
+    }
+}
+
+const DELETED: &str = "(deleted agenda)";
+
+/// Groups logs by agenda, ordered by each agenda's first log.
+fn group_by_agenda<'a>(logs: &[&'a Log]) -> Vec<(Uuid, Vec<&'a Log>)> {
+    let mut groups: Vec<(Uuid, Vec<&Log>)> = Vec::new();
+    for log in logs {
+        match groups.iter_mut().find(|(id, _)| *id == log.agenda_id) {
+            Some((_, logs)) => logs.push(log),
+            None => groups.push((log.agenda_id, vec![log])),
+        }
+    }
+    groups
+}
+
+fn icon(log_type: LogType) -> &'static str {
+    match log_type {
+        LogType::Activate => "▶",
+        LogType::PutOff => "⏸",
+        LogType::Terminate => "✔",
+        LogType::CommonLog => "✎",
+    }
+}
+
+fn label(log_type: LogType) -> &'static str {
+    match log_type {
+        LogType::Activate => "Activated",
+        LogType::PutOff => "Put off",
+        LogType::Terminate => "Terminated",
+        LogType::CommonLog => "Marked",
+    }
+}
+
+/// Lowercase ASCII letters and digits joined by single dashes.
+fn slug(title: &str) -> String {
+    let slug = title
+        .split(|ch: char| !ch.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect::<Vec<_>>()
+        .join("-");
+    if slug.is_empty() {
+        "agenda".to_string()
+    } else {
+        slug
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> (Vec<Agenda>, Vec<Log>) {
+        let agenda = |title: &str| Agenda {
+            id: Uuid::now_v7(),
+            title: title.to_string(),
+            agenda_status: AgendaStatus::Pending,
+            initiate_at: "2026-05-01T08:00:00Z".parse().unwrap(),
+            terminate_at: "2026-05-03T17:00:00Z".parse().unwrap(),
+        };
+        let agendas = vec![agenda("Write \"docs\""), agenda("Fix bug")];
+        let log = |agenda: &Agenda, at: &str, log_type, content: &str| Log {
+            id: Uuid::now_v7(),
+            agenda_id: agenda.id,
+            content: content.to_string(),
+            create_at: at.parse().unwrap(),
+            log_type,
+        };
+        let logs = vec![
+            log(&agendas[0], "2026-05-01T09:00:00Z", LogType::Activate, ""),
+            log(
+                &agendas[0],
+                "2026-05-01T10:30:00Z",
+                LogType::CommonLog,
+                "outline",
+            ),
+            log(
+                &agendas[1],
+                "2026-05-01T23:30:00Z",
+                LogType::Terminate,
+                "fixed",
+            ),
+        ];
+        (agendas, logs)
+    }
+
+    #[test]
+    fn daily_markdown_uses_local_days() {
+        let (agendas, logs) = fixture();
+        let tz = TimeZone::get("Asia/Tokyo").expect("tz");
+        let documents = render(
+            &agendas,
+            &logs,
+            JournalFormat::Markdown,
+            JournalGrouping::Day,
+            &tz,
+        );
+
+        // 23:30 UTC is already the next day in Tokyo.
+        let names: Vec<_> = documents.iter().map(|doc| doc.file_name.as_str()).collect();
+        assert_eq!(names, ["2026-05-01.md", "2026-05-02.md"]);
+        assert_eq!(
+            documents[0].contents,
+            "---\ntitle: \"Journal 2026-05-01\"\ndate: \"2026-05-01\"\nagendas: 1\nlogs: 2\n---\n\n\
+             # Journal 2026-05-01 (Fri)\n\n## Write \"docs\"\n- `18:00` ▶ Activated\n- `19:30` ✎ outline\n"
+        );
+    }
+
+    #[test]
+    fn per_agenda_org_documents() {
+        let (agendas, logs) = fixture();
+        let documents = render(
+            &agendas,
+            &logs,
+            JournalFormat::Org,
+            JournalGrouping::Agenda,
+            &TimeZone::UTC,
+        );
+
+        assert_eq!(documents.len(), 2);
+        assert!(documents[0].file_name.starts_with("write-docs-"));
+        assert!(documents[0].file_name.ends_with(".org"));
+        let contents = &documents[1].contents;
+        assert!(contents.starts_with("#+TITLE: Fix bug\n#+ID: "));
+        assert!(contents.contains("#+STATUS: pending\n"));
+        assert!(
+            contents
+                .ends_with("* Fix bug\n\n** 2026-05-01 (Fri)\n- [2026-05-01 Fri 23:30] ✔ fixed\n")
+        );
+    }
+}
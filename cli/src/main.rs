@@ -1,87 +1,1106 @@
-use clap::Parser;
+mod archive;
+mod backup;
+mod complete;
+mod config;
+mod daemon;
+mod events;
+mod heatmap;
+mod ical;
+mod journal;
+mod mcp;
+mod output;
+mod passphrase;
+mod report;
+mod rpc;
+mod slot;
+mod stats;
+mod sync;
+mod taskwarrior;
+mod time;
+mod todotxt;
+mod webhook;
 
+use backup::{Backup, Conflict, RestoreMode};
+use clap::{CommandFactory, Parser};
+use clap_complete::{ArgValueCandidates, CompleteEnv, Shell};
+use config::{Config, ConfigError};
+use domain::{AgendaRepo, AgendaStatus, LogRepo};
+use jiff::{Timestamp, ToSpan, Zoned};
+use journal::{JournalFormat, JournalGrouping};
+use output::Printer;
+use report::{Report, ReportPeriod, ReportStyle, Window};
+use slot::Slots;
+use std::error::Error;
+use std::path::PathBuf;
+
+/// Keep a bounded number of agendas in slots and log your way through them
 #[derive(Parser)]
+#[command(name = "finiate", version)]
 struct Args {
+    /// Named profile from the config file, each with its own database
+    #[arg(long, short, global = true, env = "FINIATE_PROFILE")]
+    profile: Option<String>,
+    /// Path to the config file [default: $XDG_CONFIG_HOME/finiate/config.toml]
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
     #[command(subcommand)]
     command: Commands,
 }
 
 #[derive(Parser, Debug)]
 enum Commands {
+    /// Manage the agendas occupying slots
     Slot {
         #[command(subcommand)]
         slot_command: SlotCommands,
     },
-    Mark {
-        mark_log: Option<String>,
+    /// Write a log on the current agenda
+    Mark { mark_log: Option<String> },
+    /// Put off the current agenda, keeping its slot
+    Putoff { putoff_log: Option<String> },
+    /// Terminate the current agenda, freeing its slot
+    Terminate { terminate_log: Option<String> },
+    /// Show an agenda by id
+    Show {
+        id: uuid::Uuid,
+        /// Also list every change made to its title, status and deadline
+        #[arg(long)]
+        revisions: bool,
+    },
+    /// Summarize a day or week of activity, ready to paste into a team channel
+    Report {
+        period: ReportPeriod,
+        /// Any day inside the period to report on [default: today]
+        #[arg(long)]
+        date: Option<jiff::civil::Date>,
+        #[arg(long, value_enum, default_value_t)]
+        style: ReportStyle,
+    },
+    /// Throughput, lead time and put-off statistics over a period
+    Stats {
+        /// First day of the period [default: 29 days before --until]
+        #[arg(long)]
+        since: Option<jiff::civil::Date>,
+        /// Last day of the period [default: today]
+        #[arg(long)]
+        until: Option<jiff::civil::Date>,
+        /// Print JSON regardless of the configured output format
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show a contribution grid of daily log activity and streaks
+    Heatmap {
+        /// Calendar year to show [default: current year]
+        #[arg(long)]
+        year: Option<i16>,
+    },
+    /// Export agendas and logs for other tools
+    Export {
+        #[command(subcommand)]
+        format: ExportCommands,
+    },
+    /// Import agendas from other tools
+    Import {
+        #[command(subcommand)]
+        format: ImportCommands,
+    },
+    /// Watch deadlines in the background and send reminders as they approach;
+    /// also delivers webhooks when any are configured
+    Daemon {
+        /// Scan once, send any due reminders and exit (for cron or timers)
+        #[arg(long)]
+        once: bool,
+    },
+    /// Serve the Model Context Protocol on stdin/stdout for AI assistants
+    Mcp,
+    /// Speak JSON-RPC 2.0 on stdin/stdout, one message per line, for editor plugins
+    Rpc,
+    /// Serve the HTTP API for other local tools until interrupted
+    Serve {
+        /// Address to listen on [default: server.bind from the config]
+        #[arg(long)]
+        bind: Option<std::net::SocketAddr>,
     },
-    Putoff {
-        putoff_log: Option<String>,
+    /// Deliver and inspect outgoing webhooks
+    Webhooks {
+        #[command(subcommand)]
+        action: WebhookCommands,
+    },
+    /// List, restore or purge deleted agendas and logs
+    Trash {
+        #[command(subcommand)]
+        action: TrashCommands,
+    },
+    /// Reverse the last command that changed agendas or logs
+    Undo,
+    /// Exchange changes with other devices through the shared sync.dir folder
+    Sync,
+    /// Maintain the database file
+    Db {
+        #[command(subcommand)]
+        action: DbCommands,
     },
-    Terminate {
-        terminate_log: Option<String>,
+    /// Maintain the event-sourced backend's stream and snapshots
+    Projection {
+        #[command(subcommand)]
+        action: ProjectionCommands,
+    },
+    /// Print the shell completion script
+    Completions { shell: Shell },
+    /// Print the man page, or write one page per subcommand into a directory
+    Man {
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
     },
 }
 
+#[derive(Parser, Debug)]
+enum WebhookCommands {
+    /// Send the events that are due, then exit
+    Deliver {
+        /// Keep delivering every daemon.interval until interrupted
+        #[arg(long)]
+        watch: bool,
+    },
+    /// Count pending, delivered and failed events
+    Status,
+    /// Give events that ran out of attempts another round
+    Retry,
+}
+
+#[derive(Parser, Debug)]
+enum TrashCommands {
+    /// Show what was deleted, most recent first
+    List,
+    /// Take an agenda (with its logs) or a log out of the trash
+    Restore { id: uuid::Uuid },
+    /// Delete trashed items for good
+    Purge {
+        /// Only purge items deleted longer ago than this span, like `30d`
+        #[arg(long)]
+        older_than: Option<jiff::Span>,
+    },
+}
+
+#[derive(Parser, Debug)]
+enum DbCommands {
+    /// Encrypt the database, change its passphrase, or decrypt it
+    Rekey {
+        /// Remove the encryption instead of setting a new passphrase
+        #[arg(long)]
+        decrypt: bool,
+    },
+    /// Copy the database while it stays in use; the copy is encrypted like
+    /// the database
+    Backup {
+        /// Where to write the copy [default: a new file in backup.dir]
+        file: Option<PathBuf>,
+    },
+    /// Check the database file for corruption and dangling references
+    Verify,
+    /// Replace the database with a backup, after checking the backup; the
+    /// current file is kept in backup.dir
+    Restore { file: PathBuf },
+}
+
+#[derive(Parser, Debug)]
+enum ProjectionCommands {
+    /// Drop every snapshot and take fresh ones by folding the whole stream
+    Rebuild,
+    /// Fill an empty event stream from the agenda and log tables, before
+    /// switching a database to `backend = "event_sourced"`
+    Seed,
+}
+
 #[derive(Parser, Debug)]
 enum SlotCommands {
     // Define subcommands for Slot here
+    /// Add an agenda to a free slot
     Add {
         title: Option<String>,
+        /// Deadline: a span like `2d`, a date, a date-time or an RFC 3339 timestamp
         #[arg(long, short)]
         terminate_at: Option<String>,
     },
+    /// Make a slot current, or list the slots when none is given
     Set {
+        #[arg(add = ArgValueCandidates::new(complete::slot_numbers))]
         slot: Option<u8>,
     },
+    /// Move an agenda (the current one by default) back to pending
     Shelve {
+        #[arg(add = ArgValueCandidates::new(complete::slot_titles))]
         title: Option<String>,
     },
+    /// Show the logs of the past week
     History,
 }
 
-#[tokio::main]
-async fn main() {
+#[derive(Parser, Debug)]
+enum ExportCommands {
+    /// iCalendar: agendas as VTODO entries, logs as VJOURNAL entries
+    Ical {
+        /// File to write [default: stdout]
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// todo.txt: one line per agenda, terminated ones marked done
+    Todotxt {
+        /// File to write [default: stdout]
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Markdown or Org-mode journal of the logs, one document per day or agenda
+    Journal {
+        /// Directory to write the documents to
+        #[arg(long)]
+        out_dir: PathBuf,
+        #[arg(long, value_enum, default_value_t)]
+        format: JournalFormat,
+        /// Write one document per day or per agenda
+        #[arg(long, value_enum, default_value_t)]
+        by: JournalGrouping,
+        /// First day to include [default: the first log]
+        #[arg(long)]
+        since: Option<jiff::civil::Date>,
+        /// Last day to include [default: the last log]
+        #[arg(long)]
+        until: Option<jiff::civil::Date>,
+    },
+    /// Full backup: every agenda and log, ids and timestamps preserved
+    #[command(long_flag = "json")]
+    Json {
+        /// File to write [default: stdout]
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Parser, Debug)]
+enum ImportCommands {
+    /// iCalendar VTODO entries; re-importing a file updates instead of duplicating
+    Ical { file: PathBuf },
+    /// todo.txt tasks; projects, contexts and extensions stay in the title
+    Todotxt { file: PathBuf },
+    /// Taskwarrior `task export` JSON; tasks already imported are skipped
+    Taskwarrior {
+        file: PathBuf,
+        /// Only print what would be imported
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Restore a backup made by `export --json`
+    #[command(long_flag = "json")]
+    Json {
+        file: PathBuf,
+        /// How to treat agendas and logs already in the database
+        #[arg(long, value_enum, default_value_t)]
+        mode: RestoreMode,
+    },
+}
+
+fn main() {
+    CompleteEnv::with_factory(Args::command)
+        .var(complete::COMPLETE_VAR)
+        .complete();
+
     let args = Args::parse();
+    let result = match args.command {
+        Commands::Completions { shell } => {
+            complete::write_registration(shell, &mut std::io::stdout()).map_err(Into::into)
+        }
+        Commands::Man { out_dir } => man(out_dir),
+        _ => tokio::runtime::Runtime::new()
+            .map_err(Into::into)
+            .and_then(|runtime| runtime.block_on(run(args))),
+    };
+    if let Err(error) = result {
+        eprintln!("error: {error}");
+        std::process::exit(1);
+    }
+}
+
+fn man(out_dir: Option<PathBuf>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let command = Args::command();
+    match out_dir {
+        Some(dir) => {
+            std::fs::create_dir_all(&dir)?;
+            clap_mangen::generate_to(command, &dir)?;
+        }
+        None => clap_mangen::Man::new(command).render(&mut std::io::stdout())?,
+    }
+    Ok(())
+}
+
+/// Writes `contents` to `path`, or to stdout when no path is given.
+fn write_output(path: Option<&std::path::Path>, contents: &str) -> std::io::Result<()> {
+    match path {
+        Some(path) => std::fs::write(path, contents),
+        None => std::io::Write::write_all(&mut std::io::stdout(), contents.as_bytes()),
+    }
+}
+
+/// The name `undo` reports for commands that change agendas or logs.
+fn journaled(command: &Commands) -> Option<&'static str> {
+    Some(match command {
+        Commands::Slot { slot_command } => match slot_command {
+            SlotCommands::Add { .. } => "slot add",
+            SlotCommands::Set { slot: Some(_) } => "slot set",
+            SlotCommands::Shelve { .. } => "slot shelve",
+            SlotCommands::Set { slot: None } | SlotCommands::History => return None,
+        },
+        Commands::Mark { .. } => "mark",
+        Commands::Putoff { .. } => "putoff",
+        Commands::Terminate { .. } => "terminate",
+        Commands::Import { format } => match format {
+            ImportCommands::Ical { .. } => "import ical",
+            ImportCommands::Todotxt { .. } => "import todotxt",
+            ImportCommands::Taskwarrior { dry_run: false, .. } => "import taskwarrior",
+            ImportCommands::Json { .. } => "import json",
+            ImportCommands::Taskwarrior { dry_run: true, .. } => return None,
+        },
+        Commands::Sync => "sync",
+        Commands::Trash { action } => match action {
+            TrashCommands::Restore { .. } => "trash restore",
+            TrashCommands::Purge { .. } => "trash purge",
+            TrashCommands::List => return None,
+        },
+        _ => return None,
+    })
+}
+
+/// The trash, the undo journal and sync track the agenda and log tables,
+/// which the other backends do not write.
+fn require_relational(config: &Config, command: &str) -> Result<(), String> {
+    let keeper = match config.backend {
+        storage::Backend::Relational => return Ok(()),
+        storage::Backend::EventSourced => "the event stream",
+        storage::Backend::Git => "git",
+    };
+    Err(format!(
+        "`{command}` needs the relational backend; {keeper} keeps its own history"
+    ))
+}
 
-    match args.command {
-        Commands::Slot { slot_command } => {
-            match slot_command {
-                SlotCommands::Add {
-                    title,
-                    terminate_at,
-                } => {
+/// How to open the configured database, asking for its passphrase if need
+/// be; `stdin` tells whether it may be piped in (see [`passphrase::unlock`]).
+fn db_options(
+    config: &Config,
+    stdin: bool,
+) -> Result<storage::DbOptions, Box<dyn Error + Send + Sync>> {
+    Ok(storage::DbOptions {
+        passphrase: passphrase::unlock(config, stdin)?,
+        journal_mode: config.sqlite.journal_mode,
+        synchronous: config.sqlite.synchronous,
+        busy_timeout: config.sqlite.busy_timeout,
+    })
+}
+
+/// Opens the configured database, unlocking it when it is encrypted.
+async fn open_db(
+    config: &Config,
+    options: &storage::DbOptions,
+) -> Result<storage::SqlitePool, Box<dyn Error + Send + Sync>> {
+    let path = config.database.display();
+    let written = config.database.metadata().is_ok_and(|meta| meta.len() > 0);
+    match (&config.encryption, storage::is_encrypted(&config.database)?) {
+        (None, true) => {
+            return Err(format!("{path} is encrypted; set up [encryption] to open it").into());
+        }
+        (Some(_), false) if written => {
+            return Err(
+                format!("{path} is not encrypted yet; `finiate db rekey` encrypts it").into(),
+            );
+        }
+        _ => {}
+    }
+    Ok(storage::init_db_with(&config.database, options).await?)
+}
+
+/// Runs before the database is opened, since the file is replaced.
+async fn rekey(config: &Config, decrypt: bool) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let path = &config.database;
+    if !path.exists() {
+        return Err(format!("there is no database at {}", path.display()).into());
+    }
+    let current = match storage::is_encrypted(path)? {
+        true => Some(passphrase::unlock(config, true)?.ok_or_else(|| {
+            format!(
+                "{} is encrypted; set up [encryption] with its current passphrase",
+                path.display()
+            )
+        })?),
+        false => None,
+    };
+    if decrypt && current.is_none() {
+        println!("{} is not encrypted.", path.display());
+        return Ok(());
+    }
+    let new = match decrypt {
+        true => None,
+        false => Some(passphrase::choose()?),
+    };
+    storage::rekey(path, current.as_deref(), new.as_deref()).await?;
+
+    match (&new, &config.encryption) {
+        (None, _) => println!(
+            "Decrypted {}; remove [encryption] from the config file.",
+            path.display()
+        ),
+        (Some(new), Some(config::Encryption::Keyring)) => {
+            passphrase::remember(config, new)?;
+            println!(
+                "Encrypted {} and stored the passphrase in the keyring.",
+                path.display()
+            );
+        }
+        (Some(_), Some(config::Encryption::Passphrase(_))) => println!(
+            "Encrypted {}; update encryption.passphrase in the config file.",
+            path.display()
+        ),
+        (Some(_), Some(config::Encryption::Prompt)) => {
+            println!("Encrypted {}.", path.display())
+        }
+        (Some(_), None) => println!(
+            "Encrypted {}; set up [encryption] in the config file to open it.",
+            path.display()
+        ),
+    }
+    Ok(())
+}
+
+/// Runs before the database is opened, since the file is replaced.
+async fn restore(
+    config: &Config,
+    file: &std::path::Path,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if !file.is_file() {
+        return Err(format!("there is no backup at {}", file.display()).into());
+    }
+    match (&config.encryption, storage::is_encrypted(file)?) {
+        (None, true) => {
+            return Err(format!(
+                "{} is encrypted; set up [encryption] with its passphrase to restore it",
+                file.display()
+            )
+            .into());
+        }
+        (Some(_), false) => {
+            return Err(format!(
+                "{} is not encrypted; remove [encryption] to restore it",
+                file.display()
+            )
+            .into());
+        }
+        _ => {}
+    }
+    let options = db_options(config, true)?;
+    if config.database.metadata().is_ok_and(|meta| meta.len() > 0) {
+        // Recent changes may still be in the write-ahead log; a database too
+        // damaged to open is kept as it is.
+        storage::checkpoint(&config.database, &options).await.ok();
+        let saved = archive::save_file(config)?;
+        println!("Kept the current database as {}.", saved.display());
+    }
+    storage::restore(&config.database, file, &options).await?;
+    println!(
+        "Restored {} from {}.",
+        config.database.display(),
+        file.display()
+    );
+    Ok(())
+}
+
+/// The `db` subcommands that work on the open database.
+async fn maintain(
+    action: DbCommands,
+    config: &Config,
+    pool: &storage::SqlitePool,
+    options: &storage::DbOptions,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    match action {
+        DbCommands::Backup { file: Some(file) } => {
+            storage::backup(pool, &file, options).await?;
+            println!("Backed up the database to {}.", file.display());
+        }
+        DbCommands::Backup { file: None } => {
+            let file = archive::take(pool, config, options).await?;
+            println!("Backed up the database to {}.", file.display());
+        }
+        DbCommands::Verify => {
+            let problems = storage::verify(pool).await?;
+            if !problems.is_empty() {
+                for problem in &problems {
+                    println!("{problem}");
+                }
+                return Err(format!(
+                    "{} has {} problem(s); `finiate db restore` brings back a backup",
+                    config.database.display(),
+                    problems.len()
+                )
+                .into());
+            }
+            println!(
+                "{} passed the integrity and foreign key checks.",
+                config.database.display()
+            );
+        }
+        DbCommands::Rekey { .. } | DbCommands::Restore { .. } => unreachable!("handled in run"),
+    }
+    Ok(())
+}
+
+async fn run(args: Args) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let config = Config::load(args.config.as_deref(), args.profile.as_deref())?;
+    match &args.command {
+        Commands::Db {
+            action: DbCommands::Rekey { decrypt },
+        } => return rekey(&config, *decrypt).await,
+        Commands::Db {
+            action: DbCommands::Restore { file },
+        } => return restore(&config, file).await,
+        _ => {}
+    }
+    // `rpc` and `mcp` speak their protocol on stdin.
+    let stdin = !matches!(args.command, Commands::Rpc | Commands::Mcp);
+    let options = db_options(&config, stdin)?;
+    let created = !config.database.exists();
+    let pool = open_db(&config, &options).await?;
+    if let Commands::Db { action } = args.command {
+        return maintain(action, &config, &pool, &options).await;
+    }
+    // Changes are only queued for webhooks while endpoints are configured.
+    let urls: Vec<_> = config
+        .webhooks
+        .iter()
+        .map(|hook| hook.url.clone())
+        .collect();
+    storage::create_outbox(&pool)
+        .register_endpoints(&urls)
+        .await?;
+    // The first command of the day backs the database up; failing to must
+    // not keep anyone from using it.
+    if !created {
+        match archive::daily(&pool, &config, &options).await {
+            Ok(Some(file)) => eprintln!("Backed up the database to {}.", file.display()),
+            Ok(None) => {}
+            Err(error) => eprintln!("warning: daily backup failed: {error}"),
+        }
+    }
+    let journal = storage::create_journal(&pool);
+    let operation = match journaled(&args.command) {
+        Some(command) => Some(journal.begin(command).await?),
+        None => None,
+    };
+    // Even a failed command is journaled, so its partial changes can be undone.
+    let result = execute(args.command, config, &pool).await;
+    if let Some(operation) = operation {
+        journal.finish(operation).await?;
+    }
+    result
+}
+
+async fn execute(
+    command: Commands,
+    config: Config,
+    pool: &storage::SqlitePool,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let printer = Printer::new(&config);
+    let (agenda_repo, log_repo) = storage::open_repos(pool, config.backend, &config.git_dir)?;
+    let slots = Slots::new(&agenda_repo, &log_repo, config.max_slots);
+    // Default deadlines count from now in the configured zone, so they land
+    // on the same day whichever command sets them.
+    let now = Zoned::now().with_time_zone(config.time_zone.clone());
+
+    match command {
+        Commands::Slot { slot_command } => match slot_command {
+            SlotCommands::Add {
+                title,
+                terminate_at,
+            } => {
+                let terminate_at = match terminate_at {
+                    Some(input) => time::parse_deadline(&input, &now, &config.time_zone)?,
+                    None => now.checked_add(config.default_deadline)?.timestamp(),
+                };
+                slots.add(title, terminate_at).await?;
+                printer.slots(&slots.list().await?, config.max_slots);
+            }
+            SlotCommands::Set { slot } => match slot {
+                Some(slot) => printer.agenda("Activated", &slots.set(slot).await?),
+                None => printer.slots(&slots.list().await?, config.max_slots),
+            },
+            SlotCommands::History => {
+                let since = Zoned::now()
+                    .with_time_zone(config.time_zone.clone())
+                    .checked_sub(7.days())?
+                    .timestamp();
+                printer.history(&slots.history(since).await?);
+            }
+            SlotCommands::Shelve { title } => {
+                printer.agenda("Shelved", &slots.shelve(title.as_deref()).await?);
+            }
+        },
+        Commands::Mark { mark_log } => {
+            printer.agenda("Marked", &slots.mark(mark_log).await?);
+        }
+        Commands::Putoff { putoff_log } => {
+            printer.agenda("Put off", &slots.put_off(putoff_log).await?);
+        }
+        Commands::Terminate { terminate_log } => {
+            printer.agenda("Terminated", &slots.terminate(terminate_log).await?);
+        }
+        Commands::Show { id, revisions } => {
+            let agenda = agenda_repo
+                .get_agenda_by_id(id)
+                .await?
+                .ok_or_else(|| format!("no agenda with id {id}"))?;
+            let revisions = if revisions {
+                Some(agenda_repo.get_agenda_revisions(id).await?)
+            } else {
+                None
+            };
+            printer.show(&agenda, revisions.as_deref());
+        }
+        Commands::Report {
+            period,
+            date,
+            style,
+        } => {
+            let date = date
+                .unwrap_or_else(|| Zoned::now().with_time_zone(config.time_zone.clone()).date());
+            let window = Window::new(period, date, &config.time_zone)?;
+            let report = Report::build(&agenda_repo, &log_repo, period, &window).await?;
+            if printer.is_json() {
+                printer.print_json(&serde_json::to_value(&report)?);
+            } else {
+                print!("{}", report.render(style, &config.time_zone));
+            }
+        }
+        Commands::Stats { since, until, json } => {
+            let until = until
+                .unwrap_or_else(|| Zoned::now().with_time_zone(config.time_zone.clone()).date());
+            let since = match since {
+                Some(since) => since,
+                None => until.checked_sub(29.days())?,
+            };
+            let start = since.to_zoned(config.time_zone.clone())?;
+            let end = until.to_zoned(config.time_zone.clone())?.tomorrow()?;
+            let stats = stats::collect(
+                &agenda_repo,
+                &log_repo,
+                start.timestamp(),
+                end.timestamp() - 1.millisecond(),
+            )
+            .await?;
+            if json || printer.is_json() {
+                printer.print_json(&serde_json::to_value(&stats)?);
+            } else {
+                print!("{}", stats::render(&stats, &format!("{since} to {until}")));
+            }
+        }
+        Commands::Heatmap { year } => {
+            let today = Zoned::now().with_time_zone(config.time_zone.clone());
+            let year = year.unwrap_or(today.year());
+            let first = jiff::civil::Date::new(year, 1, 1)?;
+            let last = first.last_of_year();
+            // The current streak runs back from today whatever the year
+            // shown, and may cross into the year before.
+            let fetch_from = first.min(today.date().checked_sub(1.year())?);
+            let fetch_until = last.max(today.date());
+            let logs = log_repo
+                .get_logs_by_time_range(
+                    fetch_from.to_zoned(config.time_zone.clone())?.timestamp(),
+                    fetch_until
+                        .tomorrow()?
+                        .to_zoned(config.time_zone.clone())?
+                        .timestamp()
+                        - 1.millisecond(),
+                )
+                .await?;
+            let days = heatmap::daily_activity(&logs, &config.time_zone);
+            let streaks = heatmap::streaks(&days, (first, last), today.date());
+            if printer.is_json() {
+                let in_year: std::collections::BTreeMap<_, _> = days
+                    .range(first..=last)
+                    .map(|(date, activity)| (date.to_string(), activity.logs))
+                    .collect();
+                printer.print_json(&serde_json::json!({
+                    "year": year,
+                    "days": in_year,
+                    "current_streak": streaks.current,
+                    "longest_streak": streaks.longest,
+                }));
+            } else {
+                print!("{}", heatmap::render(year, &days, printer.color()));
+                let total: usize = days.range(first..=last).map(|(_, day)| day.logs).sum();
+                println!(
+                    "\n{total} logs in {year}  current streak: {} days  longest streak: {} days",
+                    streaks.current, streaks.longest
+                );
+            }
+        }
+        Commands::Export { format } => match format {
+            ExportCommands::Ical { output } => {
+                let agendas = agenda_repo.get_agendas_by_status(None).await?;
+                let logs = log_repo
+                    .get_logs_by_time_range(Timestamp::MIN, Timestamp::MAX)
+                    .await?;
+                write_output(
+                    output.as_deref(),
+                    &ical::export(&agendas, &logs, Timestamp::now()),
+                )?;
+            }
+            ExportCommands::Todotxt { output } => {
+                let agendas = agenda_repo.get_agendas_by_status(None).await?;
+                let logs = log_repo
+                    .get_logs_by_time_range(Timestamp::MIN, Timestamp::MAX)
+                    .await?;
+                write_output(
+                    output.as_deref(),
+                    &todotxt::export(&agendas, &logs, &config.time_zone),
+                )?;
+            }
+            ExportCommands::Journal {
+                out_dir,
+                format,
+                by,
+                since,
+                until,
+            } => {
+                let start = match since {
+                    Some(since) => since.to_zoned(config.time_zone.clone())?.timestamp(),
+                    None => Timestamp::MIN,
+                };
+                let end = match until {
+                    Some(until) => {
+                        until
+                            .to_zoned(config.time_zone.clone())?
+                            .tomorrow()?
+                            .timestamp()
+                            - 1.millisecond()
+                    }
+                    None => Timestamp::MAX,
+                };
+                let agendas = agenda_repo.get_agendas_by_status(None).await?;
+                let logs = log_repo.get_logs_by_time_range(start, end).await?;
+                let documents = journal::render(&agendas, &logs, format, by, &config.time_zone);
+                std::fs::create_dir_all(&out_dir)?;
+                for document in &documents {
+                    std::fs::write(out_dir.join(&document.file_name), &document.contents)?;
+                }
+                println!(
+                    "Wrote {} documents to {}",
+                    documents.len(),
+                    out_dir.display()
+                );
+            }
+            ExportCommands::Json { output } => {
+                let backup = Backup::collect(&agenda_repo, &log_repo).await?;
+                let mut json = serde_json::to_string_pretty(&backup)?;
+                json.push('\n');
+                write_output(output.as_deref(), &json)?;
+            }
+        },
+        Commands::Import { format } => match format {
+            ImportCommands::Ical { file } => {
+                let text = std::fs::read_to_string(&file)?;
+                let todos = ical::parse_todos(&text, &config.time_zone)?;
+                let default_due = now.checked_add(config.default_deadline)?.timestamp();
+                let summary = ical::import(&agenda_repo, &todos, default_due).await?;
+                println!(
+                    "Imported {}: {} created, {} updated, {} unchanged",
+                    file.display(),
+                    summary.created,
+                    summary.updated,
+                    summary.unchanged
+                );
+            }
+            ImportCommands::Todotxt { file } => {
+                let text = std::fs::read_to_string(&file)?;
+                let default_due = now.checked_add(config.default_deadline)?.timestamp();
+                let summary = todotxt::import(
+                    &agenda_repo,
+                    &log_repo,
+                    &text,
+                    &config.time_zone,
+                    default_due,
+                )
+                .await?;
+                println!(
+                    "Imported {} agendas from {}",
+                    summary.imported,
+                    file.display()
+                );
+                let join = |set: &std::collections::BTreeSet<String>| {
+                    set.iter().cloned().collect::<Vec<_>>().join(", ")
+                };
+                for (label, set) in [
+                    ("projects", &summary.projects),
+                    ("contexts", &summary.contexts),
+                    ("extensions kept", &summary.extensions),
+                ] {
+                    if !set.is_empty() {
+                        println!("  {label}: {}", join(set));
+                    }
+                }
+            }
+            ImportCommands::Taskwarrior { file, dry_run } => {
+                let tasks = taskwarrior::parse(&std::fs::read_to_string(&file)?)?;
+                let existing = agenda_repo
+                    .get_agendas_by_status(None)
+                    .await?
+                    .into_iter()
+                    .map(|agenda| agenda.id)
+                    .collect();
+                let default_due = now.checked_add(config.default_deadline)?.timestamp();
+                let plan = taskwarrior::Plan::new(&tasks, &existing, default_due);
+                if !dry_run {
+                    plan.apply(&agenda_repo, &log_repo).await?;
+                }
+                println!(
+                    "{} {} of {} tasks: {} pending, {} terminated, {} logs",
+                    if dry_run { "Would import" } else { "Imported" },
+                    plan.imports.len(),
+                    tasks.len(),
+                    plan.count(AgendaStatus::Pending),
+                    plan.count(AgendaStatus::Terminated),
+                    plan.log_count()
+                );
+                println!(
+                    "Skipped {} already imported, {} deleted, {} recurring templates",
+                    plan.already_imported, plan.deleted, plan.recurring
+                );
+            }
+            ImportCommands::Json { file, mode } => {
+                let backup = Backup::parse(&std::fs::read_to_string(&file)?)?;
+                let summary = backup.restore(&agenda_repo, &log_repo, mode).await?;
+                if printer.is_json() {
+                    printer.print_json(&serde_json::to_value(&summary)?);
+                } else {
+                    if mode == RestoreMode::Replace {
+                        println!(
+                            "Removed {} agendas and {} logs",
+                            summary.removed_agendas, summary.removed_logs
+                        );
+                    }
                     println!(
-                        "Adding slot with title: {:?} and terminate_at: {:?}",
-                        title, terminate_at
+                        "Restored {}: {} agendas and {} logs added, {} unchanged, {} conflicts",
+                        file.display(),
+                        summary.added_agendas,
+                        summary.added_logs,
+                        summary.unchanged,
+                        summary.conflicts.len()
                     );
-                    // Implement the logic for adding a slot here
+                    for conflict in &summary.conflicts {
+                        match conflict {
+                            Conflict::Agenda(id) => println!("  conflict: agenda {id} kept local"),
+                            Conflict::Log(id) => println!("  conflict: log {id} kept local"),
+                        }
+                    }
                 }
-                SlotCommands::Set { slot } => {
-                    println!("Setting slot: {:?}", slot);
-                    // Implement the logic for setting slots here
+            }
+        },
+        Commands::Daemon { once } => {
+            let notifiers = daemon::notifiers(&config.daemon)?;
+            let mut fired = daemon::Fired::load(daemon::Fired::path_for(&config.database))?;
+            let outbox = storage::create_outbox(pool);
+            let dispatcher = webhook::Dispatcher::new(&outbox, &config.webhooks)?;
+            daemon::run(
+                &agenda_repo,
+                &config.daemon,
+                &config.time_zone,
+                &notifiers,
+                &mut fired,
+                (!config.webhooks.is_empty()).then_some(&dispatcher),
+                once,
+            )
+            .await?;
+        }
+        Commands::Webhooks { action } => {
+            let outbox = storage::create_outbox(pool);
+            match action {
+                WebhookCommands::Deliver { watch } => {
+                    let dispatcher = webhook::Dispatcher::new(&outbox, &config.webhooks)?;
+                    loop {
+                        let summary = dispatcher.deliver_due(Timestamp::now()).await?;
+                        if printer.is_json() {
+                            printer.print_json(&serde_json::to_value(summary)?);
+                        } else if summary.delivered + summary.failed > 0 || !watch {
+                            println!(
+                                "Delivered {} event(s), {} failed.",
+                                summary.delivered, summary.failed
+                            );
+                        }
+                        if !watch {
+                            break;
+                        }
+                        tokio::select! {
+                            _ = tokio::time::sleep(config.daemon.interval) => {}
+                            _ = tokio::signal::ctrl_c() => break,
+                        }
+                    }
                 }
-                SlotCommands::History => {
-                    println!("Showing slot history");
-                    // Implement the logic for showing slot history here
+                WebhookCommands::Status => {
+                    let counts = outbox.counts(webhook::MAX_ATTEMPTS).await?;
+                    if printer.is_json() {
+                        printer.print_json(&serde_json::json!({
+                            "pending": counts.pending,
+                            "delivered": counts.delivered,
+                            "failed": counts.dead,
+                        }));
+                    } else {
+                        println!(
+                            "{} pending, {} delivered, {} failed after {} attempts",
+                            counts.pending,
+                            counts.delivered,
+                            counts.dead,
+                            webhook::MAX_ATTEMPTS
+                        );
+                    }
                 }
-                SlotCommands::Shelve { title } => {
-                    println!("Shelving current slot with title: {:?}", title);
-                    // Implement the logic for shelving a slot here
+                WebhookCommands::Retry => {
+                    let revived = outbox
+                        .revive(webhook::MAX_ATTEMPTS, Timestamp::now())
+                        .await?;
+                    println!("Queued {revived} failed event(s) for delivery.");
                 }
             }
         }
-        Commands::Mark { mark_log } => {
-            println!("Marking log content: {:?}", mark_log);
-            // Implement the logic for marking log content here
+        Commands::Trash { action } => {
+            require_relational(&config, "trash")?;
+            let trash = storage::create_trash(pool);
+            match action {
+                TrashCommands::List => {
+                    printer.trash(&trash.agendas().await?, &trash.logs().await?);
+                }
+                TrashCommands::Restore { id } => match trash.restore(id).await? {
+                    Some(storage::Restored::Agenda(agenda)) => printer.agenda("Restored", &agenda),
+                    Some(storage::Restored::Log(log)) => println!("Restored log {}.", log.id),
+                    None => return Err(format!("nothing in the trash has id {id}").into()),
+                },
+                TrashCommands::Purge { older_than } => {
+                    let before = match older_than {
+                        Some(span) => Some(
+                            Zoned::now()
+                                .with_time_zone(config.time_zone.clone())
+                                .checked_sub(span)?
+                                .timestamp(),
+                        ),
+                        None => None,
+                    };
+                    let summary = trash.purge(before).await?;
+                    println!(
+                        "Purged {} agenda(s) and {} log(s).",
+                        summary.agendas, summary.logs
+                    );
+                }
+            }
         }
-        Commands::Putoff { putoff_log } => {
-            println!("Putting off log content: {:?}", putoff_log);
-            // Implement the logic for putting off log content here
+        Commands::Undo => {
+            require_relational(&config, "undo")?;
+            match storage::create_journal(pool).undo().await? {
+                Some(operation) => println!(
+                    "Undid `{}` from {} ({} change(s)).",
+                    operation.command,
+                    time::display(operation.started_at, &config.time_zone),
+                    operation.changes
+                ),
+                None => println!("Nothing to undo."),
+            }
         }
-        Commands::Terminate { terminate_log } => {
-            println!("Terminating with log content: {:?}", terminate_log);
-            // Implement the logic for terminating with log content here
+        Commands::Sync => {
+            require_relational(&config, "sync")?;
+            let dir = config.sync.dir.ok_or(ConfigError::NoSyncDir)?;
+            let device = config.sync.device.ok_or(ConfigError::NoSyncDevice)?;
+            let summary = sync::sync(&storage::create_sync(pool), &dir, &device).await?;
+            println!(
+                "Exported {} change(s); merged {} change(s) from {} device(s).",
+                summary.exported, summary.merged, summary.devices
+            );
+            if summary.merged > 0 {
+                let (put_off, overflow) = slots.normalize().await?;
+                for agenda in put_off {
+                    println!("  put off \"{}\": another one is current", agenda.title);
+                }
+                if overflow > 0 {
+                    println!(
+                        "  {overflow} agenda(s) over the {} slots; close some before adding more.",
+                        config.max_slots
+                    );
+                }
+            }
         }
+        Commands::Projection { action } => match action {
+            ProjectionCommands::Rebuild => {
+                let summary = storage::rebuild_snapshots(pool).await?;
+                println!(
+                    "Folded {} event(s) into {} agenda snapshot(s).",
+                    summary.events, summary.agendas
+                );
+            }
+            ProjectionCommands::Seed => {
+                let events = storage::seed_event_stream(pool).await?;
+                println!("Seeded the event stream with {events} event(s).");
+            }
+        },
+        Commands::Mcp => {
+            let (agenda_repo, log_repo) = events::publishing(agenda_repo, log_repo);
+            let server = mcp::Server::new(
+                &agenda_repo,
+                &log_repo,
+                config.max_slots,
+                config.time_zone.clone(),
+                config.default_deadline,
+            );
+            rpc::serve(
+                &server,
+                tokio::io::BufReader::new(tokio::io::stdin()),
+                tokio::io::stdout(),
+            )
+            .await?;
+        }
+        Commands::Rpc => {
+            let (agenda_repo, log_repo) = events::publishing(agenda_repo, log_repo);
+            let session = rpc::Session::new(
+                &agenda_repo,
+                &log_repo,
+                config.max_slots,
+                config.time_zone.clone(),
+                config.default_deadline,
+            );
+            rpc::serve(
+                &session,
+                tokio::io::BufReader::new(tokio::io::stdin()),
+                tokio::io::stdout(),
+            )
+            .await?;
+        }
+        Commands::Serve { bind } => {
+            let token = config.server.token.ok_or(ConfigError::NoServerToken)?;
+            let bind = bind.unwrap_or(config.server.bind);
+            let listener = tokio::net::TcpListener::bind(bind).await?;
+            eprintln!("Serving the finiate API on http://{bind}");
+            let (agenda_repo, log_repo) = events::publishing(agenda_repo, log_repo);
+            let router = server::router(server::AppState {
+                agendas: agenda_repo,
+                logs: log_repo,
+                token,
+                max_slots: config.max_slots,
+            });
+            server::serve(listener, router).await?;
+        }
+        Commands::Db { .. } => unreachable!("handled in run"),
+        Commands::Completions { .. } | Commands::Man { .. } => unreachable!("handled in main"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_definition_is_valid() {
+        Args::command().debug_assert();
     }
 }
@@ -0,0 +1,514 @@
+//! Model Context Protocol server over stdio.
+//!
+//! Reuses the JSON-RPC transport from [`crate::rpc`] and exposes a small set
+//! of tools for assistants plus two read-only resources. Tool input schemas
+//! are generated from the argument types below, which in turn reuse the
+//! domain types, so they cannot drift from what the tools accept.
+
+use crate::report::{Report, ReportPeriod, ReportStyle, Window};
+use crate::rpc::{
+    INTERNAL_ERROR, INVALID_PARAMS, METHOD_NOT_FOUND, Methods, Outcome, RpcError, parse,
+};
+use crate::slot::Slots;
+use crate::time;
+use domain::*;
+use jiff::{Span, Timestamp, ToSpan, Zoned, civil::Date, tz::TimeZone};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+/// Protocol revisions this server speaks, newest first.
+const PROTOCOL_VERSIONS: [&str; 3] = ["2025-06-18", "2025-03-26", "2024-11-05"];
+const RESOURCE_NOT_FOUND: i64 = -32002;
+const CURRENT_SLOT: &str = "finiate://slots/current";
+const RECENT_HISTORY: &str = "finiate://history/recent";
+
+/// Creates a pending agenda in the next free slot.
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct CreateAgenda {
+    /// Title of the new agenda.
+    title: String,
+    /// Deadline as a span (`2h`, `3d`), a date, a local date-time or an RFC
+    /// 3339 timestamp. Defaults to the configured deadline.
+    deadline: Option<String>,
+}
+
+/// Lists agendas, oldest first.
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ListAgendas {
+    /// Only list agendas with this status.
+    status: Option<AgendaStatus>,
+}
+
+/// Adds a note to an agenda.
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct AddLog {
+    /// Text of the note.
+    content: String,
+    /// Agenda to note on. Defaults to the agenda in the current slot.
+    agenda_id: Option<Uuid>,
+}
+
+/// Summarizes what was activated, put off and terminated in a day or week.
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct SummarizePeriod {
+    period: ReportPeriod,
+    /// Any day within the period. Defaults to today.
+    date: Option<Date>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ToolCall {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResourceRead {
+    uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Initialize {
+    #[serde(rename = "protocolVersion")]
+    protocol_version: String,
+}
+
+pub struct Server<'a, A, L> {
+    agendas: &'a A,
+    logs: &'a L,
+    slots: Slots<'a, A, L>,
+    time_zone: TimeZone,
+    default_deadline: Span,
+}
+
+impl<'a, A, L> Server<'a, A, L>
+where
+    A: AgendaRepo + Sync,
+    L: LogRepo + Sync,
+{
+    pub fn new(
+        agendas: &'a A,
+        logs: &'a L,
+        max_slots: u8,
+        time_zone: TimeZone,
+        default_deadline: Span,
+    ) -> Self {
+        Server {
+            agendas,
+            logs,
+            slots: Slots::new(agendas, logs, max_slots).acting_as("mcp"),
+            time_zone,
+            default_deadline,
+        }
+    }
+
+    /// Runs a tool. `Ok(Err(_))` is a failure the model should see, such as
+    /// a full slot list, as opposed to a malformed call.
+    async fn call_tool(&self, call: ToolCall) -> Result<Result<String, String>, RpcError> {
+        let arguments = match call.arguments {
+            Value::Null => json!({}),
+            arguments => arguments,
+        };
+        let invalid = |e: serde_json::Error| {
+            RpcError::new(
+                INVALID_PARAMS,
+                format!("invalid arguments for `{}`: {e}", call.name),
+            )
+        };
+        match call.name.as_str() {
+            "create_agenda" => {
+                let args: CreateAgenda = serde_json::from_value(arguments).map_err(invalid)?;
+                let now = Zoned::now().with_time_zone(self.time_zone.clone());
+                let terminate_at = match args.deadline {
+                    Some(input) => match time::parse_deadline(&input, &now, &self.time_zone) {
+                        Ok(at) => at,
+                        Err(e) => return Ok(Err(format!("invalid deadline `{input}`: {e}"))),
+                    },
+                    None => now
+                        .checked_add(self.default_deadline)
+                        .map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))?
+                        .timestamp(),
+                };
+                let id = match self.slots.add(Some(args.title), terminate_at).await {
+                    Ok(id) => id,
+                    Err(e) => return Ok(Err(e.to_string())),
+                };
+                let agenda = self.agenda(id).await?;
+                Ok(Ok(pretty(&agenda)))
+            }
+            "list_agendas" => {
+                let args: ListAgendas = serde_json::from_value(arguments).map_err(invalid)?;
+                let status = args.status.map(|status| status.to_string());
+                let mut agendas = self
+                    .agendas
+                    .get_agendas_by_status(status.as_deref())
+                    .await
+                    .map_err(RpcError::repo)?;
+                agendas.sort_by_key(|agenda| (agenda.initiate_at, agenda.id));
+                Ok(Ok(pretty(&agendas)))
+            }
+            "add_log" => {
+                let args: AddLog = serde_json::from_value(arguments).map_err(invalid)?;
+                let agenda = match args.agenda_id {
+                    Some(id) => {
+                        let Some(agenda) = self
+                            .agendas
+                            .get_agenda_by_id(id)
+                            .await
+                            .map_err(RpcError::repo)?
+                        else {
+                            return Ok(Err(format!("agenda {id} not found")));
+                        };
+                        self.logs
+                            .create_log(&LogCreate {
+                                agenda_id: id,
+                                content: args.content,
+                                log_type: LogType::CommonLog,
+                            })
+                            .await
+                            .map_err(RpcError::repo)?;
+                        agenda
+                    }
+                    None => match self.slots.mark(Some(args.content)).await {
+                        Ok(agenda) => agenda,
+                        Err(e) => return Ok(Err(e.to_string())),
+                    },
+                };
+                Ok(Ok(format!("Noted on `{}` ({}).", agenda.title, agenda.id)))
+            }
+            "summarize_period" => {
+                let args: SummarizePeriod = serde_json::from_value(arguments).map_err(invalid)?;
+                let date = args
+                    .date
+                    .unwrap_or_else(|| Zoned::now().with_time_zone(self.time_zone.clone()).date());
+                let window = Window::new(args.period, date, &self.time_zone)
+                    .map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))?;
+                let report = Report::build(self.agendas, self.logs, args.period, &window)
+                    .await
+                    .map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))?;
+                Ok(Ok(report.render(ReportStyle::Markdown, &self.time_zone)))
+            }
+            name => Err(RpcError::new(
+                INVALID_PARAMS,
+                format!("unknown tool `{name}`"),
+            )),
+        }
+    }
+
+    async fn read_resource(&self, uri: &str) -> Result<Value, RpcError> {
+        let contents = match uri {
+            CURRENT_SLOT => {
+                let current = self.slots.current().await?;
+                json!(current.map(|(slot, agenda)| json!({ "slot": slot, "agenda": agenda })))
+            }
+            RECENT_HISTORY => {
+                let since = Timestamp::now() - 168.hours();
+                let history: Vec<Value> = self
+                    .slots
+                    .history(since)
+                    .await?
+                    .into_iter()
+                    .map(|(log, title)| json!({ "log": log, "title": title }))
+                    .collect();
+                json!(history)
+            }
+            _ => {
+                return Err(RpcError::new(
+                    RESOURCE_NOT_FOUND,
+                    format!("unknown resource `{uri}`"),
+                ));
+            }
+        };
+        Ok(json!({
+            "contents": [{
+                "uri": uri,
+                "mimeType": "application/json",
+                "text": pretty(&contents),
+            }]
+        }))
+    }
+
+    async fn agenda(&self, id: Uuid) -> Result<Agenda, RpcError> {
+        self.agendas
+            .get_agenda_by_id(id)
+            .await
+            .map_err(RpcError::repo)?
+            .ok_or_else(|| RpcError::new(INTERNAL_ERROR, format!("agenda {id} vanished")))
+    }
+}
+
+impl<A, L> Methods for Server<'_, A, L>
+where
+    A: AgendaRepo + Sync,
+    L: LogRepo + Sync,
+{
+    async fn call(&self, method: &str, params: Value) -> Result<Outcome, RpcError> {
+        match method {
+            "initialize" => {
+                let Initialize { protocol_version } = parse(params)?;
+                let version = PROTOCOL_VERSIONS
+                    .into_iter()
+                    .find(|version| *version == protocol_version)
+                    .unwrap_or(PROTOCOL_VERSIONS[0]);
+                Outcome::read(json!({
+                    "protocolVersion": version,
+                    "capabilities": { "tools": {}, "resources": {} },
+                    "serverInfo": {
+                        "name": "finiate",
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                }))
+            }
+            "notifications/initialized" | "ping" => Outcome::read(json!({})),
+            "tools/list" => Outcome::read(json!({ "tools": tools() })),
+            "tools/call" => {
+                let text = match self.call_tool(parse(params)?).await? {
+                    Ok(text) => {
+                        json!({ "content": [{ "type": "text", "text": text }], "isError": false })
+                    }
+                    Err(text) => {
+                        json!({ "content": [{ "type": "text", "text": text }], "isError": true })
+                    }
+                };
+                Outcome::read(text)
+            }
+            "resources/list" => Outcome::read(json!({
+                "resources": [
+                    {
+                        "uri": CURRENT_SLOT,
+                        "name": "current-slot",
+                        "description": "The active slot and its agenda, or null",
+                        "mimeType": "application/json",
+                    },
+                    {
+                        "uri": RECENT_HISTORY,
+                        "name": "recent-history",
+                        "description": "Logs of the last seven days with their agenda titles",
+                        "mimeType": "application/json",
+                    },
+                ]
+            })),
+            "resources/read" => {
+                let ResourceRead { uri } = parse(params)?;
+                Outcome::read(self.read_resource(&uri).await?)
+            }
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("unknown method `{method}`"),
+            )),
+        }
+    }
+}
+
+/// Describes every tool, with input schemas taken from the argument types.
+fn tools() -> Vec<Value> {
+    fn tool<T: JsonSchema>(name: &str) -> Value {
+        let schema = schemars::schema_for!(T);
+        let description = schema.get("description").cloned().unwrap_or(Value::Null);
+        json!({ "name": name, "description": description, "inputSchema": schema })
+    }
+    vec![
+        tool::<CreateAgenda>("create_agenda"),
+        tool::<ListAgendas>("list_agendas"),
+        tool::<AddLog>("add_log"),
+        tool::<SummarizePeriod>("summarize_period"),
+    ]
+}
+
+fn pretty(value: &impl serde::Serialize) -> String {
+    serde_json::to_string_pretty(value).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::serve;
+    use storage::{SqliteAgendaRepo, SqliteLogRepo};
+
+    async fn setup_repos() -> (tempfile::TempDir, SqliteAgendaRepo, SqliteLogRepo) {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let pool = storage::init_db(&dir.path().join("finiate.db"))
+            .await
+            .expect("init db");
+        let (agenda_repo, log_repo) = storage::create_repos(&pool);
+        (dir, agenda_repo, log_repo)
+    }
+
+    async fn exchange(
+        agendas: &SqliteAgendaRepo,
+        logs: &SqliteLogRepo,
+        messages: &[Value],
+    ) -> Vec<Value> {
+        let server = Server::new(agendas, logs, 2, TimeZone::UTC, 1.day());
+        let input: String = messages
+            .iter()
+            .map(|message| format!("{message}\n"))
+            .collect();
+        let mut output = Vec::new();
+        serve(&server, input.as_bytes(), &mut output)
+            .await
+            .expect("serve");
+        String::from_utf8(output)
+            .expect("utf-8")
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("JSON line"))
+            .collect()
+    }
+
+    fn request(id: u64, method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+    }
+
+    fn call(id: u64, name: &str, arguments: Value) -> Value {
+        request(
+            id,
+            "tools/call",
+            json!({ "name": name, "arguments": arguments }),
+        )
+    }
+
+    #[tokio::test]
+    async fn handshake_and_tool_schemas() {
+        let (_dir, agendas, logs) = setup_repos().await;
+        let responses = exchange(
+            &agendas,
+            &logs,
+            &[
+                request(1, "initialize", json!({ "protocolVersion": "2025-03-26", "capabilities": {}, "clientInfo": { "name": "test", "version": "0" } })),
+                json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+                request(2, "tools/list", Value::Null),
+            ],
+        )
+        .await;
+
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0]["result"]["protocolVersion"], "2025-03-26");
+        assert_eq!(responses[0]["result"]["serverInfo"]["name"], "finiate");
+        let tools = responses[1]["result"]["tools"].as_array().unwrap();
+        let names: Vec<_> = tools
+            .iter()
+            .map(|tool| tool["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "create_agenda",
+                "list_agendas",
+                "add_log",
+                "summarize_period"
+            ]
+        );
+        for tool in tools {
+            assert_eq!(tool["inputSchema"]["type"], "object");
+            assert!(tool["description"].is_string());
+        }
+        let status = &tools[1]["inputSchema"].to_string();
+        assert!(status.contains("ongoing") && status.contains("terminated"));
+        assert_eq!(tools[3]["inputSchema"]["required"], json!(["period"]));
+    }
+
+    #[tokio::test]
+    async fn tools_act_on_the_repositories() {
+        let (_dir, agendas, logs) = setup_repos().await;
+        let responses = exchange(
+            &agendas,
+            &logs,
+            &[
+                call(
+                    1,
+                    "create_agenda",
+                    json!({ "title": "Draft", "deadline": "2h" }),
+                ),
+                call(2, "add_log", json!({ "content": "no current slot" })),
+                call(3, "list_agendas", json!({ "status": "pending" })),
+                call(4, "create_agenda", json!({ "title": "" })),
+                call(5, "list_agendas", json!({ "status": "bogus" })),
+                call(6, "explode", json!({})),
+            ],
+        )
+        .await;
+
+        assert_eq!(responses[0]["result"]["isError"], false);
+        assert_eq!(responses[1]["result"]["isError"], true);
+        let listed: Vec<Agenda> = serde_json::from_str(
+            responses[2]["result"]["content"][0]["text"]
+                .as_str()
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].title, "Draft");
+        assert_eq!(responses[3]["result"]["isError"], true);
+        assert_eq!(responses[4]["error"]["code"], INVALID_PARAMS);
+        assert_eq!(responses[5]["error"]["code"], INVALID_PARAMS);
+
+        let id = listed[0].id;
+        let responses = exchange(
+            &agendas,
+            &logs,
+            &[
+                call(
+                    1,
+                    "add_log",
+                    json!({ "content": "outline", "agenda_id": id }),
+                ),
+                call(2, "summarize_period", json!({ "period": "weekly" })),
+            ],
+        )
+        .await;
+        assert_eq!(responses[0]["result"]["isError"], false);
+        let logged = logs.get_logs_by_agenda_id(id).await.unwrap();
+        assert_eq!(logged[0].content, "outline");
+        assert!(
+            responses[1]["result"]["content"][0]["text"]
+                .as_str()
+                .unwrap()
+                .starts_with('#')
+        );
+    }
+
+    #[tokio::test]
+    async fn resources_expose_slot_and_history() {
+        let (_dir, agendas, logs) = setup_repos().await;
+        let slots = Slots::new(&agendas, &logs, 2);
+        slots
+            .add(Some("Focus".to_string()), Timestamp::now() + 1.hour())
+            .await
+            .unwrap();
+        slots.set(1).await.unwrap();
+
+        let responses = exchange(
+            &agendas,
+            &logs,
+            &[
+                request(1, "resources/list", Value::Null),
+                request(2, "resources/read", json!({ "uri": CURRENT_SLOT })),
+                request(3, "resources/read", json!({ "uri": RECENT_HISTORY })),
+                request(4, "resources/read", json!({ "uri": "finiate://nope" })),
+            ],
+        )
+        .await;
+
+        assert_eq!(
+            responses[0]["result"]["resources"]
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+        let text = |response: &Value| -> Value {
+            serde_json::from_str(response["result"]["contents"][0]["text"].as_str().unwrap())
+                .unwrap()
+        };
+        assert_eq!(text(&responses[1])["agenda"]["title"], "Focus");
+        assert_eq!(text(&responses[2])[0]["log"]["log_type"], "activate");
+        assert_eq!(responses[3]["error"]["code"], RESOURCE_NOT_FOUND);
+    }
+}
@@ -0,0 +1,239 @@
+use crate::config::{ColorChoice, Config, OutputFormat};
+use crate::time::display;
+use domain::*;
+use jiff::tz::TimeZone;
+use serde_json::json;
+use std::io::IsTerminal;
+
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// Renders command results to stdout in the configured format.
+pub struct Printer {
+    format: OutputFormat,
+    color: bool,
+    time_zone: TimeZone,
+}
+
+impl Printer {
+    pub fn new(config: &Config) -> Self {
+        let color = match config.color {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal()
+            }
+        };
+        Printer {
+            format: config.format,
+            color: color && config.format == OutputFormat::Text,
+            time_zone: config.time_zone.clone(),
+        }
+    }
+
+    pub fn color(&self) -> bool {
+        self.color
+    }
+
+    pub fn is_json(&self) -> bool {
+        self.format == OutputFormat::Json
+    }
+
+    pub fn paint(&self, style: &str, text: &str) -> String {
+        if self.color {
+            format!("{style}{text}{RESET}")
+        } else {
+            text.to_string()
+        }
+    }
+
+    pub fn print_json(&self, value: &serde_json::Value) {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(value).expect("serialize JSON output")
+        );
+    }
+
+    pub fn status(&self, status: AgendaStatus) -> String {
+        let style = match status {
+            AgendaStatus::Pending => YELLOW,
+            AgendaStatus::Ongoing => GREEN,
+            AgendaStatus::Terminated => DIM,
+        };
+        self.paint(style, &status.to_string())
+    }
+
+    pub fn slots(&self, slots: &[Agenda], max_slots: u8) {
+        if self.is_json() {
+            let slots: Vec<_> = slots
+                .iter()
+                .zip(1..)
+                .map(|(agenda, slot): (&Agenda, u8)| json!({ "slot": slot, "agenda": agenda }))
+                .collect();
+            self.print_json(&json!({ "max_slots": max_slots, "slots": slots }));
+            return;
+        }
+
+        println!(
+            "{}",
+            self.paint(BOLD, &format!("Slots ({}/{})", slots.len(), max_slots))
+        );
+        for (agenda, slot) in slots.iter().zip(1..) {
+            let marker = if agenda.agenda_status == AgendaStatus::Ongoing {
+                "*"
+            } else {
+                " "
+            };
+            println!(
+                "{marker} {slot:>2}  {}  due {}  [{}]",
+                agenda.title,
+                self.deadline(agenda),
+                self.status(agenda.agenda_status)
+            );
+        }
+    }
+
+    /// Reports the outcome of a command acting on a single agenda.
+    pub fn agenda(&self, action: &str, agenda: &Agenda) {
+        if self.is_json() {
+            self.print_json(&json!({ "action": action, "agenda": agenda }));
+            return;
+        }
+        println!(
+            "{} {}  due {}  [{}]",
+            self.paint(BOLD, action),
+            agenda.title,
+            self.deadline(agenda),
+            self.status(agenda.agenda_status)
+        );
+    }
+
+    pub fn history(&self, entries: &[(Log, String)]) {
+        if self.is_json() {
+            let entries: Vec<_> = entries
+                .iter()
+                .map(|(log, title)| json!({ "log": log, "agenda_title": title }))
+                .collect();
+            self.print_json(&json!(entries));
+            return;
+        }
+        for (log, title) in entries {
+            let stamp = self.paint(DIM, &display(log.create_at, &self.time_zone));
+            let content = if log.content.is_empty() {
+                String::new()
+            } else {
+                format!(": {}", log.content)
+            };
+            println!(
+                "{stamp}  {:<10}  {title}{content}",
+                log.log_type.to_string()
+            );
+        }
+    }
+
+    pub fn trash(&self, agendas: &[storage::TrashedAgenda], logs: &[storage::TrashedLog]) {
+        if self.is_json() {
+            let agendas: Vec<_> = agendas
+                .iter()
+                .map(|item| json!({ "agenda": item.agenda, "deleted_at": item.deleted_at }))
+                .collect();
+            let logs: Vec<_> = logs
+                .iter()
+                .map(|item| json!({ "log": item.log, "deleted_at": item.deleted_at }))
+                .collect();
+            self.print_json(&json!({ "agendas": agendas, "logs": logs }));
+            return;
+        }
+        if agendas.is_empty() && logs.is_empty() {
+            println!("The trash is empty.");
+            return;
+        }
+        if !agendas.is_empty() {
+            println!("{}", self.paint(BOLD, "Agendas"));
+        }
+        for item in agendas {
+            println!(
+                "{}  {}  {}",
+                self.paint(DIM, &display(item.deleted_at, &self.time_zone)),
+                item.agenda.id,
+                item.agenda.title
+            );
+        }
+        if !logs.is_empty() {
+            println!("{}", self.paint(BOLD, "Logs"));
+        }
+        for item in logs {
+            println!(
+                "{}  {}  {:<10}  {}",
+                self.paint(DIM, &display(item.deleted_at, &self.time_zone)),
+                item.log.id,
+                item.log.log_type.to_string(),
+                item.log.content
+            );
+        }
+    }
+
+    /// Details of one agenda, followed by its revisions when given.
+    pub fn show(&self, agenda: &Agenda, revisions: Option<&[AgendaRevision]>) {
+        if self.is_json() {
+            let mut value = json!({ "agenda": agenda });
+            if let Some(revisions) = revisions {
+                value["revisions"] = json!(revisions);
+            }
+            self.print_json(&value);
+            return;
+        }
+        println!(
+            "{}  due {}  [{}]",
+            self.paint(BOLD, &agenda.title),
+            self.deadline(agenda),
+            self.status(agenda.agenda_status)
+        );
+        println!("  id         {}", agenda.id);
+        println!(
+            "  initiated  {}",
+            display(agenda.initiate_at, &self.time_zone)
+        );
+        let Some(revisions) = revisions else {
+            return;
+        };
+        if revisions.is_empty() {
+            println!("No revisions.");
+            return;
+        }
+        for revision in revisions {
+            let (field, old, new) = match &revision.change {
+                FieldChange::Title { old, new } => ("title", old.clone(), new.clone()),
+                FieldChange::Status { old, new } => {
+                    ("status", self.status(*old), self.status(*new))
+                }
+                FieldChange::Deadline { old, new } => (
+                    "deadline",
+                    display(*old, &self.time_zone),
+                    display(*new, &self.time_zone),
+                ),
+            };
+            println!(
+                "{}  {:<6}  {field:<8}  {} -> {new}",
+                self.paint(DIM, &display(revision.revised_at, &self.time_zone)),
+                revision.actor.as_deref().unwrap_or("-"),
+                self.paint(RED, &old),
+            );
+        }
+    }
+
+    fn deadline(&self, agenda: &Agenda) -> String {
+        let text = display(agenda.terminate_at, &self.time_zone);
+        if agenda.agenda_status != AgendaStatus::Terminated
+            && agenda.terminate_at < jiff::Timestamp::now()
+        {
+            self.paint(RED, &text)
+        } else {
+            text
+        }
+    }
+}
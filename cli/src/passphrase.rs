@@ -0,0 +1,81 @@
+//! Passphrases of encrypted databases: from the config file, the OS keyring
+//! or the terminal.
+
+use crate::config::{Config, Encryption};
+use std::error::Error;
+use std::io::{BufRead, IsTerminal};
+
+const KEYRING_SERVICE: &str = "finiate";
+
+/// The passphrase that opens `config.database`, or `None` when it is not
+/// meant to be encrypted. A prompted passphrase may be piped in on stdin
+/// only when `stdin` says the command does not read stdin itself.
+pub fn unlock(
+    config: &Config,
+    stdin: bool,
+) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+    Ok(match &config.encryption {
+        None => None,
+        Some(Encryption::Passphrase(passphrase)) => Some(passphrase.clone()),
+        Some(Encryption::Keyring) => {
+            let passphrase = keyring_entry(config)?.get_password().map_err(|error| {
+                format!(
+                    "no passphrase for {} in the keyring ({error}); `finiate db rekey` stores one",
+                    config.database.display()
+                )
+            })?;
+            Some(passphrase)
+        }
+        Some(Encryption::Prompt) => Some(prompt(
+            &format!("Passphrase for {}: ", config.database.display()),
+            stdin,
+        )?),
+    })
+}
+
+/// Keeps `passphrase` in the keyring when that is where it is read from.
+pub fn remember(config: &Config, passphrase: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if config.encryption == Some(Encryption::Keyring) {
+        keyring_entry(config)?.set_password(passphrase)?;
+    }
+    Ok(())
+}
+
+/// Asks for a new passphrase, twice.
+pub fn choose() -> Result<String, Box<dyn Error + Send + Sync>> {
+    let passphrase = prompt("New passphrase: ", true)?;
+    if passphrase.is_empty() {
+        return Err("the passphrase must not be empty".into());
+    }
+    if prompt("Repeat the new passphrase: ", true)? != passphrase {
+        return Err("the passphrases do not match".into());
+    }
+    Ok(passphrase)
+}
+
+/// Reads a line from stdin when that is not a terminal and `stdin` allows
+/// it, so scripts can pipe passphrases in; otherwise asks on the terminal
+/// (`/dev/tty`) without echoing, whatever stdin is.
+fn prompt(label: &str, stdin: bool) -> Result<String, Box<dyn Error + Send + Sync>> {
+    if stdin && !std::io::stdin().is_terminal() {
+        let mut line = String::new();
+        std::io::stdin().lock().read_line(&mut line)?;
+        return Ok(line.trim_end_matches(['\r', '\n']).to_string());
+    }
+    rpassword::prompt_password(label).map_err(|error| {
+        format!(
+            "cannot ask for the passphrase without a terminal ({error}); \
+            set encryption.source to `keyring` or `config` instead"
+        )
+        .into()
+    })
+}
+
+/// One keyring entry per database file.
+fn keyring_entry(config: &Config) -> Result<keyring::Entry, Box<dyn Error + Send + Sync>> {
+    let path = std::path::absolute(&config.database)?;
+    Ok(keyring::Entry::new(
+        KEYRING_SERVICE,
+        &path.display().to_string(),
+    )?)
+}
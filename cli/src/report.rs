@@ -0,0 +1,356 @@
+use clap::ValueEnum;
+use domain::*;
+use jiff::{Timestamp, ToSpan, Zoned, civil::Date, tz::TimeZone};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Write;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportPeriod {
+    Daily,
+    Weekly,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ReportStyle {
+    #[default]
+    Markdown,
+    Plain,
+}
+
+/// The half-open local time window `[start, end)` a report covers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Window {
+    pub start: Zoned,
+    pub end: Zoned,
+}
+
+impl Window {
+    /// The day containing `date`, or the Monday-to-Sunday week containing it.
+    pub fn new(period: ReportPeriod, date: Date, tz: &TimeZone) -> Result<Window, jiff::Error> {
+        let first = match period {
+            ReportPeriod::Daily => date,
+            ReportPeriod::Weekly => {
+                let offset = date.weekday().to_monday_zero_offset();
+                date.checked_sub(i64::from(offset).days())?
+            }
+        };
+        let start = first.to_zoned(tz.clone())?;
+        let end = match period {
+            ReportPeriod::Daily => start.tomorrow()?,
+            ReportPeriod::Weekly => start.checked_add(1.week())?,
+        };
+        Ok(Window { start, end })
+    }
+
+    /// The window immediately following this one, of the same length.
+    fn next(&self, period: ReportPeriod) -> Result<Window, jiff::Error> {
+        let end = match period {
+            ReportPeriod::Daily => self.end.tomorrow()?,
+            ReportPeriod::Weekly => self.end.checked_add(1.week())?,
+        };
+        Ok(Window {
+            start: self.end.clone(),
+            end,
+        })
+    }
+
+    fn last_instant(&self) -> Timestamp {
+        self.end.timestamp() - 1.millisecond()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Entry {
+    pub agenda_id: Uuid,
+    pub title: String,
+    pub at: Timestamp,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub period: ReportPeriod,
+    pub start: Timestamp,
+    pub end: Timestamp,
+    pub activated: Vec<Entry>,
+    pub put_off: Vec<Entry>,
+    pub terminated: Vec<Entry>,
+    pub marks: Vec<Entry>,
+    /// Agendas still open whose deadline falls in this or the next period.
+    pub upcoming: Vec<Agenda>,
+}
+
+impl Report {
+    pub async fn build<A, L>(
+        agendas: &A,
+        logs: &L,
+        period: ReportPeriod,
+        window: &Window,
+    ) -> Result<Report, Box<dyn Error + Send + Sync>>
+    where
+        A: AgendaRepo + Sync,
+        L: LogRepo + Sync,
+    {
+        let mut period_logs = logs
+            .get_logs_by_time_range(window.start.timestamp(), window.last_instant())
+            .await?;
+        period_logs.sort_by_key(|log| (log.create_at, log.id));
+
+        let mut titles: HashMap<Uuid, String> = HashMap::new();
+        let mut report = Report {
+            period,
+            start: window.start.timestamp(),
+            end: window.end.timestamp(),
+            activated: Vec::new(),
+            put_off: Vec::new(),
+            terminated: Vec::new(),
+            marks: Vec::new(),
+            upcoming: Vec::new(),
+        };
+        for log in period_logs {
+            let title = match titles.get(&log.agenda_id) {
+                Some(title) => title.clone(),
+                None => {
+                    let title = agendas
+                        .get_agenda_by_id(log.agenda_id)
+                        .await?
+                        .map(|agenda| agenda.title)
+                        .unwrap_or_default();
+                    titles.insert(log.agenda_id, title.clone());
+                    title
+                }
+            };
+            let entry = Entry {
+                agenda_id: log.agenda_id,
+                title,
+                at: log.create_at,
+                content: log.content,
+            };
+            match log.log_type {
+                LogType::Activate => report.activated.push(entry),
+                LogType::PutOff => report.put_off.push(entry),
+                LogType::Terminate => report.terminated.push(entry),
+                LogType::CommonLog => report.marks.push(entry),
+            }
+        }
+
+        let next = window.next(period)?;
+        let mut upcoming = agendas
+            .get_agendas_by_terminate_time_range(window.start.timestamp(), next.last_instant())
+            .await?;
+        upcoming.retain(|agenda| agenda.agenda_status != AgendaStatus::Terminated);
+        upcoming.sort_by_key(|agenda| (agenda.terminate_at, agenda.id));
+        report.upcoming = upcoming;
+
+        Ok(report)
+    }
+
+    pub fn render(&self, style: ReportStyle, tz: &TimeZone) -> String {
+        let mut out = String::new();
+        let start = self.start.to_zoned(tz.clone());
+        let title = match self.period {
+            ReportPeriod::Daily => format!("Daily report: {}", start.strftime("%Y-%m-%d (%a)")),
+            ReportPeriod::Weekly => {
+                let last = self
+                    .end
+                    .to_zoned(tz.clone())
+                    .yesterday()
+                    .unwrap_or(start.clone());
+                format!(
+                    "Weekly report: {} to {}",
+                    start.strftime("%Y-%m-%d"),
+                    last.strftime("%Y-%m-%d")
+                )
+            }
+        };
+        let time_format = match self.period {
+            ReportPeriod::Daily => "%H:%M",
+            ReportPeriod::Weekly => "%a %H:%M",
+        };
+
+        match style {
+            ReportStyle::Markdown => writeln!(out, "## {title}").unwrap(),
+            ReportStyle::Plain => writeln!(out, "{title}\n{}", "=".repeat(title.len())).unwrap(),
+        }
+
+        let sections = [
+            ("Terminated", &self.terminated),
+            ("Activated", &self.activated),
+            ("Put off", &self.put_off),
+            ("Marks", &self.marks),
+        ];
+        for (heading, entries) in sections {
+            if entries.is_empty() {
+                continue;
+            }
+            write_heading(&mut out, style, heading, entries.len());
+            for entry in entries {
+                let at = entry
+                    .at
+                    .to_zoned(tz.clone())
+                    .strftime(time_format)
+                    .to_string();
+                let title = match style {
+                    ReportStyle::Markdown => format!("**{}**", entry.title),
+                    ReportStyle::Plain => entry.title.clone(),
+                };
+                let content = if entry.content.is_empty() {
+                    String::new()
+                } else {
+                    format!(": {}", entry.content)
+                };
+                writeln!(out, "- {title}{content} ({at})").unwrap();
+            }
+        }
+
+        if !self.upcoming.is_empty() {
+            write_heading(&mut out, style, "Upcoming", self.upcoming.len());
+            for agenda in &self.upcoming {
+                let due = agenda
+                    .terminate_at
+                    .to_zoned(tz.clone())
+                    .strftime("%Y-%m-%d %H:%M")
+                    .to_string();
+                writeln!(
+                    out,
+                    "- {} (due {due}, {})",
+                    agenda.title, agenda.agenda_status
+                )
+                .unwrap();
+            }
+        }
+
+        let quiet = self.activated.is_empty()
+            && self.put_off.is_empty()
+            && self.terminated.is_empty()
+            && self.marks.is_empty()
+            && self.upcoming.is_empty();
+        if quiet {
+            writeln!(out, "\nNothing happened in this period.").unwrap();
+        }
+        out
+    }
+}
+
+fn write_heading(out: &mut String, style: ReportStyle, heading: &str, count: usize) {
+    match style {
+        ReportStyle::Markdown => writeln!(out, "\n### {heading} ({count})").unwrap(),
+        ReportStyle::Plain => writeln!(out, "\n{heading} ({count})").unwrap(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jiff::civil::date;
+    use storage::{SqliteAgendaRepo, SqliteLogRepo};
+
+    async fn setup_repos() -> (tempfile::TempDir, SqliteAgendaRepo, SqliteLogRepo) {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let pool = storage::init_db(&dir.path().join("finiate.db"))
+            .await
+            .expect("init db");
+        let (agenda_repo, log_repo) = storage::create_repos(&pool);
+        (dir, agenda_repo, log_repo)
+    }
+
+    #[test]
+    fn daily_window_covers_one_local_day() {
+        let tz = TimeZone::get("Europe/Berlin").expect("tz");
+        let window = Window::new(ReportPeriod::Daily, date(2026, 3, 29), &tz).expect("window");
+
+        // The DST switch makes this day 23 hours long.
+        assert_eq!(
+            window.start.to_string(),
+            "2026-03-29T00:00:00+01:00[Europe/Berlin]"
+        );
+        assert_eq!(
+            window.end.to_string(),
+            "2026-03-30T00:00:00+02:00[Europe/Berlin]"
+        );
+    }
+
+    #[test]
+    fn weekly_window_starts_on_monday() {
+        let tz = TimeZone::UTC;
+        let window = Window::new(ReportPeriod::Weekly, date(2026, 10, 18), &tz).expect("window");
+
+        assert_eq!(window.start.date(), date(2026, 10, 12));
+        assert_eq!(window.end.date(), date(2026, 10, 19));
+    }
+
+    #[tokio::test]
+    async fn report_groups_logs_by_type() {
+        let (_dir, agendas, logs) = setup_repos().await;
+        let tz = TimeZone::UTC;
+        let today = Zoned::now().with_time_zone(tz.clone()).date();
+
+        let done = agendas
+            .create_agenda(&AgendaCreate {
+                title: "Ship release".to_string(),
+                agenda_status: AgendaStatus::Terminated,
+                terminate_at: Timestamp::now(),
+            })
+            .await
+            .expect("create agenda");
+        let open = agendas
+            .create_agenda(&AgendaCreate {
+                title: "Review PR".to_string(),
+                agenda_status: AgendaStatus::Pending,
+                terminate_at: Timestamp::now() + 1.hour(),
+            })
+            .await
+            .expect("create agenda");
+        for (agenda_id, log_type, content) in [
+            (done, LogType::Activate, ""),
+            (done, LogType::CommonLog, "tagged v1"),
+            (done, LogType::Terminate, "published"),
+            (open, LogType::PutOff, "waiting on CI"),
+        ] {
+            logs.create_log(&LogCreate {
+                agenda_id,
+                content: content.to_string(),
+                log_type,
+            })
+            .await
+            .expect("create log");
+        }
+
+        let window = Window::new(ReportPeriod::Daily, today, &tz).expect("window");
+        let report = Report::build(&agendas, &logs, ReportPeriod::Daily, &window)
+            .await
+            .expect("build report");
+
+        assert_eq!(report.activated.len(), 1);
+        assert_eq!(report.marks[0].content, "tagged v1");
+        assert_eq!(report.terminated[0].title, "Ship release");
+        assert_eq!(report.put_off[0].title, "Review PR");
+        let upcoming: Vec<_> = report.upcoming.iter().map(|agenda| agenda.id).collect();
+        assert!(upcoming.contains(&open) && !upcoming.contains(&done));
+
+        let markdown = report.render(ReportStyle::Markdown, &tz);
+        assert!(markdown.starts_with("## Daily report: "));
+        assert!(markdown.contains("### Terminated (1)\n- **Ship release**: published ("));
+        let plain = report.render(ReportStyle::Plain, &tz);
+        assert!(plain.contains("Put off (1)\n- Review PR: waiting on CI ("));
+    }
+
+    #[tokio::test]
+    async fn empty_period_says_so() {
+        let (_dir, agendas, logs) = setup_repos().await;
+        let tz = TimeZone::UTC;
+        let window = Window::new(ReportPeriod::Weekly, date(2020, 1, 1), &tz).expect("window");
+        let report = Report::build(&agendas, &logs, ReportPeriod::Weekly, &window)
+            .await
+            .expect("build report");
+
+        let text = report.render(ReportStyle::Plain, &tz);
+        assert!(text.starts_with("Weekly report: 2019-12-30 to 2020-01-05\n"));
+        assert!(text.contains("Nothing happened in this period."));
+    }
+}
@@ -0,0 +1,660 @@
+//! JSON-RPC 2.0 over stdio, one message per line.
+//!
+//! Repository methods are named after their `AgendaRepo`/`LogRepo` trait
+//! methods (`create_agenda`, `get_logs_by_time_range`, ...) and slot
+//! operations live under `slot.`. After every request that changes agendas or
+//! logs the server pushes an `agendas.changed` notification.
+
+use crate::slot::{SlotError, Slots};
+use crate::time;
+use domain::*;
+use jiff::{Span, Timestamp, ToSpan, Zoned, tz::TimeZone};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use uuid::Uuid;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+pub(crate) const METHOD_NOT_FOUND: i64 = -32601;
+pub(crate) const INVALID_PARAMS: i64 = -32602;
+pub(crate) const INTERNAL_ERROR: i64 = -32603;
+/// Slot rule violations such as a full slot list or no current agenda.
+const SLOT_ERROR: i64 = -32000;
+
+#[derive(Debug)]
+pub(crate) struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> RpcError {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn repo(e: impl std::error::Error) -> RpcError {
+        RpcError::new(INTERNAL_ERROR, format!("repository error: {e}"))
+    }
+}
+
+impl From<SlotError> for RpcError {
+    fn from(e: SlotError) -> RpcError {
+        match e {
+            SlotError::Repo(_) => RpcError::new(INTERNAL_ERROR, e.to_string()),
+            _ => RpcError::new(SLOT_ERROR, e.to_string()),
+        }
+    }
+}
+
+/// What a method returned, plus the agendas it changed when it changed any.
+pub(crate) struct Outcome {
+    result: Value,
+    changed: Option<Vec<Uuid>>,
+}
+
+impl Outcome {
+    pub fn read(result: impl serde::Serialize) -> Result<Outcome, RpcError> {
+        Ok(Outcome {
+            result: to_value(result)?,
+            changed: None,
+        })
+    }
+
+    pub fn wrote(result: impl serde::Serialize, changed: Vec<Uuid>) -> Result<Outcome, RpcError> {
+        Ok(Outcome {
+            result: to_value(result)?,
+            changed: Some(changed),
+        })
+    }
+}
+
+/// A set of methods served over the JSON-RPC transport.
+pub(crate) trait Methods {
+    async fn call(&self, method: &str, params: Value) -> Result<Outcome, RpcError>;
+}
+
+/// Answers requests line by line until `input` is closed.
+pub(crate) async fn serve<M, R, W>(methods: &M, input: R, mut output: W) -> std::io::Result<()>
+where
+    M: Methods,
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut lines = input.lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        for message in handle(methods, &line).await {
+            output.write_all(message.to_string().as_bytes()).await?;
+            output.write_all(b"\n").await?;
+        }
+        output.flush().await?;
+    }
+    Ok(())
+}
+
+/// Handles one line, returning the responses followed by notifications.
+async fn handle<M: Methods>(methods: &M, text: &str) -> Vec<Value> {
+    let message: Value = match serde_json::from_str(text) {
+        Ok(message) => message,
+        Err(e) => return vec![error_response(Value::Null, PARSE_ERROR, e.to_string())],
+    };
+    let requests = match message {
+        Value::Array(requests) if requests.is_empty() => {
+            return vec![error_response(
+                Value::Null,
+                INVALID_REQUEST,
+                "empty batch".to_string(),
+            )];
+        }
+        Value::Array(requests) => requests,
+        request => {
+            let (response, notification) = handle_request(methods, request).await;
+            return response.into_iter().chain(notification).collect();
+        }
+    };
+
+    let mut responses = Vec::new();
+    let mut notifications = Vec::new();
+    for request in requests {
+        let (response, notification) = handle_request(methods, request).await;
+        responses.extend(response);
+        notifications.extend(notification);
+    }
+    // A batch is answered with one array; notifications follow it.
+    let mut messages = Vec::new();
+    if !responses.is_empty() {
+        messages.push(Value::Array(responses));
+    }
+    messages.extend(notifications);
+    messages
+}
+
+async fn handle_request<M: Methods>(methods: &M, request: Value) -> (Option<Value>, Option<Value>) {
+    let id = request.get("id").cloned();
+    let valid = request.get("jsonrpc") == Some(&json!("2.0"))
+        && id
+            .as_ref()
+            .is_none_or(|id| id.is_string() || id.is_number() || id.is_null());
+    let (Some(method), true) = (request.get("method").and_then(Value::as_str), valid) else {
+        let response = error_response(
+            id.unwrap_or(Value::Null),
+            INVALID_REQUEST,
+            "not a JSON-RPC 2.0 request".to_string(),
+        );
+        return (Some(response), None);
+    };
+    let params = request.get("params").cloned().unwrap_or(Value::Null);
+
+    let outcome = methods.call(method, params).await;
+    let notification = match &outcome {
+        Ok(Outcome {
+            changed: Some(ids), ..
+        }) => Some(json!({
+            "jsonrpc": "2.0",
+            "method": "agendas.changed",
+            "params": { "cause": method, "ids": ids },
+        })),
+        _ => None,
+    };
+    // Requests without an id are notifications and get no response.
+    let response = id.map(|id| match outcome {
+        Ok(outcome) => json!({ "jsonrpc": "2.0", "id": id, "result": outcome.result }),
+        Err(e) => error_response(id, e.code, e.message),
+    });
+    (response, notification)
+}
+
+pub struct Session<'a, A, L> {
+    agendas: &'a A,
+    logs: &'a L,
+    slots: Slots<'a, A, L>,
+    time_zone: TimeZone,
+    default_deadline: Span,
+}
+
+impl<'a, A, L> Session<'a, A, L>
+where
+    A: AgendaRepo + Sync,
+    L: LogRepo + Sync,
+{
+    pub fn new(
+        agendas: &'a A,
+        logs: &'a L,
+        max_slots: u8,
+        time_zone: TimeZone,
+        default_deadline: Span,
+    ) -> Self {
+        Session {
+            agendas,
+            logs,
+            slots: Slots::new(agendas, logs, max_slots).acting_as("rpc"),
+            time_zone,
+            default_deadline,
+        }
+    }
+}
+
+impl<A, L> Methods for Session<'_, A, L>
+where
+    A: AgendaRepo + Sync,
+    L: LogRepo + Sync,
+{
+    async fn call(&self, method: &str, params: Value) -> Result<Outcome, RpcError> {
+        let agendas = self.agendas;
+        let logs = self.logs;
+        match method {
+            "create_agenda" => {
+                let p: NewAgenda = parse(params)?;
+                let id = agendas
+                    .create_agenda(&AgendaCreate {
+                        title: p.title,
+                        agenda_status: p.agenda_status.unwrap_or(AgendaStatus::Pending),
+                        terminate_at: p.terminate_at,
+                    })
+                    .await
+                    .map_err(RpcError::repo)?;
+                Outcome::wrote(json!({ "id": id }), vec![id])
+            }
+            "insert_agenda" => {
+                let agenda: Agenda = parse(params)?;
+                agendas
+                    .insert_agenda(&agenda)
+                    .await
+                    .map_err(RpcError::repo)?;
+                Outcome::wrote(Value::Null, vec![agenda.id])
+            }
+            "delete_agenda_by_id" => {
+                let Id { id } = parse(params)?;
+                agendas
+                    .delete_agenda_by_id(id)
+                    .await
+                    .map_err(RpcError::repo)?;
+                Outcome::wrote(Value::Null, vec![id])
+            }
+            "update_agenda" => {
+                let p: AgendaChange = parse(params)?;
+                let update = AgendaUpdate {
+                    title: p.title,
+                    agenda_status: p.agenda_status,
+                    terminate_at: p.terminate_at,
+                    actor: Some("rpc".to_string()),
+                };
+                agendas
+                    .update_agenda(p.id, &update)
+                    .await
+                    .map_err(RpcError::repo)?;
+                Outcome::wrote(Value::Null, vec![p.id])
+            }
+            "get_agenda_by_id" => {
+                let Id { id } = parse(params)?;
+                Outcome::read(agendas.get_agenda_by_id(id).await.map_err(RpcError::repo)?)
+            }
+            "get_agendas_by_title" => {
+                let Title { title } = parse(params)?;
+                Outcome::read(
+                    agendas
+                        .get_agendas_by_title(&title)
+                        .await
+                        .map_err(RpcError::repo)?,
+                )
+            }
+            "get_agendas_by_status" => {
+                let Status { status } = parse(params)?;
+                let status = status.map(|status| status.to_string());
+                Outcome::read(
+                    agendas
+                        .get_agendas_by_status(status.as_deref())
+                        .await
+                        .map_err(RpcError::repo)?,
+                )
+            }
+            "count_agendas_by_status" => {
+                let Status { status } = parse(params)?;
+                let status = status.map(|status| status.to_string());
+                Outcome::read(
+                    agendas
+                        .count_agendas_by_status(status.as_deref())
+                        .await
+                        .map_err(RpcError::repo)?,
+                )
+            }
+            "get_agendas_by_terminate_time_range" => {
+                let Range { start, end } = parse(params)?;
+                Outcome::read(
+                    agendas
+                        .get_agendas_by_terminate_time_range(start, end)
+                        .await
+                        .map_err(RpcError::repo)?,
+                )
+            }
+            "get_agendas_by_initiate_time_range" => {
+                let Range { start, end } = parse(params)?;
+                Outcome::read(
+                    agendas
+                        .get_agendas_by_initiate_time_range(start, end)
+                        .await
+                        .map_err(RpcError::repo)?,
+                )
+            }
+            "create_log" => {
+                let p: NewLog = parse(params)?;
+                let id = logs
+                    .create_log(&LogCreate {
+                        agenda_id: p.agenda_id,
+                        content: p.content,
+                        log_type: p.log_type,
+                    })
+                    .await
+                    .map_err(RpcError::repo)?;
+                Outcome::wrote(json!({ "id": id }), vec![p.agenda_id])
+            }
+            "insert_log" => {
+                let log: Log = parse(params)?;
+                logs.insert_log(&log).await.map_err(RpcError::repo)?;
+                Outcome::wrote(Value::Null, vec![log.agenda_id])
+            }
+            "delete_log" => {
+                let Id { id } = parse(params)?;
+                logs.delete_log(id).await.map_err(RpcError::repo)?;
+                Outcome::wrote(Value::Null, Vec::new())
+            }
+            "get_logs_by_agenda_id" => {
+                let AgendaId { agenda_id } = parse(params)?;
+                Outcome::read(
+                    logs.get_logs_by_agenda_id(agenda_id)
+                        .await
+                        .map_err(RpcError::repo)?,
+                )
+            }
+            "get_logs_by_time_range" => {
+                let Range { start, end } = parse(params)?;
+                Outcome::read(
+                    logs.get_logs_by_time_range(start, end)
+                        .await
+                        .map_err(RpcError::repo)?,
+                )
+            }
+            "slot.list" => {
+                let current = self.slots.current().await?.map(|(slot, _)| slot);
+                let slots: Vec<Value> = self
+                    .slots
+                    .list()
+                    .await?
+                    .into_iter()
+                    .zip(1u8..)
+                    .map(|(agenda, slot)| {
+                        json!({ "slot": slot, "current": current == Some(slot), "agenda": agenda })
+                    })
+                    .collect();
+                Outcome::read(slots)
+            }
+            "slot.current" => {
+                let current = self.slots.current().await?;
+                Outcome::read(
+                    current.map(|(slot, agenda)| json!({ "slot": slot, "agenda": agenda })),
+                )
+            }
+            "slot.add" => {
+                let p: SlotAdd = parse(params)?;
+                let now = Zoned::now().with_time_zone(self.time_zone.clone());
+                let terminate_at = match p.deadline {
+                    Some(input) => time::parse_deadline(&input, &now, &self.time_zone)
+                        .map_err(|e| RpcError::new(INVALID_PARAMS, format!("deadline: {e}")))?,
+                    None => now
+                        .checked_add(self.default_deadline)
+                        .map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))?
+                        .timestamp(),
+                };
+                let id = self.slots.add(Some(p.title), terminate_at).await?;
+                let agenda = agendas.get_agenda_by_id(id).await.map_err(RpcError::repo)?;
+                Outcome::wrote(agenda, vec![id])
+            }
+            "slot.set" => {
+                let SlotNumber { slot } = parse(params)?;
+                let previous = self.slots.current().await?.map(|(_, agenda)| agenda.id);
+                let agenda = self.slots.set(slot).await?;
+                let changed = previous.into_iter().chain([agenda.id]).collect();
+                Outcome::wrote(agenda, changed)
+            }
+            "slot.shelve" => {
+                let OptionalTitle { title } = parse(params)?;
+                let agenda = self.slots.shelve(title.as_deref()).await?;
+                let id = agenda.id;
+                Outcome::wrote(agenda, vec![id])
+            }
+            "slot.mark" | "slot.put_off" | "slot.terminate" => {
+                let Content { content } = parse(params)?;
+                let agenda = match method {
+                    "slot.mark" => self.slots.mark(content).await?,
+                    "slot.put_off" => self.slots.put_off(content).await?,
+                    _ => self.slots.terminate(content).await?,
+                };
+                let id = agenda.id;
+                Outcome::wrote(agenda, vec![id])
+            }
+            "slot.history" => {
+                let Since { since } = parse(params)?;
+                let since = match since {
+                    Some(since) => since,
+                    None => Timestamp::now() - 168.hours(),
+                };
+                let history: Vec<Value> = self
+                    .slots
+                    .history(since)
+                    .await?
+                    .into_iter()
+                    .map(|(log, title)| json!({ "log": log, "title": title }))
+                    .collect();
+                Outcome::read(history)
+            }
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("unknown method `{method}`"),
+            )),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NewAgenda {
+    title: String,
+    agenda_status: Option<AgendaStatus>,
+    terminate_at: Timestamp,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AgendaChange {
+    id: Uuid,
+    title: Option<String>,
+    agenda_status: Option<AgendaStatus>,
+    terminate_at: Option<Timestamp>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NewLog {
+    agenda_id: Uuid,
+    #[serde(default)]
+    content: String,
+    log_type: LogType,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Id {
+    id: Uuid,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AgendaId {
+    agenda_id: Uuid,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Title {
+    title: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OptionalTitle {
+    title: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Status {
+    status: Option<AgendaStatus>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Range {
+    start: Timestamp,
+    end: Timestamp,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SlotAdd {
+    title: String,
+    /// Same forms as `slot add -t`: a span, a date or a date-time.
+    deadline: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SlotNumber {
+    slot: u8,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Content {
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Since {
+    since: Option<Timestamp>,
+}
+
+/// Parses by-name params; omitted params count as an empty object.
+pub(crate) fn parse<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn to_value(value: impl serde::Serialize) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))
+}
+
+fn error_response(id: Value, code: i64, message: String) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use storage::{SqliteAgendaRepo, SqliteLogRepo};
+
+    async fn setup_repos() -> (tempfile::TempDir, SqliteAgendaRepo, SqliteLogRepo) {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let pool = storage::init_db(&dir.path().join("finiate.db"))
+            .await
+            .expect("init db");
+        let (agenda_repo, log_repo) = storage::create_repos(&pool);
+        (dir, agenda_repo, log_repo)
+    }
+
+    async fn exchange(agendas: &SqliteAgendaRepo, logs: &SqliteLogRepo, input: &str) -> Vec<Value> {
+        let session = Session::new(agendas, logs, 3, TimeZone::UTC, 1.day());
+        let mut output = Vec::new();
+        serve(&session, input.as_bytes(), &mut output)
+            .await
+            .expect("serve");
+        String::from_utf8(output)
+            .expect("utf-8")
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("JSON line"))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn slot_operations_respond_and_notify() {
+        let (_dir, agendas, logs) = setup_repos().await;
+        let input = concat!(
+            r#"{"jsonrpc":"2.0","id":1,"method":"slot.add","params":{"title":"Plugin","deadline":"2h"}}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","id":2,"method":"slot.set","params":{"slot":1}}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","id":"list","method":"slot.list"}"#,
+            "\n",
+        );
+        let messages = exchange(&agendas, &logs, input).await;
+
+        assert_eq!(messages.len(), 5);
+        assert_eq!(messages[0]["id"], 1);
+        assert_eq!(messages[0]["result"]["title"], "Plugin");
+        let id = messages[0]["result"]["id"].clone();
+        assert_eq!(messages[1]["method"], "agendas.changed");
+        assert_eq!(
+            messages[1]["params"],
+            json!({ "cause": "slot.add", "ids": [id] })
+        );
+        assert_eq!(messages[2]["result"]["agenda_status"], "ongoing");
+        assert_eq!(messages[3]["params"]["cause"], "slot.set");
+        assert_eq!(messages[4]["id"], "list");
+        assert_eq!(messages[4]["result"][0]["current"], true);
+    }
+
+    #[tokio::test]
+    async fn repo_methods_mirror_the_traits() {
+        let (_dir, agendas, logs) = setup_repos().await;
+        let input = concat!(
+            r#"{"jsonrpc":"2.0","id":1,"method":"create_agenda","params":{"title":"A","terminate_at":"2030-01-01T00:00:00Z"}}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","id":2,"method":"count_agendas_by_status","params":{"status":"pending"}}"#,
+            "\n",
+        );
+        let messages = exchange(&agendas, &logs, input).await;
+        assert_eq!(messages[2]["result"], 1);
+
+        let id = messages[0]["result"]["id"].as_str().unwrap();
+        let input = format!(
+            r#"{{"jsonrpc":"2.0","id":3,"method":"create_log","params":{{"agenda_id":"{id}","content":"hi","log_type":"common_log"}}}}
+{{"jsonrpc":"2.0","id":4,"method":"get_logs_by_agenda_id","params":{{"agenda_id":"{id}"}}}}
+"#
+        );
+        let messages = exchange(&agendas, &logs, &input).await;
+        assert_eq!(messages[2]["result"][0]["content"], "hi");
+    }
+
+    #[tokio::test]
+    async fn errors_follow_the_spec() {
+        let (_dir, agendas, logs) = setup_repos().await;
+        let input = concat!(
+            "{not json\n",
+            r#"{"jsonrpc":"2.0","id":1,"method":"nope"}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","id":2,"method":"slot.set","params":{"slot":"one"}}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","id":3,"method":"slot.mark"}"#,
+            "\n",
+            r#"{"id":4,"method":"slot.list"}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","method":"slot.mark"}"#,
+            "\n",
+        );
+        let messages = exchange(&agendas, &logs, input).await;
+
+        let codes: Vec<_> = messages
+            .iter()
+            .map(|message| message["error"]["code"].as_i64().unwrap())
+            .collect();
+        // The failing notification on the last line gets no response.
+        assert_eq!(
+            codes,
+            [
+                PARSE_ERROR,
+                METHOD_NOT_FOUND,
+                INVALID_PARAMS,
+                SLOT_ERROR,
+                INVALID_REQUEST
+            ]
+        );
+        assert_eq!(messages[0]["id"], Value::Null);
+        assert_eq!(messages[4]["id"], 4);
+    }
+
+    #[tokio::test]
+    async fn batches_get_one_array_response() {
+        let (_dir, agendas, logs) = setup_repos().await;
+        let input = concat!(
+            r#"[{"jsonrpc":"2.0","id":1,"method":"slot.add","params":{"title":"A"}},"#,
+            r#"{"jsonrpc":"2.0","method":"slot.add","params":{"title":"B"}},"#,
+            r#"{"jsonrpc":"2.0","id":2,"method":"count_agendas_by_status"}]"#,
+            "\n[]\n",
+        );
+        let messages = exchange(&agendas, &logs, input).await;
+
+        let responses = messages[0].as_array().expect("batch response");
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[1]["result"], 2);
+        assert_eq!(messages[1]["method"], "agendas.changed");
+        assert_eq!(messages[2]["method"], "agendas.changed");
+        assert_eq!(messages[3]["error"]["code"], INVALID_REQUEST);
+    }
+}
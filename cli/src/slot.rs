@@ -0,0 +1,502 @@
+use domain::*;
+use jiff::Timestamp;
+use std::error::Error;
+use uuid::Uuid;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SlotError {
+    #[error("repository error: {0}")]
+    Repo(Box<dyn Error + Send + Sync>),
+    #[error("all {0} slots are occupied; terminate or put off an agenda first")]
+    Full(u8),
+    #[error("slot {0} is empty")]
+    EmptySlot(u8),
+    #[error("no agenda is currently active; use `slot set <slot>` first")]
+    NoCurrent,
+    #[error("no agenda titled `{0}` occupies a slot")]
+    UnknownTitle(String),
+    #[error("a title is required")]
+    MissingTitle,
+}
+
+impl SlotError {
+    fn repo<E: Error + Send + Sync + 'static>(error: E) -> SlotError {
+        SlotError::Repo(Box::new(error))
+    }
+}
+
+/// Slots are the non-terminated agendas, numbered from 1 in creation order.
+/// At most one of them is `Ongoing`: that one is the current slot which
+/// `mark`, `putoff` and `terminate` act on.
+pub struct Slots<'a, A, L> {
+    agendas: &'a A,
+    logs: &'a L,
+    max_slots: u8,
+    actor: &'static str,
+}
+
+impl<'a, A: AgendaRepo + Sync, L: LogRepo + Sync> Slots<'a, A, L> {
+    pub fn new(agendas: &'a A, logs: &'a L, max_slots: u8) -> Self {
+        Slots {
+            agendas,
+            logs,
+            max_slots,
+            actor: "cli",
+        }
+    }
+
+    /// Names the front end in the revision history of the agendas it changes
+    /// (`cli` by default).
+    pub fn acting_as(mut self, actor: &'static str) -> Self {
+        self.actor = actor;
+        self
+    }
+
+    /// Returns the occupied slots in slot order.
+    pub async fn list(&self) -> Result<Vec<Agenda>, SlotError> {
+        let mut slots = Vec::new();
+        for status in [AgendaStatus::Pending, AgendaStatus::Ongoing] {
+            let agendas = self
+                .agendas
+                .get_agendas_by_status(Some(&status.to_string()))
+                .await
+                .map_err(SlotError::repo)?;
+            slots.extend(agendas);
+        }
+        // UUIDv7 ids sort by creation time, which keeps slot numbers stable.
+        slots.sort_by_key(|agenda| (agenda.initiate_at, agenda.id));
+        Ok(slots)
+    }
+
+    /// Returns the current slot number and its agenda, if any is active.
+    pub async fn current(&self) -> Result<Option<(u8, Agenda)>, SlotError> {
+        Ok(self
+            .list()
+            .await?
+            .into_iter()
+            .zip(1..)
+            .find(|(agenda, _)| agenda.agenda_status == AgendaStatus::Ongoing)
+            .map(|(agenda, slot)| (slot, agenda)))
+    }
+
+    pub async fn add(
+        &self,
+        title: Option<String>,
+        terminate_at: Timestamp,
+    ) -> Result<Uuid, SlotError> {
+        let title = title
+            .filter(|title| !title.trim().is_empty())
+            .ok_or(SlotError::MissingTitle)?;
+        let occupied = self.list().await?.len();
+        if occupied >= usize::from(self.max_slots) {
+            return Err(SlotError::Full(self.max_slots));
+        }
+        self.agendas
+            .create_agenda(&AgendaCreate {
+                title,
+                agenda_status: AgendaStatus::Pending,
+                terminate_at,
+            })
+            .await
+            .map_err(SlotError::repo)
+    }
+
+    /// Makes `slot` the current slot, putting off whichever agenda was active.
+    pub async fn set(&self, slot: u8) -> Result<Agenda, SlotError> {
+        let slots = self.list().await?;
+        let target = slot
+            .checked_sub(1)
+            .and_then(|index| slots.get(usize::from(index)))
+            .ok_or(SlotError::EmptySlot(slot))?;
+
+        for agenda in &slots {
+            if agenda.agenda_status == AgendaStatus::Ongoing && agenda.id != target.id {
+                self.transition(
+                    agenda.id,
+                    AgendaStatus::Pending,
+                    LogType::PutOff,
+                    format!("switched to slot {slot}"),
+                )
+                .await?;
+            }
+        }
+        if target.agenda_status != AgendaStatus::Ongoing {
+            self.transition(
+                target.id,
+                AgendaStatus::Ongoing,
+                LogType::Activate,
+                String::new(),
+            )
+            .await?;
+        }
+
+        let mut agenda = target.clone();
+        agenda.agenda_status = AgendaStatus::Ongoing;
+        Ok(agenda)
+    }
+
+    /// Moves the agenda titled `title` (or the current one) back to pending.
+    pub async fn shelve(&self, title: Option<&str>) -> Result<Agenda, SlotError> {
+        let mut agenda = match title {
+            Some(title) => self
+                .list()
+                .await?
+                .into_iter()
+                .find(|agenda| agenda.title == title)
+                .ok_or_else(|| SlotError::UnknownTitle(title.to_string()))?,
+            None => self.current().await?.ok_or(SlotError::NoCurrent)?.1,
+        };
+        if agenda.agenda_status == AgendaStatus::Ongoing {
+            self.transition(
+                agenda.id,
+                AgendaStatus::Pending,
+                LogType::PutOff,
+                "shelved".to_string(),
+            )
+            .await?;
+            agenda.agenda_status = AgendaStatus::Pending;
+        }
+        Ok(agenda)
+    }
+
+    /// Writes a common log on the current agenda.
+    pub async fn mark(&self, content: Option<String>) -> Result<Agenda, SlotError> {
+        let (_, agenda) = self.current().await?.ok_or(SlotError::NoCurrent)?;
+        self.logs
+            .create_log(&LogCreate {
+                agenda_id: agenda.id,
+                content: content.unwrap_or_default(),
+                log_type: LogType::CommonLog,
+            })
+            .await
+            .map_err(SlotError::repo)?;
+        Ok(agenda)
+    }
+
+    /// Puts off the current agenda; it keeps its slot but is no longer active.
+    pub async fn put_off(&self, content: Option<String>) -> Result<Agenda, SlotError> {
+        let (_, mut agenda) = self.current().await?.ok_or(SlotError::NoCurrent)?;
+        self.transition(
+            agenda.id,
+            AgendaStatus::Pending,
+            LogType::PutOff,
+            content.unwrap_or_default(),
+        )
+        .await?;
+        agenda.agenda_status = AgendaStatus::Pending;
+        Ok(agenda)
+    }
+
+    /// Terminates the current agenda, freeing its slot.
+    pub async fn terminate(&self, content: Option<String>) -> Result<Agenda, SlotError> {
+        let (_, mut agenda) = self.current().await?.ok_or(SlotError::NoCurrent)?;
+        self.transition(
+            agenda.id,
+            AgendaStatus::Terminated,
+            LogType::Terminate,
+            content.unwrap_or_default(),
+        )
+        .await?;
+        agenda.agenda_status = AgendaStatus::Terminated;
+        Ok(agenda)
+    }
+
+    /// Restores the one-current-agenda rule after changes merged from other
+    /// devices: the most recently activated agenda stays current and the
+    /// others are put off. Agendas beyond `max_slots` are left alone, since
+    /// `add` refuses new ones until enough are closed. Returns the agendas
+    /// put off and how many slots are over the limit.
+    pub async fn normalize(&self) -> Result<(Vec<Agenda>, usize), SlotError> {
+        let slots = self.list().await?;
+        let mut ongoing = Vec::new();
+        for agenda in slots
+            .iter()
+            .filter(|agenda| agenda.agenda_status == AgendaStatus::Ongoing)
+        {
+            let activated = self
+                .logs
+                .get_logs_by_agenda_id(agenda.id)
+                .await
+                .map_err(SlotError::repo)?
+                .iter()
+                .filter(|log| log.log_type == LogType::Activate)
+                .map(|log| log.create_at)
+                .max();
+            ongoing.push((activated, agenda.id));
+        }
+        ongoing.sort();
+        ongoing.pop();
+
+        let mut put_off = Vec::new();
+        for (_, id) in ongoing {
+            self.transition(
+                id,
+                AgendaStatus::Pending,
+                LogType::PutOff,
+                "another device activated an agenda".to_string(),
+            )
+            .await?;
+            if let Some(agenda) = slots.iter().find(|agenda| agenda.id == id) {
+                put_off.push(Agenda {
+                    agenda_status: AgendaStatus::Pending,
+                    ..agenda.clone()
+                });
+            }
+        }
+
+        let overflow = slots.len().saturating_sub(usize::from(self.max_slots));
+        Ok((put_off, overflow))
+    }
+
+    /// Returns the logs written since `since`, oldest first, with the title
+    /// of the agenda each one belongs to.
+    pub async fn history(&self, since: Timestamp) -> Result<Vec<(Log, String)>, SlotError> {
+        let mut logs = self
+            .logs
+            .get_logs_by_time_range(since, Timestamp::now())
+            .await
+            .map_err(SlotError::repo)?;
+        logs.sort_by_key(|log| (log.create_at, log.id));
+
+        let mut entries = Vec::with_capacity(logs.len());
+        for log in logs {
+            let title = self
+                .agendas
+                .get_agenda_by_id(log.agenda_id)
+                .await
+                .map_err(SlotError::repo)?
+                .map(|agenda| agenda.title)
+                .unwrap_or_default();
+            entries.push((log, title));
+        }
+        Ok(entries)
+    }
+
+    async fn transition(
+        &self,
+        id: Uuid,
+        status: AgendaStatus,
+        log_type: LogType,
+        content: String,
+    ) -> Result<(), SlotError> {
+        self.agendas
+            .update_agenda(
+                id,
+                &AgendaUpdate {
+                    title: None,
+                    agenda_status: Some(status),
+                    terminate_at: None,
+                    actor: Some(self.actor.to_string()),
+                },
+            )
+            .await
+            .map_err(SlotError::repo)?;
+        self.logs
+            .create_log(&LogCreate {
+                agenda_id: id,
+                content,
+                log_type,
+            })
+            .await
+            .map_err(SlotError::repo)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jiff::ToSpan;
+    use storage::{SqliteAgendaRepo, SqliteLogRepo};
+
+    async fn setup_repos() -> (tempfile::TempDir, SqliteAgendaRepo, SqliteLogRepo) {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let pool = storage::init_db(&dir.path().join("finiate.db"))
+            .await
+            .expect("init db");
+        let (agenda_repo, log_repo) = storage::create_repos(&pool);
+        (dir, agenda_repo, log_repo)
+    }
+
+    fn deadline() -> Timestamp {
+        Timestamp::now() + 1.hour()
+    }
+
+    #[tokio::test]
+    async fn add_respects_max_slots() {
+        let (_dir, agendas, logs) = setup_repos().await;
+        let slots = Slots::new(&agendas, &logs, 2);
+
+        slots
+            .add(Some("a".into()), deadline())
+            .await
+            .expect("add a");
+        slots
+            .add(Some("b".into()), deadline())
+            .await
+            .expect("add b");
+        let result = slots.add(Some("c".into()), deadline()).await;
+
+        assert!(matches!(result, Err(SlotError::Full(2))));
+        let titles: Vec<_> = slots
+            .list()
+            .await
+            .expect("list")
+            .into_iter()
+            .map(|agenda| agenda.title)
+            .collect();
+        assert_eq!(titles, ["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn add_requires_title() {
+        let (_dir, agendas, logs) = setup_repos().await;
+        let slots = Slots::new(&agendas, &logs, 2);
+
+        let result = slots.add(Some("  ".into()), deadline()).await;
+        assert!(matches!(result, Err(SlotError::MissingTitle)));
+    }
+
+    #[tokio::test]
+    async fn set_switches_current_slot() {
+        let (_dir, agendas, logs) = setup_repos().await;
+        let slots = Slots::new(&agendas, &logs, 3);
+        let first = slots
+            .add(Some("a".into()), deadline())
+            .await
+            .expect("add a");
+        let second = slots
+            .add(Some("b".into()), deadline())
+            .await
+            .expect("add b");
+
+        slots.set(1).await.expect("set 1");
+        slots.set(2).await.expect("set 2");
+
+        let (slot, current) = slots.current().await.expect("current").expect("some");
+        assert_eq!(slot, 2);
+        assert_eq!(current.id, second);
+
+        let first_logs = logs.get_logs_by_agenda_id(first).await.expect("logs");
+        let types: Vec<_> = first_logs.iter().map(|log| log.log_type).collect();
+        assert!(types.contains(&LogType::Activate));
+        assert!(types.contains(&LogType::PutOff));
+    }
+
+    #[tokio::test]
+    async fn set_empty_slot_is_an_error() {
+        let (_dir, agendas, logs) = setup_repos().await;
+        let slots = Slots::new(&agendas, &logs, 3);
+
+        assert!(matches!(slots.set(1).await, Err(SlotError::EmptySlot(1))));
+        assert!(matches!(slots.set(0).await, Err(SlotError::EmptySlot(0))));
+    }
+
+    #[tokio::test]
+    async fn terminate_frees_the_slot() {
+        let (_dir, agendas, logs) = setup_repos().await;
+        let slots = Slots::new(&agendas, &logs, 1);
+        let id = slots.add(Some("a".into()), deadline()).await.expect("add");
+        slots.set(1).await.expect("set");
+
+        slots.mark(Some("progress".into())).await.expect("mark");
+        slots
+            .terminate(Some("done".into()))
+            .await
+            .expect("terminate");
+
+        assert!(slots.list().await.expect("list").is_empty());
+        let agenda = agendas
+            .get_agenda_by_id(id)
+            .await
+            .expect("get")
+            .expect("some");
+        assert_eq!(agenda.agenda_status, AgendaStatus::Terminated);
+        slots
+            .add(Some("b".into()), deadline())
+            .await
+            .expect("slot is free again");
+    }
+
+    #[tokio::test]
+    async fn commands_without_current_slot_fail() {
+        let (_dir, agendas, logs) = setup_repos().await;
+        let slots = Slots::new(&agendas, &logs, 1);
+        slots.add(Some("a".into()), deadline()).await.expect("add");
+
+        assert!(matches!(slots.mark(None).await, Err(SlotError::NoCurrent)));
+        assert!(matches!(
+            slots.put_off(None).await,
+            Err(SlotError::NoCurrent)
+        ));
+        assert!(matches!(
+            slots.terminate(None).await,
+            Err(SlotError::NoCurrent)
+        ));
+    }
+
+    #[tokio::test]
+    async fn history_pairs_logs_with_titles() {
+        let (_dir, agendas, logs) = setup_repos().await;
+        let slots = Slots::new(&agendas, &logs, 1);
+        let since = Timestamp::now() - 1.second();
+        slots.add(Some("a".into()), deadline()).await.expect("add");
+        slots.set(1).await.expect("set");
+        slots.put_off(Some("later".into())).await.expect("put off");
+
+        let history = slots.history(since).await.expect("history");
+        let entries: Vec<_> = history
+            .iter()
+            .map(|(log, title)| (log.log_type, title.as_str()))
+            .collect();
+        assert_eq!(entries, [(LogType::Activate, "a"), (LogType::PutOff, "a")]);
+    }
+
+    #[tokio::test]
+    async fn normalize_keeps_one_current_and_reports_overflow() {
+        let (_dir, agendas, logs) = setup_repos().await;
+        let slots = Slots::new(&agendas, &logs, 3);
+        for title in ["a", "b", "c"] {
+            slots
+                .add(Some(title.into()), deadline())
+                .await
+                .expect("add");
+        }
+        slots.set(1).await.expect("set a");
+        slots.set(3).await.expect("set c");
+        // As if merged from another device: `a` active again, and a fourth.
+        let a = slots.list().await.expect("list")[0].id;
+        agendas
+            .update_agenda(
+                a,
+                &AgendaUpdate {
+                    title: None,
+                    agenda_status: Some(AgendaStatus::Ongoing),
+                    terminate_at: None,
+                    actor: None,
+                },
+            )
+            .await
+            .expect("update");
+        Slots::new(&agendas, &logs, 4)
+            .add(Some("d".into()), deadline())
+            .await
+            .expect("add d");
+
+        let (put_off, overflow) = slots.normalize().await.expect("normalize");
+        let titles: Vec<_> = put_off.iter().map(|agenda| agenda.title.as_str()).collect();
+        assert_eq!(titles, ["a"]);
+        assert_eq!(overflow, 1);
+        let (slot, current) = slots.current().await.expect("current").expect("some");
+        assert_eq!((slot, current.title.as_str()), (3, "c"));
+        // Nothing is terminated: all four are still open.
+        let open = slots.list().await.expect("list");
+        assert!(
+            open.iter()
+                .all(|agenda| agenda.agenda_status != AgendaStatus::Terminated)
+        );
+        assert_eq!(open.len(), 4);
+        assert_eq!(slots.normalize().await.expect("again"), (vec![], 1));
+        assert!(slots.add(Some("e".into()), deadline()).await.is_err());
+    }
+}
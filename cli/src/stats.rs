@@ -0,0 +1,164 @@
+use domain::*;
+use jiff::{SignedDuration, Span, SpanRound, Timestamp, Unit};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Write;
+use uuid::Uuid;
+
+/// Gathers the agendas and logs of `[start, end]` and computes their statistics.
+pub async fn collect<A, L>(
+    agendas: &A,
+    logs: &L,
+    start: Timestamp,
+    end: Timestamp,
+) -> Result<Stats, Box<dyn Error + Send + Sync>>
+where
+    A: AgendaRepo + Sync,
+    L: LogRepo + Sync,
+{
+    let created = agendas
+        .get_agendas_by_initiate_time_range(start, end)
+        .await?;
+
+    // An agenda terminated more than once in the period counts once, at its
+    // last termination.
+    let mut terminated_at: HashMap<Uuid, Timestamp> = HashMap::new();
+    for log in logs.get_logs_by_time_range(start, end).await? {
+        if log.log_type == LogType::Terminate {
+            let at = terminated_at.entry(log.agenda_id).or_insert(log.create_at);
+            *at = (*at).max(log.create_at);
+        }
+    }
+
+    let mut terminated = Vec::with_capacity(terminated_at.len());
+    for (agenda_id, at) in terminated_at {
+        let Some(agenda) = agendas.get_agenda_by_id(agenda_id).await? else {
+            continue;
+        };
+        let put_offs = logs
+            .get_logs_by_agenda_id(agenda_id)
+            .await?
+            .iter()
+            .filter(|log| log.log_type == LogType::PutOff)
+            .count();
+        terminated.push((agenda, at, put_offs));
+    }
+
+    let terminations: Vec<_> = terminated
+        .iter()
+        .map(|(agenda, at, put_offs)| Termination {
+            agenda,
+            terminated_at: *at,
+            put_offs: *put_offs,
+        })
+        .collect();
+    Ok(Stats::compute(start, end, &created, &terminations))
+}
+
+pub fn render(stats: &Stats, period: &str) -> String {
+    let mut out = String::new();
+    let percent = |ratio: f64| format!("{:.0}%", ratio * 100.0);
+    let rows = [
+        ("Created", stats.created.to_string()),
+        ("Terminated", stats.terminated.to_string()),
+        (
+            "Median lead time",
+            stats.median_lead_time.map(humanize).unwrap_or("-".into()),
+        ),
+        (
+            "On-time ratio",
+            stats.on_time_ratio.map(percent).unwrap_or("-".into()),
+        ),
+        (
+            "Avg. put-offs",
+            stats
+                .average_put_offs
+                .map(|average| format!("{average:.1}"))
+                .unwrap_or("-".into()),
+        ),
+    ];
+    writeln!(out, "Statistics {period}").unwrap();
+    for (label, value) in rows {
+        writeln!(out, "  {label:<18}{value}").unwrap();
+    }
+    out
+}
+
+/// Formats a duration like `2d 4h 5m`, counting days as 24 hours.
+fn humanize(duration: SignedDuration) -> String {
+    let rounded = Span::try_from(duration).and_then(|span| {
+        span.round(
+            SpanRound::new()
+                .largest(Unit::Day)
+                .smallest(Unit::Minute)
+                .days_are_24_hours(),
+        )
+    });
+    match rounded {
+        Ok(span) => format!("{span:#}"),
+        Err(_) => format!("{duration:#}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jiff::ToSpan;
+
+    #[tokio::test]
+    async fn collect_counts_the_period_once_per_agenda() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let pool = storage::init_db(&dir.path().join("finiate.db"))
+            .await
+            .expect("init db");
+        let (agendas, logs) = storage::create_repos(&pool);
+        let base: Timestamp = "2026-03-01T00:00:00Z".parse().unwrap();
+        let agenda = |title: &str, initiate_at: Timestamp, terminate_at: Timestamp| Agenda {
+            id: Uuid::now_v7(),
+            title: title.to_string(),
+            agenda_status: AgendaStatus::Terminated,
+            initiate_at,
+            terminate_at,
+        };
+        let log = |agenda: &Agenda, log_type: LogType, create_at: Timestamp| Log {
+            id: Uuid::now_v7(),
+            agenda_id: agenda.id,
+            content: String::new(),
+            create_at,
+            log_type,
+        };
+        let on_time = agenda("on time", base, base + 10.hours());
+        let late = agenda("late", base, base + 1.hour());
+        let earlier = agenda("earlier", base - 240.hours(), base + 24.hours());
+        let later = agenda("later", base + 1.hour(), base + 48.hours());
+        for agenda in [&on_time, &late, &earlier, &later] {
+            agendas.insert_agenda(agenda).await.expect("insert agenda");
+        }
+        for log in [
+            log(&on_time, LogType::PutOff, base + 30.minutes()),
+            log(&on_time, LogType::Terminate, base + 1.hour()),
+            // Terminated twice: counted once, at the last termination.
+            log(&late, LogType::Terminate, base + 2.hours()),
+            log(&late, LogType::Terminate, base + 3.hours()),
+            // Terminated after the period.
+            log(&later, LogType::Terminate, base + 48.hours()),
+        ] {
+            logs.insert_log(&log).await.expect("insert log");
+        }
+
+        let stats = collect(&agendas, &logs, base, base + 24.hours())
+            .await
+            .expect("collect");
+        assert_eq!(stats.created, 3);
+        assert_eq!(stats.terminated, 2);
+        assert_eq!(stats.median_lead_time, Some(SignedDuration::from_hours(2)));
+        assert_eq!(stats.on_time_ratio, Some(0.5));
+        assert_eq!(stats.average_put_offs, Some(0.5));
+    }
+
+    #[test]
+    fn humanize_uses_days() {
+        let duration = SignedDuration::from_hours(52) + SignedDuration::from_secs(300);
+        assert_eq!(humanize(duration), "2d 4h 5m");
+    }
+}
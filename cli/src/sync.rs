@@ -0,0 +1,227 @@
+//! Sync through a shared folder, such as one kept in step by Syncthing.
+//!
+//! Each device only ever appends to its own `<device>.jsonl` change file and
+//! reads everyone else's, so the folder never sees two writers on one file.
+//! Every line sets one field of one agenda or log; the database keeps, per
+//! field, the change with the greatest `(at, device, seq)`, which makes the
+//! outcome the same on every device whatever order the files arrive in.
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use storage::{SqliteSync, SyncChange, SyncValue};
+
+const EXTENSION: &str = "jsonl";
+
+#[derive(Debug, thiserror::Error)]
+pub enum SyncError {
+    #[error("{}:{line}: not a finiate change: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        line: usize,
+        source: serde_json::Error,
+    },
+}
+
+/// One line of a change file.
+#[derive(Debug, Serialize, Deserialize)]
+struct Line {
+    seq: i64,
+    at: i64,
+    entity: String,
+    id: String,
+    field: String,
+    value: Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum Value {
+    Null,
+    Integer(i64),
+    Text(String),
+}
+
+impl From<SyncChange> for Line {
+    fn from(change: SyncChange) -> Self {
+        Line {
+            seq: change.seq,
+            at: change.at,
+            entity: change.entity,
+            id: change.entity_id,
+            field: change.field,
+            value: match change.value {
+                SyncValue::Null => Value::Null,
+                SyncValue::Integer(integer) => Value::Integer(integer),
+                SyncValue::Text(text) => Value::Text(text),
+            },
+        }
+    }
+}
+
+impl From<Line> for SyncChange {
+    fn from(line: Line) -> Self {
+        SyncChange {
+            seq: line.seq,
+            at: line.at,
+            entity: line.entity,
+            entity_id: line.id,
+            field: line.field,
+            value: match line.value {
+                Value::Null => SyncValue::Null,
+                Value::Integer(integer) => SyncValue::Integer(integer),
+                Value::Text(text) => SyncValue::Text(text),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SyncSummary {
+    pub exported: usize,
+    /// Changes from other devices that won over what was known.
+    pub merged: u64,
+    /// Other devices with new lines in their change files.
+    pub devices: usize,
+}
+
+/// Exports this device's pending changes into `dir`, then merges whatever
+/// the other devices have appended since the last sync.
+pub async fn sync(
+    store: &SqliteSync,
+    dir: &Path,
+    device: &str,
+) -> Result<SyncSummary, Box<dyn Error + Send + Sync>> {
+    std::fs::create_dir_all(dir)?;
+    let mut summary = SyncSummary::default();
+
+    let pending = store.pending().await?;
+    if let Some(last) = pending.last().map(|change| change.seq) {
+        let mut text = String::new();
+        for change in pending {
+            text.push_str(&serde_json::to_string(&Line::from(change))?);
+            text.push('\n');
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(format!("{device}.{EXTENSION}")))?;
+        file.write_all(text.as_bytes())?;
+        file.sync_all()?;
+        summary.exported = text.lines().count();
+        store.exported(last).await?;
+    }
+
+    let mut peers = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == EXTENSION)
+            && let Some(peer) = path.file_stem().and_then(|stem| stem.to_str())
+            && peer != device
+        {
+            peers.push((peer.to_string(), path));
+        }
+    }
+    peers.sort();
+
+    for (peer, path) in peers {
+        let bytes = std::fs::read(&path)?;
+        let mut offset = usize::try_from(store.offset(&peer).await?).unwrap_or(usize::MAX);
+        // A shorter file was replaced wholesale; merging again is harmless.
+        if offset > bytes.len() {
+            offset = 0;
+        }
+        // A line still being written or synced has no newline yet.
+        let Some(end) = bytes[offset..].iter().rposition(|&b| b == b'\n') else {
+            continue;
+        };
+        let end = offset + end + 1;
+        let first_line = bytes[..offset].iter().filter(|&&b| b == b'\n').count() + 1;
+        let changes = String::from_utf8_lossy(&bytes[offset..end])
+            .lines()
+            .enumerate()
+            .filter(|(_, text)| !text.trim().is_empty())
+            .map(|(index, text)| {
+                serde_json::from_str::<Line>(text)
+                    .map(SyncChange::from)
+                    .map_err(|source| SyncError::Parse {
+                        path: path.clone(),
+                        line: first_line + index,
+                        source,
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        summary.merged += store.merge(device, &peer, &changes, end as u64).await?;
+        summary.devices += 1;
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domain::*;
+    use jiff::Timestamp;
+    use storage::SqliteAgendaRepo;
+
+    async fn setup(dir: &Path, name: &str) -> (SqliteAgendaRepo, SqliteSync) {
+        let pool = storage::init_db(&dir.join(format!("{name}.db")))
+            .await
+            .expect("init db");
+        let (agenda_repo, _) = storage::create_repos(&pool);
+        (agenda_repo, storage::create_sync(&pool))
+    }
+
+    #[tokio::test]
+    async fn devices_converge_through_the_shared_folder() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let shared = dir.path().join("shared");
+        let (laptop, laptop_sync) = setup(dir.path(), "laptop").await;
+        let (desk, desk_sync) = setup(dir.path(), "desk").await;
+
+        let id = laptop
+            .create_agenda(&AgendaCreate {
+                title: "Plan trip".to_string(),
+                agenda_status: AgendaStatus::Pending,
+                terminate_at: Timestamp::now(),
+            })
+            .await
+            .expect("create agenda");
+        let summary = sync(&laptop_sync, &shared, "laptop").await.unwrap();
+        assert_eq!(summary.exported, 5);
+        assert_eq!(summary.devices, 0);
+
+        let summary = sync(&desk_sync, &shared, "desk").await.unwrap();
+        assert_eq!((summary.merged, summary.devices), (5, 1));
+        let copy = desk.get_agenda_by_id(id).await.unwrap().expect("merged");
+        assert_eq!(copy.title, "Plan trip");
+
+        // Nothing new: the stored offset skips lines already merged.
+        let summary = sync(&desk_sync, &shared, "desk").await.unwrap();
+        assert_eq!(summary, SyncSummary::default());
+
+        desk.update_agenda(
+            id,
+            &AgendaUpdate {
+                title: None,
+                agenda_status: Some(AgendaStatus::Terminated),
+                terminate_at: None,
+                actor: None,
+            },
+        )
+        .await
+        .unwrap();
+        sync(&desk_sync, &shared, "desk").await.unwrap();
+        // Half a line from a sync still in flight waits for the next run.
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(shared.join("desk.jsonl"))
+            .unwrap();
+        file.write_all(b"{\"seq\":").unwrap();
+        let summary = sync(&laptop_sync, &shared, "laptop").await.unwrap();
+        assert_eq!(summary.merged, 1);
+        let agenda = laptop.get_agenda_by_id(id).await.unwrap().unwrap();
+        assert_eq!(agenda.agenda_status, AgendaStatus::Terminated);
+    }
+}
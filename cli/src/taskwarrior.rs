@@ -0,0 +1,303 @@
+//! Import of Taskwarrior `task export` JSON.
+//!
+//! Tasks keep their uuid as agenda id, so importing the same export twice
+//! skips the tasks that are already there.
+
+use domain::*;
+use jiff::Timestamp;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashSet;
+use std::error::Error;
+use uuid::Uuid;
+
+/// Annotations Taskwarrior writes with `journal.time=on`.
+const STARTED: &str = "Started task";
+const STOPPED: &str = "Stopped task";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pending,
+    Waiting,
+    Completed,
+    Deleted,
+    /// The template of a recurring task; its instances are exported separately.
+    Recurring,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Annotation {
+    #[serde(deserialize_with = "timestamp")]
+    pub entry: Timestamp,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Task {
+    pub uuid: Uuid,
+    pub description: String,
+    pub status: Status,
+    #[serde(deserialize_with = "timestamp")]
+    pub entry: Timestamp,
+    #[serde(default, deserialize_with = "optional_timestamp")]
+    pub due: Option<Timestamp>,
+    #[serde(default, deserialize_with = "optional_timestamp")]
+    pub start: Option<Timestamp>,
+    #[serde(default, deserialize_with = "optional_timestamp")]
+    pub end: Option<Timestamp>,
+    pub project: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub annotations: Vec<Annotation>,
+}
+
+impl Task {
+    /// The agenda title: the description followed by the project and `+tag`s.
+    fn title(&self) -> String {
+        let mut title = self.description.trim().to_string();
+        if let Some(project) = &self.project {
+            title.push_str(&format!(" project:{project}"));
+        }
+        for tag in &self.tags {
+            title.push_str(&format!(" +{tag}"));
+        }
+        title
+    }
+
+    fn logs(&self) -> Vec<Log> {
+        let log = |create_at, content: &str, log_type| Log {
+            id: Uuid::now_v7(),
+            agenda_id: self.uuid,
+            content: content.to_string(),
+            create_at,
+            log_type,
+        };
+        let mut logs: Vec<Log> = self
+            .annotations
+            .iter()
+            .map(|annotation| match annotation.description.as_str() {
+                STARTED => log(annotation.entry, "", LogType::Activate),
+                STOPPED => log(annotation.entry, "", LogType::PutOff),
+                text => log(annotation.entry, text, LogType::CommonLog),
+            })
+            .collect();
+        // A task that is active right now only records its last start.
+        if let Some(start) = self.start
+            && !logs.iter().any(|log| log.create_at == start)
+        {
+            logs.push(log(start, "", LogType::Activate));
+        }
+        if self.status == Status::Completed {
+            logs.push(log(self.end.unwrap_or(self.entry), "", LogType::Terminate));
+        }
+        logs.sort_by_key(|log| log.create_at);
+        // Unfinished tasks are imported pending, so a start that is still
+        // running is kept as a note rather than a transition to ongoing.
+        if self.status != Status::Completed
+            && let Some(last) = logs
+                .iter_mut()
+                .rev()
+                .find(|log| log.log_type != LogType::CommonLog)
+            && last.log_type == LogType::Activate
+        {
+            last.log_type = LogType::CommonLog;
+            last.content = STARTED.to_string();
+        }
+        logs
+    }
+}
+
+/// What an import will do, computed before anything is written.
+#[derive(Debug, Default)]
+pub struct Plan {
+    pub imports: Vec<(Agenda, Vec<Log>)>,
+    pub already_imported: usize,
+    pub deleted: usize,
+    pub recurring: usize,
+}
+
+impl Plan {
+    pub fn new(tasks: &[Task], existing: &HashSet<Uuid>, default_due: Timestamp) -> Plan {
+        let mut plan = Plan::default();
+        for task in tasks {
+            match task.status {
+                Status::Deleted => plan.deleted += 1,
+                Status::Recurring => plan.recurring += 1,
+                _ if existing.contains(&task.uuid) => plan.already_imported += 1,
+                status => {
+                    let agenda = Agenda {
+                        id: task.uuid,
+                        title: task.title(),
+                        agenda_status: if status == Status::Completed {
+                            AgendaStatus::Terminated
+                        } else {
+                            AgendaStatus::Pending
+                        },
+                        initiate_at: task.entry,
+                        terminate_at: task.due.unwrap_or(default_due),
+                    };
+                    plan.imports.push((agenda, task.logs()));
+                }
+            }
+        }
+        plan
+    }
+
+    pub fn count(&self, status: AgendaStatus) -> usize {
+        self.imports
+            .iter()
+            .filter(|(agenda, _)| agenda.agenda_status == status)
+            .count()
+    }
+
+    pub fn log_count(&self) -> usize {
+        self.imports.iter().map(|(_, logs)| logs.len()).sum()
+    }
+
+    pub async fn apply<A, L>(
+        &self,
+        agendas: &A,
+        logs: &L,
+    ) -> Result<(), Box<dyn Error + Send + Sync>>
+    where
+        A: AgendaRepo + Sync,
+        L: LogRepo + Sync,
+    {
+        for (agenda, agenda_logs) in &self.imports {
+            agendas.insert_agenda(agenda).await?;
+            for log in agenda_logs {
+                logs.insert_log(log).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn parse(text: &str) -> Result<Vec<Task>, serde_json::Error> {
+    serde_json::from_str(text)
+}
+
+/// Accepts Taskwarrior's compact `20260101T120000Z` form as well as RFC 3339.
+fn parse_timestamp(value: &str) -> Result<Timestamp, jiff::Error> {
+    value.parse().or_else(|_| {
+        jiff::fmt::strtime::parse("%Y%m%dT%H%M%S%z", value.replace('Z', "+0000"))?.to_timestamp()
+    })
+}
+
+fn timestamp<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Timestamp, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    parse_timestamp(&value).map_err(|e| serde::de::Error::custom(format!("`{value}`: {e}")))
+}
+
+fn optional_timestamp<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Timestamp>, D::Error> {
+    timestamp(deserializer).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORT: &str = r#"[
+        {"id": 1, "uuid": "6d3c1e4a-2b1f-4c7e-9a0d-1e2f3a4b5c6d", "description": "Write docs",
+         "status": "pending", "entry": "20260101T090000Z", "due": "20260110T170000Z",
+         "start": "20260102T100000Z", "project": "finiate", "tags": ["writing", "oss"],
+         "annotations": [
+            {"entry": "20260101T100000Z", "description": "outline done"},
+            {"entry": "20260101T110000Z", "description": "Started task"},
+            {"entry": "20260101T120000Z", "description": "Stopped task"}
+         ], "urgency": 4.2},
+        {"id": 0, "uuid": "7e4d2f5b-3c2a-4d8f-8b1e-2f3a4b5c6d7e", "description": "Pay rent",
+         "status": "completed", "entry": "20260103T080000Z", "end": "20260104T080000Z"},
+        {"id": 0, "uuid": "8f5e3a6c-4d3b-4e9a-9c2f-3a4b5c6d7e8f", "description": "Gone",
+         "status": "deleted", "entry": "20260103T080000Z"}
+    ]"#;
+
+    #[test]
+    fn maps_tasks_onto_agendas_and_logs() {
+        let tasks = parse(EXPORT).expect("parse");
+        let plan = Plan::new(&tasks, &HashSet::new(), Timestamp::now());
+
+        assert_eq!(plan.imports.len(), 2);
+        assert_eq!(plan.deleted, 1);
+        let (docs, docs_logs) = &plan.imports[0];
+        assert_eq!(docs.title, "Write docs project:finiate +writing +oss");
+        assert_eq!(docs.agenda_status, AgendaStatus::Pending);
+        assert_eq!(docs.initiate_at.to_string(), "2026-01-01T09:00:00Z");
+        assert_eq!(docs.terminate_at.to_string(), "2026-01-10T17:00:00Z");
+        let types: Vec<_> = docs_logs.iter().map(|log| log.log_type).collect();
+        assert_eq!(
+            types,
+            [
+                LogType::CommonLog,
+                LogType::Activate,
+                LogType::PutOff,
+                LogType::CommonLog
+            ]
+        );
+        assert_eq!(docs_logs[0].content, "outline done");
+        assert_eq!(docs_logs[3].content, STARTED);
+
+        let (rent, rent_logs) = &plan.imports[1];
+        assert_eq!(rent.agenda_status, AgendaStatus::Terminated);
+        assert_eq!(rent_logs[0].log_type, LogType::Terminate);
+        assert_eq!(rent_logs[0].create_at.to_string(), "2026-01-04T08:00:00Z");
+    }
+
+    #[tokio::test]
+    async fn reimport_skips_existing_tasks() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let pool = storage::init_db(&dir.path().join("finiate.db"))
+            .await
+            .expect("init db");
+        let (agendas, logs) = storage::create_repos(&pool);
+        let tasks = parse(EXPORT).expect("parse");
+
+        let plan = Plan::new(&tasks, &HashSet::new(), Timestamp::now());
+        plan.apply(&agendas, &logs).await.expect("apply");
+
+        let existing = agendas
+            .get_agendas_by_status(None)
+            .await
+            .expect("query")
+            .iter()
+            .map(|agenda| agenda.id)
+            .collect();
+        let again = Plan::new(&tasks, &existing, Timestamp::now());
+        assert!(again.imports.is_empty());
+        assert_eq!(again.already_imported, 2);
+    }
+
+    #[tokio::test]
+    async fn started_tasks_fold_to_pending_agendas() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let pool = storage::init_db(&dir.path().join("finiate.db"))
+            .await
+            .expect("init db");
+        let (agendas, logs) =
+            storage::open_repos(&pool, storage::Backend::EventSourced, dir.path())
+                .expect("open repos");
+        let tasks = parse(EXPORT).expect("parse");
+
+        let plan = Plan::new(&tasks, &HashSet::new(), Timestamp::now());
+        plan.apply(&agendas, &logs).await.expect("apply");
+
+        let docs = agendas
+            .get_agenda_by_id(tasks[0].uuid)
+            .await
+            .expect("get")
+            .expect("agenda");
+        assert_eq!(docs.agenda_status, AgendaStatus::Pending);
+    }
+
+    #[test]
+    fn parses_both_timestamp_forms() {
+        assert_eq!(
+            parse_timestamp("20260101T090000Z").expect("compact"),
+            parse_timestamp("2026-01-01T09:00:00Z").expect("rfc 3339")
+        );
+        assert!(parse_timestamp("yesterday").is_err());
+    }
+}
@@ -0,0 +1,80 @@
+use jiff::{
+    Span, Timestamp, Zoned,
+    civil::{Date, DateTime},
+    tz::TimeZone,
+};
+
+/// Parses a user supplied deadline.
+///
+/// Accepts a span relative to `now` (`2d`, `3h 30m`), an RFC 3339 timestamp,
+/// a civil date-time interpreted in `tz`, or a bare date meaning the end of
+/// that day in `tz`.
+pub fn parse_deadline(input: &str, now: &Zoned, tz: &TimeZone) -> Result<Timestamp, jiff::Error> {
+    let input = input.trim();
+    if let Ok(span) = input.parse::<Span>() {
+        return Ok(now.checked_add(span)?.timestamp());
+    }
+    if let Ok(timestamp) = input.parse::<Timestamp>() {
+        return Ok(timestamp);
+    }
+    if let Ok(datetime) = input.parse::<DateTime>()
+        && input.len() > "YYYY-MM-DD".len()
+    {
+        return Ok(datetime.to_zoned(tz.clone())?.timestamp());
+    }
+    let date: Date = input.parse()?;
+    Ok(date
+        .at(23, 59, 59, 999_000_000)
+        .to_zoned(tz.clone())?
+        .timestamp())
+}
+
+/// Formats a timestamp for display in `tz`, to minute precision.
+pub fn display(timestamp: Timestamp, tz: &TimeZone) -> String {
+    timestamp
+        .to_zoned(tz.clone())
+        .strftime("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> Zoned {
+        "2026-03-01T09:00:00+00:00[UTC]".parse().expect("zoned")
+    }
+
+    #[test]
+    fn relative_span() {
+        let tz = TimeZone::UTC;
+        let deadline = parse_deadline("2d 3h", &now(), &tz).expect("span");
+        assert_eq!(deadline.to_string(), "2026-03-03T12:00:00Z");
+    }
+
+    #[test]
+    fn absolute_timestamp() {
+        let tz = TimeZone::UTC;
+        let deadline = parse_deadline("2026-05-01T10:00:00+02:00", &now(), &tz).expect("ts");
+        assert_eq!(deadline.to_string(), "2026-05-01T08:00:00Z");
+    }
+
+    #[test]
+    fn civil_datetime_uses_time_zone() {
+        let tz = TimeZone::get("Asia/Tokyo").expect("tz");
+        let deadline = parse_deadline("2026-05-01 18:00", &now(), &tz).expect("datetime");
+        assert_eq!(deadline.to_string(), "2026-05-01T09:00:00Z");
+    }
+
+    #[test]
+    fn bare_date_means_end_of_day() {
+        let tz = TimeZone::UTC;
+        let deadline = parse_deadline("2026-05-01", &now(), &tz).expect("date");
+        assert_eq!(deadline.to_string(), "2026-05-01T23:59:59.999Z");
+    }
+
+    #[test]
+    fn garbage_is_rejected() {
+        assert!(parse_deadline("whenever", &now(), &TimeZone::UTC).is_err());
+    }
+}
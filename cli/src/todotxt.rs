@@ -0,0 +1,445 @@
+use domain::*;
+use jiff::{Timestamp, civil::Date, tz::TimeZone};
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::fmt;
+use uuid::Uuid;
+
+/// One task in todo.txt format.
+///
+/// `description` keeps every token except `due:` and `pri:`, so projects,
+/// contexts and unknown `key:value` extensions survive a round trip.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Task {
+    pub done: bool,
+    pub priority: Option<char>,
+    pub completion_date: Option<Date>,
+    pub creation_date: Option<Date>,
+    pub due: Option<Date>,
+    pub description: String,
+}
+
+impl Task {
+    pub fn parse(line: &str) -> Task {
+        let mut task = Task::default();
+        let mut rest = line.trim();
+
+        if let Some(after) = rest.strip_prefix("x ") {
+            task.done = true;
+            rest = after.trim_start();
+        }
+        if let Some((priority, after)) = parse_priority(rest) {
+            task.priority = Some(priority);
+            rest = after;
+        }
+        if let Some((date, after)) = parse_date(rest) {
+            // A completed task may carry a completion date before its creation date.
+            match parse_date(after) {
+                Some((created, after)) if task.done => {
+                    task.completion_date = Some(date);
+                    task.creation_date = Some(created);
+                    rest = after;
+                }
+                _ if task.done => {
+                    task.completion_date = Some(date);
+                    rest = after;
+                }
+                _ => {
+                    task.creation_date = Some(date);
+                    rest = after;
+                }
+            }
+        }
+
+        let mut words = Vec::new();
+        for word in rest.split_whitespace() {
+            match extension(word) {
+                Some(("due", value)) if task.due.is_none() => match value.parse() {
+                    Ok(date) => task.due = Some(date),
+                    Err(_) => words.push(word),
+                },
+                Some(("pri", value)) if task.priority.is_none() => match single_priority(value) {
+                    Some(priority) => task.priority = Some(priority),
+                    None => words.push(word),
+                },
+                _ => words.push(word),
+            }
+        }
+        task.description = words.join(" ");
+        task
+    }
+
+    pub fn projects(&self) -> impl Iterator<Item = &str> {
+        self.tagged('+')
+    }
+
+    pub fn contexts(&self) -> impl Iterator<Item = &str> {
+        self.tagged('@')
+    }
+
+    /// `key:value` tokens other than the ones mapped onto agenda fields.
+    pub fn extensions(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.description.split_whitespace().filter_map(extension)
+    }
+
+    fn tagged(&self, sigil: char) -> impl Iterator<Item = &str> {
+        self.description
+            .split_whitespace()
+            .filter_map(move |word| word.strip_prefix(sigil))
+            .filter(|tag| !tag.is_empty())
+    }
+
+    /// Converts to an agenda. The priority is kept as a `pri:` token in the
+    /// title because agendas have no priority field.
+    pub fn to_agenda(&self, tz: &TimeZone, default_due: Timestamp) -> Result<Agenda, jiff::Error> {
+        let mut title = self.description.clone();
+        if let Some(priority) = self.priority {
+            title.push_str(&format!(" pri:{priority}"));
+        }
+        let initiate_at = match self.creation_date {
+            Some(date) => date.to_zoned(tz.clone())?.timestamp(),
+            None => Timestamp::now(),
+        };
+        let terminate_at = match self.due {
+            Some(date) => date
+                .at(23, 59, 59, 999_000_000)
+                .to_zoned(tz.clone())?
+                .timestamp(),
+            None => default_due,
+        };
+        Ok(Agenda {
+            id: Uuid::now_v7(),
+            title: title.trim().to_string(),
+            agenda_status: if self.done {
+                AgendaStatus::Terminated
+            } else {
+                AgendaStatus::Pending
+            },
+            initiate_at,
+            terminate_at,
+        })
+    }
+
+    /// Converts an agenda; `terminated_at` is when its terminate log was written.
+    ///
+    /// The title becomes the description as it is, even when it starts like
+    /// a todo.txt prefix; only the `pri:` token `to_agenda` appends is read
+    /// back as the priority.
+    pub fn from_agenda(agenda: &Agenda, terminated_at: Option<Timestamp>, tz: &TimeZone) -> Task {
+        let (description, priority) = match agenda
+            .title
+            .rsplit_once(' ')
+            .and_then(|(rest, last)| Some((rest, single_priority(last.strip_prefix("pri:")?)?)))
+        {
+            Some((rest, priority)) => (rest.to_string(), Some(priority)),
+            None => (agenda.title.clone(), None),
+        };
+        let done = agenda.agenda_status == AgendaStatus::Terminated;
+        Task {
+            done,
+            priority,
+            completion_date: terminated_at
+                .filter(|_| done)
+                .map(|at| at.to_zoned(tz.clone()).date()),
+            creation_date: Some(agenda.initiate_at.to_zoned(tz.clone()).date()),
+            due: Some(agenda.terminate_at.to_zoned(tz.clone()).date()),
+            description,
+        }
+    }
+}
+
+impl fmt::Display for Task {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts: Vec<String> = Vec::new();
+        if self.done {
+            parts.push("x".to_string());
+            // Completed tasks drop the priority prefix; todo.txt keeps it as pri:
+            // A lone date after `x` reads as the completion date, so the
+            // creation date can only follow one. Without a completion date
+            // the creation date stands in, so a description starting like a
+            // date or a priority is never read as one.
+            if let Some(date) = self.completion_date.or(self.creation_date) {
+                parts.push(date.to_string());
+                if let Some(date) = self.creation_date {
+                    parts.push(date.to_string());
+                }
+            }
+        } else {
+            if let Some(priority) = self.priority {
+                parts.push(format!("({priority})"));
+            }
+            if let Some(date) = self.creation_date {
+                parts.push(date.to_string());
+            }
+        }
+        if !self.description.is_empty() {
+            parts.push(self.description.clone());
+        }
+        if let Some(due) = self.due {
+            parts.push(format!("due:{due}"));
+        }
+        if self.done
+            && let Some(priority) = self.priority
+        {
+            parts.push(format!("pri:{priority}"));
+        }
+        f.write_str(&parts.join(" "))
+    }
+}
+
+/// Renders agendas as a todo.txt document, one task per line. Completion
+/// dates come from each agenda's last terminate log.
+pub fn export(agendas: &[Agenda], logs: &[Log], tz: &TimeZone) -> String {
+    let mut terminated_at: HashMap<Uuid, Timestamp> = HashMap::new();
+    for log in logs.iter().filter(|log| log.log_type == LogType::Terminate) {
+        let at = terminated_at.entry(log.agenda_id).or_insert(log.create_at);
+        *at = (*at).max(log.create_at);
+    }
+    agendas
+        .iter()
+        .map(|agenda| {
+            let task = Task::from_agenda(agenda, terminated_at.get(&agenda.id).copied(), tz);
+            format!("{task}\n")
+        })
+        .collect()
+}
+
+/// What an import brought in, for the summary line.
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    pub imported: usize,
+    pub projects: BTreeSet<String>,
+    pub contexts: BTreeSet<String>,
+    /// Keys of the extensions kept in titles.
+    pub extensions: BTreeSet<String>,
+}
+
+/// Creates an agenda per non-empty line; completed tasks also get a
+/// terminate log at their completion date.
+pub async fn import<A, L>(
+    agendas: &A,
+    logs: &L,
+    text: &str,
+    tz: &TimeZone,
+    default_due: Timestamp,
+) -> Result<ImportSummary, Box<dyn Error + Send + Sync>>
+where
+    A: AgendaRepo + Sync,
+    L: LogRepo + Sync,
+{
+    let mut summary = ImportSummary::default();
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let task = Task::parse(line);
+        let agenda = task.to_agenda(tz, default_due)?;
+        agendas.insert_agenda(&agenda).await?;
+        if let Some(date) = task.completion_date.filter(|_| task.done) {
+            let create_at = date
+                .to_zoned(tz.clone())?
+                .timestamp()
+                .max(agenda.initiate_at);
+            logs.insert_log(&Log {
+                id: Uuid::now_v7(),
+                agenda_id: agenda.id,
+                content: "imported from todo.txt".to_string(),
+                create_at,
+                log_type: LogType::Terminate,
+            })
+            .await?;
+        }
+        summary.imported += 1;
+        summary.projects.extend(task.projects().map(str::to_string));
+        summary.contexts.extend(task.contexts().map(str::to_string));
+        summary
+            .extensions
+            .extend(task.extensions().map(|(key, _)| key.to_string()));
+    }
+    Ok(summary)
+}
+
+fn parse_priority(text: &str) -> Option<(char, &str)> {
+    let bytes = text.as_bytes();
+    if bytes.len() >= 4
+        && bytes[0] == b'('
+        && bytes[1].is_ascii_uppercase()
+        && bytes[2] == b')'
+        && bytes[3] == b' '
+    {
+        Some((bytes[1] as char, text[4..].trim_start()))
+    } else {
+        None
+    }
+}
+
+fn single_priority(value: &str) -> Option<char> {
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(priority), None) if priority.is_ascii_uppercase() => Some(priority),
+        _ => None,
+    }
+}
+
+fn parse_date(text: &str) -> Option<(Date, &str)> {
+    let (word, rest) = text.split_once(' ').unwrap_or((text, ""));
+    if word.len() != "YYYY-MM-DD".len() {
+        return None;
+    }
+    let date = word.parse().ok()?;
+    Some((date, rest.trim_start()))
+}
+
+/// Splits a `key:value` token; URLs and tokens with empty sides are not extensions.
+fn extension(word: &str) -> Option<(&str, &str)> {
+    let (key, value) = word.split_once(':')?;
+    if key.is_empty() || value.is_empty() || value.contains(':') || value.starts_with("//") {
+        return None;
+    }
+    Some((key, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jiff::civil::date;
+
+    #[test]
+    fn parses_full_open_task() {
+        let task = Task::parse("(A) 2026-01-05 Call Mom +Family @phone due:2026-01-10 rec:1w");
+
+        assert!(!task.done);
+        assert_eq!(task.priority, Some('A'));
+        assert_eq!(task.creation_date, Some(date(2026, 1, 5)));
+        assert_eq!(task.due, Some(date(2026, 1, 10)));
+        assert_eq!(task.description, "Call Mom +Family @phone rec:1w");
+        assert_eq!(task.projects().collect::<Vec<_>>(), ["Family"]);
+        assert_eq!(task.contexts().collect::<Vec<_>>(), ["phone"]);
+        assert_eq!(task.extensions().collect::<Vec<_>>(), [("rec", "1w")]);
+    }
+
+    #[test]
+    fn parses_completed_task_dates() {
+        let task = Task::parse("x 2026-01-07 2026-01-05 Pay rent pri:B");
+
+        assert!(task.done);
+        assert_eq!(task.completion_date, Some(date(2026, 1, 7)));
+        assert_eq!(task.creation_date, Some(date(2026, 1, 5)));
+        assert_eq!(task.priority, Some('B'));
+        assert_eq!(task.description, "Pay rent");
+    }
+
+    #[test]
+    fn lines_round_trip() {
+        for line in [
+            "(A) 2026-01-05 Call Mom +Family @phone due:2026-01-10",
+            "x 2026-01-07 2026-01-05 Pay rent +Home due:2026-01-08 pri:B",
+            "Read https://example.com/x key:value",
+        ] {
+            assert_eq!(Task::parse(line).to_string(), line);
+        }
+    }
+
+    #[test]
+    fn agenda_conversion_preserves_fields() {
+        let tz = TimeZone::UTC;
+        let task = Task::parse("(C) 2026-02-01 Fix bug +core @work due:2026-02-03 issue:42");
+        let agenda = task.to_agenda(&tz, Timestamp::now()).expect("to agenda");
+
+        assert_eq!(agenda.title, "Fix bug +core @work issue:42 pri:C");
+        assert_eq!(agenda.agenda_status, AgendaStatus::Pending);
+        assert_eq!(agenda.initiate_at.to_string(), "2026-02-01T00:00:00Z");
+        assert_eq!(agenda.terminate_at.to_string(), "2026-02-03T23:59:59.999Z");
+
+        let back = Task::from_agenda(&agenda, None, &tz);
+        assert_eq!(
+            back.to_string(),
+            "(C) 2026-02-01 Fix bug +core @work issue:42 due:2026-02-03"
+        );
+    }
+
+    #[test]
+    fn terminated_agenda_exports_completion() {
+        let tz = TimeZone::UTC;
+        let agenda = Agenda {
+            id: Uuid::now_v7(),
+            title: "Ship it +release".to_string(),
+            agenda_status: AgendaStatus::Terminated,
+            initiate_at: "2026-03-01T08:00:00Z".parse().unwrap(),
+            terminate_at: "2026-03-05T18:00:00Z".parse().unwrap(),
+        };
+        let log = Log {
+            id: Uuid::now_v7(),
+            agenda_id: agenda.id,
+            content: String::new(),
+            create_at: "2026-03-04T12:00:00Z".parse().unwrap(),
+            log_type: LogType::Terminate,
+        };
+
+        let text = export(&[agenda], &[log], &tz);
+        assert_eq!(
+            text,
+            "x 2026-03-04 2026-03-01 Ship it +release due:2026-03-05\n"
+        );
+    }
+
+    #[test]
+    fn titles_looking_like_prefixes_survive_a_round_trip() {
+        let tz = TimeZone::UTC;
+        // Terminated agendas here have no terminate log, so no completion date.
+        for status in [AgendaStatus::Pending, AgendaStatus::Terminated] {
+            for title in ["x marks the spot", "(A) is a grade", "2026-01-01 retro"] {
+                let agenda = Agenda {
+                    id: Uuid::now_v7(),
+                    title: title.to_string(),
+                    agenda_status: status,
+                    initiate_at: "2026-03-01T08:00:00Z".parse().unwrap(),
+                    terminate_at: "2026-03-05T18:00:00Z".parse().unwrap(),
+                };
+                let line = Task::from_agenda(&agenda, None, &tz).to_string();
+                let back = Task::parse(&line).to_agenda(&tz, Timestamp::now()).unwrap();
+                assert_eq!(back.title, title, "{line}");
+                assert_eq!(back.agenda_status, status, "{line}");
+            }
+        }
+    }
+
+    #[test]
+    fn completed_task_without_completion_date_is_completed_when_created() {
+        let task = Task {
+            done: true,
+            creation_date: Some(date(2026, 1, 5)),
+            description: "Pay rent".to_string(),
+            ..Task::default()
+        };
+        assert_eq!(task.to_string(), "x 2026-01-05 2026-01-05 Pay rent");
+        let untimed = Task {
+            done: true,
+            description: "Pay rent".to_string(),
+            ..Task::default()
+        };
+        assert_eq!(untimed.to_string(), "x Pay rent");
+    }
+
+    #[tokio::test]
+    async fn import_creates_agendas_and_terminate_logs() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let pool = storage::init_db(&dir.path().join("finiate.db"))
+            .await
+            .expect("init db");
+        let (agendas, logs) = storage::create_repos(&pool);
+        let text = "(A) 2026-01-05 Open task +work @desk due:2026-01-10\n\nx 2026-01-07 2026-01-05 Done task\n";
+
+        let summary = import(&agendas, &logs, text, &TimeZone::UTC, Timestamp::now())
+            .await
+            .expect("import");
+
+        assert_eq!(summary.imported, 2);
+        assert!(summary.projects.contains("work") && summary.contexts.contains("desk"));
+        let done = agendas
+            .get_agendas_by_status(Some("terminated"))
+            .await
+            .expect("query");
+        assert_eq!(done.len(), 1);
+        let done_logs = logs.get_logs_by_agenda_id(done[0].id).await.expect("logs");
+        assert_eq!(done_logs[0].log_type, LogType::Terminate);
+        assert_eq!(done_logs[0].create_at.to_string(), "2026-01-07T00:00:00Z");
+    }
+}
@@ -0,0 +1,385 @@
+//! Outgoing webhooks.
+//!
+//! Database triggers record every lifecycle event in the `webhook_outbox`
+//! table in the same transaction as the change, so events survive crashes
+//! and restarts no matter which front end made the change. Events are only
+//! recorded while endpoints are registered, and imports and restores record
+//! none. The dispatcher POSTs each event as JSON to every subscribed
+//! endpoint registered before it happened and retries failed deliveries
+//! with exponential backoff; events no endpoint wants are dropped.
+//!
+//! Each request carries `X-Finiate-Event`, `X-Finiate-Delivery` (the outbox
+//! id, stable across retries), `X-Finiate-Timestamp` (Unix seconds) and
+//! `X-Finiate-Signature: sha256=<hex>`, the HMAC-SHA256 of
+//! `<timestamp>.<body>` keyed with the endpoint's secret.
+
+use crate::config::WebhookConfig;
+use domain::*;
+use hmac::{Hmac, Mac};
+use jiff::{SignedDuration, Timestamp};
+use serde::Serialize;
+use sha2::Sha256;
+use std::error::Error;
+use storage::{OutboxEvent, SqliteOutbox};
+
+pub const EVENTS: [&str; 5] = [
+    "agenda.created",
+    "agenda.activated",
+    "agenda.put_off",
+    "agenda.terminated",
+    "log.created",
+];
+
+/// Attempts per event before it is given up on; `webhooks retry` revives it.
+pub const MAX_ATTEMPTS: u32 = 10;
+const FIRST_RETRY: SignedDuration = SignedDuration::from_secs(30);
+const MAX_RETRY: SignedDuration = SignedDuration::from_hours(1);
+const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+/// Delivered events are kept this long for `webhooks status`.
+const KEEP_DELIVERED: SignedDuration = SignedDuration::from_hours(24 * 7);
+const BATCH: u32 = 100;
+
+#[derive(Debug, Serialize)]
+struct Payload<'a> {
+    id: i64,
+    event: &'a str,
+    occurred_at: Timestamp,
+    agenda: &'a Agenda,
+    log: Option<&'a Log>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DeliverySummary {
+    pub delivered: usize,
+    pub failed: usize,
+}
+
+/// The `X-Finiate-Signature` value for a body sent at `timestamp`.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// How long to wait before the next attempt after `attempts` failures.
+fn backoff(attempts: u32) -> SignedDuration {
+    let factor = 2i32.saturating_pow(attempts.saturating_sub(1));
+    FIRST_RETRY
+        .checked_mul(factor)
+        .map_or(MAX_RETRY, |delay| delay.min(MAX_RETRY))
+}
+
+/// Whether `endpoint` subscribes to `event` and was registered by the time
+/// it was queued.
+fn wants(
+    endpoint: &WebhookConfig,
+    event: &OutboxEvent,
+    registered: &[(String, Timestamp)],
+) -> bool {
+    (endpoint.events.is_empty() || endpoint.events.contains(&event.event))
+        && registered
+            .iter()
+            .any(|(url, added_at)| *url == endpoint.url && *added_at <= event.queued_at)
+}
+
+pub struct Dispatcher<'a> {
+    outbox: &'a SqliteOutbox,
+    endpoints: &'a [WebhookConfig],
+    client: reqwest::Client,
+}
+
+impl<'a> Dispatcher<'a> {
+    pub fn new(
+        outbox: &'a SqliteOutbox,
+        endpoints: &'a [WebhookConfig],
+    ) -> Result<Self, reqwest::Error> {
+        let client = reqwest::Client::builder()
+            .timeout(TIMEOUT)
+            .user_agent(concat!("finiate/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        Ok(Dispatcher {
+            outbox,
+            endpoints,
+            client,
+        })
+    }
+
+    /// Attempts every event that is due at `now`.
+    pub async fn deliver_due(
+        &self,
+        now: Timestamp,
+    ) -> Result<DeliverySummary, Box<dyn Error + Send + Sync>> {
+        let urls: Vec<_> = self.endpoints.iter().map(|e| e.url.clone()).collect();
+        self.outbox.register_endpoints(&urls).await?;
+        let registered = self.outbox.endpoints().await?;
+        let mut summary = DeliverySummary::default();
+        loop {
+            let events = self.outbox.due(now, MAX_ATTEMPTS, BATCH).await?;
+            let batch_len = events.len();
+            for event in events {
+                let wanted: Vec<_> = self
+                    .endpoints
+                    .iter()
+                    .filter(|endpoint| wants(endpoint, &event, &registered))
+                    .collect();
+                if wanted.is_empty() {
+                    self.outbox.discard(event.id).await?;
+                } else if self.deliver(&event, &wanted, now).await? {
+                    summary.delivered += 1;
+                } else {
+                    summary.failed += 1;
+                }
+            }
+            // Failed events are no longer due, so the next batch is new work.
+            if batch_len < BATCH as usize {
+                break;
+            }
+        }
+        self.outbox.prune_delivered(now - KEEP_DELIVERED).await?;
+        Ok(summary)
+    }
+
+    /// Sends one event to the `wanted` endpoints that still need it and
+    /// records the outcome; returns whether every endpoint has it now.
+    async fn deliver(
+        &self,
+        event: &OutboxEvent,
+        wanted: &[&WebhookConfig],
+        now: Timestamp,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let body = serde_json::to_vec(&Payload {
+            id: event.id,
+            event: &event.event,
+            occurred_at: event.occurred_at,
+            agenda: &event.agenda,
+            log: event.log.as_ref(),
+        })?;
+        let mut delivered_to = event.delivered_to.clone();
+        let mut errors = Vec::new();
+        let pending = wanted
+            .iter()
+            .filter(|endpoint| !event.delivered_to.contains(&endpoint.url));
+        for endpoint in pending {
+            match self.post(endpoint, event, &body).await {
+                Ok(()) => delivered_to.push(endpoint.url.clone()),
+                Err(e) => errors.push(format!("{}: {e}", endpoint.url)),
+            }
+        }
+
+        if errors.is_empty() {
+            self.outbox.mark_delivered(event.id, now).await?;
+            return Ok(true);
+        }
+        let retry_at = now + backoff(event.attempts + 1);
+        self.outbox
+            .mark_failed(event.id, &delivered_to, &errors.join("; "), retry_at)
+            .await?;
+        Ok(false)
+    }
+
+    async fn post(
+        &self,
+        endpoint: &WebhookConfig,
+        event: &OutboxEvent,
+        body: &[u8],
+    ) -> Result<(), reqwest::Error> {
+        let timestamp = Timestamp::now().as_second();
+        self.client
+            .post(&endpoint.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-Finiate-Event", &event.event)
+            .header("X-Finiate-Delivery", event.id.to_string())
+            .header("X-Finiate-Timestamp", timestamp.to_string())
+            .header(
+                "X-Finiate-Signature",
+                sign(&endpoint.secret, timestamp, body),
+            )
+            .body(body.to_vec())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use jiff::ToSpan;
+    use std::sync::{Arc, Mutex};
+
+    type Received = Arc<Mutex<Vec<(HeaderMap, Vec<u8>)>>>;
+
+    /// A local endpoint that records requests and answers with `status`.
+    async fn stub(status: StatusCode) -> (String, Received) {
+        let received = Received::default();
+        let sink = received.clone();
+        let app = axum::Router::new().route(
+            "/hook",
+            post(
+                move |headers: HeaderMap, body: axum::body::Bytes| async move {
+                    sink.lock().unwrap().push((headers, body.to_vec()));
+                    status
+                },
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (url, received)
+    }
+
+    fn endpoint(url: &str, events: &[&str]) -> WebhookConfig {
+        WebhookConfig {
+            url: url.to_string(),
+            secret: "s3cret".to_string(),
+            events: events.iter().map(|event| event.to_string()).collect(),
+        }
+    }
+
+    async fn terminated_agenda(
+        dir: &tempfile::TempDir,
+        endpoints: &[WebhookConfig],
+    ) -> SqliteOutbox {
+        let pool = storage::init_db(&dir.path().join("finiate.db"))
+            .await
+            .expect("init db");
+        let urls: Vec<_> = endpoints.iter().map(|e| e.url.clone()).collect();
+        storage::create_outbox(&pool)
+            .register_endpoints(&urls)
+            .await
+            .unwrap();
+        let (agendas, logs) = storage::create_repos(&pool);
+        let id = agendas
+            .create_agenda(&AgendaCreate {
+                title: "Release".to_string(),
+                agenda_status: AgendaStatus::Pending,
+                terminate_at: Timestamp::now() + 24.hours(),
+            })
+            .await
+            .unwrap();
+        logs.create_log(&LogCreate {
+            agenda_id: id,
+            content: "shipped".to_string(),
+            log_type: LogType::Terminate,
+        })
+        .await
+        .unwrap();
+        storage::create_outbox(&pool)
+    }
+
+    #[tokio::test]
+    async fn posts_signed_payloads_to_subscribers() {
+        let dir = tempfile::tempdir().unwrap();
+        let (all_url, all) = stub(StatusCode::OK).await;
+        let (done_url, done) = stub(StatusCode::NO_CONTENT).await;
+        let endpoints = [
+            endpoint(&all_url, &[]),
+            endpoint(&done_url, &["agenda.terminated"]),
+        ];
+        let outbox = terminated_agenda(&dir, &endpoints).await;
+        let dispatcher = Dispatcher::new(&outbox, &endpoints).unwrap();
+
+        let now = Timestamp::now() + 1.second();
+        let summary = dispatcher.deliver_due(now).await.unwrap();
+        assert_eq!(
+            summary,
+            DeliverySummary {
+                delivered: 2,
+                failed: 0
+            }
+        );
+        assert_eq!(all.lock().unwrap().len(), 2);
+
+        let received = done.lock().unwrap().clone();
+        assert_eq!(received.len(), 1);
+        let (headers, body) = &received[0];
+        assert_eq!(headers["x-finiate-event"], "agenda.terminated");
+        let timestamp: i64 = headers["x-finiate-timestamp"]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(
+            headers["x-finiate-signature"],
+            sign("s3cret", timestamp, body).as_str()
+        );
+        let payload: serde_json::Value = serde_json::from_slice(body).unwrap();
+        assert_eq!(payload["agenda"]["title"], "Release");
+        assert_eq!(payload["log"]["content"], "shipped");
+
+        assert_eq!(
+            dispatcher.deliver_due(now).await.unwrap(),
+            DeliverySummary::default()
+        );
+    }
+
+    #[tokio::test]
+    async fn failures_retry_only_the_failing_endpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let (ok_url, ok) = stub(StatusCode::OK).await;
+        let (bad_url, bad) = stub(StatusCode::SERVICE_UNAVAILABLE).await;
+        let endpoints = [
+            endpoint(&ok_url, &["agenda.created"]),
+            endpoint(&bad_url, &["agenda.created"]),
+        ];
+        let outbox = terminated_agenda(&dir, &endpoints).await;
+        let dispatcher = Dispatcher::new(&outbox, &endpoints).unwrap();
+
+        let now = Timestamp::now() + 1.second();
+        let summary = dispatcher.deliver_due(now).await.unwrap();
+        // The terminate event has no subscriber and is dropped.
+        assert_eq!(
+            summary,
+            DeliverySummary {
+                delivered: 0,
+                failed: 1
+            }
+        );
+        let counts = outbox.counts(MAX_ATTEMPTS).await.unwrap();
+        assert_eq!((counts.pending, counts.delivered), (1, 0));
+
+        // Not due again before the backoff has passed.
+        dispatcher.deliver_due(now + 10.seconds()).await.unwrap();
+        assert_eq!(bad.lock().unwrap().len(), 1);
+        dispatcher.deliver_due(now + 31.seconds()).await.unwrap();
+        assert_eq!(bad.lock().unwrap().len(), 2);
+        assert_eq!(ok.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn endpoints_added_later_get_no_earlier_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let (old_url, old) = stub(StatusCode::OK).await;
+        let (new_url, new) = stub(StatusCode::OK).await;
+        let outbox = terminated_agenda(&dir, &[endpoint(&old_url, &["agenda.created"])]).await;
+        let endpoints = [endpoint(&new_url, &[])];
+        let dispatcher = Dispatcher::new(&outbox, &endpoints).unwrap();
+
+        let now = Timestamp::now() + 1.second();
+        assert_eq!(
+            dispatcher.deliver_due(now).await.unwrap(),
+            DeliverySummary::default()
+        );
+        assert!(old.lock().unwrap().is_empty());
+        assert!(new.lock().unwrap().is_empty());
+        assert_eq!(
+            outbox.counts(MAX_ATTEMPTS).await.unwrap(),
+            storage::OutboxCounts::default()
+        );
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_an_hour() {
+        assert_eq!(backoff(1), SignedDuration::from_secs(30));
+        assert_eq!(backoff(2), SignedDuration::from_secs(60));
+        assert_eq!(backoff(4), SignedDuration::from_secs(240));
+        assert_eq!(backoff(9), MAX_RETRY);
+        assert_eq!(backoff(40), MAX_RETRY);
+    }
+}
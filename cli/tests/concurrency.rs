@@ -0,0 +1,111 @@
+use domain::{AgendaRepo, AgendaStatus};
+use std::path::Path;
+use std::process::{Command, Output};
+
+const ROUNDS: usize = 20;
+
+fn finiate(config: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_finiate"))
+        .arg("--config")
+        .arg(config)
+        .args(args)
+        .env_remove("FINIATE_PROFILE")
+        .output()
+        .expect("run finiate")
+}
+
+#[tokio::test]
+async fn concurrent_processes_do_not_hit_a_locked_database() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let config = dir.path().join("config.toml");
+    std::fs::write(
+        &config,
+        "database = \"finiate.db\"\nmax_slots = 100\n[backup]\nkeep = 0\n",
+    )
+    .unwrap();
+    assert!(finiate(&config, &["slot", "add", "Seed"]).status.success());
+
+    let writers: Vec<_> = ["laptop", "daemon"]
+        .into_iter()
+        .map(|writer| {
+            let config = config.clone();
+            std::thread::spawn(move || {
+                for round in 0..ROUNDS {
+                    let title = format!("{writer} {round}");
+                    let output = finiate(&config, &["slot", "add", &title]);
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    assert!(output.status.success(), "{title}: {stderr}");
+                    assert!(!stderr.contains("locked"), "{title}: {stderr}");
+                }
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.join().expect("writer finished");
+    }
+
+    let pool = storage::init_db(&dir.path().join("finiate.db"))
+        .await
+        .expect("open db");
+    let (agendas, _) = storage::create_repos(&pool);
+    let all = agendas.get_agendas_by_status(None).await.unwrap();
+    assert_eq!(all.len(), 2 * ROUNDS + 1);
+}
+
+#[tokio::test]
+async fn concurrent_slot_changes_do_not_hit_a_locked_database() {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let config = dir.path().join("config.toml");
+    std::fs::write(
+        &config,
+        "database = \"finiate.db\"\nmax_slots = 100\n[backup]\nkeep = 0\n",
+    )
+    .unwrap();
+    for title in ["Seed 1", "Seed 2"] {
+        assert!(finiate(&config, &["slot", "add", title]).status.success());
+    }
+
+    for round in 0..ROUNDS {
+        assert!(finiate(&config, &["slot", "set", "1"]).status.success());
+        // Whichever runs first, the current agenda is only ever closed by
+        // the putoff or terminate, so each of these must succeed.
+        let close = if round % 2 == 0 {
+            "terminate"
+        } else {
+            "putoff"
+        };
+        let title = format!("Added {round}");
+        let runs: Vec<_> = [
+            vec![close.to_string()],
+            vec!["slot".into(), "set".into(), "1".into()],
+            vec!["slot".into(), "add".into(), title],
+        ]
+        .into_iter()
+        .map(|args| {
+            let config = config.clone();
+            std::thread::spawn(move || {
+                let args: Vec<&str> = args.iter().map(String::as_str).collect();
+                let output = finiate(&config, &args);
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                assert!(output.status.success(), "{args:?}: {stderr}");
+                assert!(!stderr.contains("locked"), "{args:?}: {stderr}");
+            })
+        })
+        .collect();
+        for run in runs {
+            run.join().expect("command finished");
+        }
+    }
+
+    let pool = storage::init_db(&dir.path().join("finiate.db"))
+        .await
+        .expect("open db");
+    let (agendas, _) = storage::create_repos(&pool);
+    let terminated = agendas
+        .get_agendas_by_status(Some(&AgendaStatus::Terminated.to_string()))
+        .await
+        .unwrap();
+    assert_eq!(terminated.len(), ROUNDS / 2);
+    let all = agendas.get_agendas_by_status(None).await.unwrap();
+    assert_eq!(all.len(), ROUNDS + 2);
+}
@@ -1,24 +1,32 @@
 use async_trait::async_trait;
 use jiff::Timestamp;
+use serde::{Deserialize, Serialize};
 use std::error::Error;
+use std::fmt;
 use uuid::Uuid;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
 pub enum AgendaStatus {
     Pending,
     Ongoing,
     Terminated,
 }
 
-impl AgendaStatus {
-    pub fn to_string(&self) -> String {
-        match self {
-            AgendaStatus::Pending => "pending".to_string(),
-            AgendaStatus::Ongoing => "ongoing".to_string(),
-            AgendaStatus::Terminated => "terminated".to_string(),
-        }
+impl fmt::Display for AgendaStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            AgendaStatus::Pending => "pending",
+            AgendaStatus::Ongoing => "ongoing",
+            AgendaStatus::Terminated => "terminated",
+        };
+        f.write_str(s)
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Agenda {
     pub id: Uuid,
     pub title: String,
@@ -37,15 +45,79 @@ pub struct AgendaUpdate {
     pub title: Option<String>,
     pub agenda_status: Option<AgendaStatus>,
     pub terminate_at: Option<Timestamp>,
+    /// Who is making the change (`cli`, `api`, ...), kept in the revision
+    /// history.
+    pub actor: Option<String>,
+}
+
+impl AgendaUpdate {
+    /// The fields this update actually changes on `agenda`.
+    pub fn changes(&self, agenda: &Agenda) -> Vec<FieldChange> {
+        let mut changes = Vec::new();
+        if let Some(title) = self.title.as_ref().filter(|title| **title != agenda.title) {
+            changes.push(FieldChange::Title {
+                old: agenda.title.clone(),
+                new: title.clone(),
+            });
+        }
+        if let Some(status) = self
+            .agenda_status
+            .filter(|status| *status != agenda.agenda_status)
+        {
+            changes.push(FieldChange::Status {
+                old: agenda.agenda_status,
+                new: status,
+            });
+        }
+        if let Some(at) = self.terminate_at.filter(|at| *at != agenda.terminate_at) {
+            changes.push(FieldChange::Deadline {
+                old: agenda.terminate_at,
+                new: at,
+            });
+        }
+        changes
+    }
+}
+
+/// One field of an agenda changed by an update, with its old and new value.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "field", rename_all = "snake_case")]
+pub enum FieldChange {
+    Title {
+        old: String,
+        new: String,
+    },
+    Status {
+        old: AgendaStatus,
+        new: AgendaStatus,
+    },
+    Deadline {
+        old: Timestamp,
+        new: Timestamp,
+    },
+}
+
+/// An entry of an agenda's audit trail.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgendaRevision {
+    pub agenda_id: Uuid,
+    pub revised_at: Timestamp,
+    pub actor: Option<String>,
+    #[serde(flatten)]
+    pub change: FieldChange,
 }
 
 #[async_trait]
 pub trait AgendaRepo {
     type Error: Error + Send + Sync + 'static;
     async fn create_agenda(&self, agenda: &AgendaCreate) -> Result<Uuid, Self::Error>;
+    /// Inserts an agenda as-is, keeping its id and timestamps (used by importers).
+    async fn insert_agenda(&self, agenda: &Agenda) -> Result<(), Self::Error>;
 
     async fn delete_agenda_by_id(&self, id: Uuid) -> Result<(), Self::Error>;
     async fn update_agenda(&self, id: Uuid, update: &AgendaUpdate) -> Result<(), Self::Error>;
+    /// Every field change made to an agenda by `update_agenda`, oldest first.
+    async fn get_agenda_revisions(&self, id: Uuid) -> Result<Vec<AgendaRevision>, Self::Error>;
     async fn get_agenda_by_id(&self, id: Uuid) -> Result<Option<Agenda>, Self::Error>;
     async fn get_agendas_by_title(&self, title: &str) -> Result<Vec<Agenda>, Self::Error>;
     async fn get_agendas_by_status(&self, status: Option<&str>)
@@ -56,5 +128,10 @@ pub trait AgendaRepo {
         start: Timestamp,
         end: Timestamp,
     ) -> Result<Vec<Agenda>, Self::Error>;
+    async fn get_agendas_by_initiate_time_range(
+        &self,
+        start: Timestamp,
+        end: Timestamp,
+    ) -> Result<Vec<Agenda>, Self::Error>;
     // More query methods if needed
 }
@@ -0,0 +1,525 @@
+//! Domain events and an in-process bus to publish them.
+//!
+//! Wrap any repository in [`PublishingAgendaRepo`] or [`PublishingLogRepo`]
+//! and every successful mutation publishes a [`DomainEvent`] to the handlers
+//! subscribed on the [`EventBus`]. Reads pass straight through.
+
+use crate::{
+    Agenda, AgendaCreate, AgendaRepo, AgendaRevision, AgendaStatus, AgendaUpdate, Log, LogCreate,
+    LogRepo,
+};
+use async_trait::async_trait;
+use jiff::Timestamp;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum DomainEvent {
+    AgendaCreated {
+        agenda: Agenda,
+    },
+    /// The agenda before and after an update that found it.
+    AgendaUpdated {
+        before: Agenda,
+        after: Agenda,
+    },
+    AgendaDeleted {
+        agenda: Agenda,
+    },
+    LogCreated {
+        log: Log,
+    },
+    LogDeleted {
+        id: Uuid,
+    },
+}
+
+impl DomainEvent {
+    /// The agenda the event concerns, when known.
+    pub fn agenda_id(&self) -> Option<Uuid> {
+        match self {
+            DomainEvent::AgendaCreated { agenda } | DomainEvent::AgendaDeleted { agenda } => {
+                Some(agenda.id)
+            }
+            DomainEvent::AgendaUpdated { after, .. } => Some(after.id),
+            DomainEvent::LogCreated { log } => Some(log.agenda_id),
+            DomainEvent::LogDeleted { .. } => None,
+        }
+    }
+
+    /// The old and new status when an update changed it.
+    pub fn status_change(&self) -> Option<(AgendaStatus, AgendaStatus)> {
+        match self {
+            DomainEvent::AgendaUpdated { before, after }
+                if before.agenda_status != after.agenda_status =>
+            {
+                Some((before.agenda_status, after.agenda_status))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Something that reacts to domain events, such as an indexer or notifier.
+///
+/// Handlers run one after another in subscription order, after the change
+/// has been made, so they cannot veto it; a handler that can fail deals
+/// with its own errors.
+#[async_trait]
+pub trait EventHandler: Send + Sync {
+    async fn handle(&self, event: &DomainEvent);
+}
+
+/// Fans events out to subscribed handlers. Clones share their subscribers.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    handlers: Arc<Mutex<Vec<Arc<dyn EventHandler>>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subscribe(&self, handler: impl EventHandler + 'static) {
+        self.lock().push(Arc::new(handler));
+    }
+
+    pub async fn publish(&self, event: DomainEvent) {
+        // Snapshot the handlers so none is called with the lock held.
+        let handlers = self.lock().clone();
+        for handler in handlers {
+            handler.handle(&event).await;
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Arc<dyn EventHandler>>> {
+        self.handlers
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+impl std::fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventBus")
+            .field("handlers", &self.lock().len())
+            .finish()
+    }
+}
+
+/// An [`AgendaRepo`] that publishes an event after every successful write.
+///
+/// Events carry whole agendas, so writes read the agenda back (and updates
+/// and deletes read it first). Writes that touch no agenda publish nothing.
+pub struct PublishingAgendaRepo<R> {
+    inner: R,
+    bus: EventBus,
+}
+
+impl<R> PublishingAgendaRepo<R> {
+    pub fn new(inner: R, bus: EventBus) -> Self {
+        PublishingAgendaRepo { inner, bus }
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+}
+
+#[async_trait]
+impl<R: AgendaRepo + Send + Sync> AgendaRepo for PublishingAgendaRepo<R> {
+    type Error = R::Error;
+
+    async fn create_agenda(&self, agenda: &AgendaCreate) -> Result<Uuid, Self::Error> {
+        let id = self.inner.create_agenda(agenda).await?;
+        if let Ok(Some(agenda)) = self.inner.get_agenda_by_id(id).await {
+            self.bus
+                .publish(DomainEvent::AgendaCreated { agenda })
+                .await;
+        }
+        Ok(id)
+    }
+
+    async fn insert_agenda(&self, agenda: &Agenda) -> Result<(), Self::Error> {
+        self.inner.insert_agenda(agenda).await?;
+        self.bus
+            .publish(DomainEvent::AgendaCreated {
+                agenda: agenda.clone(),
+            })
+            .await;
+        Ok(())
+    }
+
+    async fn delete_agenda_by_id(&self, id: Uuid) -> Result<(), Self::Error> {
+        let before = self.inner.get_agenda_by_id(id).await?;
+        self.inner.delete_agenda_by_id(id).await?;
+        if let Some(agenda) = before {
+            self.bus
+                .publish(DomainEvent::AgendaDeleted { agenda })
+                .await;
+        }
+        Ok(())
+    }
+
+    async fn update_agenda(&self, id: Uuid, update: &AgendaUpdate) -> Result<(), Self::Error> {
+        let before = self.inner.get_agenda_by_id(id).await?;
+        self.inner.update_agenda(id, update).await?;
+        if let Some(before) = before
+            && let Ok(Some(after)) = self.inner.get_agenda_by_id(id).await
+        {
+            self.bus
+                .publish(DomainEvent::AgendaUpdated { before, after })
+                .await;
+        }
+        Ok(())
+    }
+
+    async fn get_agenda_revisions(&self, id: Uuid) -> Result<Vec<AgendaRevision>, Self::Error> {
+        self.inner.get_agenda_revisions(id).await
+    }
+
+    async fn get_agenda_by_id(&self, id: Uuid) -> Result<Option<Agenda>, Self::Error> {
+        self.inner.get_agenda_by_id(id).await
+    }
+
+    async fn get_agendas_by_title(&self, title: &str) -> Result<Vec<Agenda>, Self::Error> {
+        self.inner.get_agendas_by_title(title).await
+    }
+
+    async fn get_agendas_by_status(
+        &self,
+        status: Option<&str>,
+    ) -> Result<Vec<Agenda>, Self::Error> {
+        self.inner.get_agendas_by_status(status).await
+    }
+
+    async fn count_agendas_by_status(&self, status: Option<&str>) -> Result<u64, Self::Error> {
+        self.inner.count_agendas_by_status(status).await
+    }
+
+    async fn get_agendas_by_terminate_time_range(
+        &self,
+        start: Timestamp,
+        end: Timestamp,
+    ) -> Result<Vec<Agenda>, Self::Error> {
+        self.inner
+            .get_agendas_by_terminate_time_range(start, end)
+            .await
+    }
+
+    async fn get_agendas_by_initiate_time_range(
+        &self,
+        start: Timestamp,
+        end: Timestamp,
+    ) -> Result<Vec<Agenda>, Self::Error> {
+        self.inner
+            .get_agendas_by_initiate_time_range(start, end)
+            .await
+    }
+}
+
+/// A [`LogRepo`] that publishes an event after every successful write.
+pub struct PublishingLogRepo<R> {
+    inner: R,
+    bus: EventBus,
+}
+
+impl<R> PublishingLogRepo<R> {
+    pub fn new(inner: R, bus: EventBus) -> Self {
+        PublishingLogRepo { inner, bus }
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+}
+
+#[async_trait]
+impl<R: LogRepo + Send + Sync> LogRepo for PublishingLogRepo<R> {
+    type Error = R::Error;
+
+    async fn create_log(&self, new_log: &LogCreate) -> Result<Uuid, Self::Error> {
+        let id = self.inner.create_log(new_log).await?;
+        let created = self
+            .inner
+            .get_logs_by_agenda_id(new_log.agenda_id)
+            .await
+            .ok()
+            .and_then(|logs| logs.into_iter().find(|log| log.id == id));
+        if let Some(log) = created {
+            self.bus.publish(DomainEvent::LogCreated { log }).await;
+        }
+        Ok(id)
+    }
+
+    async fn insert_log(&self, log: &Log) -> Result<(), Self::Error> {
+        self.inner.insert_log(log).await?;
+        self.bus
+            .publish(DomainEvent::LogCreated { log: log.clone() })
+            .await;
+        Ok(())
+    }
+
+    async fn delete_log(&self, id: Uuid) -> Result<(), Self::Error> {
+        self.inner.delete_log(id).await?;
+        self.bus.publish(DomainEvent::LogDeleted { id }).await;
+        Ok(())
+    }
+
+    async fn get_log_by_id(&self, id: Uuid) -> Result<Option<Log>, Self::Error> {
+        self.inner.get_log_by_id(id).await
+    }
+
+    async fn get_logs_by_agenda_id(&self, agenda_id: Uuid) -> Result<Vec<Log>, Self::Error> {
+        self.inner.get_logs_by_agenda_id(agenda_id).await
+    }
+
+    async fn get_logs_by_time_range(
+        &self,
+        start: Timestamp,
+        end: Timestamp,
+    ) -> Result<Vec<Log>, Self::Error> {
+        self.inner.get_logs_by_time_range(start, end).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LogType;
+    use jiff::ToSpan;
+
+    #[derive(Debug)]
+    struct Never;
+
+    impl std::fmt::Display for Never {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("never")
+        }
+    }
+
+    impl std::error::Error for Never {}
+
+    /// Just enough of a repository to drive the wrappers.
+    #[derive(Default)]
+    struct Memory {
+        agendas: Mutex<Vec<Agenda>>,
+        logs: Mutex<Vec<Log>>,
+    }
+
+    #[async_trait]
+    impl AgendaRepo for Memory {
+        type Error = Never;
+
+        async fn create_agenda(&self, agenda: &AgendaCreate) -> Result<Uuid, Never> {
+            let id = Uuid::now_v7();
+            self.agendas.lock().unwrap().push(Agenda {
+                id,
+                title: agenda.title.clone(),
+                agenda_status: agenda.agenda_status,
+                initiate_at: Timestamp::now(),
+                terminate_at: agenda.terminate_at,
+            });
+            Ok(id)
+        }
+        async fn insert_agenda(&self, agenda: &Agenda) -> Result<(), Never> {
+            self.agendas.lock().unwrap().push(agenda.clone());
+            Ok(())
+        }
+        async fn delete_agenda_by_id(&self, id: Uuid) -> Result<(), Never> {
+            self.agendas
+                .lock()
+                .unwrap()
+                .retain(|agenda| agenda.id != id);
+            Ok(())
+        }
+        async fn update_agenda(&self, id: Uuid, update: &AgendaUpdate) -> Result<(), Never> {
+            for agenda in self.agendas.lock().unwrap().iter_mut() {
+                if agenda.id == id {
+                    if let Some(status) = update.agenda_status {
+                        agenda.agenda_status = status;
+                    }
+                    if let Some(title) = &update.title {
+                        agenda.title = title.clone();
+                    }
+                }
+            }
+            Ok(())
+        }
+        async fn get_agenda_revisions(&self, _id: Uuid) -> Result<Vec<AgendaRevision>, Never> {
+            Ok(Vec::new())
+        }
+        async fn get_agenda_by_id(&self, id: Uuid) -> Result<Option<Agenda>, Never> {
+            let agendas = self.agendas.lock().unwrap();
+            Ok(agendas.iter().find(|agenda| agenda.id == id).cloned())
+        }
+        async fn get_agendas_by_title(&self, _: &str) -> Result<Vec<Agenda>, Never> {
+            Ok(Vec::new())
+        }
+        async fn get_agendas_by_status(&self, _: Option<&str>) -> Result<Vec<Agenda>, Never> {
+            Ok(Vec::new())
+        }
+        async fn count_agendas_by_status(&self, _: Option<&str>) -> Result<u64, Never> {
+            Ok(0)
+        }
+        async fn get_agendas_by_terminate_time_range(
+            &self,
+            _: Timestamp,
+            _: Timestamp,
+        ) -> Result<Vec<Agenda>, Never> {
+            Ok(Vec::new())
+        }
+        async fn get_agendas_by_initiate_time_range(
+            &self,
+            _: Timestamp,
+            _: Timestamp,
+        ) -> Result<Vec<Agenda>, Never> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[async_trait]
+    impl LogRepo for Memory {
+        type Error = Never;
+
+        async fn create_log(&self, new_log: &LogCreate) -> Result<Uuid, Never> {
+            let id = Uuid::now_v7();
+            self.logs.lock().unwrap().push(Log {
+                id,
+                agenda_id: new_log.agenda_id,
+                content: new_log.content.clone(),
+                create_at: Timestamp::now(),
+                log_type: new_log.log_type,
+            });
+            Ok(id)
+        }
+        async fn insert_log(&self, log: &Log) -> Result<(), Never> {
+            self.logs.lock().unwrap().push(log.clone());
+            Ok(())
+        }
+        async fn delete_log(&self, id: Uuid) -> Result<(), Never> {
+            self.logs.lock().unwrap().retain(|log| log.id != id);
+            Ok(())
+        }
+        async fn get_log_by_id(&self, id: Uuid) -> Result<Option<Log>, Never> {
+            let logs = self.logs.lock().unwrap();
+            Ok(logs.iter().find(|log| log.id == id).cloned())
+        }
+        async fn get_logs_by_agenda_id(&self, agenda_id: Uuid) -> Result<Vec<Log>, Never> {
+            let logs = self.logs.lock().unwrap();
+            Ok(logs
+                .iter()
+                .filter(|log| log.agenda_id == agenda_id)
+                .cloned()
+                .collect())
+        }
+        async fn get_logs_by_time_range(
+            &self,
+            _: Timestamp,
+            _: Timestamp,
+        ) -> Result<Vec<Log>, Never> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<DomainEvent>>>);
+
+    #[async_trait]
+    impl EventHandler for Recorder {
+        async fn handle(&self, event: &DomainEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    #[tokio::test]
+    async fn mutations_publish_events_to_every_handler() {
+        let bus = EventBus::new();
+        let (first, second) = (Recorder::default(), Recorder::default());
+        bus.subscribe(first.clone());
+        bus.subscribe(second.clone());
+        let agendas = PublishingAgendaRepo::new(Memory::default(), bus.clone());
+        let logs = PublishingLogRepo::new(Memory::default(), bus.clone());
+
+        let id = agendas
+            .create_agenda(&AgendaCreate {
+                title: "Write".to_string(),
+                agenda_status: AgendaStatus::Pending,
+                terminate_at: Timestamp::now() + 1.hour(),
+            })
+            .await
+            .unwrap();
+        agendas
+            .update_agenda(
+                id,
+                &AgendaUpdate {
+                    title: None,
+                    agenda_status: Some(AgendaStatus::Ongoing),
+                    terminate_at: None,
+                    actor: None,
+                },
+            )
+            .await
+            .unwrap();
+        let log_id = logs
+            .create_log(&LogCreate {
+                agenda_id: id,
+                content: "started".to_string(),
+                log_type: LogType::Activate,
+            })
+            .await
+            .unwrap();
+        logs.delete_log(log_id).await.unwrap();
+        agendas.delete_agenda_by_id(id).await.unwrap();
+
+        let events = first.0.lock().unwrap().clone();
+        assert_eq!(events, *second.0.lock().unwrap());
+        assert_eq!(events.len(), 5);
+        assert!(
+            matches!(&events[0], DomainEvent::AgendaCreated { agenda } if agenda.title == "Write")
+        );
+        assert_eq!(
+            events[1].status_change(),
+            Some((AgendaStatus::Pending, AgendaStatus::Ongoing))
+        );
+        assert!(matches!(&events[2], DomainEvent::LogCreated { log } if log.id == log_id));
+        assert_eq!(events[3], DomainEvent::LogDeleted { id: log_id });
+        assert!(matches!(&events[4], DomainEvent::AgendaDeleted { agenda } if agenda.id == id));
+        assert!(
+            events[..3]
+                .iter()
+                .all(|event| event.agenda_id() == Some(id))
+        );
+    }
+
+    #[tokio::test]
+    async fn writes_that_miss_publish_nothing() {
+        let bus = EventBus::new();
+        let recorder = Recorder::default();
+        bus.subscribe(recorder.clone());
+        let agendas = PublishingAgendaRepo::new(Memory::default(), bus);
+
+        let missing = Uuid::now_v7();
+        agendas.delete_agenda_by_id(missing).await.unwrap();
+        agendas
+            .update_agenda(
+                missing,
+                &AgendaUpdate {
+                    title: Some("ghost".to_string()),
+                    agenda_status: None,
+                    terminate_at: None,
+                    actor: None,
+                },
+            )
+            .await
+            .unwrap();
+        assert!(recorder.0.lock().unwrap().is_empty());
+        assert_eq!(
+            serde_json::to_value(DomainEvent::LogDeleted { id: missing }).unwrap()["event"],
+            "log_deleted"
+        );
+    }
+}
@@ -1,5 +1,9 @@
 mod agenda;
+mod event;
 mod log;
+mod stats;
 
 pub use agenda::*;
+pub use event::*;
 pub use log::*;
+pub use stats::*;
@@ -1,6 +1,11 @@
 use async_trait::async_trait;
 use jiff::Timestamp;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use uuid::Uuid;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
 pub enum LogType {
     Activate,
     PutOff,
@@ -8,17 +13,20 @@ pub enum LogType {
     CommonLog,
 }
 
-impl LogType {
-    pub fn to_string(&self) -> String {
-        match self {
-            LogType::Activate => "activate".to_string(),
-            LogType::PutOff => "put_off".to_string(),
-            LogType::Terminate => "terminate".to_string(),
-            LogType::CommonLog => "common_log".to_string(),
-        }
+impl fmt::Display for LogType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            LogType::Activate => "activate",
+            LogType::PutOff => "put_off",
+            LogType::Terminate => "terminate",
+            LogType::CommonLog => "common_log",
+        };
+        f.write_str(s)
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Log {
     pub id: Uuid,
     pub agenda_id: Uuid,
@@ -37,7 +45,10 @@ pub struct LogCreate {
 pub trait LogRepo {
     type Error: std::error::Error + Send + Sync + 'static;
     async fn create_log(&self, new_log: &LogCreate) -> Result<Uuid, Self::Error>;
+    /// Inserts a log as-is, keeping its id and timestamp (used by importers).
+    async fn insert_log(&self, log: &Log) -> Result<(), Self::Error>;
     async fn delete_log(&self, id: Uuid) -> Result<(), Self::Error>;
+    async fn get_log_by_id(&self, id: Uuid) -> Result<Option<Log>, Self::Error>;
     async fn get_logs_by_agenda_id(&self, agenda_id: Uuid) -> Result<Vec<Log>, Self::Error>;
     async fn get_logs_by_time_range(
         &self,
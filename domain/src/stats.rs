@@ -0,0 +1,150 @@
+use crate::Agenda;
+use jiff::{SignedDuration, Timestamp};
+use serde::{Deserialize, Serialize};
+
+/// An agenda terminated inside the measured period.
+pub struct Termination<'a> {
+    pub agenda: &'a Agenda,
+    /// When the terminate log was written.
+    pub terminated_at: Timestamp,
+    /// Put-off logs written over the agenda's whole lifetime.
+    pub put_offs: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    pub start: Timestamp,
+    pub end: Timestamp,
+    /// Agendas whose `initiate_at` falls in the period.
+    pub created: usize,
+    /// Agendas whose terminate log falls in the period.
+    pub terminated: usize,
+    /// Median time from `initiate_at` to the terminate log.
+    pub median_lead_time: Option<SignedDuration>,
+    /// Share of terminated agendas finished no later than their `terminate_at`.
+    pub on_time_ratio: Option<f64>,
+    /// Mean number of put-offs among the terminated agendas.
+    pub average_put_offs: Option<f64>,
+}
+
+impl Stats {
+    pub fn compute(
+        start: Timestamp,
+        end: Timestamp,
+        created: &[Agenda],
+        terminations: &[Termination<'_>],
+    ) -> Stats {
+        let mut lead_times: Vec<SignedDuration> = terminations
+            .iter()
+            .map(|t| t.agenda.initiate_at.duration_until(t.terminated_at))
+            .collect();
+        lead_times.sort();
+
+        let count = terminations.len();
+        let ratio = |numerator: usize| (count > 0).then(|| numerator as f64 / count as f64);
+        let on_time = terminations
+            .iter()
+            .filter(|t| t.terminated_at <= t.agenda.terminate_at)
+            .count();
+        let put_offs = terminations.iter().map(|t| t.put_offs).sum();
+
+        Stats {
+            start,
+            end,
+            created: created.len(),
+            terminated: count,
+            median_lead_time: median(&lead_times),
+            on_time_ratio: ratio(on_time),
+            average_put_offs: ratio(put_offs),
+        }
+    }
+}
+
+fn median(sorted: &[SignedDuration]) -> Option<SignedDuration> {
+    let mid = sorted.len() / 2;
+    match sorted.len() {
+        0 => None,
+        len if len % 2 == 1 => Some(sorted[mid]),
+        _ => {
+            let (low, high) = (sorted[mid - 1], sorted[mid]);
+            Some(low + (high - low) / 2)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AgendaStatus;
+    use jiff::ToSpan;
+    use uuid::Uuid;
+
+    fn agenda(initiate_at: Timestamp, terminate_at: Timestamp) -> Agenda {
+        Agenda {
+            id: Uuid::now_v7(),
+            title: "agenda".to_string(),
+            agenda_status: AgendaStatus::Terminated,
+            initiate_at,
+            terminate_at,
+        }
+    }
+
+    #[test]
+    fn empty_period_has_no_ratios() {
+        let now = Timestamp::now();
+        let stats = Stats::compute(now, now, &[], &[]);
+
+        assert_eq!(stats.created, 0);
+        assert_eq!(stats.terminated, 0);
+        assert_eq!(stats.median_lead_time, None);
+        assert_eq!(stats.on_time_ratio, None);
+        assert_eq!(stats.average_put_offs, None);
+    }
+
+    #[test]
+    fn computes_lead_time_and_ratios() {
+        let base = Timestamp::UNIX_EPOCH;
+        let quick = agenda(base, base + 10.hours());
+        let slow = agenda(base, base + 1.hour());
+        let medium = agenda(base, base + 5.hours());
+        let terminations = [
+            Termination {
+                agenda: &quick,
+                terminated_at: base + 1.hour(),
+                put_offs: 0,
+            },
+            Termination {
+                agenda: &slow,
+                terminated_at: base + 3.hours(),
+                put_offs: 2,
+            },
+            Termination {
+                agenda: &medium,
+                terminated_at: base + 2.hours(),
+                put_offs: 1,
+            },
+        ];
+
+        let stats = Stats::compute(
+            base,
+            base + 24.hours(),
+            std::slice::from_ref(&quick),
+            &terminations,
+        );
+
+        assert_eq!(stats.created, 1);
+        assert_eq!(stats.terminated, 3);
+        assert_eq!(stats.median_lead_time, Some(SignedDuration::from_hours(2)));
+        assert_eq!(stats.on_time_ratio, Some(2.0 / 3.0));
+        assert_eq!(stats.average_put_offs, Some(1.0));
+    }
+
+    #[test]
+    fn even_count_median_is_midpoint() {
+        let hours = |h: i64| SignedDuration::from_hours(h);
+        assert_eq!(
+            median(&[hours(1), hours(4)]),
+            Some(SignedDuration::from_mins(150))
+        );
+    }
+}
@@ -0,0 +1,263 @@
+use crate::extract::{Body, Params, PathParam};
+use crate::{ApiError, AppState};
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use domain::*;
+use jiff::Timestamp;
+use serde::Deserialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// How changes made through the API appear in agenda revision histories.
+const ACTOR: &str = "api";
+
+/// Filters for `GET /agendas`; all given filters must match.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AgendaQuery {
+    status: Option<AgendaStatus>,
+    title: Option<String>,
+    due_after: Option<Timestamp>,
+    due_before: Option<Timestamp>,
+    initiated_after: Option<Timestamp>,
+    initiated_before: Option<Timestamp>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NewAgenda {
+    title: String,
+    terminate_at: Timestamp,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AgendaPatch {
+    title: Option<String>,
+    terminate_at: Option<Timestamp>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Transition {
+    status: AgendaStatus,
+    #[serde(default)]
+    content: String,
+}
+
+pub async fn list<A, L>(
+    State(state): State<Arc<AppState<A, L>>>,
+    Params(query): Params<AgendaQuery>,
+) -> Result<Json<Vec<Agenda>>, ApiError>
+where
+    A: AgendaRepo + Send + Sync,
+{
+    let repo = &state.agendas;
+    // Let the repository do the most selective lookup, then apply the rest.
+    let mut agendas = if let Some(title) = &query.title {
+        repo.get_agendas_by_title(title).await
+    } else if query.due_after.is_some() || query.due_before.is_some() {
+        repo.get_agendas_by_terminate_time_range(
+            query.due_after.unwrap_or(Timestamp::MIN),
+            query.due_before.unwrap_or(Timestamp::MAX),
+        )
+        .await
+    } else if query.initiated_after.is_some() || query.initiated_before.is_some() {
+        repo.get_agendas_by_initiate_time_range(
+            query.initiated_after.unwrap_or(Timestamp::MIN),
+            query.initiated_before.unwrap_or(Timestamp::MAX),
+        )
+        .await
+    } else {
+        let status = query.status.map(|status| status.to_string());
+        repo.get_agendas_by_status(status.as_deref()).await
+    }
+    .map_err(ApiError::repo)?;
+
+    let within = |at: Timestamp, after: Option<Timestamp>, before: Option<Timestamp>| {
+        after.is_none_or(|after| at >= after) && before.is_none_or(|before| at <= before)
+    };
+    agendas.retain(|agenda| {
+        query
+            .status
+            .is_none_or(|status| agenda.agenda_status == status)
+            && within(agenda.terminate_at, query.due_after, query.due_before)
+            && within(
+                agenda.initiate_at,
+                query.initiated_after,
+                query.initiated_before,
+            )
+    });
+    agendas.sort_by_key(|agenda| (agenda.initiate_at, agenda.id));
+    Ok(Json(agendas))
+}
+
+pub async fn get<A, L>(
+    State(state): State<Arc<AppState<A, L>>>,
+    PathParam(id): PathParam<Uuid>,
+) -> Result<Json<Agenda>, ApiError>
+where
+    A: AgendaRepo + Send + Sync,
+{
+    find(&state.agendas, id).await.map(Json)
+}
+
+pub async fn create<A, L>(
+    State(state): State<Arc<AppState<A, L>>>,
+    Body(body): Body<NewAgenda>,
+) -> Result<(StatusCode, Json<Agenda>), ApiError>
+where
+    A: AgendaRepo + Send + Sync,
+{
+    let title = non_empty(body.title)?;
+    let open = state
+        .agendas
+        .count_agendas_by_status(Some("pending"))
+        .await
+        .map_err(ApiError::repo)?
+        + state
+            .agendas
+            .count_agendas_by_status(Some("ongoing"))
+            .await
+            .map_err(ApiError::repo)?;
+    if open >= u64::from(state.max_slots) {
+        return Err(ApiError::Conflict(format!(
+            "all {} slots are occupied",
+            state.max_slots
+        )));
+    }
+    let id = state
+        .agendas
+        .create_agenda(&AgendaCreate {
+            title,
+            agenda_status: AgendaStatus::Pending,
+            terminate_at: body.terminate_at,
+        })
+        .await
+        .map_err(ApiError::repo)?;
+    Ok((StatusCode::CREATED, Json(find(&state.agendas, id).await?)))
+}
+
+/// Changes the title or deadline; the status only changes through transitions.
+pub async fn update<A, L>(
+    State(state): State<Arc<AppState<A, L>>>,
+    PathParam(id): PathParam<Uuid>,
+    Body(body): Body<AgendaPatch>,
+) -> Result<Json<Agenda>, ApiError>
+where
+    A: AgendaRepo + Send + Sync,
+{
+    find(&state.agendas, id).await?;
+    let update = AgendaUpdate {
+        title: body.title.map(non_empty).transpose()?,
+        agenda_status: None,
+        terminate_at: body.terminate_at,
+        actor: Some(ACTOR.to_string()),
+    };
+    if update.title.is_some() || update.terminate_at.is_some() {
+        state
+            .agendas
+            .update_agenda(id, &update)
+            .await
+            .map_err(ApiError::repo)?;
+    }
+    find(&state.agendas, id).await.map(Json)
+}
+
+pub async fn delete<A, L>(
+    State(state): State<Arc<AppState<A, L>>>,
+    PathParam(id): PathParam<Uuid>,
+) -> Result<StatusCode, ApiError>
+where
+    A: AgendaRepo + Send + Sync,
+{
+    find(&state.agendas, id).await?;
+    state
+        .agendas
+        .delete_agenda_by_id(id)
+        .await
+        .map_err(ApiError::repo)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Moves an agenda to another status and writes the matching log, keeping
+/// at most one agenda ongoing.
+pub async fn transition<A, L>(
+    State(state): State<Arc<AppState<A, L>>>,
+    PathParam(id): PathParam<Uuid>,
+    Body(body): Body<Transition>,
+) -> Result<Json<Agenda>, ApiError>
+where
+    A: AgendaRepo + Send + Sync,
+    L: LogRepo + Send + Sync,
+{
+    let agenda = find(&state.agendas, id).await?;
+    if agenda.agenda_status == AgendaStatus::Terminated {
+        return Err(ApiError::Conflict(format!("agenda {id} is terminated")));
+    }
+    if agenda.agenda_status == body.status {
+        return Err(ApiError::Conflict(format!(
+            "agenda {id} is already {}",
+            body.status
+        )));
+    }
+    let log_type = match body.status {
+        AgendaStatus::Ongoing => {
+            let ongoing = state
+                .agendas
+                .get_agendas_by_status(Some("ongoing"))
+                .await
+                .map_err(ApiError::repo)?;
+            if let Some(other) = ongoing.first() {
+                return Err(ApiError::Conflict(format!(
+                    "agenda {} is already ongoing",
+                    other.id
+                )));
+            }
+            LogType::Activate
+        }
+        AgendaStatus::Pending => LogType::PutOff,
+        AgendaStatus::Terminated => LogType::Terminate,
+    };
+
+    state
+        .agendas
+        .update_agenda(
+            id,
+            &AgendaUpdate {
+                title: None,
+                agenda_status: Some(body.status),
+                terminate_at: None,
+                actor: Some(ACTOR.to_string()),
+            },
+        )
+        .await
+        .map_err(ApiError::repo)?;
+    state
+        .logs
+        .create_log(&LogCreate {
+            agenda_id: id,
+            content: body.content,
+            log_type,
+        })
+        .await
+        .map_err(ApiError::repo)?;
+    find(&state.agendas, id).await.map(Json)
+}
+
+pub(crate) async fn find<A: AgendaRepo>(agendas: &A, id: Uuid) -> Result<Agenda, ApiError> {
+    agendas
+        .get_agenda_by_id(id)
+        .await
+        .map_err(ApiError::repo)?
+        .ok_or_else(|| ApiError::NotFound(format!("agenda {id} not found")))
+}
+
+fn non_empty(title: String) -> Result<String, ApiError> {
+    if title.trim().is_empty() {
+        Err(ApiError::BadRequest("title must not be empty".to_string()))
+    } else {
+        Ok(title)
+    }
+}
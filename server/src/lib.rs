@@ -0,0 +1,105 @@
+//! Local HTTP API over the agenda and log repositories.
+//!
+//! Every route except `/openapi.json` requires `Authorization: Bearer <token>`.
+
+mod agendas;
+mod error;
+mod extract;
+mod logs;
+
+use axum::extract::{Request, State};
+use axum::http::header;
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use domain::{AgendaRepo, LogRepo};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+pub use error::ApiError;
+
+/// The OpenAPI 3.1 description of every route.
+pub const OPENAPI: &str = include_str!("../openapi.json");
+
+pub struct AppState<A, L> {
+    pub agendas: A,
+    pub logs: L,
+    /// The bearer token clients must present.
+    pub token: String,
+    /// Creating an agenda fails once this many are open, as with `slot add`.
+    pub max_slots: u8,
+}
+
+pub fn router<A, L>(state: AppState<A, L>) -> Router
+where
+    A: AgendaRepo + Send + Sync + 'static,
+    L: LogRepo + Send + Sync + 'static,
+{
+    let state = Arc::new(state);
+    let api = Router::new()
+        .route(
+            "/agendas",
+            get(agendas::list::<A, L>).post(agendas::create::<A, L>),
+        )
+        .route(
+            "/agendas/{id}",
+            get(agendas::get::<A, L>)
+                .patch(agendas::update::<A, L>)
+                .delete(agendas::delete::<A, L>),
+        )
+        .route(
+            "/agendas/{id}/transitions",
+            post(agendas::transition::<A, L>),
+        )
+        .route("/agendas/{id}/logs", get(logs::list_for_agenda::<A, L>))
+        .route("/logs", get(logs::list::<A, L>).post(logs::create::<A, L>))
+        .route("/logs/{id}", delete(logs::delete::<A, L>))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            authenticate::<A, L>,
+        ))
+        .with_state(state);
+    Router::new()
+        .route("/openapi.json", get(openapi))
+        .merge(api)
+}
+
+/// Serves `router` until Ctrl-C is pressed.
+pub async fn serve(listener: TcpListener, router: Router) -> std::io::Result<()> {
+    axum::serve(listener, router)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await
+}
+
+async fn openapi() -> Response {
+    let document: serde_json::Value =
+        serde_json::from_str(OPENAPI).expect("bundled OpenAPI document is valid JSON");
+    Json(document).into_response()
+}
+
+async fn authenticate<A, L>(
+    State(state): State<Arc<AppState<A, L>>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let presented = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match presented {
+        Some(token) if constant_time_eq(token.as_bytes(), state.token.as_bytes()) => {
+            Ok(next.run(request).await)
+        }
+        _ => Err(ApiError::Unauthorized),
+    }
+}
+
+/// Compares without returning early, so response times do not leak how much
+/// of the token matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,117 @@
+use crate::agendas::find;
+use crate::extract::{Body, Params, PathParam};
+use crate::{ApiError, AppState};
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use domain::*;
+use jiff::Timestamp;
+use serde::Deserialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Filters for `GET /logs`: an inclusive time range, optionally narrowed to
+/// one agenda and one log type.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LogQuery {
+    since: Option<Timestamp>,
+    until: Option<Timestamp>,
+    log_type: Option<LogType>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NewLog {
+    agenda_id: Uuid,
+    content: String,
+}
+
+pub async fn list<A, L>(
+    State(state): State<Arc<AppState<A, L>>>,
+    Params(query): Params<LogQuery>,
+) -> Result<Json<Vec<Log>>, ApiError>
+where
+    L: LogRepo + Send + Sync,
+{
+    let mut logs = state
+        .logs
+        .get_logs_by_time_range(
+            query.since.unwrap_or(Timestamp::MIN),
+            query.until.unwrap_or(Timestamp::MAX),
+        )
+        .await
+        .map_err(ApiError::repo)?;
+    logs.retain(|log| {
+        query
+            .log_type
+            .is_none_or(|log_type| log.log_type == log_type)
+    });
+    logs.sort_by_key(|log| (log.create_at, log.id));
+    Ok(Json(logs))
+}
+
+pub async fn list_for_agenda<A, L>(
+    State(state): State<Arc<AppState<A, L>>>,
+    PathParam(id): PathParam<Uuid>,
+) -> Result<Json<Vec<Log>>, ApiError>
+where
+    A: AgendaRepo + Send + Sync,
+    L: LogRepo + Send + Sync,
+{
+    find(&state.agendas, id).await?;
+    let mut logs = state
+        .logs
+        .get_logs_by_agenda_id(id)
+        .await
+        .map_err(ApiError::repo)?;
+    logs.sort_by_key(|log| (log.create_at, log.id));
+    Ok(Json(logs))
+}
+
+/// Adds a mark to an agenda; other log types are written by transitions.
+pub async fn create<A, L>(
+    State(state): State<Arc<AppState<A, L>>>,
+    Body(body): Body<NewLog>,
+) -> Result<(StatusCode, Json<Log>), ApiError>
+where
+    A: AgendaRepo + Send + Sync,
+    L: LogRepo + Send + Sync,
+{
+    find(&state.agendas, body.agenda_id).await?;
+    let id = state
+        .logs
+        .create_log(&LogCreate {
+            agenda_id: body.agenda_id,
+            content: body.content,
+            log_type: LogType::CommonLog,
+        })
+        .await
+        .map_err(ApiError::repo)?;
+    let log = state
+        .logs
+        .get_logs_by_agenda_id(body.agenda_id)
+        .await
+        .map_err(ApiError::repo)?
+        .into_iter()
+        .find(|log| log.id == id)
+        .ok_or_else(|| ApiError::NotFound(format!("log {id} not found")))?;
+    Ok((StatusCode::CREATED, Json(log)))
+}
+
+pub async fn delete<A, L>(
+    State(state): State<Arc<AppState<A, L>>>,
+    PathParam(id): PathParam<Uuid>,
+) -> Result<StatusCode, ApiError>
+where
+    L: LogRepo + Send + Sync,
+{
+    state
+        .logs
+        .get_log_by_id(id)
+        .await
+        .map_err(ApiError::repo)?
+        .ok_or_else(|| ApiError::NotFound(format!("log {id} not found")))?;
+    state.logs.delete_log(id).await.map_err(ApiError::repo)?;
+    Ok(StatusCode::NO_CONTENT)
+}
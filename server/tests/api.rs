@@ -0,0 +1,330 @@
+use axum::Router;
+use axum::body::Body;
+use axum::http::{Method, Request, StatusCode, header};
+use http_body_util::BodyExt;
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+const TOKEN: &str = "test-token";
+
+struct TestServer {
+    _dir: tempfile::TempDir,
+    router: Router,
+}
+
+impl TestServer {
+    async fn new(max_slots: u8) -> TestServer {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let pool = storage::init_db(&dir.path().join("finiate.db"))
+            .await
+            .expect("init db");
+        let (agendas, logs) = storage::create_repos(&pool);
+        let router = server::router(server::AppState {
+            agendas,
+            logs,
+            token: TOKEN.to_string(),
+            max_slots,
+        });
+        TestServer { _dir: dir, router }
+    }
+
+    async fn send(&self, method: Method, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        self.send_as(Some(TOKEN), method, uri, body).await
+    }
+
+    async fn send_as(
+        &self,
+        token: Option<&str>,
+        method: Method,
+        uri: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+        let request = match body {
+            Some(body) => request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        }
+        .expect("build request");
+
+        let response = self
+            .router
+            .clone()
+            .oneshot(request)
+            .await
+            .expect("send request");
+        let status = response.status();
+        let bytes = response
+            .into_body()
+            .collect()
+            .await
+            .expect("read body")
+            .to_bytes();
+        let value = if bytes.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&bytes).expect("JSON body")
+        };
+        (status, value)
+    }
+
+    async fn create_agenda(&self, title: &str) -> String {
+        let (status, agenda) = self
+            .send(
+                Method::POST,
+                "/agendas",
+                Some(json!({ "title": title, "terminate_at": "2030-01-01T00:00:00Z" })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::CREATED);
+        agenda["id"].as_str().expect("agenda id").to_string()
+    }
+}
+
+#[tokio::test]
+async fn requires_bearer_token() {
+    let server = TestServer::new(5).await;
+
+    let (status, body) = server.send_as(None, Method::GET, "/agendas", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"], "missing or invalid bearer token");
+
+    let (status, _) = server
+        .send_as(Some("wrong"), Method::GET, "/agendas", None)
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, document) = server
+        .send_as(None, Method::GET, "/openapi.json", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(document["openapi"], "3.1.0");
+}
+
+#[tokio::test]
+async fn agenda_crud() {
+    let server = TestServer::new(5).await;
+    let id = server.create_agenda("Write API").await;
+
+    let (status, agenda) = server
+        .send(Method::GET, &format!("/agendas/{id}"), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(agenda["title"], "Write API");
+    assert_eq!(agenda["agenda_status"], "pending");
+
+    let (status, agenda) = server
+        .send(
+            Method::PATCH,
+            &format!("/agendas/{id}"),
+            Some(json!({ "title": "Write REST API" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(agenda["title"], "Write REST API");
+
+    let (status, _) = server
+        .send(Method::DELETE, &format!("/agendas/{id}"), None)
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, body) = server
+        .send(Method::GET, &format!("/agendas/{id}"), None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(body["error"].as_str().unwrap().contains(&id));
+}
+
+#[tokio::test]
+async fn query_parameters_filter_agendas() {
+    let server = TestServer::new(5).await;
+    let first = server.create_agenda("First").await;
+    server.create_agenda("Second").await;
+    server
+        .send(
+            Method::POST,
+            &format!("/agendas/{first}/transitions"),
+            Some(json!({ "status": "ongoing" })),
+        )
+        .await;
+
+    let (_, all) = server.send(Method::GET, "/agendas", None).await;
+    assert_eq!(all.as_array().unwrap().len(), 2);
+    let (_, ongoing) = server
+        .send(Method::GET, "/agendas?status=ongoing", None)
+        .await;
+    assert_eq!(ongoing[0]["id"], first);
+    assert_eq!(ongoing.as_array().unwrap().len(), 1);
+    let (_, titled) = server
+        .send(Method::GET, "/agendas?title=Second&status=ongoing", None)
+        .await;
+    assert!(titled.as_array().unwrap().is_empty());
+    let (_, due) = server
+        .send(
+            Method::GET,
+            "/agendas?due_before=2029-12-31T00:00:00Z",
+            None,
+        )
+        .await;
+    assert!(due.as_array().unwrap().is_empty());
+
+    let (status, body) = server
+        .send(Method::GET, "/agendas?status=bogus", None)
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"].is_string());
+}
+
+#[tokio::test]
+async fn transitions_write_logs_and_keep_one_ongoing() {
+    let server = TestServer::new(5).await;
+    let first = server.create_agenda("First").await;
+    let second = server.create_agenda("Second").await;
+    let transition = |id: &str| format!("/agendas/{id}/transitions");
+
+    let (status, agenda) = server
+        .send(
+            Method::POST,
+            &transition(&first),
+            Some(json!({ "status": "ongoing" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(agenda["agenda_status"], "ongoing");
+
+    let (status, _) = server
+        .send(
+            Method::POST,
+            &transition(&second),
+            Some(json!({ "status": "ongoing" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, _) = server
+        .send(
+            Method::POST,
+            &transition(&first),
+            Some(json!({ "status": "terminated", "content": "done" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = server
+        .send(
+            Method::POST,
+            &transition(&first),
+            Some(json!({ "status": "pending" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (_, logs) = server
+        .send(Method::GET, &format!("/agendas/{first}/logs"), None)
+        .await;
+    let types: Vec<_> = logs
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|log| log["log_type"].as_str().unwrap())
+        .collect();
+    assert_eq!(types, ["activate", "terminate"]);
+    assert_eq!(logs[1]["content"], "done");
+}
+
+#[tokio::test]
+async fn log_endpoints() {
+    let server = TestServer::new(5).await;
+    let id = server.create_agenda("Logged").await;
+
+    let (status, log) = server
+        .send(
+            Method::POST,
+            "/logs",
+            Some(json!({ "agenda_id": id, "content": "halfway" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(log["log_type"], "common_log");
+    let log_id = log["id"].as_str().unwrap().to_string();
+
+    let (_, marks) = server
+        .send(Method::GET, "/logs?log_type=common_log", None)
+        .await;
+    assert_eq!(marks[0]["id"], log_id.as_str());
+    let (_, none) = server
+        .send(Method::GET, "/logs?until=2000-01-01T00:00:00Z", None)
+        .await;
+    assert!(none.as_array().unwrap().is_empty());
+
+    let (status, _) = server
+        .send(
+            Method::POST,
+            "/logs",
+            Some(json!({ "agenda_id": uuid::Uuid::now_v7(), "content": "orphan" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = server
+        .send(Method::DELETE, &format!("/logs/{log_id}"), None)
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (_, logs) = server.send(Method::GET, "/logs", None).await;
+    assert!(logs.as_array().unwrap().is_empty());
+    let (status, _) = server
+        .send(Method::DELETE, &format!("/logs/{log_id}"), None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn create_respects_slot_limit_and_validates_bodies() {
+    let server = TestServer::new(1).await;
+    server.create_agenda("Only slot").await;
+
+    let (status, body) = server
+        .send(
+            Method::POST,
+            "/agendas",
+            Some(json!({ "title": "One too many", "terminate_at": "2030-01-01T00:00:00Z" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["error"], "all 1 slots are occupied");
+
+    let (status, body) = server
+        .send(
+            Method::POST,
+            "/agendas",
+            Some(json!({ "title": "No deadline" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"].as_str().unwrap().contains("terminate_at"));
+}
+
+#[test]
+fn openapi_documents_every_route() {
+    let document: Value = serde_json::from_str(server::OPENAPI).expect("valid JSON");
+    let paths: Vec<_> = document["paths"]
+        .as_object()
+        .expect("paths")
+        .keys()
+        .map(String::as_str)
+        .collect();
+    for path in [
+        "/openapi.json",
+        "/agendas",
+        "/agendas/{id}",
+        "/agendas/{id}/transitions",
+        "/agendas/{id}/logs",
+        "/logs",
+        "/logs/{id}",
+    ] {
+        assert!(paths.contains(&path), "{path} is not documented");
+    }
+    assert_eq!(paths.len(), 7);
+}
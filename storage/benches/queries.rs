@@ -0,0 +1,119 @@
+//! Query latency against a large history: 100k agendas and 1M logs, seeded
+//! once into `target/tmp` and reused by later runs. Seeding takes minutes.
+//!
+//! Run with `cargo bench -p storage`.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use domain::{AgendaRepo, LogRepo};
+use jiff::{SignedDuration, Timestamp};
+use std::hint::black_box;
+use std::path::Path;
+use storage::SqlitePool;
+use tokio::runtime::Runtime;
+use uuid::Uuid;
+
+const AGENDAS: i64 = 100_000;
+const LOGS: i64 = 1_000_000;
+/// 2025-01-01T00:00:00Z; the seeded history spans about a year from here.
+const START_MS: i64 = 1_735_689_600_000;
+const DEADLINE_EVERY_MS: i64 = 5 * 60 * 1000;
+const LOG_EVERY_MS: i64 = 30 * 1000;
+
+/// Ids are made up from the row number so benchmarks can name rows.
+fn agenda_id(n: i64) -> Uuid {
+    Uuid::parse_str(&format!("00000000-0000-7000-8000-{n:012x}")).expect("agenda id")
+}
+
+async fn open(path: &Path) -> SqlitePool {
+    if path.exists() {
+        let pool = storage::init_db(path).await.expect("open bench db");
+        let (agendas, logs) = counts(&pool).await;
+        if (agendas, logs) == (AGENDAS, LOGS) {
+            return pool;
+        }
+        pool.close().await;
+        std::fs::remove_file(path).expect("remove stale bench db");
+    }
+    let pool = storage::init_db(path).await.expect("create bench db");
+    seed(&pool).await;
+    pool
+}
+
+async fn counts(pool: &SqlitePool) -> (i64, i64) {
+    let agendas = sqlx::query_scalar("SELECT COUNT(*) FROM agenda")
+        .fetch_one(pool)
+        .await
+        .expect("count agendas");
+    let logs = sqlx::query_scalar("SELECT COUNT(*) FROM log")
+        .fetch_one(pool)
+        .await
+        .expect("count logs");
+    (agendas, logs)
+}
+
+/// Inserts the history in bulk. Sync is paused so no change feed is
+/// recorded, and the webhook events the inserts queue are dropped.
+async fn seed(pool: &SqlitePool) {
+    let mut tx = pool.begin().await.expect("begin seeding");
+    for statement in [
+        "INSERT OR IGNORE INTO sync_applying (applying) VALUES (1)",
+        "WITH RECURSIVE n(i) AS (SELECT 0 UNION ALL SELECT i + 1 FROM n WHERE i + 1 < ?1)
+         INSERT INTO agenda (id, title, agenda_status, initiate_at, terminate_at)
+         SELECT printf('00000000-0000-7000-8000-%012x', i),
+                'Agenda ' || i,
+                CASE i % 50 WHEN 0 THEN 'pending' WHEN 1 THEN 'ongoing' ELSE 'terminated' END,
+                ?3 + i * ?4 - 86400000,
+                ?3 + i * ?4
+         FROM n",
+        "WITH RECURSIVE n(i) AS (SELECT 0 UNION ALL SELECT i + 1 FROM n WHERE i + 1 < ?2)
+         INSERT INTO log (id, create_at, content, log_type, agenda_id)
+         SELECT printf('00000000-0000-7000-9000-%012x', i),
+                ?3 + i * ?5,
+                'Worked on agenda ' || (i % ?1),
+                'common_log',
+                printf('00000000-0000-7000-8000-%012x', i % ?1)
+         FROM n",
+        "DELETE FROM webhook_outbox",
+        "DELETE FROM sync_applying",
+    ] {
+        sqlx::query(statement)
+            .bind(AGENDAS)
+            .bind(LOGS)
+            .bind(START_MS)
+            .bind(DEADLINE_EVERY_MS)
+            .bind(LOG_EVERY_MS)
+            .execute(&mut *tx)
+            .await
+            .expect("seed bench db");
+    }
+    tx.commit().await.expect("commit seeding");
+}
+
+fn queries(c: &mut Criterion) {
+    let runtime = Runtime::new().expect("tokio runtime");
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("finiate-bench.db");
+    let pool = runtime.block_on(open(&path));
+    let (agendas, logs) = storage::create_repos(&pool);
+
+    let middle = Timestamp::from_millisecond(START_MS + LOGS / 2 * LOG_EVERY_MS).unwrap();
+    let day = SignedDuration::from_hours(24);
+
+    c.bench_function("logs of one agenda", |b| {
+        let id = agenda_id(AGENDAS / 2);
+        b.iter(|| runtime.block_on(logs.get_logs_by_agenda_id(black_box(id))))
+    });
+    c.bench_function("logs of one day", |b| {
+        b.iter(|| runtime.block_on(logs.get_logs_by_time_range(middle, middle + day)))
+    });
+    c.bench_function("deadlines of one day", |b| {
+        b.iter(|| {
+            runtime.block_on(agendas.get_agendas_by_terminate_time_range(middle, middle + day))
+        })
+    });
+    c.bench_function("pending agendas", |b| {
+        b.iter(|| runtime.block_on(agendas.get_agendas_by_status(black_box(Some("pending")))))
+    });
+}
+
+criterion_group!(benches, queries);
+criterion_main!(benches);
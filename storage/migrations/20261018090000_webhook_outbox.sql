@@ -0,0 +1,59 @@
+-- Transactional outbox for webhooks: triggers record every lifecycle event
+-- in the same transaction as the change itself, with a snapshot of the
+-- agenda (and log) at that moment. Delivery bookkeeping lives on each row.
+
+CREATE TABLE IF NOT EXISTS webhook_outbox
+(
+    id              INTEGER PRIMARY KEY AUTOINCREMENT,
+    event           TEXT                NOT NULL,
+    occurred_at     INTEGER             NOT NULL,
+    agenda_id       TEXT                NOT NULL,
+    title           TEXT                NOT NULL,
+    agenda_status   TEXT                NOT NULL,
+    initiate_at     INTEGER             NOT NULL,
+    terminate_at    INTEGER             NOT NULL,
+    log_id          TEXT,
+    content         TEXT,
+    log_type        TEXT,
+    create_at       INTEGER,
+    attempts        INTEGER             NOT NULL DEFAULT 0,
+    next_attempt_at INTEGER             NOT NULL,
+    delivered_to    TEXT                NOT NULL DEFAULT '',
+    delivered_at    INTEGER,
+    last_error      TEXT
+);
+
+CREATE INDEX IF NOT EXISTS webhook_outbox_pending
+    ON webhook_outbox (next_attempt_at) WHERE delivered_at IS NULL;
+
+CREATE TRIGGER IF NOT EXISTS webhook_agenda_created
+AFTER INSERT ON agenda
+BEGIN
+    INSERT INTO webhook_outbox
+        (event, occurred_at, agenda_id, title, agenda_status, initiate_at, terminate_at,
+         next_attempt_at)
+    VALUES
+        ('agenda.created', NEW.initiate_at, NEW.id, NEW.title, NEW.agenda_status,
+         NEW.initiate_at, NEW.terminate_at, NEW.initiate_at);
+END;
+
+-- Every status transition writes a log, so logs carry the lifecycle events.
+-- Logs without an agenda produce no event.
+CREATE TRIGGER IF NOT EXISTS webhook_log_created
+AFTER INSERT ON log
+BEGIN
+    INSERT INTO webhook_outbox
+        (event, occurred_at, agenda_id, title, agenda_status, initiate_at, terminate_at,
+         log_id, content, log_type, create_at, next_attempt_at)
+    SELECT
+        CASE NEW.log_type
+            WHEN 'activate' THEN 'agenda.activated'
+            WHEN 'put_off' THEN 'agenda.put_off'
+            WHEN 'terminate' THEN 'agenda.terminated'
+            ELSE 'log.created'
+        END,
+        NEW.create_at, agenda.id, agenda.title, agenda.agenda_status, agenda.initiate_at,
+        agenda.terminate_at, NEW.id, NEW.content, NEW.log_type, NEW.create_at, NEW.create_at
+    FROM agenda
+    WHERE agenda.id = NEW.agenda_id;
+END;
@@ -0,0 +1,46 @@
+-- Append-only event stream for the event-sourced backend. Agenda state is
+-- the fold of an agenda's events; `marked` events are its logs.
+
+CREATE TABLE IF NOT EXISTS agenda_event
+(
+    seq             INTEGER PRIMARY KEY AUTOINCREMENT,
+    agenda_id       TEXT                NOT NULL,
+    -- created, retitled, deadline_changed, status_changed, marked,
+    -- log_deleted or deleted
+    kind            TEXT                NOT NULL,
+    at              INTEGER             NOT NULL,
+    title           TEXT,
+    agenda_status   TEXT,
+    terminate_at    INTEGER,
+    log_id          TEXT,
+    content         TEXT,
+    log_type        TEXT
+);
+
+CREATE INDEX IF NOT EXISTS agenda_event_agenda ON agenda_event (agenda_id, seq);
+CREATE INDEX IF NOT EXISTS agenda_event_log ON agenda_event (log_id) WHERE log_id IS NOT NULL;
+
+CREATE TRIGGER IF NOT EXISTS agenda_event_no_update
+BEFORE UPDATE ON agenda_event
+BEGIN
+    SELECT RAISE(ABORT, 'agenda_event is append-only');
+END;
+
+CREATE TRIGGER IF NOT EXISTS agenda_event_no_delete
+BEFORE DELETE ON agenda_event
+BEGIN
+    SELECT RAISE(ABORT, 'agenda_event is append-only');
+END;
+
+-- Each agenda's state as of event `seq`, so loading it only folds the
+-- events after that. Disposable: it can always be rebuilt from the stream.
+CREATE TABLE IF NOT EXISTS agenda_snapshot
+(
+    agenda_id       TEXT PRIMARY KEY,
+    seq             INTEGER             NOT NULL,
+    -- The state columns are NULL once the agenda is deleted.
+    title           TEXT,
+    agenda_status   TEXT,
+    initiate_at     INTEGER,
+    terminate_at    INTEGER
+);
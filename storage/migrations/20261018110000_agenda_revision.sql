@@ -0,0 +1,21 @@
+-- Audit trail of agenda updates: one row per changed field. Values are
+-- stored as text: the title, the status name, or the deadline in
+-- milliseconds.
+
+CREATE TABLE IF NOT EXISTS agenda_revision
+(
+    id              INTEGER PRIMARY KEY AUTOINCREMENT,
+    agenda_id       TEXT                NOT NULL,
+    revised_at      INTEGER             NOT NULL,
+    actor           TEXT,
+    -- title, status or deadline
+    field           TEXT                NOT NULL,
+    old_value       TEXT                NOT NULL,
+    new_value       TEXT                NOT NULL,
+    FOREIGN KEY (agenda_id) REFERENCES agenda (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS agenda_revision_agenda ON agenda_revision (agenda_id, id);
+
+-- The event-sourced backend keeps the actor on the events themselves.
+ALTER TABLE agenda_event ADD COLUMN actor TEXT;
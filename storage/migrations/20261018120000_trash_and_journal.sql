@@ -0,0 +1,120 @@
+-- Deleting agendas and logs moves them to the trash: `deleted_at` is set
+-- and every query skips the row. A trashed agenda hides its logs too.
+ALTER TABLE agenda ADD COLUMN deleted_at INTEGER;
+ALTER TABLE log ADD COLUMN deleted_at INTEGER;
+
+-- Operation journal for `finiate undo`. While an operation is open, the
+-- triggers below record, for every changed row, the statement that puts it
+-- back; undoing runs them in reverse.
+CREATE TABLE IF NOT EXISTS operation
+(
+    id              INTEGER PRIMARY KEY AUTOINCREMENT,
+    command         TEXT                NOT NULL,
+    started_at      INTEGER             NOT NULL,
+    open            INTEGER             NOT NULL DEFAULT 1,
+    undone_at       INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS operation_step
+(
+    seq             INTEGER PRIMARY KEY AUTOINCREMENT,
+    operation_id    INTEGER             NOT NULL,
+    statement       TEXT                NOT NULL,
+    FOREIGN KEY (operation_id) REFERENCES operation (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS operation_step_operation ON operation_step (operation_id, seq);
+
+CREATE TRIGGER IF NOT EXISTS journal_agenda_insert
+AFTER INSERT ON agenda
+WHEN EXISTS (SELECT 1 FROM operation WHERE open = 1)
+BEGIN
+    INSERT INTO operation_step (operation_id, statement)
+    SELECT MAX(id), 'DELETE FROM agenda WHERE id = ' || quote(NEW.id)
+    FROM operation WHERE open = 1;
+END;
+
+CREATE TRIGGER IF NOT EXISTS journal_agenda_update
+AFTER UPDATE ON agenda
+WHEN EXISTS (SELECT 1 FROM operation WHERE open = 1)
+BEGIN
+    INSERT INTO operation_step (operation_id, statement)
+    SELECT MAX(id), 'UPDATE agenda SET id = ' || quote(OLD.id)
+        || ', title = ' || quote(OLD.title)
+        || ', agenda_status = ' || quote(OLD.agenda_status)
+        || ', initiate_at = ' || quote(OLD.initiate_at)
+        || ', terminate_at = ' || quote(OLD.terminate_at)
+        || ', deleted_at = ' || quote(OLD.deleted_at)
+        || ' WHERE id = ' || quote(NEW.id)
+    FROM operation WHERE open = 1;
+END;
+
+CREATE TRIGGER IF NOT EXISTS journal_agenda_delete
+AFTER DELETE ON agenda
+WHEN EXISTS (SELECT 1 FROM operation WHERE open = 1)
+BEGIN
+    INSERT INTO operation_step (operation_id, statement)
+    SELECT MAX(id), 'INSERT INTO agenda (id, title, agenda_status, initiate_at, terminate_at, deleted_at) VALUES ('
+        || quote(OLD.id) || ', ' || quote(OLD.title) || ', ' || quote(OLD.agenda_status) || ', '
+        || quote(OLD.initiate_at) || ', ' || quote(OLD.terminate_at) || ', '
+        || quote(OLD.deleted_at) || ')'
+    FROM operation WHERE open = 1;
+END;
+
+CREATE TRIGGER IF NOT EXISTS journal_log_insert
+AFTER INSERT ON log
+WHEN EXISTS (SELECT 1 FROM operation WHERE open = 1)
+BEGIN
+    INSERT INTO operation_step (operation_id, statement)
+    SELECT MAX(id), 'DELETE FROM log WHERE id = ' || quote(NEW.id)
+    FROM operation WHERE open = 1;
+END;
+
+CREATE TRIGGER IF NOT EXISTS journal_log_update
+AFTER UPDATE ON log
+WHEN EXISTS (SELECT 1 FROM operation WHERE open = 1)
+BEGIN
+    INSERT INTO operation_step (operation_id, statement)
+    SELECT MAX(id), 'UPDATE log SET id = ' || quote(OLD.id)
+        || ', create_at = ' || quote(OLD.create_at)
+        || ', content = ' || quote(OLD.content)
+        || ', log_type = ' || quote(OLD.log_type)
+        || ', agenda_id = ' || quote(OLD.agenda_id)
+        || ', deleted_at = ' || quote(OLD.deleted_at)
+        || ' WHERE id = ' || quote(NEW.id)
+    FROM operation WHERE open = 1;
+END;
+
+CREATE TRIGGER IF NOT EXISTS journal_log_delete
+AFTER DELETE ON log
+WHEN EXISTS (SELECT 1 FROM operation WHERE open = 1)
+BEGIN
+    INSERT INTO operation_step (operation_id, statement)
+    SELECT MAX(id), 'INSERT INTO log (id, create_at, content, log_type, agenda_id, deleted_at) VALUES ('
+        || quote(OLD.id) || ', ' || quote(OLD.create_at) || ', ' || quote(OLD.content) || ', '
+        || quote(OLD.log_type) || ', ' || quote(OLD.agenda_id) || ', '
+        || quote(OLD.deleted_at) || ')'
+    FROM operation WHERE open = 1;
+END;
+
+-- Undoing an update also drops the revisions it recorded.
+CREATE TRIGGER IF NOT EXISTS journal_revision_insert
+AFTER INSERT ON agenda_revision
+WHEN EXISTS (SELECT 1 FROM operation WHERE open = 1)
+BEGIN
+    INSERT INTO operation_step (operation_id, statement)
+    SELECT MAX(id), 'DELETE FROM agenda_revision WHERE id = ' || quote(NEW.id)
+    FROM operation WHERE open = 1;
+END;
+
+CREATE TRIGGER IF NOT EXISTS journal_revision_delete
+AFTER DELETE ON agenda_revision
+WHEN EXISTS (SELECT 1 FROM operation WHERE open = 1)
+BEGIN
+    INSERT INTO operation_step (operation_id, statement)
+    SELECT MAX(id), 'INSERT INTO agenda_revision (id, agenda_id, revised_at, actor, field, old_value, new_value) VALUES ('
+        || quote(OLD.id) || ', ' || quote(OLD.agenda_id) || ', ' || quote(OLD.revised_at) || ', '
+        || quote(OLD.actor) || ', ' || quote(OLD.field) || ', ' || quote(OLD.old_value) || ', '
+        || quote(OLD.new_value) || ')'
+    FROM operation WHERE open = 1;
+END;
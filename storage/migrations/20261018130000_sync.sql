@@ -0,0 +1,155 @@
+-- Multi-device sync. Every local change to an agenda or log field is
+-- captured in `sync_change` until `finiate sync` exports it, and
+-- `sync_register` keeps, per field, the value that wins: the change with the
+-- highest (at, device, seq). A NULL device is this device.
+
+CREATE TABLE IF NOT EXISTS sync_change
+(
+    seq             INTEGER PRIMARY KEY AUTOINCREMENT,
+    at              INTEGER             NOT NULL
+        DEFAULT (CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER)),
+    -- agenda or log
+    entity          TEXT                NOT NULL,
+    entity_id       TEXT                NOT NULL,
+    field           TEXT                NOT NULL,
+    -- Untyped, so integers, text and NULL keep their type.
+    value
+);
+
+CREATE TABLE IF NOT EXISTS sync_register
+(
+    entity          TEXT                NOT NULL,
+    entity_id       TEXT                NOT NULL,
+    field           TEXT                NOT NULL,
+    value,
+    at              INTEGER             NOT NULL,
+    device          TEXT,
+    seq             INTEGER             NOT NULL,
+    PRIMARY KEY (entity, entity_id, field)
+);
+
+-- How far each other device's change file has been merged, in bytes.
+CREATE TABLE IF NOT EXISTS sync_peer
+(
+    device          TEXT PRIMARY KEY,
+    offset          INTEGER             NOT NULL
+);
+
+-- Holds a row while remote changes are applied, so they are not captured
+-- as local ones.
+CREATE TABLE IF NOT EXISTS sync_applying
+(
+    applying        INTEGER PRIMARY KEY
+);
+
+-- A local change never loses to what this device has already seen, even
+-- with a clock behind the other device's.
+CREATE TRIGGER IF NOT EXISTS sync_change_register
+AFTER INSERT ON sync_change
+BEGIN
+    UPDATE sync_change SET at = MAX(at, (
+        SELECT r.at + 1 FROM sync_register r
+        WHERE r.entity = NEW.entity AND r.entity_id = NEW.entity_id AND r.field = NEW.field
+    )) WHERE seq = NEW.seq AND EXISTS (
+        SELECT 1 FROM sync_register r
+        WHERE r.entity = NEW.entity AND r.entity_id = NEW.entity_id AND r.field = NEW.field
+    );
+    INSERT INTO sync_register (entity, entity_id, field, value, at, device, seq)
+    SELECT NEW.entity, NEW.entity_id, NEW.field, NEW.value, at, NULL, NEW.seq
+    FROM sync_change WHERE seq = NEW.seq
+    ON CONFLICT (entity, entity_id, field) DO UPDATE SET
+        value = excluded.value, at = excluded.at, device = NULL, seq = excluded.seq;
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_agenda_insert
+AFTER INSERT ON agenda
+WHEN NOT EXISTS (SELECT 1 FROM sync_applying)
+BEGIN
+    INSERT INTO sync_change (entity, entity_id, field, value) VALUES
+        ('agenda', NEW.id, 'title', NEW.title),
+        ('agenda', NEW.id, 'agenda_status', NEW.agenda_status),
+        ('agenda', NEW.id, 'initiate_at', NEW.initiate_at),
+        ('agenda', NEW.id, 'terminate_at', NEW.terminate_at),
+        ('agenda', NEW.id, 'deleted_at', NEW.deleted_at);
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_agenda_update
+AFTER UPDATE ON agenda
+WHEN NOT EXISTS (SELECT 1 FROM sync_applying)
+BEGIN
+    INSERT INTO sync_change (entity, entity_id, field, value)
+    SELECT 'agenda', NEW.id, field, value FROM (
+        SELECT 'title' AS field, NEW.title AS value WHERE OLD.title IS NOT NEW.title
+        UNION ALL SELECT 'agenda_status', NEW.agenda_status
+            WHERE OLD.agenda_status IS NOT NEW.agenda_status
+        UNION ALL SELECT 'initiate_at', NEW.initiate_at
+            WHERE OLD.initiate_at IS NOT NEW.initiate_at
+        UNION ALL SELECT 'terminate_at', NEW.terminate_at
+            WHERE OLD.terminate_at IS NOT NEW.terminate_at
+        UNION ALL SELECT 'deleted_at', NEW.deleted_at
+            WHERE OLD.deleted_at IS NOT NEW.deleted_at
+    );
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_log_insert
+AFTER INSERT ON log
+WHEN NOT EXISTS (SELECT 1 FROM sync_applying)
+BEGIN
+    INSERT INTO sync_change (entity, entity_id, field, value) VALUES
+        ('log', NEW.id, 'create_at', NEW.create_at),
+        ('log', NEW.id, 'content', NEW.content),
+        ('log', NEW.id, 'log_type', NEW.log_type),
+        ('log', NEW.id, 'agenda_id', NEW.agenda_id),
+        ('log', NEW.id, 'deleted_at', NEW.deleted_at);
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_log_update
+AFTER UPDATE ON log
+WHEN NOT EXISTS (SELECT 1 FROM sync_applying)
+BEGIN
+    INSERT INTO sync_change (entity, entity_id, field, value)
+    SELECT 'log', NEW.id, field, value FROM (
+        SELECT 'create_at' AS field, NEW.create_at AS value
+            WHERE OLD.create_at IS NOT NEW.create_at
+        UNION ALL SELECT 'content', NEW.content WHERE OLD.content IS NOT NEW.content
+        UNION ALL SELECT 'log_type', NEW.log_type WHERE OLD.log_type IS NOT NEW.log_type
+        UNION ALL SELECT 'agenda_id', NEW.agenda_id WHERE OLD.agenda_id IS NOT NEW.agenda_id
+        UNION ALL SELECT 'deleted_at', NEW.deleted_at WHERE OLD.deleted_at IS NOT NEW.deleted_at
+    );
+END;
+
+-- Rows deleted for good here (by a purge or an undo) go to the trash on
+-- the other devices.
+CREATE TRIGGER IF NOT EXISTS sync_agenda_delete
+AFTER DELETE ON agenda
+WHEN NOT EXISTS (SELECT 1 FROM sync_applying)
+BEGIN
+    INSERT INTO sync_change (entity, entity_id, field, value)
+    SELECT 'agenda', OLD.id, 'deleted_at',
+        COALESCE(OLD.deleted_at, CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_log_delete
+AFTER DELETE ON log
+WHEN NOT EXISTS (SELECT 1 FROM sync_applying)
+BEGIN
+    INSERT INTO sync_change (entity, entity_id, field, value)
+    SELECT 'log', OLD.id, 'deleted_at',
+        COALESCE(OLD.deleted_at, CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER));
+END;
+
+-- Rows that existed before sync was set up enter the change log as if
+-- they had just been created.
+INSERT INTO sync_change (entity, entity_id, field, value)
+SELECT 'agenda', id, 'title', title FROM agenda
+UNION ALL SELECT 'agenda', id, 'agenda_status', agenda_status FROM agenda
+UNION ALL SELECT 'agenda', id, 'initiate_at', initiate_at FROM agenda
+UNION ALL SELECT 'agenda', id, 'terminate_at', terminate_at FROM agenda
+UNION ALL SELECT 'agenda', id, 'deleted_at', deleted_at FROM agenda;
+
+INSERT INTO sync_change (entity, entity_id, field, value)
+SELECT 'log', id, 'create_at', create_at FROM log
+UNION ALL SELECT 'log', id, 'content', content FROM log
+UNION ALL SELECT 'log', id, 'log_type', log_type FROM log
+UNION ALL SELECT 'log', id, 'agenda_id', agenda_id FROM log
+UNION ALL SELECT 'log', id, 'deleted_at', deleted_at FROM log;
@@ -0,0 +1,7 @@
+-- Indexes for the queries that grow with the history: a slot's logs, logs
+-- and deadlines within a period, and agendas by status. Without them each
+-- of these scans the whole table.
+CREATE INDEX IF NOT EXISTS log_agenda_create_at ON log (agenda_id, create_at);
+CREATE INDEX IF NOT EXISTS log_create_at ON log (create_at);
+CREATE INDEX IF NOT EXISTS agenda_status ON agenda (agenda_status);
+CREATE INDEX IF NOT EXISTS agenda_terminate_at ON agenda (terminate_at);
@@ -0,0 +1,55 @@
+-- Events are only queued while some endpoint is registered, and only reach
+-- endpoints registered before they were queued. The CLI keeps
+-- `webhook_endpoint` in step with the configured webhooks.
+
+CREATE TABLE IF NOT EXISTS webhook_endpoint
+(
+    url             TEXT PRIMARY KEY    NOT NULL,
+    added_at        INTEGER             NOT NULL
+);
+
+-- Holds a row while an import or restore writes existing records, which
+-- are history rather than news. Set and cleared inside one transaction.
+CREATE TABLE IF NOT EXISTS webhook_muted
+(
+    muted           INTEGER PRIMARY KEY
+);
+
+ALTER TABLE webhook_outbox ADD COLUMN queued_at INTEGER;
+
+DROP TRIGGER IF EXISTS webhook_agenda_created;
+DROP TRIGGER IF EXISTS webhook_log_created;
+
+CREATE TRIGGER IF NOT EXISTS webhook_agenda_created
+AFTER INSERT ON agenda
+WHEN EXISTS (SELECT 1 FROM webhook_endpoint) AND NOT EXISTS (SELECT 1 FROM webhook_muted)
+BEGIN
+    INSERT INTO webhook_outbox
+        (event, occurred_at, agenda_id, title, agenda_status, initiate_at, terminate_at,
+         next_attempt_at, queued_at)
+    VALUES
+        ('agenda.created', NEW.initiate_at, NEW.id, NEW.title, NEW.agenda_status,
+         NEW.initiate_at, NEW.terminate_at, NEW.initiate_at,
+         CAST(ROUND((julianday('now') - 2440587.5) * 86400000) AS INTEGER));
+END;
+
+CREATE TRIGGER IF NOT EXISTS webhook_log_created
+AFTER INSERT ON log
+WHEN EXISTS (SELECT 1 FROM webhook_endpoint) AND NOT EXISTS (SELECT 1 FROM webhook_muted)
+BEGIN
+    INSERT INTO webhook_outbox
+        (event, occurred_at, agenda_id, title, agenda_status, initiate_at, terminate_at,
+         log_id, content, log_type, create_at, next_attempt_at, queued_at)
+    SELECT
+        CASE NEW.log_type
+            WHEN 'activate' THEN 'agenda.activated'
+            WHEN 'put_off' THEN 'agenda.put_off'
+            WHEN 'terminate' THEN 'agenda.terminated'
+            ELSE 'log.created'
+        END,
+        NEW.create_at, agenda.id, agenda.title, agenda.agenda_status, agenda.initiate_at,
+        agenda.terminate_at, NEW.id, NEW.content, NEW.log_type, NEW.create_at, NEW.create_at,
+        CAST(ROUND((julianday('now') - 2440587.5) * 86400000) AS INTEGER)
+    FROM agenda
+    WHERE agenda.id = NEW.agenda_id;
+END;
@@ -0,0 +1,14 @@
+-- The journal only records changes made by the process whose command is
+-- running: each pool installs TEMP triggers on its own connections (see
+-- `repo::journal`) and tags its operations with an owner, so writes from
+-- other processes and their open operations are left alone.
+ALTER TABLE operation ADD COLUMN owner TEXT;
+
+DROP TRIGGER IF EXISTS journal_agenda_insert;
+DROP TRIGGER IF EXISTS journal_agenda_update;
+DROP TRIGGER IF EXISTS journal_agenda_delete;
+DROP TRIGGER IF EXISTS journal_log_insert;
+DROP TRIGGER IF EXISTS journal_log_update;
+DROP TRIGGER IF EXISTS journal_log_delete;
+DROP TRIGGER IF EXISTS journal_revision_insert;
+DROP TRIGGER IF EXISTS journal_revision_delete;
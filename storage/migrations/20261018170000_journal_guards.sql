@@ -0,0 +1,4 @@
+-- Each journal step keeps a query telling whether the row is still as the
+-- step left it, so undo refuses to write back over later changes it did not
+-- journal (see `repo::journal`). Steps journaled before have none.
+ALTER TABLE operation_step ADD COLUMN guard TEXT;
@@ -1,42 +1,610 @@
-use super::repo::{agenda_repo::SqliteAgendaRepo, log_repo::SqliteLogRepo};
-use sqlx::{SqlitePool, migrate::MigrateDatabase, sqlite};
-const DB_URL: &str = "sqlite://finiate.db";
+use super::repo::journal::{self, SqliteJournal};
+use super::repo::{
+    agenda_repo::SqliteAgendaRepo, log_repo::SqliteLogRepo, outbox::SqliteOutbox, sync::SqliteSync,
+    trash::SqliteTrash,
+};
+use libsqlite3_sys as ffi;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection, SqlitePoolOptions};
+pub use sqlx::sqlite::{SqliteJournalMode, SqliteSynchronous};
+use sqlx::{ConnectOptions, Connection, Row, SqlitePool};
+use std::ffi::CStr;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use uuid::Uuid;
 
-pub async fn init_db() -> Result<SqlitePool, sqlx::Error> {
-    if !sqlite::Sqlite::database_exists(DB_URL)
-        .await
-        .unwrap_or(false)
-    {
-        sqlite::Sqlite::create_database(DB_URL).await?;
-        println!("Database created.");
-    } else {
-        println!("Database already exists.")
+/// SQLite files start with this header; SQLCipher encrypts it away.
+const PLAINTEXT_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// How to open the database. Foreign keys are always enforced.
+#[derive(Debug, Clone)]
+pub struct DbOptions {
+    /// The SQLCipher passphrase of an encrypted database.
+    pub passphrase: Option<String>,
+    /// WAL by default, so readers and a writer in other processes, like a
+    /// running daemon, do not block each other.
+    pub journal_mode: SqliteJournalMode,
+    /// `NORMAL` by default, which in WAL mode can only lose the last
+    /// transactions on power loss, never corrupt the file.
+    pub synchronous: SqliteSynchronous,
+    /// How long to wait for another connection's lock before giving up with
+    /// "database is locked".
+    pub busy_timeout: Duration,
+}
+
+impl Default for DbOptions {
+    fn default() -> Self {
+        DbOptions {
+            passphrase: None,
+            journal_mode: SqliteJournalMode::Wal,
+            synchronous: SqliteSynchronous::Normal,
+            busy_timeout: Duration::from_secs(5),
+        }
     }
+}
 
-    let pool = SqlitePool::connect(DB_URL).await?;
+pub async fn init_db(db_path: &Path) -> Result<SqlitePool, sqlx::Error> {
+    init_db_with(db_path, &DbOptions::default()).await
+}
+
+pub async fn init_db_with(db_path: &Path, options: &DbOptions) -> Result<SqlitePool, sqlx::Error> {
+    let created = !db_path.exists();
+    if created && let Some(parent) = db_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut conn = connect_options(db_path, options)
+        .create_if_missing(true)
+        .connect()
+        .await?;
+    check_key(&mut conn).await?;
+    if created {
+        eprintln!("Database created at {}.", db_path.display());
+    }
 
     // use env! to get the stable storage crate directory path
     let crate_dir = env!("CARGO_MANIFEST_DIR");
-    println!("crate_dir: {}", crate_dir);
     let migrations = std::path::Path::new(&crate_dir).join("./migrations");
     let migration_results = sqlx::migrate::Migrator::new(migrations)
         .await
         .unwrap()
-        .run(&pool)
+        .run(&mut conn)
         .await;
-    match migration_results {
-        Ok(_) => println!("Migration success"),
-        Err(error) => {
-            panic!("error: {}", error);
-        }
+    if let Err(error) = migration_results {
+        panic!("error: {}", error);
     }
-    println!("migration: {:?}", migration_results);
     // migration code end
+    conn.close().await?;
+
+    // Every connection journals its own writes under this pool's operations.
+    let owner = Uuid::now_v7();
+    let pool = SqlitePoolOptions::new()
+        .after_connect(move |conn, _| Box::pin(journal::attach(conn, owner)))
+        .connect_with(connect_options(db_path, options))
+        .await?;
     Ok(pool)
 }
 
+/// Opens an existing database for reading only, without creating or
+/// migrating anything.
+pub async fn open_read_only(
+    db_path: &Path,
+    options: &DbOptions,
+) -> Result<SqlitePool, sqlx::Error> {
+    let connect = SqliteConnectOptions::new()
+        .filename(db_path)
+        .read_only(true)
+        .busy_timeout(options.busy_timeout);
+    let connect = match &options.passphrase {
+        Some(passphrase) => connect.pragma("key", quote(passphrase)),
+        None => connect,
+    };
+    let pool = SqlitePool::connect_with(connect).await?;
+    check_key(&pool).await?;
+    Ok(pool)
+}
+
+fn connect_options(db_path: &Path, options: &DbOptions) -> SqliteConnectOptions {
+    // sqlx sends the key before any other pragma, as SQLCipher requires.
+    let connect = SqliteConnectOptions::new()
+        .filename(db_path)
+        .foreign_keys(true)
+        .journal_mode(options.journal_mode)
+        .synchronous(options.synchronous)
+        .busy_timeout(options.busy_timeout);
+    match &options.passphrase {
+        Some(passphrase) => connect.pragma("key", quote(passphrase)),
+        None => connect,
+    }
+}
+
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+/// SQLCipher accepts any key; reading the schema tells whether it was right.
+async fn check_key<'c, E>(executor: E) -> Result<(), sqlx::Error>
+where
+    E: sqlx::Executor<'c, Database = sqlx::Sqlite>,
+{
+    match sqlx::query("SELECT COUNT(*) FROM sqlite_master")
+        .execute(executor)
+        .await
+    {
+        Err(sqlx::Error::Database(error)) if error.code().as_deref() == Some("26") => {
+            Err(sqlx::Error::Configuration(
+                "not a database, or encrypted with another passphrase".into(),
+            ))
+        }
+        result => result.map(|_| ()),
+    }
+}
+
+/// Whether the file at `db_path` is an encrypted database; a missing or
+/// empty file is not.
+pub fn is_encrypted(db_path: &Path) -> std::io::Result<bool> {
+    let mut header = [0; 16];
+    match std::fs::File::open(db_path).and_then(|mut file| file.read_exact(&mut header)) {
+        Ok(()) => Ok(&header != PLAINTEXT_HEADER),
+        Err(error)
+            if matches!(
+                error.kind(),
+                std::io::ErrorKind::NotFound | std::io::ErrorKind::UnexpectedEof
+            ) =>
+        {
+            Ok(false)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// Re-encrypts the database at `db_path`, which must not be open, from the
+/// `current` passphrase to the `new` one; `None` stands for no encryption.
+/// The data is exported into a fresh file that then replaces the old one.
+pub async fn rekey(
+    db_path: &Path,
+    current: Option<&str>,
+    new: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    let target = sibling(db_path, "rekey")?;
+    if !db_path.exists() {
+        return Err(std::io::Error::from(std::io::ErrorKind::NotFound).into());
+    }
+    // ATTACH opens the new file with the connection's flags.
+    let current = DbOptions {
+        passphrase: current.map(str::to_string),
+        ..DbOptions::default()
+    };
+    let mut conn = connect_options(db_path, &current)
+        .create_if_missing(true)
+        .connect()
+        .await?;
+    check_key(&mut conn).await?;
+    sqlx::query("ATTACH DATABASE ? AS rekeyed KEY ?")
+        .bind(target.to_string_lossy().into_owned())
+        .bind(new.unwrap_or_default())
+        .execute(&mut conn)
+        .await?;
+    sqlx::query("SELECT sqlcipher_export('rekeyed')")
+        .execute(&mut conn)
+        .await?;
+    sqlx::query("DETACH DATABASE rekeyed")
+        .execute(&mut conn)
+        .await?;
+    conn.close().await?;
+    std::fs::rename(&target, db_path)?;
+    Ok(())
+}
+
+/// Moves everything in the write-ahead log of the database at `db_path`,
+/// which must not be open, into the file itself, so the file alone holds
+/// the whole database.
+pub async fn checkpoint(db_path: &Path, options: &DbOptions) -> Result<(), sqlx::Error> {
+    let mut conn = connect_options(db_path, options).connect().await?;
+    check_key(&mut conn).await?;
+    sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+        .execute(&mut conn)
+        .await?;
+    conn.close().await?;
+    Ok(())
+}
+
+/// Copies the open database into `target` with SQLite's online backup API,
+/// so writers are not shut out while it runs. The copy is encrypted like
+/// the database, which `options` must describe.
+pub async fn backup(
+    pool: &SqlitePool,
+    target: &Path,
+    options: &DbOptions,
+) -> Result<(), sqlx::Error> {
+    let partial = sibling(target, "partial")?;
+    let mut source = pool.acquire().await?;
+    let mut copy = connect_options(&partial, options)
+        .create_if_missing(true)
+        .connect()
+        .await?;
+    copy_pages(&mut source, &mut copy).await?;
+    copy.close().await?;
+    // Only a complete copy ever carries the backup's name.
+    std::fs::rename(&partial, target)?;
+    Ok(())
+}
+
+/// Runs SQLite's integrity check and foreign key check, returning one line
+/// per problem found; a sound database yields none.
+pub async fn verify(pool: &SqlitePool) -> Result<Vec<String>, sqlx::Error> {
+    problems(&mut *pool.acquire().await?).await
+}
+
+/// Replaces the database at `db_path`, which must not be open, with the
+/// backup at `backup_path`, once the backup has passed [`verify`]'s checks.
+/// Both files use the passphrase in `options`.
+pub async fn restore(
+    db_path: &Path,
+    backup_path: &Path,
+    options: &DbOptions,
+) -> Result<(), sqlx::Error> {
+    let mut source = connect_options(backup_path, options).connect().await?;
+    check_key(&mut source).await?;
+    let problems = problems(&mut source).await?;
+    if !problems.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "{} is damaged: {}",
+                backup_path.display(),
+                problems.join("; ")
+            ),
+        )
+        .into());
+    }
+
+    if let Some(parent) = db_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let target = sibling(db_path, "restore")?;
+    let mut copy = connect_options(&target, options)
+        .create_if_missing(true)
+        .connect()
+        .await?;
+    copy_pages(&mut source, &mut copy).await?;
+    copy.close().await?;
+    source.close().await?;
+    // A journal left behind by the old file must not be replayed into the new one.
+    for suffix in ["-wal", "-shm"] {
+        let mut journal = db_path.as_os_str().to_owned();
+        journal.push(suffix);
+        match std::fs::remove_file(PathBuf::from(journal)) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => return Err(error.into()),
+            _ => {}
+        }
+    }
+    std::fs::rename(&target, db_path)?;
+    Ok(())
+}
+
+async fn problems(conn: &mut SqliteConnection) -> Result<Vec<String>, sqlx::Error> {
+    let mut problems: Vec<String> = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .filter(|line: &String| line != "ok")
+        .collect();
+    for row in sqlx::query("PRAGMA foreign_key_check")
+        .fetch_all(&mut *conn)
+        .await?
+    {
+        let table: String = row.try_get("table")?;
+        let rowid: Option<i64> = row.try_get("rowid")?;
+        let parent: String = row.try_get("parent")?;
+        problems.push(match rowid {
+            Some(rowid) => format!("{table} row {rowid} refers to a missing {parent} row"),
+            None => format!("a {table} row refers to a missing {parent} row"),
+        });
+    }
+    Ok(problems)
+}
+
+/// How often, and how far apart, a backup step that found a database busy
+/// or locked is retried: about as long as the busy timeout.
+const BACKUP_RETRIES: u32 = 50;
+const BACKUP_RETRY_WAIT: Duration = Duration::from_millis(100);
+
+/// Copies every page of `source`'s main database into `target`'s in one
+/// step. SQLCipher only copies between databases keyed alike.
+async fn copy_pages(
+    source: &mut SqliteConnection,
+    target: &mut SqliteConnection,
+) -> Result<(), sqlx::Error> {
+    let mut source = source.lock_handle().await?;
+    let mut target = target.lock_handle().await?;
+    let failed = |target: *mut ffi::sqlite3| {
+        // SAFETY: the target connection is open while locked.
+        let message = unsafe { CStr::from_ptr(ffi::sqlite3_errmsg(target)) };
+        std::io::Error::other(format!("backup failed: {}", message.to_string_lossy())).into()
+    };
+    // SAFETY: both connections stay locked, and so open and unused
+    // elsewhere, until the backup is finished.
+    let backup = Backup(unsafe {
+        ffi::sqlite3_backup_init(
+            target.as_raw_handle().as_ptr(),
+            c"main".as_ptr(),
+            source.as_raw_handle().as_ptr(),
+            c"main".as_ptr(),
+        )
+    });
+    if backup.0.is_null() {
+        return Err(failed(target.as_raw_handle().as_ptr()));
+    }
+    let mut step = backup.step();
+    for _ in 0..BACKUP_RETRIES {
+        if !matches!(step, ffi::SQLITE_BUSY | ffi::SQLITE_LOCKED) {
+            break;
+        }
+        tokio::time::sleep(BACKUP_RETRY_WAIT).await;
+        step = backup.step();
+    }
+    let finish = backup.finish();
+    match step {
+        ffi::SQLITE_DONE if finish == ffi::SQLITE_OK => Ok(()),
+        ffi::SQLITE_DONE => Err(failed(target.as_raw_handle().as_ptr())),
+        // Name the step that failed; finishing only repeats its code.
+        code => {
+            // SAFETY: SQLite's error strings are static.
+            let message = unsafe { CStr::from_ptr(ffi::sqlite3_errstr(code)) };
+            Err(
+                std::io::Error::other(format!("backup failed: {}", message.to_string_lossy()))
+                    .into(),
+            )
+        }
+    }
+}
+
+/// An online backup in progress, finished when dropped, so a cancelled
+/// [`copy_pages`] does not leave it on the connections.
+struct Backup(*mut ffi::sqlite3_backup);
+
+// SAFETY: the backup is only used by the `copy_pages` call that holds both
+// of its connections locked, whichever thread that call moves to.
+unsafe impl Send for Backup {}
+
+impl Backup {
+    fn step(&self) -> std::os::raw::c_int {
+        // SAFETY: the backup is unfinished until `finish` or `drop`.
+        unsafe { ffi::sqlite3_backup_step(self.0, -1) }
+    }
+
+    fn finish(mut self) -> std::os::raw::c_int {
+        // SAFETY: as in `step`; the pointer is cleared so `drop` skips it.
+        unsafe { ffi::sqlite3_backup_finish(std::mem::replace(&mut self.0, std::ptr::null_mut())) }
+    }
+}
+
+impl Drop for Backup {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            // SAFETY: as in `step`.
+            unsafe { ffi::sqlite3_backup_finish(self.0) };
+        }
+    }
+}
+
+/// `<path>.<suffix>`, cleared of any leftover from an interrupted run.
+fn sibling(path: &Path, suffix: &str) -> std::io::Result<PathBuf> {
+    let mut sibling = path.as_os_str().to_owned();
+    sibling.push(format!(".{suffix}"));
+    let sibling = PathBuf::from(sibling);
+    if sibling.exists() {
+        std::fs::remove_file(&sibling)?;
+    }
+    Ok(sibling)
+}
+
 pub fn create_repos(pool: &SqlitePool) -> (SqliteAgendaRepo, SqliteLogRepo) {
     let agenda_repo = SqliteAgendaRepo { pool: pool.clone() };
     let log_repo = SqliteLogRepo { pool: pool.clone() };
     (agenda_repo, log_repo)
 }
+
+pub fn create_outbox(pool: &SqlitePool) -> SqliteOutbox {
+    SqliteOutbox { pool: pool.clone() }
+}
+
+pub fn create_trash(pool: &SqlitePool) -> SqliteTrash {
+    SqliteTrash { pool: pool.clone() }
+}
+
+pub fn create_journal(pool: &SqlitePool) -> SqliteJournal {
+    SqliteJournal { pool: pool.clone() }
+}
+
+pub fn create_sync(pool: &SqlitePool) -> SqliteSync {
+    SqliteSync { pool: pool.clone() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domain::*;
+    use jiff::Timestamp;
+
+    const SECRET: &str = "Client Acme Corporation";
+
+    async fn write_secret(pool: &SqlitePool) {
+        let (agendas, logs) = create_repos(pool);
+        let agenda_id = agendas
+            .create_agenda(&AgendaCreate {
+                title: SECRET.to_string(),
+                agenda_status: AgendaStatus::Pending,
+                terminate_at: Timestamp::now(),
+            })
+            .await
+            .expect("create agenda");
+        logs.create_log(&LogCreate {
+            agenda_id,
+            content: format!("call {SECRET}"),
+            log_type: LogType::CommonLog,
+        })
+        .await
+        .expect("create log");
+    }
+
+    /// Looks through the file and its write-ahead log, which a closed pool
+    /// may leave behind.
+    fn contains(path: &Path, needle: &str) -> bool {
+        let mut bytes = std::fs::read(path).expect("read database file");
+        if let Ok(wal) = std::fs::read(path.with_extension("db-wal")) {
+            bytes.extend(wal);
+        }
+        bytes
+            .windows(needle.len())
+            .any(|window| window == needle.as_bytes())
+    }
+
+    fn keyed(passphrase: &str) -> DbOptions {
+        DbOptions {
+            passphrase: Some(passphrase.to_string()),
+            ..DbOptions::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn encrypted_file_holds_no_plaintext() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("finiate.db");
+        let pool = init_db_with(&path, &keyed("it's secret")).await.unwrap();
+        write_secret(&pool).await;
+        pool.close().await;
+
+        assert!(is_encrypted(&path).unwrap());
+        assert!(!contains(&path, SECRET));
+        assert!(!contains(&path, "SQLite format 3"));
+
+        assert!(init_db_with(&path, &keyed("guess")).await.is_err());
+        assert!(init_db(&path).await.is_err());
+        let pool = init_db_with(&path, &keyed("it's secret")).await.unwrap();
+        let (agendas, _) = create_repos(&pool);
+        assert_eq!(agendas.get_agendas_by_title(SECRET).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn rekey_encrypts_changes_and_removes_the_passphrase() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("finiate.db");
+        let pool = init_db(&path).await.unwrap();
+        write_secret(&pool).await;
+        pool.close().await;
+        checkpoint(&path, &DbOptions::default()).await.unwrap();
+        let wal = std::fs::metadata(path.with_extension("db-wal")).map_or(0, |meta| meta.len());
+        assert_eq!(wal, 0);
+        assert!(!is_encrypted(&path).unwrap());
+        assert!(contains(&path, SECRET));
+
+        rekey(&path, None, Some("first")).await.unwrap();
+        assert!(is_encrypted(&path).unwrap());
+        assert!(!contains(&path, SECRET));
+        rekey(&path, Some("first"), Some("second")).await.unwrap();
+        assert!(rekey(&path, Some("first"), None).await.is_err());
+
+        let pool = init_db_with(&path, &keyed("second")).await.unwrap();
+        assert_eq!(
+            create_repos(&pool)
+                .0
+                .get_agendas_by_title(SECRET)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        pool.close().await;
+
+        rekey(&path, Some("second"), None).await.unwrap();
+        assert!(!is_encrypted(&path).unwrap());
+        assert!(init_db(&path).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn restore_brings_back_the_backed_up_state() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("finiate.db");
+        let copy = dir.path().join("backups").join("copy.db");
+        std::fs::create_dir_all(copy.parent().unwrap()).unwrap();
+        let options = keyed("it's secret");
+        let pool = init_db_with(&path, &options).await.unwrap();
+        write_secret(&pool).await;
+        backup(&pool, &copy, &options).await.unwrap();
+        write_secret(&pool).await;
+        assert!(verify(&pool).await.unwrap().is_empty());
+        pool.close().await;
+        assert!(is_encrypted(&copy).unwrap());
+        assert!(!contains(&copy, SECRET));
+
+        assert!(restore(&path, &copy, &keyed("guess")).await.is_err());
+        restore(&path, &copy, &options).await.unwrap();
+        let pool = init_db_with(&path, &options).await.unwrap();
+        let (agendas, _) = create_repos(&pool);
+        assert_eq!(agendas.get_agendas_by_title(SECRET).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn verify_reports_dangling_logs_and_restore_refuses_them() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("finiate.db");
+        let pool = init_db(&path).await.unwrap();
+        write_secret(&pool).await;
+        let mut conn = pool.acquire().await.unwrap();
+        sqlx::query("PRAGMA foreign_keys = OFF")
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE log SET agenda_id = 'gone'")
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        drop(conn);
+        let problems = verify(&pool).await.unwrap();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("log row "), "{problems:?}");
+
+        let copy = dir.path().join("copy.db");
+        backup(&pool, &copy, &DbOptions::default()).await.unwrap();
+        pool.close().await;
+        let error = restore(&dir.path().join("other.db"), &copy, &DbOptions::default())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("is damaged"), "{error}");
+        assert!(!dir.path().join("other.db").exists());
+    }
+
+    #[tokio::test]
+    async fn connections_use_wal_and_enforce_foreign_keys() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("finiate.db");
+        let pool = init_db(&path).await.unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+        let journal_mode: String = sqlx::query_scalar("PRAGMA journal_mode")
+            .fetch_one(&mut *conn)
+            .await
+            .unwrap();
+        assert_eq!(journal_mode, "wal");
+        let foreign_keys: i64 = sqlx::query_scalar("PRAGMA foreign_keys")
+            .fetch_one(&mut *conn)
+            .await
+            .unwrap();
+        assert_eq!(foreign_keys, 1);
+        let busy_timeout: i64 = sqlx::query_scalar("PRAGMA busy_timeout")
+            .fetch_one(&mut *conn)
+            .await
+            .unwrap();
+        assert_eq!(busy_timeout, 5000);
+
+        let error = sqlx::query(
+            "INSERT INTO log (id, agenda_id, content, log_type, create_at) \
+             VALUES ('orphan', 'missing', '', 'CommonLog', 0)",
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap_err();
+        assert!(error.to_string().contains("FOREIGN KEY"), "{error}");
+    }
+}
@@ -2,4 +2,8 @@ mod db;
 mod repo;
 
 pub use db::*;
-pub use repo::{agenda_repo::*, log_repo::*};
+pub use repo::{
+    agenda_repo::*, event_store::*, git_store::*, journal::*, log_repo::*, outbox::*, store::*,
+    sync::*, trash::*,
+};
+pub use sqlx::SqlitePool;
@@ -1,50 +1,201 @@
 use async_trait::async_trait;
 use domain::*;
 use jiff::Timestamp;
-use sqlx::{FromRow, SqlitePool};
+use sqlx::{FromRow, SqliteConnection, SqlitePool};
 use uuid::Uuid;
 
 use crate::repo::repo_error::RepoError;
+use crate::repo::store::ReplaceSummary;
+use crate::repo::{log_repo, outbox};
 
 #[derive(FromRow)]
-struct DbAgenda {
-    id: String,
-    title: String,
-    agenda_status: String,
-    initiate_at: i64,
-    terminate_at: i64,
+pub(crate) struct DbAgenda {
+    pub(crate) id: String,
+    pub(crate) title: String,
+    pub(crate) agenda_status: String,
+    pub(crate) initiate_at: i64,
+    pub(crate) terminate_at: i64,
 }
 
 impl DbAgenda {
-    fn to_agenda(&self) -> Result<Agenda, RepoError> {
+    pub(crate) fn to_agenda(&self) -> Result<Agenda, RepoError> {
         Ok(Agenda {
             id: Uuid::parse_str(&self.id)?,
             title: self.title.clone(),
-            agenda_status: match self.agenda_status.as_str() {
-                "pending" => AgendaStatus::Pending,
-                "ongoing" => AgendaStatus::Ongoing,
-                "terminated" => AgendaStatus::Terminated,
-                _ => {
-                    return Err(sqlx::Error::ColumnDecode {
-                        index: "agenda_status".to_string(),
-                        source: Box::new(std::io::Error::new(
-                            std::io::ErrorKind::InvalidData,
-                            "invalid agenda_status in database",
-                        )),
-                    }
-                    .into());
-                }
-            },
+            agenda_status: parse_status(&self.agenda_status)?,
             initiate_at: Timestamp::from_millisecond(self.initiate_at)?,
             terminate_at: Timestamp::from_millisecond(self.terminate_at)?,
         })
     }
 }
 
+pub(crate) fn parse_status(status: &str) -> Result<AgendaStatus, RepoError> {
+    match status {
+        "pending" => Ok(AgendaStatus::Pending),
+        "ongoing" => Ok(AgendaStatus::Ongoing),
+        "terminated" => Ok(AgendaStatus::Terminated),
+        _ => Err(sqlx::Error::ColumnDecode {
+            index: "agenda_status".to_string(),
+            source: Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "invalid agenda_status in database",
+            )),
+        }
+        .into()),
+    }
+}
+
+#[derive(FromRow)]
+struct DbRevision {
+    agenda_id: String,
+    revised_at: i64,
+    actor: Option<String>,
+    field: String,
+    old_value: String,
+    new_value: String,
+}
+
+impl DbRevision {
+    fn to_revision(&self) -> Result<AgendaRevision, RepoError> {
+        let millis = |value: &str| {
+            value
+                .parse::<i64>()
+                .map_err(|e| RepoError::Revision(format!("deadline `{value}`: {e}")))
+                .and_then(|ms| Ok(Timestamp::from_millisecond(ms)?))
+        };
+        let change = match self.field.as_str() {
+            "title" => FieldChange::Title {
+                old: self.old_value.clone(),
+                new: self.new_value.clone(),
+            },
+            "status" => FieldChange::Status {
+                old: parse_status(&self.old_value)?,
+                new: parse_status(&self.new_value)?,
+            },
+            "deadline" => FieldChange::Deadline {
+                old: millis(&self.old_value)?,
+                new: millis(&self.new_value)?,
+            },
+            other => {
+                return Err(RepoError::Revision(format!("unknown field `{other}`")));
+            }
+        };
+        Ok(AgendaRevision {
+            agenda_id: Uuid::parse_str(&self.agenda_id)?,
+            revised_at: Timestamp::from_millisecond(self.revised_at)?,
+            actor: self.actor.clone(),
+            change,
+        })
+    }
+}
+
+/// The `field`, `old_value` and `new_value` columns of a revision row.
+fn revision_columns(change: &FieldChange) -> (&'static str, String, String) {
+    match change {
+        FieldChange::Title { old, new } => ("title", old.clone(), new.clone()),
+        FieldChange::Status { old, new } => ("status", old.to_string(), new.to_string()),
+        FieldChange::Deadline { old, new } => (
+            "deadline",
+            old.as_millisecond().to_string(),
+            new.as_millisecond().to_string(),
+        ),
+    }
+}
+
+/// Inserts an agenda keeping its id and timestamps.
+async fn insert_row(conn: &mut SqliteConnection, agenda: &Agenda) -> Result<(), RepoError> {
+    // A trashed agenda with the same id is taken out of the trash and
+    // overwritten; a live one is a conflict.
+    let result = sqlx::query(
+        "INSERT INTO agenda
+        (id, title, agenda_status, initiate_at, terminate_at)
+        VALUES
+        (?, ?, ?, ?, ?)
+        ON CONFLICT (id) DO UPDATE SET
+            title = excluded.title,
+            agenda_status = excluded.agenda_status,
+            initiate_at = excluded.initiate_at,
+            terminate_at = excluded.terminate_at,
+            deleted_at = NULL
+        WHERE agenda.deleted_at IS NOT NULL",
+    )
+    .bind(agenda.id.to_string())
+    .bind(&agenda.title)
+    .bind(agenda.agenda_status.to_string())
+    .bind(agenda.initiate_at.as_millisecond())
+    .bind(agenda.terminate_at.as_millisecond())
+    .execute(&mut *conn)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(RepoError::Duplicate(format!("agenda {}", agenda.id)));
+    }
+    Ok(())
+}
+
 pub struct SqliteAgendaRepo {
     pub pool: SqlitePool,
 }
 
+impl SqliteAgendaRepo {
+    /// Moves every live agenda and log to the trash and inserts `agendas`
+    /// and `logs` in their place, all in one transaction.
+    pub(crate) async fn replace_all(
+        &self,
+        agendas: &[Agenda],
+        logs: &[Log],
+    ) -> Result<ReplaceSummary, RepoError> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        outbox::mute(&mut tx).await?;
+        let now = Timestamp::now().as_millisecond();
+        let removed_logs = sqlx::query(
+            "UPDATE log SET deleted_at = ? WHERE deleted_at IS NULL
+              AND agenda_id IN (SELECT id FROM agenda WHERE deleted_at IS NULL)",
+        )
+        .bind(now)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        let removed_agendas =
+            sqlx::query("UPDATE agenda SET deleted_at = ? WHERE deleted_at IS NULL")
+                .bind(now)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+        for agenda in agendas {
+            insert_row(&mut tx, agenda).await?;
+        }
+        for log in logs {
+            log_repo::insert_row(&mut tx, log).await?;
+        }
+        outbox::unmute(&mut tx).await?;
+        tx.commit().await?;
+        Ok(ReplaceSummary {
+            agendas: removed_agendas,
+            logs: removed_logs,
+        })
+    }
+
+    /// Inserts `agendas` and `logs`, none of which may exist yet, all in one
+    /// transaction.
+    pub(crate) async fn merge_all(
+        &self,
+        agendas: &[Agenda],
+        logs: &[Log],
+    ) -> Result<(), RepoError> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        outbox::mute(&mut tx).await?;
+        for agenda in agendas {
+            insert_row(&mut tx, agenda).await?;
+        }
+        for log in logs {
+            log_repo::insert_row(&mut tx, log).await?;
+        }
+        outbox::unmute(&mut tx).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
 #[async_trait]
 impl AgendaRepo for SqliteAgendaRepo {
     type Error = RepoError;
@@ -60,16 +211,28 @@ impl AgendaRepo for SqliteAgendaRepo {
         )
         .bind(uuid.to_string())
         .bind(&agenda.title)
-        .bind(&agenda.agenda_status.to_string())
-        .bind(&timestamp)
-        .bind(&agenda.terminate_at.as_millisecond())
+        .bind(agenda.agenda_status.to_string())
+        .bind(timestamp)
+        .bind(agenda.terminate_at.as_millisecond())
         .execute(&self.pool)
         .await?;
         Ok(uuid)
     }
 
+    async fn insert_agenda(&self, agenda: &Agenda) -> Result<(), Self::Error> {
+        // Inserted agendas already exist elsewhere, so they are no news.
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        outbox::mute(&mut tx).await?;
+        insert_row(&mut tx, agenda).await?;
+        outbox::unmute(&mut tx).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
     async fn delete_agenda_by_id(&self, id: Uuid) -> Result<(), Self::Error> {
-        sqlx::query("DELETE FROM agenda WHERE id = ?")
+        // Deleting moves the agenda, and with it its logs, to the trash.
+        sqlx::query("UPDATE agenda SET deleted_at = ? WHERE id = ? AND deleted_at IS NULL")
+            .bind(Timestamp::now().as_millisecond())
             .bind(id.to_string())
             .execute(&self.pool)
             .await?;
@@ -77,6 +240,21 @@ impl AgendaRepo for SqliteAgendaRepo {
     }
 
     async fn update_agenda(&self, id: Uuid, update: &AgendaUpdate) -> Result<(), Self::Error> {
+        // Take the write lock up front: a read transaction that later writes
+        // fails at once, without waiting out the busy timeout, when another
+        // connection wrote in between.
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        let before = sqlx::query_as::<_, DbAgenda>(
+            "SELECT * FROM agenda WHERE id = ? AND deleted_at IS NULL",
+        )
+        .bind(id.to_string())
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(before) = before else {
+            return Ok(());
+        };
+        let changes = update.changes(&before.to_agenda()?);
+
         let mut query = "UPDATE agenda SET ".to_string();
         let mut args: Vec<(String, String)> = Vec::new();
 
@@ -111,15 +289,47 @@ impl AgendaRepo for SqliteAgendaRepo {
         }
         sql_query = sql_query.bind(id.to_string());
 
-        sql_query.execute(&self.pool).await?;
+        sql_query.execute(&mut *tx).await?;
+
+        let now = Timestamp::now().as_millisecond();
+        for change in &changes {
+            let (field, old_value, new_value) = revision_columns(change);
+            sqlx::query(
+                "INSERT INTO agenda_revision
+                    (agenda_id, revised_at, actor, field, old_value, new_value)
+                VALUES (?, ?, ?, ?, ?, ?)",
+            )
+            .bind(id.to_string())
+            .bind(now)
+            .bind(&update.actor)
+            .bind(field)
+            .bind(old_value)
+            .bind(new_value)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
         Ok(())
     }
 
+    async fn get_agenda_revisions(&self, id: Uuid) -> Result<Vec<AgendaRevision>, Self::Error> {
+        let rows = sqlx::query_as::<_, DbRevision>(
+            "SELECT agenda_id, revised_at, actor, field, old_value, new_value
+            FROM agenda_revision WHERE agenda_id = ? ORDER BY id",
+        )
+        .bind(id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(DbRevision::to_revision).collect()
+    }
+
     async fn get_agenda_by_id(&self, id: Uuid) -> Result<Option<Agenda>, Self::Error> {
-        let row = sqlx::query_as::<_, DbAgenda>("SELECT * FROM agenda WHERE id = ?")
-            .bind(id.to_string())
-            .fetch_optional(&self.pool)
-            .await?;
+        let row = sqlx::query_as::<_, DbAgenda>(
+            "SELECT * FROM agenda WHERE id = ? AND deleted_at IS NULL",
+        )
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
 
         let db_aegnda = match row {
             Some(db_agenda) => db_agenda,
@@ -130,10 +340,12 @@ impl AgendaRepo for SqliteAgendaRepo {
     }
 
     async fn get_agendas_by_title(&self, title: &str) -> Result<Vec<Agenda>, Self::Error> {
-        let rows = sqlx::query_as::<_, DbAgenda>("SELECT * FROM agenda WHERE title = ?")
-            .bind(title)
-            .fetch_all(&self.pool)
-            .await?;
+        let rows = sqlx::query_as::<_, DbAgenda>(
+            "SELECT * FROM agenda WHERE title = ? AND deleted_at IS NULL",
+        )
+        .bind(title)
+        .fetch_all(&self.pool)
+        .await?;
 
         let agendas = rows
             .into_iter()
@@ -147,12 +359,14 @@ impl AgendaRepo for SqliteAgendaRepo {
         status: Option<&str>,
     ) -> Result<Vec<Agenda>, Self::Error> {
         let rows = if let Some(status) = status {
-            sqlx::query_as::<_, DbAgenda>("SELECT * FROM agenda WHERE agenda_status = ?")
-                .bind(status)
-                .fetch_all(&self.pool)
-                .await?
+            sqlx::query_as::<_, DbAgenda>(
+                "SELECT * FROM agenda WHERE agenda_status = ? AND deleted_at IS NULL",
+            )
+            .bind(status)
+            .fetch_all(&self.pool)
+            .await?
         } else {
-            sqlx::query_as::<_, DbAgenda>("SELECT * FROM agenda")
+            sqlx::query_as::<_, DbAgenda>("SELECT * FROM agenda WHERE deleted_at IS NULL")
                 .fetch_all(&self.pool)
                 .await?
         };
@@ -165,12 +379,14 @@ impl AgendaRepo for SqliteAgendaRepo {
     }
     async fn count_agendas_by_status(&self, status: Option<&str>) -> Result<u64, Self::Error> {
         let count: i64 = if let Some(status) = status {
-            sqlx::query_scalar("SELECT COUNT(*) FROM agenda WHERE agenda_status = ?")
-                .bind(status)
-                .fetch_one(&self.pool)
-                .await?
+            sqlx::query_scalar(
+                "SELECT COUNT(*) FROM agenda WHERE agenda_status = ? AND deleted_at IS NULL",
+            )
+            .bind(status)
+            .fetch_one(&self.pool)
+            .await?
         } else {
-            sqlx::query_scalar("SELECT COUNT(*) FROM agenda")
+            sqlx::query_scalar("SELECT COUNT(*) FROM agenda WHERE deleted_at IS NULL")
                 .fetch_one(&self.pool)
                 .await?
         };
@@ -182,7 +398,28 @@ impl AgendaRepo for SqliteAgendaRepo {
         end: Timestamp,
     ) -> Result<Vec<Agenda>, Self::Error> {
         let rows = sqlx::query_as::<_, DbAgenda>(
-            "SELECT * FROM agenda WHERE terminate_at >= ? AND terminate_at <= ?",
+            "SELECT * FROM agenda
+            WHERE terminate_at >= ? AND terminate_at <= ? AND deleted_at IS NULL",
+        )
+        .bind(start.as_millisecond())
+        .bind(end.as_millisecond())
+        .fetch_all(&self.pool)
+        .await?;
+
+        let agendas = rows
+            .into_iter()
+            .map(|db_agenda| db_agenda.to_agenda())
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(agendas)
+    }
+    async fn get_agendas_by_initiate_time_range(
+        &self,
+        start: Timestamp,
+        end: Timestamp,
+    ) -> Result<Vec<Agenda>, Self::Error> {
+        let rows = sqlx::query_as::<_, DbAgenda>(
+            "SELECT * FROM agenda
+            WHERE initiate_at >= ? AND initiate_at <= ? AND deleted_at IS NULL",
         )
         .bind(start.as_millisecond())
         .bind(end.as_millisecond())
@@ -266,7 +503,49 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn delete_agenda_removes_row() {
+    async fn insert_agenda_preserves_id_and_timestamps() {
+        let pool = setup_pool().await;
+        let repo = SqliteAgendaRepo { pool: pool.clone() };
+
+        let agenda = Agenda {
+            id: Uuid::now_v7(),
+            title: "Imported".to_string(),
+            agenda_status: AgendaStatus::Terminated,
+            initiate_at: Timestamp::from_millisecond(1_700_000_000_123).unwrap(),
+            terminate_at: Timestamp::from_millisecond(1_700_086_400_456).unwrap(),
+        };
+        repo.insert_agenda(&agenda).await.expect("insert agenda");
+
+        let fetched = repo
+            .get_agenda_by_id(agenda.id)
+            .await
+            .expect("get agenda")
+            .expect("agenda exists");
+        assert_eq!(fetched.title, "Imported");
+        assert_eq!(fetched.agenda_status, AgendaStatus::Terminated);
+        assert_eq!(fetched.initiate_at, agenda.initiate_at);
+        assert_eq!(fetched.terminate_at, agenda.terminate_at);
+    }
+
+    #[tokio::test]
+    async fn insert_agenda_duplicate_id_errors() {
+        let pool = setup_pool().await;
+        let repo = SqliteAgendaRepo { pool: pool.clone() };
+
+        let agenda = Agenda {
+            id: Uuid::now_v7(),
+            title: "Imported".to_string(),
+            agenda_status: AgendaStatus::Pending,
+            initiate_at: Timestamp::now(),
+            terminate_at: Timestamp::now(),
+        };
+        repo.insert_agenda(&agenda).await.expect("first insert");
+        let result = repo.insert_agenda(&agenda).await;
+        assert!(result.is_err(), "duplicate id should be rejected");
+    }
+
+    #[tokio::test]
+    async fn delete_agenda_moves_it_to_the_trash() {
         let pool = setup_pool().await;
         let repo = SqliteAgendaRepo { pool: pool.clone() };
 
@@ -292,13 +571,22 @@ mod tests {
             .await
             .expect("delete agenda");
 
-        // verify deletion
-        let count_after: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM agenda WHERE id = ?")
-            .bind(agenda_id.to_string())
-            .fetch_one(&pool)
-            .await
-            .expect("count after delete");
-        assert_eq!(count_after, 0);
+        // verify the row is trashed and hidden from queries
+        let trashed: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM agenda WHERE id = ? AND deleted_at IS NOT NULL",
+        )
+        .bind(agenda_id.to_string())
+        .fetch_one(&pool)
+        .await
+        .expect("count after delete");
+        assert_eq!(trashed, 1);
+        assert!(
+            repo.get_agenda_by_id(agenda_id)
+                .await
+                .expect("get agenda")
+                .is_none()
+        );
+        assert_eq!(repo.count_agendas_by_status(None).await.expect("count"), 0);
     }
 
     #[tokio::test]
@@ -334,6 +622,7 @@ mod tests {
             title: Some("Updated title".to_string()),
             agenda_status: None,
             terminate_at: None,
+            actor: None,
         };
         repo.update_agenda(agenda_id, &update)
             .await
@@ -375,6 +664,7 @@ mod tests {
             title: Some("New title".to_string()),
             agenda_status: Some(new_status),
             terminate_at: None,
+            actor: None,
         };
         repo.update_agenda(agenda_id, &update)
             .await
@@ -415,6 +705,7 @@ mod tests {
             title: Some("Fully updated".to_string()),
             agenda_status: Some(AgendaStatus::Terminated),
             terminate_at: Some(new_terminate),
+            actor: None,
         };
         repo.update_agenda(agenda_id, &update)
             .await
@@ -446,6 +737,7 @@ mod tests {
             title: Some("Won't be saved".to_string()),
             agenda_status: None,
             terminate_at: None,
+            actor: None,
         };
 
         let result = repo.update_agenda(non_existent_id, &update).await;
@@ -479,6 +771,7 @@ mod tests {
             title: None,
             agenda_status: None,
             terminate_at: None,
+            actor: None,
         };
         repo.update_agenda(agenda_id, &update)
             .await
@@ -608,6 +901,7 @@ mod tests {
             title: Some("Updated".to_string()),
             agenda_status: Some(AgendaStatus::Ongoing),
             terminate_at: Some(new_terminate),
+            actor: None,
         };
         repo.update_agenda(agenda_id, &update)
             .await
@@ -893,6 +1187,67 @@ mod tests {
         assert!(result.is_empty());
     }
 
+    async fn insert_agenda_initiated_at(
+        pool: &SqlitePool,
+        title: &str,
+        initiate_at: Timestamp,
+    ) -> Uuid {
+        let id = Uuid::now_v7();
+        sqlx::query(
+            "INSERT INTO agenda (id, title, agenda_status, initiate_at, terminate_at) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(id.to_string())
+        .bind(title)
+        .bind("pending")
+        .bind(initiate_at.as_millisecond())
+        .bind(initiate_at.as_millisecond() + 1000)
+        .execute(pool)
+        .await
+        .expect("insert agenda");
+        id
+    }
+
+    #[tokio::test]
+    async fn get_agendas_by_initiate_time_range_filters() {
+        let pool = setup_pool().await;
+        let repo = SqliteAgendaRepo { pool: pool.clone() };
+
+        let base = Timestamp::now();
+        insert_agenda_initiated_at(&pool, "Before", base).await;
+        let in_range_id = insert_agenda_initiated_at(&pool, "In range", base + 10.seconds()).await;
+        insert_agenda_initiated_at(&pool, "After", base + 30.seconds()).await;
+
+        let result = repo
+            .get_agendas_by_initiate_time_range(base + 5.seconds(), base + 20.seconds())
+            .await
+            .expect("query range");
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].id, in_range_id);
+    }
+
+    #[tokio::test]
+    async fn get_agendas_by_initiate_time_range_inclusive_bounds() {
+        let pool = setup_pool().await;
+        let repo = SqliteAgendaRepo { pool: pool.clone() };
+
+        let base = Timestamp::now();
+        let start = base + 5.seconds();
+        let end = base + 15.seconds();
+        let start_id = insert_agenda_initiated_at(&pool, "At start", start).await;
+        let end_id = insert_agenda_initiated_at(&pool, "At end", end).await;
+
+        let result = repo
+            .get_agendas_by_initiate_time_range(start, end)
+            .await
+            .expect("query range");
+
+        let ids: Vec<_> = result.iter().map(|a| a.id).collect();
+        assert_eq!(result.len(), 2);
+        assert!(ids.contains(&start_id));
+        assert!(ids.contains(&end_id));
+    }
+
     #[tokio::test]
     async fn get_agendas_by_title_exact_match() {
         let pool = setup_pool().await;
@@ -1039,4 +1394,78 @@ mod tests {
 
         assert_eq!(total_count, 3);
     }
+
+    #[tokio::test]
+    async fn updates_record_revisions_per_changed_field() {
+        let pool = setup_pool().await;
+        let repo = SqliteAgendaRepo { pool: pool.clone() };
+
+        let terminate_at = Timestamp::from_millisecond(1_700_000_000_000).unwrap();
+        let agenda_id = repo
+            .create_agenda(&AgendaCreate {
+                title: "Draft".to_string(),
+                agenda_status: AgendaStatus::Pending,
+                terminate_at,
+            })
+            .await
+            .expect("create agenda");
+
+        // The unchanged title is not a revision.
+        let later = terminate_at + 1.hour();
+        let update = AgendaUpdate {
+            title: Some("Draft".to_string()),
+            agenda_status: Some(AgendaStatus::Ongoing),
+            terminate_at: Some(later),
+            actor: Some("cli".to_string()),
+        };
+        repo.update_agenda(agenda_id, &update)
+            .await
+            .expect("update agenda");
+        let update = AgendaUpdate {
+            title: Some("Final".to_string()),
+            agenda_status: None,
+            terminate_at: None,
+            actor: None,
+        };
+        repo.update_agenda(agenda_id, &update)
+            .await
+            .expect("update agenda");
+
+        let revisions = repo
+            .get_agenda_revisions(agenda_id)
+            .await
+            .expect("get revisions");
+        let changes: Vec<_> = revisions.iter().map(|r| r.change.clone()).collect();
+        assert_eq!(
+            changes,
+            [
+                FieldChange::Status {
+                    old: AgendaStatus::Pending,
+                    new: AgendaStatus::Ongoing
+                },
+                FieldChange::Deadline {
+                    old: terminate_at,
+                    new: later
+                },
+                FieldChange::Title {
+                    old: "Draft".to_string(),
+                    new: "Final".to_string()
+                },
+            ]
+        );
+        assert_eq!(revisions[0].actor.as_deref(), Some("cli"));
+        assert_eq!(revisions[2].actor, None);
+
+        // Trashing keeps the history so a restored agenda still has it.
+        repo.delete_agenda_by_id(agenda_id)
+            .await
+            .expect("delete agenda");
+        assert_eq!(
+            repo.get_agenda_revisions(agenda_id)
+                .await
+                .expect("get revisions")
+                .len(),
+            3
+        );
+    }
 }
@@ -0,0 +1,1120 @@
+use async_trait::async_trait;
+use domain::*;
+use jiff::Timestamp;
+use sqlx::{FromRow, SqliteConnection, SqlitePool};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+use crate::repo::agenda_repo::{DbAgenda, parse_status};
+use crate::repo::log_repo::DbLog;
+use crate::repo::outbox;
+use crate::repo::repo_error::RepoError;
+use crate::repo::store::ReplaceSummary;
+
+/// How many events an agenda may gather after its last snapshot before the
+/// next write takes a new one.
+const SNAPSHOT_EVERY: i64 = 16;
+
+/// The logs that are still visible: `marked` events not undone by a later
+/// `log_deleted`, whose agenda was not deleted afterwards.
+const LIVE_LOGS: &str =
+    "SELECT e.log_id AS id, e.at AS create_at, e.content, e.log_type, e.agenda_id
+    FROM agenda_event e
+    WHERE e.kind = 'marked'
+      AND NOT EXISTS (SELECT 1 FROM agenda_event d
+          WHERE d.kind = 'log_deleted' AND d.log_id = e.log_id AND d.seq > e.seq)
+      AND NOT EXISTS (SELECT 1 FROM agenda_event d
+          WHERE d.kind = 'deleted' AND d.agenda_id = e.agenda_id AND d.seq > e.seq)";
+
+/// One entry of an agenda's event stream.
+#[derive(Debug, Clone)]
+enum Change {
+    Created(Agenda),
+    Retitled(String),
+    DeadlineChanged(Timestamp),
+    StatusChanged(AgendaStatus),
+    Marked(Log),
+    LogDeleted(Uuid),
+    Deleted,
+}
+
+impl Change {
+    fn kind(&self) -> &'static str {
+        match self {
+            Change::Created(_) => "created",
+            Change::Retitled(_) => "retitled",
+            Change::DeadlineChanged(_) => "deadline_changed",
+            Change::StatusChanged(_) => "status_changed",
+            Change::Marked(_) => "marked",
+            Change::LogDeleted(_) => "log_deleted",
+            Change::Deleted => "deleted",
+        }
+    }
+
+    /// The webhook this change produces, matching the outbox triggers.
+    fn webhook(&self) -> Option<(&'static str, Option<&Log>)> {
+        match self {
+            Change::Created(_) => Some(("agenda.created", None)),
+            Change::Marked(log) => Some((outbox::log_event(log.log_type), Some(log))),
+            _ => None,
+        }
+    }
+}
+
+/// The status a transition log moves its agenda to.
+fn transition(log_type: LogType) -> Option<AgendaStatus> {
+    match log_type {
+        LogType::Activate => Some(AgendaStatus::Ongoing),
+        LogType::PutOff => Some(AgendaStatus::Pending),
+        LogType::Terminate => Some(AgendaStatus::Terminated),
+        LogType::CommonLog => None,
+    }
+}
+
+/// Applies one event to the agendas folded so far.
+fn apply(agendas: &mut BTreeMap<Uuid, Agenda>, agenda_id: Uuid, change: Change) {
+    match change {
+        Change::Created(agenda) => {
+            agendas.insert(agenda_id, agenda);
+        }
+        Change::Deleted => {
+            agendas.remove(&agenda_id);
+        }
+        change => {
+            let Some(agenda) = agendas.get_mut(&agenda_id) else {
+                return;
+            };
+            match change {
+                Change::Retitled(title) => agenda.title = title,
+                Change::DeadlineChanged(at) => agenda.terminate_at = at,
+                Change::StatusChanged(status) => agenda.agenda_status = status,
+                // Transition logs are the source of truth for the status.
+                Change::Marked(log) => {
+                    if let Some(status) = transition(log.log_type) {
+                        agenda.agenda_status = status;
+                    }
+                }
+                Change::LogDeleted(_) | Change::Created(_) | Change::Deleted => {}
+            }
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct DbEvent {
+    seq: i64,
+    agenda_id: String,
+    kind: String,
+    at: i64,
+    title: Option<String>,
+    agenda_status: Option<String>,
+    terminate_at: Option<i64>,
+    log_id: Option<String>,
+    content: Option<String>,
+    log_type: Option<String>,
+    actor: Option<String>,
+}
+
+impl DbEvent {
+    fn to_change(&self) -> Result<(Uuid, Change), RepoError> {
+        let missing =
+            |column: &str| RepoError::Stream(format!("event {} has no {column}", self.seq));
+        let title = || self.title.clone().ok_or_else(|| missing("title"));
+        let log_id = || self.log_id.clone().ok_or_else(|| missing("log_id"));
+        let change = match self.kind.as_str() {
+            "created" => Change::Created(
+                DbAgenda {
+                    id: self.agenda_id.clone(),
+                    title: title()?,
+                    agenda_status: self
+                        .agenda_status
+                        .clone()
+                        .ok_or_else(|| missing("agenda_status"))?,
+                    initiate_at: self.at,
+                    terminate_at: self.terminate_at.ok_or_else(|| missing("terminate_at"))?,
+                }
+                .to_agenda()?,
+            ),
+            "retitled" => Change::Retitled(title()?),
+            "deadline_changed" => Change::DeadlineChanged(Timestamp::from_millisecond(
+                self.terminate_at.ok_or_else(|| missing("terminate_at"))?,
+            )?),
+            "status_changed" => Change::StatusChanged(parse_status(
+                self.agenda_status
+                    .as_deref()
+                    .ok_or_else(|| missing("agenda_status"))?,
+            )?),
+            "marked" => Change::Marked(
+                DbLog {
+                    id: log_id()?,
+                    create_at: self.at,
+                    content: self.content.clone().ok_or_else(|| missing("content"))?,
+                    log_type: self.log_type.clone().ok_or_else(|| missing("log_type"))?,
+                    agenda_id: self.agenda_id.clone(),
+                }
+                .to_log()?,
+            ),
+            "log_deleted" => Change::LogDeleted(Uuid::parse_str(&log_id()?)?),
+            "deleted" => Change::Deleted,
+            other => {
+                return Err(RepoError::Stream(format!(
+                    "event {} has unknown kind `{other}`",
+                    self.seq
+                )));
+            }
+        };
+        Ok((Uuid::parse_str(&self.agenda_id)?, change))
+    }
+}
+
+#[derive(FromRow)]
+struct DbSnapshot {
+    agenda_id: String,
+    seq: i64,
+    title: Option<String>,
+    agenda_status: Option<String>,
+    initiate_at: Option<i64>,
+    terminate_at: Option<i64>,
+}
+
+impl DbSnapshot {
+    /// The agenda, or `None` when the snapshot records a deletion.
+    fn to_agenda(&self) -> Result<Option<Agenda>, RepoError> {
+        let (Some(title), Some(agenda_status), Some(initiate_at), Some(terminate_at)) = (
+            &self.title,
+            &self.agenda_status,
+            self.initiate_at,
+            self.terminate_at,
+        ) else {
+            return Ok(None);
+        };
+        DbAgenda {
+            id: self.agenda_id.clone(),
+            title: title.clone(),
+            agenda_status: agenda_status.clone(),
+            initiate_at,
+            terminate_at,
+        }
+        .to_agenda()
+        .map(Some)
+    }
+}
+
+/// Folds one agenda: its snapshot, if any, then the events after it.
+async fn load(conn: &mut SqliteConnection, id: Uuid) -> Result<Option<Agenda>, RepoError> {
+    let snapshot = sqlx::query_as::<_, DbSnapshot>(
+        "SELECT agenda_id, seq, title, agenda_status, initiate_at, terminate_at
+        FROM agenda_snapshot WHERE agenda_id = ?",
+    )
+    .bind(id.to_string())
+    .fetch_optional(&mut *conn)
+    .await?;
+    let mut agendas = BTreeMap::new();
+    let mut after = 0;
+    if let Some(snapshot) = snapshot {
+        after = snapshot.seq;
+        if let Some(agenda) = snapshot.to_agenda()? {
+            agendas.insert(id, agenda);
+        }
+    }
+
+    let events = sqlx::query_as::<_, DbEvent>(
+        "SELECT seq, agenda_id, kind, at, title, agenda_status, terminate_at, log_id, content,
+            log_type, actor
+        FROM agenda_event WHERE agenda_id = ? AND seq > ? ORDER BY seq",
+    )
+    .bind(id.to_string())
+    .bind(after)
+    .fetch_all(&mut *conn)
+    .await?;
+    for event in &events {
+        let (agenda_id, change) = event.to_change()?;
+        apply(&mut agendas, agenda_id, change);
+    }
+    Ok(agendas.remove(&id))
+}
+
+/// Folds every agenda from the snapshots and the events after them.
+async fn load_all(conn: &mut SqliteConnection) -> Result<Vec<Agenda>, RepoError> {
+    let snapshots = sqlx::query_as::<_, DbSnapshot>(
+        "SELECT agenda_id, seq, title, agenda_status, initiate_at, terminate_at
+        FROM agenda_snapshot",
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    let mut agendas = BTreeMap::new();
+    for snapshot in &snapshots {
+        if let Some(agenda) = snapshot.to_agenda()? {
+            agendas.insert(agenda.id, agenda);
+        }
+    }
+
+    let events = sqlx::query_as::<_, DbEvent>(
+        "SELECT e.seq, e.agenda_id, e.kind, e.at, e.title, e.agenda_status, e.terminate_at,
+            e.log_id, e.content, e.log_type, e.actor
+        FROM agenda_event e LEFT JOIN agenda_snapshot s ON s.agenda_id = e.agenda_id
+        WHERE e.seq > COALESCE(s.seq, 0)
+        ORDER BY e.seq",
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    for event in &events {
+        let (agenda_id, change) = event.to_change()?;
+        apply(&mut agendas, agenda_id, change);
+    }
+    Ok(agendas.into_values().collect())
+}
+
+async fn append(
+    conn: &mut SqliteConnection,
+    agenda_id: Uuid,
+    at: Timestamp,
+    actor: Option<&str>,
+    change: &Change,
+) -> Result<(), RepoError> {
+    let mut query = sqlx::query(
+        "INSERT INTO agenda_event
+            (agenda_id, kind, actor, at, title, agenda_status, terminate_at, log_id, content,
+             log_type)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(agenda_id.to_string())
+    .bind(change.kind())
+    .bind(actor);
+    query = match change {
+        Change::Created(agenda) => query
+            .bind(agenda.initiate_at.as_millisecond())
+            .bind(&agenda.title)
+            .bind(agenda.agenda_status.to_string())
+            .bind(agenda.terminate_at.as_millisecond())
+            .bind(None::<String>)
+            .bind(None::<String>)
+            .bind(None::<String>),
+        Change::Marked(log) => query
+            .bind(log.create_at.as_millisecond())
+            .bind(None::<String>)
+            .bind(None::<String>)
+            .bind(None::<i64>)
+            .bind(log.id.to_string())
+            .bind(&log.content)
+            .bind(log.log_type.to_string()),
+        change => query
+            .bind(at.as_millisecond())
+            .bind(match change {
+                Change::Retitled(title) => Some(title.clone()),
+                _ => None,
+            })
+            .bind(match change {
+                Change::StatusChanged(status) => Some(status.to_string()),
+                _ => None,
+            })
+            .bind(match change {
+                Change::DeadlineChanged(at) => Some(at.as_millisecond()),
+                _ => None,
+            })
+            .bind(match change {
+                Change::LogDeleted(id) => Some(id.to_string()),
+                _ => None,
+            })
+            .bind(None::<String>)
+            .bind(None::<String>),
+    };
+    query.execute(&mut *conn).await?;
+    Ok(())
+}
+
+async fn write_snapshot(
+    conn: &mut SqliteConnection,
+    agenda_id: Uuid,
+    seq: i64,
+    agenda: Option<&Agenda>,
+) -> Result<(), RepoError> {
+    sqlx::query(
+        "INSERT OR REPLACE INTO agenda_snapshot
+            (agenda_id, seq, title, agenda_status, initiate_at, terminate_at)
+        VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(agenda_id.to_string())
+    .bind(seq)
+    .bind(agenda.map(|agenda| agenda.title.clone()))
+    .bind(agenda.map(|agenda| agenda.agenda_status.to_string()))
+    .bind(agenda.map(|agenda| agenda.initiate_at.as_millisecond()))
+    .bind(agenda.map(|agenda| agenda.terminate_at.as_millisecond()))
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Appends `changes` to an agenda's stream, queues their webhooks and takes
+/// a snapshot when enough events have gathered since the last one.
+async fn record(
+    conn: &mut SqliteConnection,
+    agenda_id: Uuid,
+    actor: Option<&str>,
+    changes: &[Change],
+) -> Result<(), RepoError> {
+    let now = Timestamp::now();
+    for change in changes {
+        append(conn, agenda_id, now, actor, change).await?;
+    }
+    let agenda = load(conn, agenda_id).await?;
+    if let Some(agenda) = &agenda {
+        for (event, log) in changes.iter().filter_map(Change::webhook) {
+            outbox::enqueue(conn, event, agenda, log).await?;
+        }
+    }
+
+    let (pending, last): (i64, i64) = sqlx::query_as(
+        "SELECT COUNT(*), COALESCE(MAX(seq), 0) FROM agenda_event
+        WHERE agenda_id = ?1
+          AND seq > COALESCE((SELECT seq FROM agenda_snapshot WHERE agenda_id = ?1), 0)",
+    )
+    .bind(agenda_id.to_string())
+    .fetch_one(&mut *conn)
+    .await?;
+    if pending >= SNAPSHOT_EVERY {
+        write_snapshot(conn, agenda_id, last, agenda.as_ref()).await?;
+    }
+    Ok(())
+}
+
+/// The live logs matching `filter`, in stream order.
+fn live_logs(filter: &str) -> String {
+    format!("{LIVE_LOGS} AND {filter} ORDER BY e.seq")
+}
+
+/// Counts of a projection rebuild.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RebuildSummary {
+    pub events: u64,
+    pub agendas: u64,
+}
+
+/// Throws away every snapshot and takes fresh ones by folding the whole
+/// event stream.
+pub async fn rebuild_snapshots(pool: &SqlitePool) -> Result<RebuildSummary, RepoError> {
+    let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+    sqlx::query("DELETE FROM agenda_snapshot")
+        .execute(&mut *tx)
+        .await?;
+    let events = sqlx::query_as::<_, DbEvent>(
+        "SELECT seq, agenda_id, kind, at, title, agenda_status, terminate_at, log_id, content,
+            log_type, actor
+        FROM agenda_event ORDER BY seq",
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut agendas = BTreeMap::new();
+    let mut last_seq = BTreeMap::new();
+    for event in &events {
+        let (agenda_id, change) = event.to_change()?;
+        apply(&mut agendas, agenda_id, change);
+        last_seq.insert(agenda_id, event.seq);
+    }
+    for (agenda_id, seq) in &last_seq {
+        write_snapshot(&mut tx, *agenda_id, *seq, agendas.get(agenda_id)).await?;
+    }
+    tx.commit().await?;
+    Ok(RebuildSummary {
+        events: events.len() as u64,
+        agendas: agendas.len() as u64,
+    })
+}
+
+/// Fills an empty event stream from the `agenda` and `log` tables, so an
+/// existing database can switch to the event-sourced backend. Where the
+/// transition logs disagree with a stored status, a `status_changed` event
+/// keeps the stored one. Returns the number of events written.
+pub async fn seed_event_stream(pool: &SqlitePool) -> Result<u64, RepoError> {
+    let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+    let existing: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM agenda_event")
+        .fetch_one(&mut *tx)
+        .await?;
+    if existing > 0 {
+        return Err(RepoError::Stream(format!(
+            "the event stream already holds {existing} events"
+        )));
+    }
+
+    let agendas = sqlx::query_as::<_, DbAgenda>(
+        "SELECT * FROM agenda WHERE deleted_at IS NULL ORDER BY initiate_at, id",
+    )
+    .fetch_all(&mut *tx)
+    .await?
+    .iter()
+    .map(DbAgenda::to_agenda)
+    .collect::<Result<Vec<_>, _>>()?;
+    let logs = sqlx::query_as::<_, DbLog>(
+        "SELECT id, create_at, content, log_type, agenda_id FROM log
+        WHERE deleted_at IS NULL
+          AND agenda_id IN (SELECT id FROM agenda WHERE deleted_at IS NULL)
+        ORDER BY create_at, id",
+    )
+    .fetch_all(&mut *tx)
+    .await?
+    .iter()
+    .map(DbLog::to_log)
+    .collect::<Result<Vec<_>, _>>()?;
+
+    let now = Timestamp::now();
+    let mut written = 0;
+    for agenda in &agendas {
+        append(
+            &mut tx,
+            agenda.id,
+            now,
+            None,
+            &Change::Created(agenda.clone()),
+        )
+        .await?;
+        written += 1;
+    }
+    for log in &logs {
+        append(
+            &mut tx,
+            log.agenda_id,
+            now,
+            None,
+            &Change::Marked(log.clone()),
+        )
+        .await?;
+        written += 1;
+    }
+    for agenda in &agendas {
+        let folded = load(&mut tx, agenda.id).await?;
+        if folded.is_some_and(|folded| folded.agenda_status != agenda.agenda_status) {
+            let change = Change::StatusChanged(agenda.agenda_status);
+            append(&mut tx, agenda.id, now, None, &change).await?;
+            written += 1;
+        }
+    }
+    tx.commit().await?;
+    rebuild_snapshots(pool).await?;
+    Ok(written)
+}
+
+/// Agendas whose state is the fold of their events in `agenda_event`.
+pub struct EventSourcedAgendaRepo {
+    pub pool: SqlitePool,
+}
+
+impl EventSourcedAgendaRepo {
+    /// Deletes every live agenda and appends `agendas` and `logs` in their
+    /// place, all in one transaction.
+    pub(crate) async fn replace_all(
+        &self,
+        agendas: &[Agenda],
+        logs: &[Log],
+    ) -> Result<ReplaceSummary, RepoError> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        outbox::mute(&mut tx).await?;
+        let removed_logs: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM ({LIVE_LOGS})"))
+            .fetch_one(&mut *tx)
+            .await?;
+        let live = load_all(&mut tx).await?;
+        for agenda in &live {
+            record(&mut tx, agenda.id, None, &[Change::Deleted]).await?;
+        }
+        for agenda in agendas {
+            record(&mut tx, agenda.id, None, &[Change::Created(agenda.clone())]).await?;
+        }
+        for log in logs {
+            mark(&mut tx, log.clone(), false).await?;
+        }
+        outbox::unmute(&mut tx).await?;
+        tx.commit().await?;
+        Ok(ReplaceSummary {
+            agendas: live.len() as u64,
+            logs: removed_logs as u64,
+        })
+    }
+
+    /// Appends the creation of `agendas` and `logs`, none of which may exist
+    /// yet, all in one transaction.
+    pub(crate) async fn merge_all(
+        &self,
+        agendas: &[Agenda],
+        logs: &[Log],
+    ) -> Result<(), RepoError> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        outbox::mute(&mut tx).await?;
+        for agenda in agendas {
+            if load(&mut tx, agenda.id).await?.is_some() {
+                return Err(RepoError::Stream(format!(
+                    "agenda {} already exists",
+                    agenda.id
+                )));
+            }
+            record(&mut tx, agenda.id, None, &[Change::Created(agenda.clone())]).await?;
+        }
+        for log in logs {
+            mark(&mut tx, log.clone(), false).await?;
+        }
+        outbox::unmute(&mut tx).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AgendaRepo for EventSourcedAgendaRepo {
+    type Error = RepoError;
+
+    async fn create_agenda(&self, agenda: &AgendaCreate) -> Result<Uuid, Self::Error> {
+        let id = Uuid::now_v7();
+        let created = Agenda {
+            id,
+            title: agenda.title.clone(),
+            agenda_status: agenda.agenda_status,
+            initiate_at: Timestamp::now(),
+            terminate_at: agenda.terminate_at,
+        };
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        record(&mut tx, id, None, &[Change::Created(created)]).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    async fn insert_agenda(&self, agenda: &Agenda) -> Result<(), Self::Error> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        if load(&mut tx, agenda.id).await?.is_some() {
+            return Err(RepoError::Stream(format!(
+                "agenda {} already exists",
+                agenda.id
+            )));
+        }
+        // Inserted agendas already exist elsewhere, so they are no news.
+        outbox::mute(&mut tx).await?;
+        record(&mut tx, agenda.id, None, &[Change::Created(agenda.clone())]).await?;
+        outbox::unmute(&mut tx).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn delete_agenda_by_id(&self, id: Uuid) -> Result<(), Self::Error> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        if load(&mut tx, id).await?.is_some() {
+            record(&mut tx, id, None, &[Change::Deleted]).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn update_agenda(&self, id: Uuid, update: &AgendaUpdate) -> Result<(), Self::Error> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        let Some(current) = load(&mut tx, id).await? else {
+            return Ok(());
+        };
+        let changes: Vec<_> = update
+            .changes(&current)
+            .into_iter()
+            .map(|change| match change {
+                FieldChange::Title { new, .. } => Change::Retitled(new),
+                FieldChange::Status { new, .. } => Change::StatusChanged(new),
+                FieldChange::Deadline { new, .. } => Change::DeadlineChanged(new),
+            })
+            .collect();
+        if !changes.is_empty() {
+            record(&mut tx, id, update.actor.as_deref(), &changes).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get_agenda_revisions(&self, id: Uuid) -> Result<Vec<AgendaRevision>, Self::Error> {
+        let events = sqlx::query_as::<_, DbEvent>(
+            "SELECT seq, agenda_id, kind, at, title, agenda_status, terminate_at, log_id, content,
+                log_type, actor
+            FROM agenda_event WHERE agenda_id = ? AND kind != 'log_deleted' ORDER BY seq",
+        )
+        .bind(id.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        // Revisions are the field changes, each against the state before it.
+        let mut agendas: BTreeMap<Uuid, Agenda> = BTreeMap::new();
+        let mut revisions = Vec::new();
+        for event in &events {
+            let (agenda_id, change) = event.to_change()?;
+            let before = agendas.get(&agenda_id);
+            let field = match (&change, before) {
+                (Change::Retitled(new), Some(before)) => Some(FieldChange::Title {
+                    old: before.title.clone(),
+                    new: new.clone(),
+                }),
+                (Change::StatusChanged(new), Some(before)) => Some(FieldChange::Status {
+                    old: before.agenda_status,
+                    new: *new,
+                }),
+                (Change::DeadlineChanged(new), Some(before)) => Some(FieldChange::Deadline {
+                    old: before.terminate_at,
+                    new: *new,
+                }),
+                // The history goes with the agenda, as in the relational backend.
+                (Change::Deleted, _) => {
+                    revisions.clear();
+                    None
+                }
+                _ => None,
+            };
+            if let Some(change) = field {
+                revisions.push(AgendaRevision {
+                    agenda_id,
+                    revised_at: Timestamp::from_millisecond(event.at)?,
+                    actor: event.actor.clone(),
+                    change,
+                });
+            }
+            apply(&mut agendas, agenda_id, change);
+        }
+        Ok(revisions)
+    }
+
+    async fn get_agenda_by_id(&self, id: Uuid) -> Result<Option<Agenda>, Self::Error> {
+        let mut conn = self.pool.acquire().await?;
+        load(&mut conn, id).await
+    }
+
+    async fn get_agendas_by_title(&self, title: &str) -> Result<Vec<Agenda>, Self::Error> {
+        let mut conn = self.pool.acquire().await?;
+        let mut agendas = load_all(&mut conn).await?;
+        agendas.retain(|agenda| agenda.title == title);
+        Ok(agendas)
+    }
+
+    async fn get_agendas_by_status(
+        &self,
+        status: Option<&str>,
+    ) -> Result<Vec<Agenda>, Self::Error> {
+        let mut conn = self.pool.acquire().await?;
+        let mut agendas = load_all(&mut conn).await?;
+        if let Some(status) = status {
+            agendas.retain(|agenda| agenda.agenda_status.to_string() == status);
+        }
+        Ok(agendas)
+    }
+
+    async fn count_agendas_by_status(&self, status: Option<&str>) -> Result<u64, Self::Error> {
+        Ok(self.get_agendas_by_status(status).await?.len() as u64)
+    }
+
+    async fn get_agendas_by_terminate_time_range(
+        &self,
+        start: Timestamp,
+        end: Timestamp,
+    ) -> Result<Vec<Agenda>, Self::Error> {
+        let mut conn = self.pool.acquire().await?;
+        let mut agendas = load_all(&mut conn).await?;
+        agendas.retain(|agenda| agenda.terminate_at >= start && agenda.terminate_at <= end);
+        Ok(agendas)
+    }
+
+    async fn get_agendas_by_initiate_time_range(
+        &self,
+        start: Timestamp,
+        end: Timestamp,
+    ) -> Result<Vec<Agenda>, Self::Error> {
+        let mut conn = self.pool.acquire().await?;
+        let mut agendas = load_all(&mut conn).await?;
+        agendas.retain(|agenda| agenda.initiate_at >= start && agenda.initiate_at <= end);
+        Ok(agendas)
+    }
+}
+
+/// Logs read from the `marked` events of the agenda event stream.
+pub struct EventSourcedLogRepo {
+    pub pool: SqlitePool,
+}
+
+/// Appends a `marked` event for `log`; unless it is `fresh`, a live log
+/// with the same id is a conflict.
+async fn mark(conn: &mut SqliteConnection, log: Log, fresh: bool) -> Result<(), RepoError> {
+    if load(conn, log.agenda_id).await?.is_none() {
+        return Err(RepoError::Stream(format!(
+            "agenda {} does not exist",
+            log.agenda_id
+        )));
+    }
+    if !fresh {
+        let sql = format!("SELECT COUNT(*) FROM ({LIVE_LOGS} AND e.log_id = ?)");
+        let existing: i64 = sqlx::query_scalar(&sql)
+            .bind(log.id.to_string())
+            .fetch_one(&mut *conn)
+            .await?;
+        if existing > 0 {
+            return Err(RepoError::Stream(format!("log {} already exists", log.id)));
+        }
+    }
+    record(conn, log.agenda_id, None, &[Change::Marked(log)]).await
+}
+
+#[async_trait]
+impl LogRepo for EventSourcedLogRepo {
+    type Error = RepoError;
+
+    async fn create_log(&self, new_log: &LogCreate) -> Result<Uuid, Self::Error> {
+        let id = Uuid::now_v7();
+        let log = Log {
+            id,
+            agenda_id: new_log.agenda_id,
+            content: new_log.content.clone(),
+            create_at: Timestamp::now(),
+            log_type: new_log.log_type,
+        };
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        mark(&mut tx, log, true).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    async fn insert_log(&self, log: &Log) -> Result<(), Self::Error> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        outbox::mute(&mut tx).await?;
+        mark(&mut tx, log.clone(), false).await?;
+        outbox::unmute(&mut tx).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn delete_log(&self, id: Uuid) -> Result<(), Self::Error> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        let sql = format!("SELECT e.agenda_id FROM ({LIVE_LOGS} AND e.log_id = ?) AS e");
+        let agenda_id: Option<String> = sqlx::query_scalar(&sql)
+            .bind(id.to_string())
+            .fetch_optional(&mut *tx)
+            .await?;
+        if let Some(agenda_id) = agenda_id {
+            record(
+                &mut tx,
+                Uuid::parse_str(&agenda_id)?,
+                None,
+                &[Change::LogDeleted(id)],
+            )
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get_log_by_id(&self, id: Uuid) -> Result<Option<Log>, Self::Error> {
+        let row = sqlx::query_as::<_, DbLog>(&live_logs("e.log_id = ?"))
+            .bind(id.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(DbLog::to_log).transpose()
+    }
+
+    async fn get_logs_by_agenda_id(&self, agenda_id: Uuid) -> Result<Vec<Log>, Self::Error> {
+        let rows = sqlx::query_as::<_, DbLog>(&live_logs("e.agenda_id = ?"))
+            .bind(agenda_id.to_string())
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(DbLog::to_log).collect()
+    }
+
+    async fn get_logs_by_time_range(
+        &self,
+        start: Timestamp,
+        end: Timestamp,
+    ) -> Result<Vec<Log>, Self::Error> {
+        let rows = sqlx::query_as::<_, DbLog>(&live_logs("e.at >= ? AND e.at <= ?"))
+            .bind(start.as_millisecond())
+            .bind(end.as_millisecond())
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(DbLog::to_log).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::agenda_repo::SqliteAgendaRepo;
+    use crate::repo::log_repo::SqliteLogRepo;
+    use jiff::ToSpan;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn setup_pool() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .expect("create in-memory sqlite pool");
+
+        let crate_dir = env!("CARGO_MANIFEST_DIR");
+        let migrations = std::path::Path::new(crate_dir).join("migrations");
+        sqlx::migrate::Migrator::new(migrations)
+            .await
+            .expect("load migrations")
+            .run(&pool)
+            .await
+            .expect("run migrations");
+
+        pool
+    }
+
+    fn repos(pool: &SqlitePool) -> (EventSourcedAgendaRepo, EventSourcedLogRepo) {
+        (
+            EventSourcedAgendaRepo { pool: pool.clone() },
+            EventSourcedLogRepo { pool: pool.clone() },
+        )
+    }
+
+    fn new_agenda(title: &str) -> AgendaCreate {
+        AgendaCreate {
+            title: title.to_string(),
+            agenda_status: AgendaStatus::Pending,
+            terminate_at: Timestamp::now() + 1.hour(),
+        }
+    }
+
+    fn new_log(agenda_id: Uuid, log_type: LogType) -> LogCreate {
+        LogCreate {
+            agenda_id,
+            content: log_type.to_string(),
+            log_type,
+        }
+    }
+
+    #[tokio::test]
+    async fn state_is_the_fold_of_the_stream() {
+        let pool = setup_pool().await;
+        let (agendas, logs) = repos(&pool);
+        let id = agendas.create_agenda(&new_agenda("Draft")).await.unwrap();
+        let other = agendas.create_agenda(&new_agenda("Other")).await.unwrap();
+        let deadline =
+            Timestamp::from_millisecond(Timestamp::now().as_millisecond()).unwrap() + 2.hours();
+        agendas
+            .update_agenda(
+                id,
+                &AgendaUpdate {
+                    title: Some("Final".to_string()),
+                    terminate_at: Some(deadline),
+                    agenda_status: None,
+                    actor: None,
+                },
+            )
+            .await
+            .unwrap();
+        logs.create_log(&new_log(id, LogType::Activate))
+            .await
+            .unwrap();
+        let note = logs
+            .create_log(&new_log(id, LogType::CommonLog))
+            .await
+            .unwrap();
+
+        let agenda = agendas.get_agenda_by_id(id).await.unwrap().unwrap();
+        assert_eq!(agenda.title, "Final");
+        assert_eq!(agenda.terminate_at, deadline);
+        // The activate log alone moves the agenda to ongoing.
+        assert_eq!(agenda.agenda_status, AgendaStatus::Ongoing);
+        assert_eq!(
+            agendas
+                .count_agendas_by_status(Some("ongoing"))
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            agendas.get_agendas_by_title("Final").await.unwrap().len(),
+            1
+        );
+
+        logs.delete_log(note).await.unwrap();
+        assert_eq!(logs.get_logs_by_agenda_id(id).await.unwrap().len(), 1);
+
+        agendas.delete_agenda_by_id(id).await.unwrap();
+        assert!(agendas.get_agenda_by_id(id).await.unwrap().is_none());
+        assert!(logs.get_logs_by_agenda_id(id).await.unwrap().is_empty());
+        let remaining = agendas.get_agendas_by_status(None).await.unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].id, other);
+
+        assert!(
+            logs.create_log(&new_log(id, LogType::CommonLog))
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn revisions_come_from_the_stream() {
+        let pool = setup_pool().await;
+        let (agendas, logs) = repos(&pool);
+        let id = agendas.create_agenda(&new_agenda("Draft")).await.unwrap();
+        // Transition logs change the status but are not revisions.
+        logs.create_log(&new_log(id, LogType::Activate))
+            .await
+            .unwrap();
+        agendas
+            .update_agenda(
+                id,
+                &AgendaUpdate {
+                    title: Some("Final".to_string()),
+                    agenda_status: Some(AgendaStatus::Pending),
+                    terminate_at: None,
+                    actor: Some("rpc".to_string()),
+                },
+            )
+            .await
+            .unwrap();
+
+        let revisions = agendas.get_agenda_revisions(id).await.unwrap();
+        let changes: Vec<_> = revisions.iter().map(|r| r.change.clone()).collect();
+        assert_eq!(
+            changes,
+            [
+                FieldChange::Title {
+                    old: "Draft".to_string(),
+                    new: "Final".to_string()
+                },
+                FieldChange::Status {
+                    old: AgendaStatus::Ongoing,
+                    new: AgendaStatus::Pending
+                },
+            ]
+        );
+        assert!(revisions.iter().all(|r| r.actor.as_deref() == Some("rpc")));
+    }
+
+    #[tokio::test]
+    async fn the_stream_is_append_only() {
+        let pool = setup_pool().await;
+        let (agendas, _) = repos(&pool);
+        agendas.create_agenda(&new_agenda("Keep")).await.unwrap();
+
+        assert!(
+            sqlx::query("UPDATE agenda_event SET title = 'Changed'")
+                .execute(&pool)
+                .await
+                .is_err()
+        );
+        assert!(
+            sqlx::query("DELETE FROM agenda_event")
+                .execute(&pool)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn snapshots_match_a_full_fold() {
+        let pool = setup_pool().await;
+        let (agendas, logs) = repos(&pool);
+        let id = agendas.create_agenda(&new_agenda("Busy")).await.unwrap();
+        for n in 0..SNAPSHOT_EVERY {
+            logs.create_log(&new_log(id, LogType::CommonLog))
+                .await
+                .unwrap();
+            agendas
+                .update_agenda(
+                    id,
+                    &AgendaUpdate {
+                        title: Some(format!("Busy {n}")),
+                        terminate_at: None,
+                        agenda_status: None,
+                        actor: None,
+                    },
+                )
+                .await
+                .unwrap();
+        }
+        logs.create_log(&new_log(id, LogType::PutOff))
+            .await
+            .unwrap();
+
+        let snapshots: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM agenda_snapshot")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(snapshots, 1);
+        let from_snapshot = agendas.get_agenda_by_id(id).await.unwrap().unwrap();
+        assert_eq!(from_snapshot.title, format!("Busy {}", SNAPSHOT_EVERY - 1));
+
+        let summary = rebuild_snapshots(&pool).await.unwrap();
+        assert_eq!(
+            summary,
+            RebuildSummary {
+                events: 2 * SNAPSHOT_EVERY as u64 + 2,
+                agendas: 1
+            }
+        );
+        assert_eq!(
+            agendas.get_agenda_by_id(id).await.unwrap(),
+            Some(from_snapshot)
+        );
+        assert_eq!(
+            logs.get_logs_by_agenda_id(id).await.unwrap().len(),
+            SNAPSHOT_EVERY as usize + 1
+        );
+    }
+
+    #[tokio::test]
+    async fn writes_queue_webhooks() {
+        let pool = setup_pool().await;
+        let (agendas, logs) = repos(&pool);
+        crate::create_outbox(&pool)
+            .register_endpoints(&["https://a.example/hook".to_string()])
+            .await
+            .unwrap();
+        let id = agendas.create_agenda(&new_agenda("Hooked")).await.unwrap();
+        logs.create_log(&new_log(id, LogType::Terminate))
+            .await
+            .unwrap();
+
+        let events: Vec<(String, String)> =
+            sqlx::query_as("SELECT event, agenda_status FROM webhook_outbox ORDER BY id")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(
+            events,
+            [
+                ("agenda.created".to_string(), "pending".to_string()),
+                ("agenda.terminated".to_string(), "terminated".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn seeding_replays_the_tables() {
+        let pool = setup_pool().await;
+        let tables = SqliteAgendaRepo { pool: pool.clone() };
+        let table_logs = SqliteLogRepo { pool: pool.clone() };
+        let id = tables.create_agenda(&new_agenda("Legacy")).await.unwrap();
+        table_logs
+            .create_log(&new_log(id, LogType::Activate))
+            .await
+            .unwrap();
+        // The stored status disagrees with the logs and wins.
+        tables
+            .update_agenda(
+                id,
+                &AgendaUpdate {
+                    title: None,
+                    terminate_at: None,
+                    agenda_status: Some(AgendaStatus::Pending),
+                    actor: None,
+                },
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(seed_event_stream(&pool).await.unwrap(), 3);
+        let (agendas, logs) = repos(&pool);
+        assert_eq!(
+            agendas.get_agenda_by_id(id).await.unwrap(),
+            tables.get_agenda_by_id(id).await.unwrap()
+        );
+        assert_eq!(
+            logs.get_logs_by_agenda_id(id).await.unwrap(),
+            table_logs.get_logs_by_agenda_id(id).await.unwrap()
+        );
+        assert!(matches!(
+            seed_event_stream(&pool).await,
+            Err(RepoError::Stream(_))
+        ));
+    }
+}
@@ -0,0 +1,850 @@
+use async_trait::async_trait;
+use domain::*;
+use git2::{Repository, Signature};
+use jiff::Timestamp;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::{BTreeMap, btree_map};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::repo::outbox;
+use crate::repo::repo_error::RepoError;
+use crate::repo::store::ReplaceSummary;
+
+/// Where agenda documents live inside the repository.
+const AGENDA_DIR: &str = "agendas";
+
+/// One agenda as a TOML document, `agendas/<id>.toml`, with its logs
+/// appended as `[[log]]` tables in the order they were written.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Document {
+    id: Uuid,
+    title: String,
+    status: AgendaStatus,
+    initiate_at: Timestamp,
+    terminate_at: Timestamp,
+    #[serde(default, rename = "log", skip_serializing_if = "Vec::is_empty")]
+    logs: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    id: Uuid,
+    create_at: Timestamp,
+    log_type: LogType,
+    content: String,
+}
+
+impl Document {
+    fn new(agenda: &Agenda) -> Self {
+        Document {
+            id: agenda.id,
+            title: agenda.title.clone(),
+            status: agenda.agenda_status,
+            initiate_at: agenda.initiate_at,
+            terminate_at: agenda.terminate_at,
+            logs: Vec::new(),
+        }
+    }
+
+    fn parse(text: &str, name: &str) -> Result<Self, RepoError> {
+        toml::from_str(text).map_err(|e| RepoError::Document(format!("{name}: {e}")))
+    }
+
+    fn agenda(&self) -> Agenda {
+        Agenda {
+            id: self.id,
+            title: self.title.clone(),
+            agenda_status: self.status,
+            initiate_at: self.initiate_at,
+            terminate_at: self.terminate_at,
+        }
+    }
+
+    fn logs(&self) -> impl Iterator<Item = Log> + '_ {
+        self.logs.iter().map(|entry| Log {
+            id: entry.id,
+            agenda_id: self.id,
+            content: entry.content.clone(),
+            create_at: entry.create_at,
+            log_type: entry.log_type,
+        })
+    }
+}
+
+/// The path of an agenda's document, relative to the repository root.
+fn document_path(id: Uuid) -> PathBuf {
+    Path::new(AGENDA_DIR).join(format!("{id}.toml"))
+}
+
+/// Timestamps are kept to the millisecond, as in the database backends.
+fn now() -> Result<Timestamp, RepoError> {
+    Ok(Timestamp::from_millisecond(
+        Timestamp::now().as_millisecond(),
+    )?)
+}
+
+/// The first line of a log, short enough for a commit subject.
+fn summary(content: &str) -> String {
+    let line = content.lines().next().unwrap_or_default();
+    match line.char_indices().nth(50) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+/// A git working tree holding one document per agenda. Every change is
+/// committed with a message describing it, so the history can be diffed,
+/// reviewed and pushed like any other repository.
+#[derive(Clone)]
+pub struct GitStore {
+    dir: PathBuf,
+    /// Webhooks are still queued in the database's outbox.
+    pool: SqlitePool,
+    /// Held across a whole read-modify-commit, so concurrent writes do not
+    /// lose each other's changes, and by reads, so none sees a document
+    /// half written.
+    lock: Arc<Mutex<()>>,
+}
+
+impl GitStore {
+    /// Opens the repository at `dir`, creating it when it does not exist.
+    pub fn open(dir: &Path, pool: &SqlitePool) -> Result<Self, RepoError> {
+        if Repository::open(dir).is_err() {
+            Repository::init(dir)?;
+        }
+        std::fs::create_dir_all(dir.join(AGENDA_DIR))?;
+        Ok(GitStore {
+            dir: dir.to_path_buf(),
+            pool: pool.clone(),
+            lock: Arc::new(Mutex::new(())),
+        })
+    }
+
+    /// Runs `work` with the store locked on the blocking thread pool, since
+    /// git2 and file system calls block.
+    async fn run<T, F>(&self, work: F) -> Result<T, RepoError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&GitStore) -> Result<T, RepoError> + Send + 'static,
+    {
+        let _guard = self.lock.lock().await;
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || work(&store))
+            .await
+            .map_err(|e| RepoError::Io(std::io::Error::other(e)))?
+    }
+
+    fn read(&self, id: Uuid) -> Result<Option<Document>, RepoError> {
+        let path = document_path(id);
+        match std::fs::read_to_string(self.dir.join(&path)) {
+            Ok(text) => Document::parse(&text, &path.display().to_string()).map(Some),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Every document, oldest agenda first.
+    fn documents(&self) -> Result<Vec<Document>, RepoError> {
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(self.dir.join(AGENDA_DIR))? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "toml") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        paths
+            .iter()
+            .map(|path| {
+                let text = std::fs::read_to_string(path)?;
+                Document::parse(&text, &path.display().to_string())
+            })
+            .collect()
+    }
+
+    fn find_log(&self, id: Uuid) -> Result<Option<Document>, RepoError> {
+        Ok(self
+            .documents()?
+            .into_iter()
+            .find(|document| document.logs.iter().any(|entry| entry.id == id)))
+    }
+
+    /// Writes `document` (or removes the agenda's file when there is none)
+    /// and commits the change with `message`, followed by an `Actor:` trailer
+    /// when the caller said who made it.
+    fn commit(
+        &self,
+        id: Uuid,
+        document: Option<&Document>,
+        message: &str,
+        actor: Option<&str>,
+    ) -> Result<(), RepoError> {
+        self.commit_all(&[(id, document)], message, actor)
+    }
+
+    /// Writes or removes several agendas' documents in a single commit.
+    fn commit_all(
+        &self,
+        changes: &[(Uuid, Option<&Document>)],
+        message: &str,
+        actor: Option<&str>,
+    ) -> Result<(), RepoError> {
+        let repo = Repository::open(&self.dir)?;
+        let mut index = repo.index()?;
+        for (id, document) in changes {
+            let path = document_path(*id);
+            match document {
+                Some(document) => {
+                    let text = toml::to_string(document)
+                        .map_err(|e| RepoError::Document(format!("{}: {e}", path.display())))?;
+                    std::fs::write(self.dir.join(&path), text)?;
+                    index.add_path(&path)?;
+                }
+                None => {
+                    std::fs::remove_file(self.dir.join(&path))?;
+                    index.remove_path(&path)?;
+                }
+            }
+        }
+        index.write()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        let signature = repo
+            .signature()
+            .or_else(|_| Signature::now("finiate", "finiate@localhost"))?;
+        let parent = match repo.head() {
+            Ok(head) => Some(head.peel_to_commit()?),
+            Err(_) => None,
+        };
+        let message = match actor {
+            Some(actor) => format!("{message}\n\nActor: {actor}\n"),
+            None => format!("{message}\n"),
+        };
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            &message,
+            &tree,
+            &parent.iter().collect::<Vec<_>>(),
+        )?;
+        Ok(())
+    }
+
+    /// The agenda's field changes, read back from the commits that touched
+    /// its document, oldest first.
+    fn revisions(&self, id: Uuid) -> Result<Vec<AgendaRevision>, RepoError> {
+        let repo = Repository::open(&self.dir)?;
+        if repo.head().is_err() {
+            return Ok(Vec::new());
+        }
+        let path = document_path(id);
+        let mut walk = repo.revwalk()?;
+        walk.push_head()?;
+        walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE)?;
+
+        let mut revisions = Vec::new();
+        let mut previous: Option<(git2::Oid, Agenda)> = None;
+        for oid in walk {
+            let commit = repo.find_commit(oid?)?;
+            let Ok(entry) = commit.tree()?.get_path(&path) else {
+                previous = None;
+                continue;
+            };
+            if previous
+                .as_ref()
+                .is_some_and(|(blob, _)| *blob == entry.id())
+            {
+                continue;
+            }
+            let blob = repo.find_blob(entry.id())?;
+            let text = String::from_utf8_lossy(blob.content());
+            let agenda = Document::parse(&text, &path.display().to_string())?.agenda();
+            if let Some((_, before)) = &previous {
+                let update = AgendaUpdate {
+                    title: Some(agenda.title.clone()),
+                    agenda_status: Some(agenda.agenda_status),
+                    terminate_at: Some(agenda.terminate_at),
+                    actor: None,
+                };
+                let actor = commit.message().and_then(|message| {
+                    message
+                        .lines()
+                        .find_map(|line| line.strip_prefix("Actor: "))
+                        .map(str::to_string)
+                });
+                let revised_at = Timestamp::from_second(commit.time().seconds())?;
+                revisions.extend(
+                    update
+                        .changes(before)
+                        .into_iter()
+                        .map(|change| AgendaRevision {
+                            agenda_id: id,
+                            revised_at,
+                            actor: actor.clone(),
+                            change,
+                        }),
+                );
+            }
+            previous = Some((entry.id(), agenda));
+        }
+        Ok(revisions)
+    }
+
+    async fn enqueue(
+        &self,
+        event: &str,
+        agenda: &Agenda,
+        log: Option<&Log>,
+    ) -> Result<(), RepoError> {
+        let mut conn = self.pool.acquire().await?;
+        outbox::enqueue(&mut conn, event, agenda, log).await
+    }
+}
+
+pub struct GitAgendaRepo {
+    pub store: GitStore,
+}
+
+pub struct GitLogRepo {
+    pub store: GitStore,
+}
+
+#[async_trait]
+impl AgendaRepo for GitAgendaRepo {
+    type Error = RepoError;
+
+    async fn create_agenda(&self, agenda: &AgendaCreate) -> Result<Uuid, Self::Error> {
+        let created = Agenda {
+            id: Uuid::now_v7(),
+            title: agenda.title.clone(),
+            agenda_status: agenda.agenda_status,
+            initiate_at: now()?,
+            terminate_at: agenda.terminate_at,
+        };
+        self.add(&created, true).await?;
+        Ok(created.id)
+    }
+
+    async fn insert_agenda(&self, agenda: &Agenda) -> Result<(), Self::Error> {
+        self.add(agenda, false).await
+    }
+
+    async fn delete_agenda_by_id(&self, id: Uuid) -> Result<(), Self::Error> {
+        self.store
+            .run(move |store| {
+                if let Some(document) = store.read(id)? {
+                    store.commit(
+                        id,
+                        None,
+                        &format!("Delete agenda \"{}\"", document.title),
+                        None,
+                    )?;
+                }
+                Ok(())
+            })
+            .await
+    }
+
+    async fn update_agenda(&self, id: Uuid, update: &AgendaUpdate) -> Result<(), Self::Error> {
+        let update = AgendaUpdate {
+            title: update.title.clone(),
+            agenda_status: update.agenda_status,
+            terminate_at: update.terminate_at,
+            actor: update.actor.clone(),
+        };
+        self.store
+            .run(move |store| {
+                let Some(mut document) = store.read(id)? else {
+                    return Ok(());
+                };
+                let changes = update.changes(&document.agenda());
+                if changes.is_empty() {
+                    return Ok(());
+                }
+                let mut message = format!("Update agenda \"{}\"\n", document.title);
+                for change in &changes {
+                    match change {
+                        FieldChange::Title { old, new } => {
+                            message.push_str(&format!("\ntitle: \"{old}\" -> \"{new}\""));
+                            document.title = new.clone();
+                        }
+                        FieldChange::Status { old, new } => {
+                            message.push_str(&format!("\nstatus: {old} -> {new}"));
+                            document.status = *new;
+                        }
+                        FieldChange::Deadline { old, new } => {
+                            message.push_str(&format!("\ndeadline: {old} -> {new}"));
+                            document.terminate_at = *new;
+                        }
+                    }
+                }
+                store.commit(id, Some(&document), &message, update.actor.as_deref())
+            })
+            .await
+    }
+
+    async fn get_agenda_revisions(&self, id: Uuid) -> Result<Vec<AgendaRevision>, Self::Error> {
+        self.store.run(move |store| store.revisions(id)).await
+    }
+
+    async fn get_agenda_by_id(&self, id: Uuid) -> Result<Option<Agenda>, Self::Error> {
+        let document = self.store.run(move |store| store.read(id)).await?;
+        Ok(document.map(|document| document.agenda()))
+    }
+
+    async fn get_agendas_by_title(&self, title: &str) -> Result<Vec<Agenda>, Self::Error> {
+        self.filter(|agenda| agenda.title == title).await
+    }
+
+    async fn get_agendas_by_status(
+        &self,
+        status: Option<&str>,
+    ) -> Result<Vec<Agenda>, Self::Error> {
+        self.filter(|agenda| status.is_none_or(|status| agenda.agenda_status.to_string() == status))
+            .await
+    }
+
+    async fn count_agendas_by_status(&self, status: Option<&str>) -> Result<u64, Self::Error> {
+        Ok(self.get_agendas_by_status(status).await?.len() as u64)
+    }
+
+    async fn get_agendas_by_terminate_time_range(
+        &self,
+        start: Timestamp,
+        end: Timestamp,
+    ) -> Result<Vec<Agenda>, Self::Error> {
+        self.filter(|agenda| (start..=end).contains(&agenda.terminate_at))
+            .await
+    }
+
+    async fn get_agendas_by_initiate_time_range(
+        &self,
+        start: Timestamp,
+        end: Timestamp,
+    ) -> Result<Vec<Agenda>, Self::Error> {
+        self.filter(|agenda| (start..=end).contains(&agenda.initiate_at))
+            .await
+    }
+}
+
+impl GitAgendaRepo {
+    /// Commits a new agenda; only `news` queues its webhook, since inserted
+    /// agendas already exist elsewhere.
+    async fn add(&self, agenda: &Agenda, news: bool) -> Result<(), RepoError> {
+        let document = Document::new(agenda);
+        self.store
+            .run(move |store| {
+                if store.read(document.id)?.is_some() {
+                    return Err(RepoError::Duplicate(format!("agenda {}", document.id)));
+                }
+                store.commit(
+                    document.id,
+                    Some(&document),
+                    &format!("Add agenda \"{}\"", document.title),
+                    None,
+                )
+            })
+            .await?;
+        if !news {
+            return Ok(());
+        }
+        self.store.enqueue("agenda.created", agenda, None).await
+    }
+
+    /// Removes every agenda's document and writes `agendas` and `logs` in
+    /// their place, all in one commit.
+    pub(crate) async fn replace_all(
+        &self,
+        agendas: &[Agenda],
+        logs: &[Log],
+    ) -> Result<ReplaceSummary, RepoError> {
+        let mut documents: BTreeMap<Uuid, Document> = agendas
+            .iter()
+            .map(|agenda| (agenda.id, Document::new(agenda)))
+            .collect();
+        for log in logs {
+            let Some(document) = documents.get_mut(&log.agenda_id) else {
+                return Err(RepoError::Document(format!(
+                    "log {} refers to agenda {}, which does not exist",
+                    log.id, log.agenda_id
+                )));
+            };
+            document.logs.push(Entry {
+                id: log.id,
+                create_at: log.create_at,
+                log_type: log.log_type,
+                content: log.content.clone(),
+            });
+        }
+        let message = format!("Replace every agenda with {} from a backup", agendas.len());
+        self.store
+            .run(move |store| {
+                let live = store.documents()?;
+                let mut changes: Vec<_> = live
+                    .iter()
+                    .filter(|document| !documents.contains_key(&document.id))
+                    .map(|document| (document.id, None))
+                    .collect();
+                changes.extend(documents.iter().map(|(id, document)| (*id, Some(document))));
+                store.commit_all(&changes, &message, None)?;
+                Ok(ReplaceSummary {
+                    agendas: live.len() as u64,
+                    logs: live.iter().map(|document| document.logs.len() as u64).sum(),
+                })
+            })
+            .await
+    }
+
+    /// Writes `agendas` and `logs`, none of which may exist yet, all in one
+    /// commit.
+    pub(crate) async fn merge_all(
+        &self,
+        agendas: &[Agenda],
+        logs: &[Log],
+    ) -> Result<(), RepoError> {
+        let (agendas, logs) = (agendas.to_vec(), logs.to_vec());
+        let message = format!(
+            "Merge {} agenda(s) and {} log(s) from a backup",
+            agendas.len(),
+            logs.len()
+        );
+        self.store
+            .run(move |store| {
+                let mut documents = BTreeMap::new();
+                for agenda in &agendas {
+                    if store.read(agenda.id)?.is_some() {
+                        return Err(RepoError::Duplicate(format!("agenda {}", agenda.id)));
+                    }
+                    documents.insert(agenda.id, Document::new(agenda));
+                }
+                for log in logs {
+                    if store.find_log(log.id)?.is_some() {
+                        return Err(RepoError::Duplicate(format!("log {}", log.id)));
+                    }
+                    let document = match documents.entry(log.agenda_id) {
+                        btree_map::Entry::Occupied(entry) => entry.into_mut(),
+                        btree_map::Entry::Vacant(entry) => match store.read(log.agenda_id)? {
+                            Some(document) => entry.insert(document),
+                            None => {
+                                return Err(RepoError::Document(format!(
+                                    "log {} refers to agenda {}, which does not exist",
+                                    log.id, log.agenda_id
+                                )));
+                            }
+                        },
+                    };
+                    document.logs.push(Entry {
+                        id: log.id,
+                        create_at: log.create_at,
+                        log_type: log.log_type,
+                        content: log.content,
+                    });
+                }
+                if documents.is_empty() {
+                    return Ok(());
+                }
+                let changes: Vec<_> = documents
+                    .iter()
+                    .map(|(id, document)| (*id, Some(document)))
+                    .collect();
+                store.commit_all(&changes, &message, None)
+            })
+            .await
+    }
+
+    async fn filter(&self, keep: impl Fn(&Agenda) -> bool) -> Result<Vec<Agenda>, RepoError> {
+        Ok(self
+            .store
+            .run(|store| store.documents())
+            .await?
+            .iter()
+            .map(Document::agenda)
+            .filter(|agenda| keep(agenda))
+            .collect())
+    }
+}
+
+impl GitLogRepo {
+    /// Commits a new log; only `news` queues its webhook.
+    async fn add(&self, log: &Log, news: bool) -> Result<(), RepoError> {
+        let new = log.clone();
+        let agenda = self
+            .store
+            .run(move |store| {
+                if store.find_log(new.id)?.is_some() {
+                    return Err(RepoError::Duplicate(format!("log {}", new.id)));
+                }
+                let Some(mut document) = store.read(new.agenda_id)? else {
+                    return Err(RepoError::Document(format!(
+                        "log {} refers to agenda {}, which does not exist",
+                        new.id, new.agenda_id
+                    )));
+                };
+                let mut message = format!("Log {} on \"{}\"", new.log_type, document.title);
+                if !new.content.trim().is_empty() {
+                    message.push_str(&format!(": {}", summary(&new.content)));
+                }
+                document.logs.push(Entry {
+                    id: new.id,
+                    create_at: new.create_at,
+                    log_type: new.log_type,
+                    content: new.content,
+                });
+                store.commit(new.agenda_id, Some(&document), &message, None)?;
+                Ok(document.agenda())
+            })
+            .await?;
+        if !news {
+            return Ok(());
+        }
+        self.store
+            .enqueue(outbox::log_event(log.log_type), &agenda, Some(log))
+            .await
+    }
+}
+
+#[async_trait]
+impl LogRepo for GitLogRepo {
+    type Error = RepoError;
+
+    async fn create_log(&self, new_log: &LogCreate) -> Result<Uuid, Self::Error> {
+        let log = Log {
+            id: Uuid::now_v7(),
+            agenda_id: new_log.agenda_id,
+            content: new_log.content.clone(),
+            create_at: now()?,
+            log_type: new_log.log_type,
+        };
+        self.add(&log, true).await?;
+        Ok(log.id)
+    }
+
+    async fn insert_log(&self, log: &Log) -> Result<(), Self::Error> {
+        self.add(log, false).await
+    }
+
+    async fn delete_log(&self, id: Uuid) -> Result<(), Self::Error> {
+        self.store
+            .run(move |store| {
+                let Some(mut document) = store.find_log(id)? else {
+                    return Ok(());
+                };
+                document.logs.retain(|entry| entry.id != id);
+                let message = format!("Delete log {id} from \"{}\"", document.title);
+                store.commit(document.id, Some(&document), &message, None)
+            })
+            .await
+    }
+
+    async fn get_log_by_id(&self, id: Uuid) -> Result<Option<Log>, Self::Error> {
+        let document = self.store.run(move |store| store.find_log(id)).await?;
+        Ok(document.and_then(|document| document.logs().find(|log| log.id == id)))
+    }
+
+    async fn get_logs_by_agenda_id(&self, agenda_id: Uuid) -> Result<Vec<Log>, Self::Error> {
+        let document = self.store.run(move |store| store.read(agenda_id)).await?;
+        Ok(document
+            .map(|document| document.logs().collect())
+            .unwrap_or_default())
+    }
+
+    async fn get_logs_by_time_range(
+        &self,
+        start: Timestamp,
+        end: Timestamp,
+    ) -> Result<Vec<Log>, Self::Error> {
+        Ok(self
+            .store
+            .run(|store| store.documents())
+            .await?
+            .iter()
+            .flat_map(Document::logs)
+            .filter(|log| (start..=end).contains(&log.create_at))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jiff::ToSpan;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn setup_pool() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .expect("create in-memory sqlite pool");
+
+        let crate_dir = env!("CARGO_MANIFEST_DIR");
+        let migrations = std::path::Path::new(crate_dir).join("migrations");
+        sqlx::migrate::Migrator::new(migrations)
+            .await
+            .expect("load migrations")
+            .run(&pool)
+            .await
+            .expect("run migrations");
+
+        pool
+    }
+
+    async fn setup() -> (tempfile::TempDir, GitAgendaRepo, GitLogRepo) {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let store = GitStore::open(dir.path(), &setup_pool().await).expect("open store");
+        let agendas = GitAgendaRepo {
+            store: store.clone(),
+        };
+        (dir, agendas, GitLogRepo { store })
+    }
+
+    fn history(dir: &Path) -> Vec<String> {
+        let repo = Repository::open(dir).unwrap();
+        let mut walk = repo.revwalk().unwrap();
+        walk.push_head().unwrap();
+        walk.map(|oid| {
+            let commit = repo.find_commit(oid.unwrap()).unwrap();
+            commit.summary().unwrap().to_string()
+        })
+        .collect()
+    }
+
+    #[tokio::test]
+    async fn every_change_is_a_commit_on_a_readable_document() {
+        let (dir, agendas, logs) = setup().await;
+        let id = agendas
+            .create_agenda(&AgendaCreate {
+                title: "Write report".to_string(),
+                agenda_status: AgendaStatus::Pending,
+                terminate_at: now().unwrap() + 2.hours(),
+            })
+            .await
+            .unwrap();
+        let log_id = logs
+            .create_log(&LogCreate {
+                agenda_id: id,
+                content: "Outline done\nNext: figures".to_string(),
+                log_type: LogType::CommonLog,
+            })
+            .await
+            .unwrap();
+
+        let agenda = agendas.get_agenda_by_id(id).await.unwrap().unwrap();
+        assert_eq!(agenda.title, "Write report");
+        let read = logs.get_logs_by_agenda_id(id).await.unwrap();
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[0].id, log_id);
+        assert_eq!(read[0].content, "Outline done\nNext: figures");
+        assert_eq!(
+            agendas
+                .count_agendas_by_status(Some("pending"))
+                .await
+                .unwrap(),
+            1
+        );
+
+        let text = std::fs::read_to_string(dir.path().join(document_path(id))).unwrap();
+        assert!(text.contains("title = \"Write report\""));
+        assert!(text.contains("[[log]]"));
+
+        logs.delete_log(log_id).await.unwrap();
+        agendas.delete_agenda_by_id(id).await.unwrap();
+        assert!(agendas.get_agenda_by_id(id).await.unwrap().is_none());
+        assert_eq!(
+            history(dir.path()),
+            [
+                "Delete agenda \"Write report\"".to_string(),
+                format!("Delete log {log_id} from \"Write report\""),
+                "Log common_log on \"Write report\": Outline done".to_string(),
+                "Add agenda \"Write report\"".to_string(),
+            ]
+        );
+
+        let missing = logs
+            .create_log(&LogCreate {
+                agenda_id: id,
+                content: "late".to_string(),
+                log_type: LogType::CommonLog,
+            })
+            .await;
+        assert!(matches!(missing, Err(RepoError::Document(_))));
+    }
+
+    #[tokio::test]
+    async fn revisions_are_read_from_the_history() {
+        let (_dir, agendas, _) = setup().await;
+        let id = agendas
+            .create_agenda(&AgendaCreate {
+                title: "Draft".to_string(),
+                agenda_status: AgendaStatus::Pending,
+                terminate_at: now().unwrap(),
+            })
+            .await
+            .unwrap();
+        agendas
+            .update_agenda(
+                id,
+                &AgendaUpdate {
+                    title: Some("Final".to_string()),
+                    agenda_status: Some(AgendaStatus::Ongoing),
+                    terminate_at: None,
+                    actor: Some("cli".to_string()),
+                },
+            )
+            .await
+            .unwrap();
+
+        let revisions = agendas.get_agenda_revisions(id).await.unwrap();
+        assert_eq!(revisions.len(), 2);
+        assert_eq!(
+            revisions[0].change,
+            FieldChange::Title {
+                old: "Draft".to_string(),
+                new: "Final".to_string()
+            }
+        );
+        assert_eq!(revisions[1].actor.as_deref(), Some("cli"));
+        assert!(matches!(
+            agendas
+                .insert_agenda(&agendas.get_agenda_by_id(id).await.unwrap().unwrap())
+                .await,
+            Err(RepoError::Duplicate(_))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_logs_on_one_agenda_are_all_kept() {
+        let (dir, agendas, logs) = setup().await;
+        let id = agendas
+            .create_agenda(&AgendaCreate {
+                title: "Busy".to_string(),
+                agenda_status: AgendaStatus::Ongoing,
+                terminate_at: now().unwrap() + 1.hours(),
+            })
+            .await
+            .unwrap();
+        let logs = Arc::new(logs);
+        let writers: Vec<_> = (0..8)
+            .map(|n| {
+                let logs = logs.clone();
+                tokio::spawn(async move {
+                    logs.create_log(&LogCreate {
+                        agenda_id: id,
+                        content: format!("note {n}"),
+                        log_type: LogType::CommonLog,
+                    })
+                    .await
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.await.unwrap().unwrap();
+        }
+        assert_eq!(logs.get_logs_by_agenda_id(id).await.unwrap().len(), 8);
+        assert_eq!(history(dir.path()).len(), 9);
+    }
+}
@@ -0,0 +1,482 @@
+use jiff::Timestamp;
+use sqlx::{Executor, FromRow, SqliteConnection, SqlitePool};
+use uuid::Uuid;
+
+use crate::repo::repo_error::RepoError;
+
+/// How many operations are kept for `undo`.
+const KEEP_OPERATIONS: i64 = 100;
+
+/// Installed on every connection of a pool by [`attach`]. The triggers are
+/// TEMP, so they only see that connection's writes, and they journal under
+/// the newest open operation of the pool's owner. Each step also keeps a
+/// guard: a query telling whether the row is still as the step left it.
+const TEMP_TRIGGERS: &str = "
+CREATE TEMP VIEW IF NOT EXISTS journal_open AS
+SELECT MAX(id) AS id FROM main.operation
+WHERE open = 1 AND owner = (SELECT owner FROM temp.journal_owner);
+
+CREATE TEMP TRIGGER IF NOT EXISTS journal_agenda_insert
+AFTER INSERT ON main.agenda
+WHEN (SELECT id FROM journal_open) IS NOT NULL
+BEGIN
+    INSERT INTO operation_step (operation_id, statement, guard)
+    SELECT id, 'DELETE FROM agenda WHERE id = ' || quote(NEW.id),
+        'SELECT EXISTS (SELECT 1 FROM agenda WHERE id = ' || quote(NEW.id)
+        || ' AND title IS ' || quote(NEW.title)
+        || ' AND agenda_status IS ' || quote(NEW.agenda_status)
+        || ' AND initiate_at IS ' || quote(NEW.initiate_at)
+        || ' AND terminate_at IS ' || quote(NEW.terminate_at)
+        || ' AND deleted_at IS ' || quote(NEW.deleted_at) || ')'
+    FROM journal_open;
+END;
+
+CREATE TEMP TRIGGER IF NOT EXISTS journal_agenda_update
+AFTER UPDATE ON main.agenda
+WHEN (SELECT id FROM journal_open) IS NOT NULL
+BEGIN
+    INSERT INTO operation_step (operation_id, statement, guard)
+    SELECT id, 'UPDATE agenda SET id = ' || quote(OLD.id)
+        || ', title = ' || quote(OLD.title)
+        || ', agenda_status = ' || quote(OLD.agenda_status)
+        || ', initiate_at = ' || quote(OLD.initiate_at)
+        || ', terminate_at = ' || quote(OLD.terminate_at)
+        || ', deleted_at = ' || quote(OLD.deleted_at)
+        || ' WHERE id = ' || quote(NEW.id),
+        'SELECT EXISTS (SELECT 1 FROM agenda WHERE id = ' || quote(NEW.id)
+        || ' AND title IS ' || quote(NEW.title)
+        || ' AND agenda_status IS ' || quote(NEW.agenda_status)
+        || ' AND initiate_at IS ' || quote(NEW.initiate_at)
+        || ' AND terminate_at IS ' || quote(NEW.terminate_at)
+        || ' AND deleted_at IS ' || quote(NEW.deleted_at) || ')'
+    FROM journal_open;
+END;
+
+CREATE TEMP TRIGGER IF NOT EXISTS journal_agenda_delete
+AFTER DELETE ON main.agenda
+WHEN (SELECT id FROM journal_open) IS NOT NULL
+BEGIN
+    INSERT INTO operation_step (operation_id, statement, guard)
+    SELECT id, 'INSERT INTO agenda (id, title, agenda_status, initiate_at, terminate_at, deleted_at) VALUES ('
+        || quote(OLD.id) || ', ' || quote(OLD.title) || ', ' || quote(OLD.agenda_status) || ', '
+        || quote(OLD.initiate_at) || ', ' || quote(OLD.terminate_at) || ', '
+        || quote(OLD.deleted_at) || ')',
+        'SELECT NOT EXISTS (SELECT 1 FROM agenda WHERE id = ' || quote(OLD.id) || ')'
+    FROM journal_open;
+END;
+
+CREATE TEMP TRIGGER IF NOT EXISTS journal_log_insert
+AFTER INSERT ON main.log
+WHEN (SELECT id FROM journal_open) IS NOT NULL
+BEGIN
+    INSERT INTO operation_step (operation_id, statement, guard)
+    SELECT id, 'DELETE FROM log WHERE id = ' || quote(NEW.id),
+        'SELECT EXISTS (SELECT 1 FROM log WHERE id = ' || quote(NEW.id)
+        || ' AND create_at IS ' || quote(NEW.create_at)
+        || ' AND content IS ' || quote(NEW.content)
+        || ' AND log_type IS ' || quote(NEW.log_type)
+        || ' AND agenda_id IS ' || quote(NEW.agenda_id)
+        || ' AND deleted_at IS ' || quote(NEW.deleted_at) || ')'
+    FROM journal_open;
+END;
+
+CREATE TEMP TRIGGER IF NOT EXISTS journal_log_update
+AFTER UPDATE ON main.log
+WHEN (SELECT id FROM journal_open) IS NOT NULL
+BEGIN
+    INSERT INTO operation_step (operation_id, statement, guard)
+    SELECT id, 'UPDATE log SET id = ' || quote(OLD.id)
+        || ', create_at = ' || quote(OLD.create_at)
+        || ', content = ' || quote(OLD.content)
+        || ', log_type = ' || quote(OLD.log_type)
+        || ', agenda_id = ' || quote(OLD.agenda_id)
+        || ', deleted_at = ' || quote(OLD.deleted_at)
+        || ' WHERE id = ' || quote(NEW.id),
+        'SELECT EXISTS (SELECT 1 FROM log WHERE id = ' || quote(NEW.id)
+        || ' AND create_at IS ' || quote(NEW.create_at)
+        || ' AND content IS ' || quote(NEW.content)
+        || ' AND log_type IS ' || quote(NEW.log_type)
+        || ' AND agenda_id IS ' || quote(NEW.agenda_id)
+        || ' AND deleted_at IS ' || quote(NEW.deleted_at) || ')'
+    FROM journal_open;
+END;
+
+CREATE TEMP TRIGGER IF NOT EXISTS journal_log_delete
+AFTER DELETE ON main.log
+WHEN (SELECT id FROM journal_open) IS NOT NULL
+BEGIN
+    INSERT INTO operation_step (operation_id, statement, guard)
+    SELECT id, 'INSERT INTO log (id, create_at, content, log_type, agenda_id, deleted_at) VALUES ('
+        || quote(OLD.id) || ', ' || quote(OLD.create_at) || ', ' || quote(OLD.content) || ', '
+        || quote(OLD.log_type) || ', ' || quote(OLD.agenda_id) || ', '
+        || quote(OLD.deleted_at) || ')',
+        'SELECT NOT EXISTS (SELECT 1 FROM log WHERE id = ' || quote(OLD.id) || ')'
+    FROM journal_open;
+END;
+
+CREATE TEMP TRIGGER IF NOT EXISTS journal_revision_insert
+AFTER INSERT ON main.agenda_revision
+WHEN (SELECT id FROM journal_open) IS NOT NULL
+BEGIN
+    INSERT INTO operation_step (operation_id, statement, guard)
+    SELECT id, 'DELETE FROM agenda_revision WHERE id = ' || quote(NEW.id),
+        'SELECT EXISTS (SELECT 1 FROM agenda_revision WHERE id = ' || quote(NEW.id) || ')'
+    FROM journal_open;
+END;
+
+CREATE TEMP TRIGGER IF NOT EXISTS journal_revision_delete
+AFTER DELETE ON main.agenda_revision
+WHEN (SELECT id FROM journal_open) IS NOT NULL
+BEGIN
+    INSERT INTO operation_step (operation_id, statement, guard)
+    SELECT id, 'INSERT INTO agenda_revision (id, agenda_id, revised_at, actor, field, old_value, new_value) VALUES ('
+        || quote(OLD.id) || ', ' || quote(OLD.agenda_id) || ', ' || quote(OLD.revised_at) || ', '
+        || quote(OLD.actor) || ', ' || quote(OLD.field) || ', ' || quote(OLD.old_value) || ', '
+        || quote(OLD.new_value) || ')',
+        'SELECT NOT EXISTS (SELECT 1 FROM agenda_revision WHERE id = ' || quote(OLD.id) || ')'
+    FROM journal_open;
+END;
+";
+
+/// Journals the writes `conn` makes under operations begun by `owner`. The
+/// schema must be migrated already.
+pub(crate) async fn attach(conn: &mut SqliteConnection, owner: Uuid) -> Result<(), sqlx::Error> {
+    let sql = format!(
+        "CREATE TEMP TABLE IF NOT EXISTS journal_owner (owner TEXT NOT NULL);
+        DELETE FROM temp.journal_owner;
+        INSERT INTO temp.journal_owner (owner) VALUES ('{owner}');
+        {TEMP_TRIGGERS}"
+    );
+    conn.execute(sqlx::raw_sql(&sql)).await?;
+    Ok(())
+}
+
+/// A journaled command and how many row changes it made.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operation {
+    pub id: i64,
+    pub command: String,
+    pub started_at: Timestamp,
+    pub changes: u64,
+}
+
+#[derive(FromRow)]
+struct DbOperation {
+    id: i64,
+    command: String,
+    started_at: i64,
+    changes: i64,
+}
+
+impl DbOperation {
+    fn to_operation(&self) -> Result<Operation, RepoError> {
+        Ok(Operation {
+            id: self.id,
+            command: self.command.clone(),
+            started_at: Timestamp::from_millisecond(self.started_at)?,
+            changes: u64::try_from(self.changes).unwrap_or_default(),
+        })
+    }
+}
+
+/// The operation journal. Triggers record how to reverse every change to
+/// agendas, logs and revisions that this pool's connections make while one
+/// of its operations is open.
+pub struct SqliteJournal {
+    pub pool: SqlitePool,
+}
+
+impl SqliteJournal {
+    /// Opens an operation for `command`; changes are journaled under it
+    /// until [`SqliteJournal::finish`].
+    pub async fn begin(&self, command: &str) -> Result<i64, RepoError> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        // Only an operation of this pool that was never finished is closed;
+        // other processes' are theirs to finish.
+        sqlx::query(
+            "UPDATE operation SET open = 0
+            WHERE open = 1 AND owner = (SELECT owner FROM temp.journal_owner)",
+        )
+        .execute(&mut *tx)
+        .await?;
+        let id = sqlx::query(
+            "INSERT INTO operation (command, started_at, owner)
+            SELECT ?, ?, owner FROM temp.journal_owner",
+        )
+        .bind(command)
+        .bind(Timestamp::now().as_millisecond())
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    /// Closes an operation, dropping it when it changed nothing, and forgets
+    /// the oldest operations beyond the ones kept.
+    pub async fn finish(&self, id: i64) -> Result<(), RepoError> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        sqlx::query("UPDATE operation SET open = 0 WHERE id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            "DELETE FROM operation WHERE id = ?1
+                AND NOT EXISTS (SELECT 1 FROM operation_step WHERE operation_id = ?1)",
+        )
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM operation WHERE id <= (SELECT MAX(id) FROM operation) - ?")
+            .bind(KEEP_OPERATIONS)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// The most recent operation that has not been undone.
+    pub async fn last(&self) -> Result<Option<Operation>, RepoError> {
+        let row = sqlx::query_as::<_, DbOperation>(
+            "SELECT o.id, o.command, o.started_at,
+                (SELECT COUNT(*) FROM operation_step s WHERE s.operation_id = o.id) AS changes
+            FROM operation o
+            WHERE o.open = 0 AND o.undone_at IS NULL
+            ORDER BY o.id DESC
+            LIMIT 1",
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        row.as_ref().map(DbOperation::to_operation).transpose()
+    }
+
+    /// Reverses the most recent operation that has not been undone yet.
+    pub async fn undo(&self) -> Result<Option<Operation>, RepoError> {
+        let Some(operation) = self.last().await? else {
+            return Ok(None);
+        };
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        let steps: Vec<(String, Option<String>)> = sqlx::query_as(
+            "SELECT statement, guard FROM operation_step WHERE operation_id = ? ORDER BY seq DESC",
+        )
+        .bind(operation.id)
+        .fetch_all(&mut *tx)
+        .await?;
+        for (statement, guard) in &steps {
+            // Writing back a saved row would silently drop whatever changed
+            // it since, like another process's command or a sync.
+            if let Some(guard) = guard
+                && !sqlx::query_scalar::<_, bool>(guard)
+                    .fetch_one(&mut *tx)
+                    .await?
+            {
+                return Err(RepoError::Conflict(format!(
+                    "cannot undo `{}`: what it changed was changed again since",
+                    operation.command
+                )));
+            }
+            sqlx::raw_sql(statement).execute(&mut *tx).await?;
+        }
+        sqlx::query("UPDATE operation SET undone_at = ? WHERE id = ?")
+            .bind(Timestamp::now().as_millisecond())
+            .bind(operation.id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(Some(operation))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::agenda_repo::SqliteAgendaRepo;
+    use crate::repo::log_repo::SqliteLogRepo;
+    use crate::repo::trash::SqliteTrash;
+    use domain::*;
+    use jiff::ToSpan;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn setup_pool() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .expect("create in-memory sqlite pool");
+
+        let crate_dir = env!("CARGO_MANIFEST_DIR");
+        let migrations = std::path::Path::new(crate_dir).join("migrations");
+        sqlx::migrate::Migrator::new(migrations)
+            .await
+            .expect("load migrations")
+            .run(&pool)
+            .await
+            .expect("run migrations");
+        attach(&mut pool.acquire().await.unwrap(), Uuid::now_v7())
+            .await
+            .expect("attach the journal");
+
+        pool
+    }
+
+    async fn create_agenda(pool: &SqlitePool, title: &str) -> Uuid {
+        SqliteAgendaRepo { pool: pool.clone() }
+            .create_agenda(&AgendaCreate {
+                title: title.to_string(),
+                agenda_status: AgendaStatus::Pending,
+                terminate_at: Timestamp::now() + 1.hour(),
+            })
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn undo_reverses_operations_newest_first() {
+        let pool = setup_pool().await;
+        let journal = SqliteJournal { pool: pool.clone() };
+        let agendas = SqliteAgendaRepo { pool: pool.clone() };
+        let logs = SqliteLogRepo { pool: pool.clone() };
+
+        let op = journal.begin("slot add").await.unwrap();
+        let id = agendas
+            .create_agenda(&AgendaCreate {
+                title: "Write 'quoted' notes".to_string(),
+                agenda_status: AgendaStatus::Pending,
+                terminate_at: Timestamp::now() + 1.hour(),
+            })
+            .await
+            .unwrap();
+        journal.finish(op).await.unwrap();
+
+        let op = journal.begin("slot set").await.unwrap();
+        agendas
+            .update_agenda(
+                id,
+                &AgendaUpdate {
+                    title: None,
+                    agenda_status: Some(AgendaStatus::Ongoing),
+                    terminate_at: None,
+                    actor: None,
+                },
+            )
+            .await
+            .unwrap();
+        logs.create_log(&LogCreate {
+            agenda_id: id,
+            content: String::new(),
+            log_type: LogType::Activate,
+        })
+        .await
+        .unwrap();
+        journal.finish(op).await.unwrap();
+
+        // Read-only commands leave no operation behind.
+        let op = journal.begin("show").await.unwrap();
+        journal.finish(op).await.unwrap();
+
+        let undone = journal.undo().await.unwrap().unwrap();
+        assert_eq!((undone.command.as_str(), undone.changes), ("slot set", 3));
+        let agenda = agendas.get_agenda_by_id(id).await.unwrap().unwrap();
+        assert_eq!(agenda.agenda_status, AgendaStatus::Pending);
+        assert!(logs.get_logs_by_agenda_id(id).await.unwrap().is_empty());
+        assert!(agendas.get_agenda_revisions(id).await.unwrap().is_empty());
+
+        let undone = journal.undo().await.unwrap().unwrap();
+        assert_eq!(undone.command, "slot add");
+        assert!(agendas.get_agenda_by_id(id).await.unwrap().is_none());
+        assert!(journal.undo().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn purging_the_trash_can_be_undone() {
+        let pool = setup_pool().await;
+        let journal = SqliteJournal { pool: pool.clone() };
+        let agendas = SqliteAgendaRepo { pool: pool.clone() };
+        let trash = SqliteTrash { pool: pool.clone() };
+        let id = agendas
+            .create_agenda(&AgendaCreate {
+                title: "Trashed".to_string(),
+                agenda_status: AgendaStatus::Pending,
+                terminate_at: Timestamp::now() + 1.hour(),
+            })
+            .await
+            .unwrap();
+        agendas.delete_agenda_by_id(id).await.unwrap();
+
+        let op = journal.begin("trash purge").await.unwrap();
+        trash.purge(None).await.unwrap();
+        journal.finish(op).await.unwrap();
+        assert!(trash.agendas().await.unwrap().is_empty());
+
+        journal.undo().await.unwrap();
+        assert_eq!(trash.agendas().await.unwrap()[0].agenda.id, id);
+    }
+
+    #[tokio::test]
+    async fn other_connections_are_not_journaled() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("finiate.db");
+        let ours = crate::init_db(&path).await.unwrap();
+        let theirs = crate::init_db(&path).await.unwrap();
+        let journal = SqliteJournal { pool: ours.clone() };
+        let their_journal = SqliteJournal {
+            pool: theirs.clone(),
+        };
+
+        let their_op = their_journal.begin("mark").await.unwrap();
+        let op = journal.begin("slot add").await.unwrap();
+        let mine = create_agenda(&ours, "Ours").await;
+        let other = create_agenda(&theirs, "Theirs").await;
+        journal.finish(op).await.unwrap();
+
+        let open: bool = sqlx::query_scalar("SELECT open FROM operation WHERE id = ?")
+            .bind(their_op)
+            .fetch_one(&ours)
+            .await
+            .unwrap();
+        assert!(open);
+        their_journal.finish(their_op).await.unwrap();
+
+        let agendas = SqliteAgendaRepo { pool: ours.clone() };
+        let undone = journal.undo().await.unwrap().unwrap();
+        assert_eq!((undone.command.as_str(), undone.changes), ("slot add", 1));
+        assert!(agendas.get_agenda_by_id(mine).await.unwrap().is_none());
+        assert!(agendas.get_agenda_by_id(other).await.unwrap().is_some());
+
+        let undone = journal.undo().await.unwrap().unwrap();
+        assert_eq!((undone.command.as_str(), undone.changes), ("mark", 1));
+        assert!(agendas.get_agenda_by_id(other).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn undo_refuses_to_overwrite_later_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("finiate.db");
+        let ours = crate::init_db(&path).await.unwrap();
+        let theirs = crate::init_db(&path).await.unwrap();
+        let journal = SqliteJournal { pool: ours.clone() };
+        let agendas = SqliteAgendaRepo { pool: ours.clone() };
+        let id = create_agenda(&ours, "Shared").await;
+        let rename = |title: &str| AgendaUpdate {
+            title: Some(title.to_string()),
+            agenda_status: None,
+            terminate_at: None,
+            actor: None,
+        };
+
+        let op = journal.begin("edit").await.unwrap();
+        agendas.update_agenda(id, &rename("Ours")).await.unwrap();
+        journal.finish(op).await.unwrap();
+        // Another process edits the same agenda, outside this journal.
+        SqliteAgendaRepo { pool: theirs }
+            .update_agenda(id, &rename("Theirs"))
+            .await
+            .unwrap();
+
+        assert!(matches!(journal.undo().await, Err(RepoError::Conflict(_))));
+        let agenda = agendas.get_agenda_by_id(id).await.unwrap().unwrap();
+        assert_eq!(agenda.title, "Theirs");
+        assert_eq!(agendas.get_agenda_revisions(id).await.unwrap().len(), 2);
+        assert_eq!(journal.last().await.unwrap().unwrap().command, "edit");
+    }
+}
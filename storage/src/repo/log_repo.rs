@@ -1,47 +1,84 @@
 use async_trait::async_trait;
 use domain::*;
 use jiff::Timestamp;
-use sqlx::{FromRow, SqlitePool};
+use sqlx::{FromRow, SqliteConnection, SqlitePool};
 use uuid::Uuid;
 
+use crate::repo::outbox;
 use crate::repo::repo_error::RepoError;
 
 #[derive(FromRow)]
-struct DbLog {
-    id: String,
-    create_at: i64,
-    content: String,
-    log_type: String,
-    agenda_id: String,
+pub(crate) struct DbLog {
+    pub(crate) id: String,
+    pub(crate) create_at: i64,
+    pub(crate) content: String,
+    pub(crate) log_type: String,
+    pub(crate) agenda_id: String,
 }
 
 impl DbLog {
-    fn to_log(&self) -> Result<Log, RepoError> {
+    pub(crate) fn to_log(&self) -> Result<Log, RepoError> {
         Ok(Log {
             id: Uuid::parse_str(&self.id)?,
             agenda_id: Uuid::parse_str(&self.agenda_id)?,
             content: self.content.clone(),
             create_at: Timestamp::from_millisecond(self.create_at)?,
-            log_type: match self.log_type.as_str() {
-                "activate" => LogType::Activate,
-                "put_off" => LogType::PutOff,
-                "terminate" => LogType::Terminate,
-                "common_log" => LogType::CommonLog,
-                _ => {
-                    return Err(sqlx::Error::ColumnDecode {
-                        index: "agenda_status".to_string(),
-                        source: Box::new(std::io::Error::new(
-                            std::io::ErrorKind::InvalidData,
-                            "invalid agenda_status in database",
-                        )),
-                    }
-                    .into());
-                }
-            },
+            log_type: parse_log_type(&self.log_type)?,
         })
     }
 }
 
+pub(crate) fn parse_log_type(log_type: &str) -> Result<LogType, RepoError> {
+    match log_type {
+        "activate" => Ok(LogType::Activate),
+        "put_off" => Ok(LogType::PutOff),
+        "terminate" => Ok(LogType::Terminate),
+        "common_log" => Ok(LogType::CommonLog),
+        _ => Err(sqlx::Error::ColumnDecode {
+            index: "log_type".to_string(),
+            source: Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "invalid log_type in database",
+            )),
+        }
+        .into()),
+    }
+}
+
+/// Logs that are neither trashed themselves nor belong to a trashed agenda.
+// The agenda is looked up by key per log, rather than listing every live
+// agenda for each query.
+const LIVE_LOGS: &str = "SELECT id, create_at, content, log_type, agenda_id FROM log
+    WHERE deleted_at IS NULL
+      AND EXISTS (SELECT 1 FROM agenda
+                  WHERE agenda.id = log.agenda_id AND agenda.deleted_at IS NULL)";
+
+/// Inserts a log keeping its id and timestamp.
+pub(crate) async fn insert_row(conn: &mut SqliteConnection, log: &Log) -> Result<(), RepoError> {
+    // As with agendas, only a trashed log with the same id is replaced.
+    let result = sqlx::query(
+        "INSERT INTO log (id, create_at, content, log_type, agenda_id) VALUES (?, ?, ?, ?, ?)
+        ON CONFLICT (id) DO UPDATE SET
+            create_at = excluded.create_at,
+            content = excluded.content,
+            log_type = excluded.log_type,
+            agenda_id = excluded.agenda_id,
+            deleted_at = NULL
+        WHERE log.deleted_at IS NOT NULL",
+    )
+    .bind(log.id.to_string())
+    .bind(log.create_at.as_millisecond())
+    .bind(&log.content)
+    .bind(log.log_type.to_string())
+    .bind(log.agenda_id.to_string())
+    .execute(&mut *conn)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(RepoError::Duplicate(format!("log {}", log.id)));
+    }
+    Ok(())
+}
+
 pub struct SqliteLogRepo {
     pub pool: SqlitePool,
 }
@@ -59,28 +96,44 @@ impl LogRepo for SqliteLogRepo {
         .bind(uuid.to_string())
         .bind(timestamp)
         .bind(&new_log.content)
-        .bind(&new_log.log_type.to_string())
+        .bind(new_log.log_type.to_string())
         .bind(new_log.agenda_id.to_string())
         .execute(&self.pool)
         .await?;
         Ok(uuid)
     }
 
+    async fn insert_log(&self, log: &Log) -> Result<(), Self::Error> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        outbox::mute(&mut tx).await?;
+        insert_row(&mut tx, log).await?;
+        outbox::unmute(&mut tx).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
     async fn delete_log(&self, id: Uuid) -> Result<(), Self::Error> {
-        sqlx::query("DELETE FROM log WHERE id = ?")
+        sqlx::query("UPDATE log SET deleted_at = ? WHERE id = ? AND deleted_at IS NULL")
+            .bind(Timestamp::now().as_millisecond())
             .bind(id.to_string())
             .execute(&self.pool)
             .await?;
         Ok(())
     }
 
+    async fn get_log_by_id(&self, id: Uuid) -> Result<Option<Log>, Self::Error> {
+        let row = sqlx::query_as::<_, DbLog>(&format!("{LIVE_LOGS} AND id = ?"))
+            .bind(id.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(DbLog::to_log).transpose()
+    }
+
     async fn get_logs_by_agenda_id(&self, agenda_id: Uuid) -> Result<Vec<Log>, Self::Error> {
-        let rows = sqlx::query_as::<_, DbLog>(
-            "SELECT id, create_at, content, log_type, agenda_id FROM log WHERE agenda_id = ?",
-        )
-        .bind(agenda_id.to_string())
-        .fetch_all(&self.pool)
-        .await?;
+        let rows = sqlx::query_as::<_, DbLog>(&format!("{LIVE_LOGS} AND agenda_id = ?"))
+            .bind(agenda_id.to_string())
+            .fetch_all(&self.pool)
+            .await?;
 
         let logs = rows
             .into_iter()
@@ -94,9 +147,9 @@ impl LogRepo for SqliteLogRepo {
         start: Timestamp,
         end: Timestamp,
     ) -> Result<Vec<Log>, Self::Error> {
-        let rows = sqlx::query_as::<_, DbLog>(
-            "SELECT id, create_at, content, log_type, agenda_id FROM log WHERE create_at >= ? AND create_at <= ?",
-        )
+        let rows = sqlx::query_as::<_, DbLog>(&format!(
+            "{LIVE_LOGS} AND create_at >= ? AND create_at <= ?"
+        ))
         .bind(start.as_millisecond())
         .bind(end.as_millisecond())
         .fetch_all(&self.pool)
@@ -196,7 +249,34 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn delete_log_removes_row() {
+    async fn insert_log_preserves_id_and_timestamp() {
+        let pool = setup_pool().await;
+        let repo = SqliteLogRepo { pool: pool.clone() };
+
+        let agenda_id = Uuid::now_v7();
+        insert_agenda(&pool, agenda_id).await;
+
+        let log = Log {
+            id: Uuid::now_v7(),
+            agenda_id,
+            content: "imported".to_string(),
+            create_at: Timestamp::from_millisecond(1_700_000_000_789).unwrap(),
+            log_type: LogType::Terminate,
+        };
+        repo.insert_log(&log).await.expect("insert log");
+
+        let logs = repo
+            .get_logs_by_agenda_id(agenda_id)
+            .await
+            .expect("query logs");
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].id, log.id);
+        assert_eq!(logs[0].create_at, log.create_at);
+        assert_eq!(logs[0].log_type, LogType::Terminate);
+    }
+
+    #[tokio::test]
+    async fn delete_log_moves_it_to_the_trash() {
         let pool = setup_pool().await;
         let repo = SqliteLogRepo { pool: pool.clone() };
 
@@ -219,12 +299,19 @@ mod tests {
 
         repo.delete_log(log_id).await.expect("delete log");
 
-        let count_after: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM log WHERE id = ?")
-            .bind(log_id.to_string())
-            .fetch_one(&pool)
-            .await
-            .expect("count after delete");
-        assert_eq!(count_after, 0);
+        let trashed: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM log WHERE id = ? AND deleted_at IS NOT NULL")
+                .bind(log_id.to_string())
+                .fetch_one(&pool)
+                .await
+                .expect("count after delete");
+        assert_eq!(trashed, 1);
+        assert!(
+            repo.get_logs_by_agenda_id(agenda_id)
+                .await
+                .expect("get logs")
+                .is_empty()
+        );
     }
 
     #[tokio::test]
@@ -424,4 +511,36 @@ mod tests {
 
         assert!(result.is_empty());
     }
+
+    async fn query_plan(pool: &SqlitePool, query: &str, params: usize) -> String {
+        let explain = format!("EXPLAIN QUERY PLAN {query}");
+        let mut explain = sqlx::query(&explain);
+        for _ in 0..params {
+            explain = explain.bind(0);
+        }
+        explain
+            .fetch_all(pool)
+            .await
+            .expect("explain query")
+            .iter()
+            .map(|row| row.get::<String, _>("detail"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[tokio::test]
+    async fn log_queries_use_indexes() {
+        let pool = setup_pool().await;
+        let by_agenda = query_plan(&pool, &format!("{LIVE_LOGS} AND agenda_id = ?"), 1).await;
+        assert!(by_agenda.contains("log_agenda_create_at"), "{by_agenda}");
+        assert!(!by_agenda.contains("SCAN"), "{by_agenda}");
+        let by_time = query_plan(
+            &pool,
+            &format!("{LIVE_LOGS} AND create_at >= ? AND create_at <= ?"),
+            2,
+        )
+        .await;
+        assert!(by_time.contains("USING INDEX log_create_at"), "{by_time}");
+        assert!(!by_time.contains("SCAN"), "{by_time}");
+    }
 }
@@ -1,3 +1,10 @@
 pub mod agenda_repo;
+pub mod event_store;
+pub mod git_store;
+pub mod journal;
 pub mod log_repo;
+pub mod outbox;
 mod repo_error;
+pub mod store;
+pub mod sync;
+pub mod trash;
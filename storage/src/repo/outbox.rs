@@ -0,0 +1,473 @@
+use domain::*;
+use jiff::Timestamp;
+use sqlx::{FromRow, SqliteConnection, SqlitePool};
+
+use crate::repo::agenda_repo::DbAgenda;
+use crate::repo::log_repo::DbLog;
+use crate::repo::repo_error::RepoError;
+
+/// A lifecycle event recorded by the outbox triggers, with the agenda (and
+/// log) as they were when it happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutboxEvent {
+    pub id: i64,
+    /// `agenda.created`, `agenda.activated`, `agenda.put_off`,
+    /// `agenda.terminated` or `log.created`.
+    pub event: String,
+    pub occurred_at: Timestamp,
+    /// When the event entered the outbox; endpoints registered later do
+    /// not receive it.
+    pub queued_at: Timestamp,
+    pub agenda: Agenda,
+    pub log: Option<Log>,
+    pub attempts: u32,
+    /// Endpoints that already accepted this event.
+    pub delivered_to: Vec<String>,
+}
+
+/// How many events are waiting, done, or out of attempts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OutboxCounts {
+    pub pending: u64,
+    pub delivered: u64,
+    pub dead: u64,
+}
+
+#[derive(FromRow)]
+struct DbOutboxEvent {
+    id: i64,
+    event: String,
+    occurred_at: i64,
+    queued_at: i64,
+    agenda_id: String,
+    title: String,
+    agenda_status: String,
+    initiate_at: i64,
+    terminate_at: i64,
+    log_id: Option<String>,
+    content: Option<String>,
+    log_type: Option<String>,
+    create_at: Option<i64>,
+    attempts: i64,
+    delivered_to: String,
+}
+
+impl DbOutboxEvent {
+    fn to_event(&self) -> Result<OutboxEvent, RepoError> {
+        let agenda = DbAgenda {
+            id: self.agenda_id.clone(),
+            title: self.title.clone(),
+            agenda_status: self.agenda_status.clone(),
+            initiate_at: self.initiate_at,
+            terminate_at: self.terminate_at,
+        }
+        .to_agenda()?;
+        let log = match (&self.log_id, &self.content, &self.log_type, self.create_at) {
+            (Some(id), Some(content), Some(log_type), Some(create_at)) => Some(
+                DbLog {
+                    id: id.clone(),
+                    create_at,
+                    content: content.clone(),
+                    log_type: log_type.clone(),
+                    agenda_id: self.agenda_id.clone(),
+                }
+                .to_log()?,
+            ),
+            _ => None,
+        };
+        Ok(OutboxEvent {
+            id: self.id,
+            event: self.event.clone(),
+            occurred_at: Timestamp::from_millisecond(self.occurred_at)?,
+            queued_at: Timestamp::from_millisecond(self.queued_at)?,
+            agenda,
+            log,
+            attempts: u32::try_from(self.attempts).unwrap_or(u32::MAX),
+            delivered_to: self
+                .delivered_to
+                .lines()
+                .filter(|url| !url.is_empty())
+                .map(str::to_string)
+                .collect(),
+        })
+    }
+}
+
+/// The webhook outbox. Rows are written by triggers, so this only reads
+/// them and keeps track of deliveries.
+pub struct SqliteOutbox {
+    pub pool: SqlitePool,
+}
+
+impl SqliteOutbox {
+    /// Makes `urls` the registered endpoints. Newly listed ones are
+    /// registered as of now; once none are left, undelivered events are
+    /// dropped since nobody is waiting for them.
+    pub async fn register_endpoints(&self, urls: &[String]) -> Result<(), RepoError> {
+        let registered: Vec<String> = sqlx::query_scalar("SELECT url FROM webhook_endpoint")
+            .fetch_all(&self.pool)
+            .await?;
+        if registered.len() == urls.len() && urls.iter().all(|url| registered.contains(url)) {
+            return Ok(());
+        }
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        let now = Timestamp::now().as_millisecond();
+        for url in registered.iter().filter(|url| !urls.contains(url)) {
+            sqlx::query("DELETE FROM webhook_endpoint WHERE url = ?")
+                .bind(url)
+                .execute(&mut *tx)
+                .await?;
+        }
+        for url in urls {
+            sqlx::query(
+                "INSERT INTO webhook_endpoint (url, added_at) VALUES (?, ?) ON CONFLICT DO NOTHING",
+            )
+            .bind(url)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+        if urls.is_empty() {
+            sqlx::query("DELETE FROM webhook_outbox WHERE delivered_at IS NULL")
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// The registered endpoints and when each was registered.
+    pub async fn endpoints(&self) -> Result<Vec<(String, Timestamp)>, RepoError> {
+        let rows: Vec<(String, i64)> =
+            sqlx::query_as("SELECT url, added_at FROM webhook_endpoint ORDER BY url")
+                .fetch_all(&self.pool)
+                .await?;
+        rows.into_iter()
+            .map(|(url, added_at)| Ok((url, Timestamp::from_millisecond(added_at)?)))
+            .collect()
+    }
+
+    /// Undelivered events whose next attempt is due, oldest first.
+    pub async fn due(
+        &self,
+        now: Timestamp,
+        max_attempts: u32,
+        limit: u32,
+    ) -> Result<Vec<OutboxEvent>, RepoError> {
+        let rows = sqlx::query_as::<_, DbOutboxEvent>(
+            "SELECT id, event, occurred_at, COALESCE(queued_at, occurred_at) AS queued_at, agenda_id, title, agenda_status, initiate_at,
+                terminate_at, log_id, content, log_type, create_at, attempts, delivered_to
+            FROM webhook_outbox
+            WHERE delivered_at IS NULL AND attempts < ? AND next_attempt_at <= ?
+            ORDER BY id
+            LIMIT ?",
+        )
+        .bind(i64::from(max_attempts))
+        .bind(now.as_millisecond())
+        .bind(i64::from(limit))
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(DbOutboxEvent::to_event).collect()
+    }
+
+    pub async fn mark_delivered(&self, id: i64, at: Timestamp) -> Result<(), RepoError> {
+        sqlx::query(
+            "UPDATE webhook_outbox
+            SET attempts = attempts + 1, delivered_at = ?, last_error = NULL
+            WHERE id = ?",
+        )
+        .bind(at.as_millisecond())
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Records a failed attempt; `delivered_to` lists the endpoints that did
+    /// accept the event so the retry skips them.
+    pub async fn mark_failed(
+        &self,
+        id: i64,
+        delivered_to: &[String],
+        error: &str,
+        next_attempt_at: Timestamp,
+    ) -> Result<(), RepoError> {
+        sqlx::query(
+            "UPDATE webhook_outbox
+            SET attempts = attempts + 1, delivered_to = ?, last_error = ?, next_attempt_at = ?
+            WHERE id = ?",
+        )
+        .bind(delivered_to.join("\n"))
+        .bind(error)
+        .bind(next_attempt_at.as_millisecond())
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Gives events that ran out of attempts a fresh set, due immediately.
+    pub async fn revive(&self, max_attempts: u32, now: Timestamp) -> Result<u64, RepoError> {
+        let result = sqlx::query(
+            "UPDATE webhook_outbox SET attempts = 0, next_attempt_at = ?
+            WHERE delivered_at IS NULL AND attempts >= ?",
+        )
+        .bind(now.as_millisecond())
+        .bind(i64::from(max_attempts))
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Drops an undelivered event that no endpoint wants.
+    pub async fn discard(&self, id: i64) -> Result<(), RepoError> {
+        sqlx::query("DELETE FROM webhook_outbox WHERE id = ? AND delivered_at IS NULL")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Forgets delivered events older than `before`.
+    pub async fn prune_delivered(&self, before: Timestamp) -> Result<u64, RepoError> {
+        let result = sqlx::query(
+            "DELETE FROM webhook_outbox WHERE delivered_at IS NOT NULL AND delivered_at < ?",
+        )
+        .bind(before.as_millisecond())
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn counts(&self, max_attempts: u32) -> Result<OutboxCounts, RepoError> {
+        let (pending, delivered, dead): (i64, i64, i64) = sqlx::query_as(
+            "SELECT
+                COALESCE(SUM(delivered_at IS NULL AND attempts < ?), 0),
+                COALESCE(SUM(delivered_at IS NOT NULL), 0),
+                COALESCE(SUM(delivered_at IS NULL AND attempts >= ?), 0)
+            FROM webhook_outbox",
+        )
+        .bind(i64::from(max_attempts))
+        .bind(i64::from(max_attempts))
+        .fetch_one(&self.pool)
+        .await?;
+        let count = |n: i64| u64::try_from(n).unwrap_or_default();
+        Ok(OutboxCounts {
+            pending: count(pending),
+            delivered: count(delivered),
+            dead: count(dead),
+        })
+    }
+}
+
+/// The event a new log of this type records, matching the outbox triggers.
+pub(crate) fn log_event(log_type: LogType) -> &'static str {
+    match log_type {
+        LogType::Activate => "agenda.activated",
+        LogType::PutOff => "agenda.put_off",
+        LogType::Terminate => "agenda.terminated",
+        LogType::CommonLog => "log.created",
+    }
+}
+
+/// Keeps the rest of the transaction out of the outbox; `unmute` must
+/// follow before it commits.
+pub(crate) async fn mute(conn: &mut SqliteConnection) -> Result<(), RepoError> {
+    sqlx::query("INSERT OR IGNORE INTO webhook_muted (muted) VALUES (1)")
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+pub(crate) async fn unmute(conn: &mut SqliteConnection) -> Result<(), RepoError> {
+    sqlx::query("DELETE FROM webhook_muted")
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Records an event the way the outbox triggers do, for writes that do not
+/// go through the `agenda` and `log` tables.
+pub(crate) async fn enqueue(
+    conn: &mut SqliteConnection,
+    event: &str,
+    agenda: &Agenda,
+    log: Option<&Log>,
+) -> Result<(), RepoError> {
+    let occurred_at = log.map_or(agenda.initiate_at, |log| log.create_at);
+    sqlx::query(
+        "INSERT INTO webhook_outbox
+            (event, occurred_at, agenda_id, title, agenda_status, initiate_at, terminate_at,
+             log_id, content, log_type, create_at, next_attempt_at, queued_at)
+        SELECT ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?
+        WHERE EXISTS (SELECT 1 FROM webhook_endpoint)
+          AND NOT EXISTS (SELECT 1 FROM webhook_muted)",
+    )
+    .bind(event)
+    .bind(occurred_at.as_millisecond())
+    .bind(agenda.id.to_string())
+    .bind(&agenda.title)
+    .bind(agenda.agenda_status.to_string())
+    .bind(agenda.initiate_at.as_millisecond())
+    .bind(agenda.terminate_at.as_millisecond())
+    .bind(log.map(|log| log.id.to_string()))
+    .bind(log.map(|log| log.content.clone()))
+    .bind(log.map(|log| log.log_type.to_string()))
+    .bind(log.map(|log| log.create_at.as_millisecond()))
+    .bind(occurred_at.as_millisecond())
+    .bind(Timestamp::now().as_millisecond())
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::agenda_repo::SqliteAgendaRepo;
+    use crate::repo::log_repo::SqliteLogRepo;
+    use jiff::ToSpan;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn setup_pool() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .expect("create in-memory sqlite pool");
+
+        let crate_dir = env!("CARGO_MANIFEST_DIR");
+        let migrations = std::path::Path::new(crate_dir).join("migrations");
+        sqlx::migrate::Migrator::new(migrations)
+            .await
+            .expect("load migrations")
+            .run(&pool)
+            .await
+            .expect("run migrations");
+
+        pool
+    }
+
+    async fn register(pool: &SqlitePool) -> SqliteOutbox {
+        let outbox = SqliteOutbox { pool: pool.clone() };
+        outbox
+            .register_endpoints(&["https://a.example/hook".to_string()])
+            .await
+            .expect("register endpoint");
+        outbox
+    }
+
+    async fn create_agenda(pool: &SqlitePool) -> uuid::Uuid {
+        SqliteAgendaRepo { pool: pool.clone() }
+            .create_agenda(&AgendaCreate {
+                title: "Ship it".to_string(),
+                agenda_status: AgendaStatus::Pending,
+                terminate_at: Timestamp::now() + 1.hour(),
+            })
+            .await
+            .expect("create agenda")
+    }
+
+    #[tokio::test]
+    async fn triggers_record_lifecycle_events() {
+        let pool = setup_pool().await;
+        let outbox = register(&pool).await;
+        let logs = SqliteLogRepo { pool: pool.clone() };
+        let agenda_id = create_agenda(&pool).await;
+        for log_type in [LogType::Activate, LogType::CommonLog, LogType::Terminate] {
+            logs.create_log(&LogCreate {
+                agenda_id,
+                content: log_type.to_string(),
+                log_type,
+            })
+            .await
+            .expect("create log");
+        }
+        let events = outbox
+            .due(Timestamp::now() + 1.second(), 5, 100)
+            .await
+            .expect("due events");
+        let names: Vec<_> = events.iter().map(|event| event.event.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "agenda.created",
+                "agenda.activated",
+                "log.created",
+                "agenda.terminated"
+            ]
+        );
+        assert!(events[0].log.is_none());
+        assert_eq!(events[0].agenda.id, agenda_id);
+        assert_eq!(events[2].log.as_ref().unwrap().content, "common_log");
+    }
+
+    #[tokio::test]
+    async fn failed_attempts_back_off_and_die() {
+        let pool = setup_pool().await;
+        let outbox = register(&pool).await;
+        create_agenda(&pool).await;
+        let now = Timestamp::now() + 1.second();
+        let id = outbox.due(now, 2, 10).await.unwrap()[0].id;
+
+        let delivered = ["https://a.example/hook".to_string()];
+        outbox
+            .mark_failed(id, &delivered, "503", now + 1.minute())
+            .await
+            .unwrap();
+        assert!(outbox.due(now, 2, 10).await.unwrap().is_empty());
+        let retry = outbox.due(now + 1.minute(), 2, 10).await.unwrap();
+        assert_eq!(retry[0].attempts, 1);
+        assert_eq!(retry[0].delivered_to, delivered);
+
+        outbox
+            .mark_failed(id, &delivered, "503", now)
+            .await
+            .unwrap();
+        assert!(outbox.due(now, 2, 10).await.unwrap().is_empty());
+        assert_eq!(
+            outbox.counts(2).await.unwrap(),
+            OutboxCounts {
+                pending: 0,
+                delivered: 0,
+                dead: 1
+            }
+        );
+
+        assert_eq!(outbox.revive(2, now).await.unwrap(), 1);
+        outbox.mark_delivered(id, now).await.unwrap();
+        assert_eq!(outbox.counts(2).await.unwrap().delivered, 1);
+        assert_eq!(outbox.prune_delivered(now + 1.second()).await.unwrap(), 1);
+        assert_eq!(outbox.counts(2).await.unwrap(), OutboxCounts::default());
+    }
+
+    #[tokio::test]
+    async fn only_new_changes_are_queued_while_endpoints_are_registered() {
+        let pool = setup_pool().await;
+        let outbox = SqliteOutbox { pool: pool.clone() };
+        let later = Timestamp::now() + 1.second();
+        create_agenda(&pool).await;
+        assert!(outbox.due(later, 5, 10).await.unwrap().is_empty());
+
+        let outbox = register(&pool).await;
+        let imported = Agenda {
+            id: uuid::Uuid::now_v7(),
+            title: "Imported".to_string(),
+            agenda_status: AgendaStatus::Terminated,
+            initiate_at: Timestamp::now() - 1.hour(),
+            terminate_at: Timestamp::now(),
+        };
+        SqliteAgendaRepo { pool: pool.clone() }
+            .insert_agenda(&imported)
+            .await
+            .unwrap();
+        assert!(outbox.due(later, 5, 10).await.unwrap().is_empty());
+
+        create_agenda(&pool).await;
+        assert_eq!(outbox.counts(5).await.unwrap().pending, 1);
+        outbox.register_endpoints(&[]).await.unwrap();
+        assert_eq!(outbox.counts(5).await.unwrap(), OutboxCounts::default());
+        create_agenda(&pool).await;
+        assert_eq!(outbox.counts(5).await.unwrap(), OutboxCounts::default());
+    }
+}
@@ -9,4 +9,18 @@ pub enum RepoError {
     Jiff(#[from] JiffError),
     #[error("uuid error: {0}")]
     Uuid(#[from] UuidError),
+    #[error("event stream: {0}")]
+    Stream(String),
+    #[error("{0} already exists")]
+    Duplicate(String),
+    #[error("invalid revision: {0}")]
+    Revision(String),
+    #[error("git error: {0}")]
+    Git(#[from] git2::Error),
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid document: {0}")]
+    Document(String),
+    #[error("{0}")]
+    Conflict(String),
 }
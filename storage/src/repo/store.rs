@@ -0,0 +1,336 @@
+use async_trait::async_trait;
+use domain::*;
+use jiff::Timestamp;
+use sqlx::SqlitePool;
+use std::path::Path;
+use std::str::FromStr;
+use uuid::Uuid;
+
+use crate::repo::agenda_repo::SqliteAgendaRepo;
+use crate::repo::event_store::{EventSourcedAgendaRepo, EventSourcedLogRepo};
+use crate::repo::git_store::{GitAgendaRepo, GitLogRepo, GitStore};
+use crate::repo::log_repo::SqliteLogRepo;
+use crate::repo::repo_error::RepoError;
+
+/// Where agendas and logs live.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// The `agenda` and `log` tables.
+    #[default]
+    Relational,
+    /// State folded from the append-only `agenda_event` stream.
+    EventSourced,
+    /// One TOML document per agenda in a git repository, a commit per change.
+    Git,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "relational" => Ok(Backend::Relational),
+            "event_sourced" => Ok(Backend::EventSourced),
+            "git" => Ok(Backend::Git),
+            other => Err(format!(
+                "unknown backend `{other}`, expected `relational`, `event_sourced` or `git`"
+            )),
+        }
+    }
+}
+
+/// The agenda repository of the configured backend.
+pub enum AgendaStore {
+    Relational(SqliteAgendaRepo),
+    EventSourced(EventSourcedAgendaRepo),
+    Git(GitAgendaRepo),
+}
+
+/// The log repository of the configured backend.
+pub enum LogStore {
+    Relational(SqliteLogRepo),
+    EventSourced(EventSourcedLogRepo),
+    Git(GitLogRepo),
+}
+
+/// How many live agendas and logs a `replace_all` removed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReplaceSummary {
+    pub agendas: u64,
+    pub logs: u64,
+}
+
+impl AgendaStore {
+    /// Swaps every live agenda and log for `agendas` and `logs` at once, so
+    /// a failure part way leaves the store as it was.
+    pub async fn replace_all(
+        &self,
+        agendas: &[Agenda],
+        logs: &[Log],
+    ) -> Result<ReplaceSummary, RepoError> {
+        match self {
+            AgendaStore::Relational(repo) => repo.replace_all(agendas, logs).await,
+            AgendaStore::EventSourced(repo) => repo.replace_all(agendas, logs).await,
+            AgendaStore::Git(repo) => repo.replace_all(agendas, logs).await,
+        }
+    }
+
+    /// Adds `agendas` and `logs`, none of which may exist yet, at once, so a
+    /// failure part way leaves the store as it was.
+    pub async fn merge_all(&self, agendas: &[Agenda], logs: &[Log]) -> Result<(), RepoError> {
+        match self {
+            AgendaStore::Relational(repo) => repo.merge_all(agendas, logs).await,
+            AgendaStore::EventSourced(repo) => repo.merge_all(agendas, logs).await,
+            AgendaStore::Git(repo) => repo.merge_all(agendas, logs).await,
+        }
+    }
+}
+
+/// Opens the repositories of `backend`; `git_dir` is only used by the git
+/// backend, which creates the repository on first use.
+pub fn open_repos(
+    pool: &SqlitePool,
+    backend: Backend,
+    git_dir: &Path,
+) -> Result<(AgendaStore, LogStore), RepoError> {
+    let pool = pool.clone();
+    Ok(match backend {
+        Backend::Relational => (
+            AgendaStore::Relational(SqliteAgendaRepo { pool: pool.clone() }),
+            LogStore::Relational(SqliteLogRepo { pool }),
+        ),
+        Backend::EventSourced => (
+            AgendaStore::EventSourced(EventSourcedAgendaRepo { pool: pool.clone() }),
+            LogStore::EventSourced(EventSourcedLogRepo { pool }),
+        ),
+        Backend::Git => {
+            let store = GitStore::open(git_dir, &pool)?;
+            (
+                AgendaStore::Git(GitAgendaRepo {
+                    store: store.clone(),
+                }),
+                LogStore::Git(GitLogRepo { store }),
+            )
+        }
+    })
+}
+
+macro_rules! dispatch {
+    ($store:ident, $self:ident, $repo:ident => $call:expr) => {
+        match $self {
+            $store::Relational($repo) => $call.await,
+            $store::EventSourced($repo) => $call.await,
+            $store::Git($repo) => $call.await,
+        }
+    };
+}
+
+#[async_trait]
+impl AgendaRepo for AgendaStore {
+    type Error = RepoError;
+
+    async fn create_agenda(&self, agenda: &AgendaCreate) -> Result<Uuid, Self::Error> {
+        dispatch!(AgendaStore, self, repo => repo.create_agenda(agenda))
+    }
+
+    async fn insert_agenda(&self, agenda: &Agenda) -> Result<(), Self::Error> {
+        dispatch!(AgendaStore, self, repo => repo.insert_agenda(agenda))
+    }
+
+    async fn delete_agenda_by_id(&self, id: Uuid) -> Result<(), Self::Error> {
+        dispatch!(AgendaStore, self, repo => repo.delete_agenda_by_id(id))
+    }
+
+    async fn update_agenda(&self, id: Uuid, update: &AgendaUpdate) -> Result<(), Self::Error> {
+        dispatch!(AgendaStore, self, repo => repo.update_agenda(id, update))
+    }
+
+    async fn get_agenda_revisions(&self, id: Uuid) -> Result<Vec<AgendaRevision>, Self::Error> {
+        dispatch!(AgendaStore, self, repo => repo.get_agenda_revisions(id))
+    }
+
+    async fn get_agenda_by_id(&self, id: Uuid) -> Result<Option<Agenda>, Self::Error> {
+        dispatch!(AgendaStore, self, repo => repo.get_agenda_by_id(id))
+    }
+
+    async fn get_agendas_by_title(&self, title: &str) -> Result<Vec<Agenda>, Self::Error> {
+        dispatch!(AgendaStore, self, repo => repo.get_agendas_by_title(title))
+    }
+
+    async fn get_agendas_by_status(
+        &self,
+        status: Option<&str>,
+    ) -> Result<Vec<Agenda>, Self::Error> {
+        dispatch!(AgendaStore, self, repo => repo.get_agendas_by_status(status))
+    }
+
+    async fn count_agendas_by_status(&self, status: Option<&str>) -> Result<u64, Self::Error> {
+        dispatch!(AgendaStore, self, repo => repo.count_agendas_by_status(status))
+    }
+
+    async fn get_agendas_by_terminate_time_range(
+        &self,
+        start: Timestamp,
+        end: Timestamp,
+    ) -> Result<Vec<Agenda>, Self::Error> {
+        dispatch!(AgendaStore, self, repo => repo.get_agendas_by_terminate_time_range(start, end))
+    }
+
+    async fn get_agendas_by_initiate_time_range(
+        &self,
+        start: Timestamp,
+        end: Timestamp,
+    ) -> Result<Vec<Agenda>, Self::Error> {
+        dispatch!(AgendaStore, self, repo => repo.get_agendas_by_initiate_time_range(start, end))
+    }
+}
+
+#[async_trait]
+impl LogRepo for LogStore {
+    type Error = RepoError;
+
+    async fn create_log(&self, new_log: &LogCreate) -> Result<Uuid, Self::Error> {
+        dispatch!(LogStore, self, repo => repo.create_log(new_log))
+    }
+
+    async fn insert_log(&self, log: &Log) -> Result<(), Self::Error> {
+        dispatch!(LogStore, self, repo => repo.insert_log(log))
+    }
+
+    async fn delete_log(&self, id: Uuid) -> Result<(), Self::Error> {
+        dispatch!(LogStore, self, repo => repo.delete_log(id))
+    }
+
+    async fn get_log_by_id(&self, id: Uuid) -> Result<Option<Log>, Self::Error> {
+        dispatch!(LogStore, self, repo => repo.get_log_by_id(id))
+    }
+
+    async fn get_logs_by_agenda_id(&self, agenda_id: Uuid) -> Result<Vec<Log>, Self::Error> {
+        dispatch!(LogStore, self, repo => repo.get_logs_by_agenda_id(agenda_id))
+    }
+
+    async fn get_logs_by_time_range(
+        &self,
+        start: Timestamp,
+        end: Timestamp,
+    ) -> Result<Vec<Log>, Self::Error> {
+        dispatch!(LogStore, self, repo => repo.get_logs_by_time_range(start, end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn replace_all_swaps_every_record_in_each_backend() {
+        for backend in [Backend::Relational, Backend::EventSourced, Backend::Git] {
+            let dir = tempfile::tempdir().expect("create temp dir");
+            let pool = crate::init_db(&dir.path().join("finiate.db"))
+                .await
+                .expect("init db");
+            let (agendas, logs) =
+                open_repos(&pool, backend, &dir.path().join("git")).expect("open repos");
+            let old = agendas
+                .create_agenda(&AgendaCreate {
+                    title: "Local".to_string(),
+                    agenda_status: AgendaStatus::Pending,
+                    terminate_at: Timestamp::now(),
+                })
+                .await
+                .expect("create agenda");
+            logs.create_log(&LogCreate {
+                agenda_id: old,
+                content: "local note".to_string(),
+                log_type: LogType::CommonLog,
+            })
+            .await
+            .expect("create log");
+
+            let restored = Agenda {
+                id: Uuid::now_v7(),
+                title: "Restored".to_string(),
+                agenda_status: AgendaStatus::Terminated,
+                initiate_at: Timestamp::from_millisecond(1_700_000_000_000).unwrap(),
+                terminate_at: Timestamp::from_millisecond(1_700_000_100_000).unwrap(),
+            };
+            let note = Log {
+                id: Uuid::now_v7(),
+                agenda_id: restored.id,
+                content: "restored note".to_string(),
+                create_at: Timestamp::from_millisecond(1_700_000_050_000).unwrap(),
+                log_type: LogType::CommonLog,
+            };
+            let removed = agendas
+                .replace_all(std::slice::from_ref(&restored), std::slice::from_ref(&note))
+                .await
+                .expect("replace");
+
+            assert_eq!(
+                removed,
+                ReplaceSummary {
+                    agendas: 1,
+                    logs: 1
+                },
+                "{backend:?}"
+            );
+            let all = agendas.get_agendas_by_status(None).await.unwrap();
+            assert_eq!(all, [restored], "{backend:?}");
+            let all_logs = logs
+                .get_logs_by_time_range(Timestamp::MIN, Timestamp::MAX)
+                .await
+                .unwrap();
+            assert_eq!(all_logs, [note], "{backend:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn merge_all_adds_everything_or_nothing_in_each_backend() {
+        for backend in [Backend::Relational, Backend::EventSourced, Backend::Git] {
+            let dir = tempfile::tempdir().expect("create temp dir");
+            let pool = crate::init_db(&dir.path().join("finiate.db"))
+                .await
+                .expect("init db");
+            let (agendas, logs) =
+                open_repos(&pool, backend, &dir.path().join("git")).expect("open repos");
+            let merged = Agenda {
+                id: Uuid::now_v7(),
+                title: "Merged".to_string(),
+                agenda_status: AgendaStatus::Pending,
+                initiate_at: Timestamp::from_millisecond(1_700_000_000_000).unwrap(),
+                terminate_at: Timestamp::from_millisecond(1_700_000_100_000).unwrap(),
+            };
+            let note = Log {
+                id: Uuid::now_v7(),
+                agenda_id: merged.id,
+                content: "merged note".to_string(),
+                create_at: Timestamp::from_millisecond(1_700_000_050_000).unwrap(),
+                log_type: LogType::CommonLog,
+            };
+            let dangling = Log {
+                id: Uuid::now_v7(),
+                agenda_id: Uuid::now_v7(),
+                ..note.clone()
+            };
+
+            let failed = agendas
+                .merge_all(std::slice::from_ref(&merged), &[note.clone(), dangling])
+                .await;
+            assert!(failed.is_err(), "{backend:?}");
+            let all = agendas.get_agendas_by_status(None).await.unwrap();
+            assert!(all.is_empty(), "{backend:?}");
+
+            agendas
+                .merge_all(std::slice::from_ref(&merged), std::slice::from_ref(&note))
+                .await
+                .expect("merge");
+            let all = agendas.get_agendas_by_status(None).await.unwrap();
+            assert_eq!(all, [merged], "{backend:?}");
+            let all_logs = logs
+                .get_logs_by_time_range(Timestamp::MIN, Timestamp::MAX)
+                .await
+                .unwrap();
+            assert_eq!(all_logs, [note], "{backend:?}");
+        }
+    }
+}
@@ -0,0 +1,428 @@
+use std::collections::BTreeSet;
+
+use sqlx::{FromRow, Sqlite, SqlitePool, Transaction};
+
+use crate::repo::repo_error::RepoError;
+
+/// Fields that sync, per entity. Anything else in a change file comes from
+/// a newer version and is skipped.
+const AGENDA_FIELDS: [&str; 5] = [
+    "title",
+    "agenda_status",
+    "initiate_at",
+    "terminate_at",
+    "deleted_at",
+];
+const LOG_FIELDS: [&str; 5] = [
+    "create_at",
+    "content",
+    "log_type",
+    "agenda_id",
+    "deleted_at",
+];
+
+/// Builds an agenda from its registers once every required field is known,
+/// or brings an existing one up to date.
+const MATERIALIZE_AGENDA: &str = "INSERT INTO agenda
+        (id, title, agenda_status, initiate_at, terminate_at, deleted_at)
+    SELECT id, title, agenda_status, initiate_at, terminate_at, deleted_at FROM (
+        SELECT ?1 AS id,
+            MAX(CASE field WHEN 'title' THEN value END) AS title,
+            MAX(CASE field WHEN 'agenda_status' THEN value END) AS agenda_status,
+            MAX(CASE field WHEN 'initiate_at' THEN value END) AS initiate_at,
+            MAX(CASE field WHEN 'terminate_at' THEN value END) AS terminate_at,
+            MAX(CASE field WHEN 'deleted_at' THEN value END) AS deleted_at,
+            COUNT(CASE WHEN field IN ('title', 'agenda_status', 'initiate_at', 'terminate_at')
+                THEN 1 END) AS known
+        FROM sync_register WHERE entity = 'agenda' AND entity_id = ?1
+    ) WHERE known = 4
+    ON CONFLICT (id) DO UPDATE SET
+        title = excluded.title,
+        agenda_status = excluded.agenda_status,
+        initiate_at = excluded.initiate_at,
+        terminate_at = excluded.terminate_at,
+        deleted_at = excluded.deleted_at
+    WHERE agenda.title IS NOT excluded.title
+        OR agenda.agenda_status IS NOT excluded.agenda_status
+        OR agenda.initiate_at IS NOT excluded.initiate_at
+        OR agenda.terminate_at IS NOT excluded.terminate_at
+        OR agenda.deleted_at IS NOT excluded.deleted_at";
+
+/// Like [`MATERIALIZE_AGENDA`]; a log waits until its agenda has arrived.
+const MATERIALIZE_LOG: &str = "INSERT INTO log
+        (id, create_at, content, log_type, agenda_id, deleted_at)
+    SELECT id, create_at, content, log_type, agenda_id, deleted_at FROM (
+        SELECT ?1 AS id,
+            MAX(CASE field WHEN 'create_at' THEN value END) AS create_at,
+            MAX(CASE field WHEN 'content' THEN value END) AS content,
+            MAX(CASE field WHEN 'log_type' THEN value END) AS log_type,
+            MAX(CASE field WHEN 'agenda_id' THEN value END) AS agenda_id,
+            MAX(CASE field WHEN 'deleted_at' THEN value END) AS deleted_at,
+            COUNT(CASE WHEN field IN ('create_at', 'content', 'log_type', 'agenda_id')
+                THEN 1 END) AS known
+        FROM sync_register WHERE entity = 'log' AND entity_id = ?1
+    ) WHERE known = 4 AND (agenda_id IS NULL OR agenda_id IN (SELECT id FROM agenda))
+    ON CONFLICT (id) DO UPDATE SET
+        create_at = excluded.create_at,
+        content = excluded.content,
+        log_type = excluded.log_type,
+        agenda_id = excluded.agenda_id,
+        deleted_at = excluded.deleted_at
+    WHERE log.create_at IS NOT excluded.create_at
+        OR log.content IS NOT excluded.content
+        OR log.log_type IS NOT excluded.log_type
+        OR log.agenda_id IS NOT excluded.agenda_id
+        OR log.deleted_at IS NOT excluded.deleted_at";
+
+/// A field value as stored: sync keeps SQLite's own types.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncValue {
+    Null,
+    Integer(i64),
+    Text(String),
+}
+
+/// One field of one agenda or log set to a value. Concurrent changes to the
+/// same field are ordered by `(at, device, seq)` and the greatest wins.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncChange {
+    pub seq: i64,
+    /// Milliseconds since the epoch on a clock that never runs behind the
+    /// changes this device has seen.
+    pub at: i64,
+    pub entity: String,
+    pub entity_id: String,
+    pub field: String,
+    pub value: SyncValue,
+}
+
+#[derive(FromRow)]
+struct DbSyncChange {
+    seq: i64,
+    at: i64,
+    entity: String,
+    entity_id: String,
+    field: String,
+    integer: Option<i64>,
+    text: Option<String>,
+}
+
+impl DbSyncChange {
+    fn into_change(self) -> SyncChange {
+        let value = match (self.integer, self.text) {
+            (Some(integer), _) => SyncValue::Integer(integer),
+            (None, Some(text)) => SyncValue::Text(text),
+            (None, None) => SyncValue::Null,
+        };
+        SyncChange {
+            seq: self.seq,
+            at: self.at,
+            entity: self.entity,
+            entity_id: self.entity_id,
+            field: self.field,
+            value,
+        }
+    }
+}
+
+fn syncs(change: &SyncChange) -> bool {
+    match change.entity.as_str() {
+        "agenda" => AGENDA_FIELDS.contains(&change.field.as_str()),
+        "log" => LOG_FIELDS.contains(&change.field.as_str()),
+        _ => false,
+    }
+}
+
+/// Local changes waiting to be exported, and the merge of other devices'
+/// changes into this database.
+pub struct SqliteSync {
+    pub pool: SqlitePool,
+}
+
+impl SqliteSync {
+    /// Local changes not exported yet, oldest first.
+    pub async fn pending(&self) -> Result<Vec<SyncChange>, RepoError> {
+        let rows = sqlx::query_as::<_, DbSyncChange>(
+            "SELECT seq, at, entity, entity_id, field,
+                CASE typeof(value) WHEN 'integer' THEN value END AS integer,
+                CASE typeof(value) WHEN 'text' THEN value END AS text
+            FROM sync_change ORDER BY seq",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(DbSyncChange::into_change).collect())
+    }
+
+    /// Forgets the pending changes up to `seq` once they are safely exported.
+    pub async fn exported(&self, seq: i64) -> Result<(), RepoError> {
+        sqlx::query("DELETE FROM sync_change WHERE seq <= ?")
+            .bind(seq)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// How many bytes of `device`'s change file have been merged.
+    pub async fn offset(&self, device: &str) -> Result<u64, RepoError> {
+        let offset: Option<i64> =
+            sqlx::query_scalar("SELECT offset FROM sync_peer WHERE device = ?")
+                .bind(device)
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(offset.map_or(0, |offset| u64::try_from(offset).unwrap_or_default()))
+    }
+
+    /// Merges changes made on `device` into this database, which syncs as
+    /// `me`, and records that its change file has been read up to `offset`.
+    /// Returns how many changes won over what was already known.
+    pub async fn merge(
+        &self,
+        me: &str,
+        device: &str,
+        changes: &[SyncChange],
+        offset: u64,
+    ) -> Result<u64, RepoError> {
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        sqlx::query("INSERT OR IGNORE INTO sync_applying (applying) VALUES (1)")
+            .execute(&mut *tx)
+            .await?;
+
+        let mut won = 0;
+        let mut agendas = BTreeSet::new();
+        let mut logs = BTreeSet::new();
+        for change in changes.iter().filter(|change| syncs(change)) {
+            let query = sqlx::query(
+                "INSERT INTO sync_register (entity, entity_id, field, value, at, device, seq)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                ON CONFLICT (entity, entity_id, field) DO UPDATE SET
+                    value = excluded.value, at = excluded.at,
+                    device = excluded.device, seq = excluded.seq
+                WHERE (excluded.at, excluded.device, excluded.seq)
+                    > (sync_register.at, COALESCE(sync_register.device, ?8), sync_register.seq)",
+            )
+            .bind(&change.entity)
+            .bind(&change.entity_id)
+            .bind(&change.field);
+            let query = match &change.value {
+                SyncValue::Null => query.bind(None::<i64>),
+                SyncValue::Integer(integer) => query.bind(*integer),
+                SyncValue::Text(text) => query.bind(text),
+            };
+            let applied = query
+                .bind(change.at)
+                .bind(device)
+                .bind(change.seq)
+                .bind(me)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+            if applied > 0 {
+                won += 1;
+                match change.entity.as_str() {
+                    "agenda" => agendas.insert(change.entity_id.clone()),
+                    _ => logs.insert(change.entity_id.clone()),
+                };
+            }
+        }
+
+        for id in &agendas {
+            materialize(&mut tx, MATERIALIZE_AGENDA, id).await?;
+        }
+        // Logs that arrived before their agenda get another chance.
+        let waiting: Vec<String> = sqlx::query_scalar(
+            "SELECT DISTINCT entity_id FROM sync_register
+            WHERE entity = 'log' AND entity_id NOT IN (SELECT id FROM log)",
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        logs.extend(waiting);
+        for id in &logs {
+            materialize(&mut tx, MATERIALIZE_LOG, id).await?;
+        }
+
+        sqlx::query(
+            "INSERT INTO sync_peer (device, offset) VALUES (?, ?)
+            ON CONFLICT (device) DO UPDATE SET offset = excluded.offset",
+        )
+        .bind(device)
+        .bind(i64::try_from(offset).unwrap_or(i64::MAX))
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM sync_applying")
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(won)
+    }
+}
+
+async fn materialize(
+    tx: &mut Transaction<'_, Sqlite>,
+    statement: &str,
+    id: &str,
+) -> Result<(), RepoError> {
+    sqlx::query(statement).bind(id).execute(&mut **tx).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::agenda_repo::SqliteAgendaRepo;
+    use crate::repo::log_repo::SqliteLogRepo;
+    use domain::*;
+    use jiff::{Timestamp, ToSpan};
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn setup_pool() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .expect("create in-memory sqlite pool");
+
+        let crate_dir = env!("CARGO_MANIFEST_DIR");
+        let migrations = std::path::Path::new(crate_dir).join("migrations");
+        sqlx::migrate::Migrator::new(migrations)
+            .await
+            .expect("load migrations")
+            .run(&pool)
+            .await
+            .expect("run migrations");
+
+        pool
+    }
+
+    /// Exports everything pending on `from` and merges it into `into`.
+    async fn send(from: &SqliteSync, from_device: &str, into: &SqliteSync, device: &str) -> u64 {
+        let changes = from.pending().await.unwrap();
+        if let Some(last) = changes.last() {
+            from.exported(last.seq).await.unwrap();
+        }
+        into.merge(device, from_device, &changes, 0).await.unwrap()
+    }
+
+    fn retitle(title: &str) -> AgendaUpdate {
+        AgendaUpdate {
+            title: Some(title.to_string()),
+            agenda_status: None,
+            terminate_at: None,
+            actor: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn agendas_and_logs_travel_between_devices() {
+        let (a, b) = (setup_pool().await, setup_pool().await);
+        let (sync_a, sync_b) = (
+            SqliteSync { pool: a.clone() },
+            SqliteSync { pool: b.clone() },
+        );
+        let agenda_id = SqliteAgendaRepo { pool: a.clone() }
+            .create_agenda(&AgendaCreate {
+                title: "Shared".to_string(),
+                agenda_status: AgendaStatus::Pending,
+                terminate_at: Timestamp::now() + 1.hour(),
+            })
+            .await
+            .unwrap();
+        let log_id = SqliteLogRepo { pool: a.clone() }
+            .create_log(&LogCreate {
+                agenda_id,
+                content: "from a".to_string(),
+                log_type: LogType::CommonLog,
+            })
+            .await
+            .unwrap();
+
+        // The log alone cannot be built until its agenda arrives.
+        let changes = sync_a.pending().await.unwrap();
+        let (agenda_part, log_part): (Vec<_>, Vec<_>) = changes
+            .into_iter()
+            .partition(|change| change.entity == "agenda");
+        sync_b.merge("b", "a", &log_part, 0).await.unwrap();
+        let logs_b = SqliteLogRepo { pool: b.clone() };
+        assert!(
+            logs_b
+                .get_logs_by_agenda_id(agenda_id)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        sync_b.merge("b", "a", &agenda_part, 0).await.unwrap();
+        let merged = logs_b.get_logs_by_agenda_id(agenda_id).await.unwrap();
+        assert_eq!(merged[0].id, log_id);
+        assert_eq!(merged[0].content, "from a");
+        let agenda = SqliteAgendaRepo { pool: b.clone() }
+            .get_agenda_by_id(agenda_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(agenda.title, "Shared");
+
+        // Merged changes are not exported again.
+        assert!(sync_b.pending().await.unwrap().is_empty());
+        assert_eq!(sync_b.offset("a").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn concurrent_edits_converge_on_the_same_winner() {
+        let (a, b) = (setup_pool().await, setup_pool().await);
+        let (sync_a, sync_b) = (
+            SqliteSync { pool: a.clone() },
+            SqliteSync { pool: b.clone() },
+        );
+        let (agendas_a, agendas_b) = (
+            SqliteAgendaRepo { pool: a.clone() },
+            SqliteAgendaRepo { pool: b.clone() },
+        );
+        let id = agendas_a
+            .create_agenda(&AgendaCreate {
+                title: "Draft".to_string(),
+                agenda_status: AgendaStatus::Pending,
+                terminate_at: Timestamp::now() + 1.hour(),
+            })
+            .await
+            .unwrap();
+        send(&sync_a, "a", &sync_b, "b").await;
+
+        agendas_a
+            .update_agenda(id, &retitle("From a"))
+            .await
+            .unwrap();
+        agendas_b
+            .update_agenda(id, &retitle("From b"))
+            .await
+            .unwrap();
+        // Pin both edits to the same instant: the device name breaks the tie.
+        for pool in [&a, &b] {
+            sqlx::query(
+                "UPDATE sync_change SET at = 1000000000000000;
+                UPDATE sync_register SET at = 1000000000000000 WHERE field = 'title';",
+            )
+            .execute(pool)
+            .await
+            .unwrap();
+        }
+        let to_b = sync_a.pending().await.unwrap();
+        let to_a = sync_b.pending().await.unwrap();
+        assert_eq!(sync_b.merge("b", "a", &to_b, 0).await.unwrap(), 0);
+        assert_eq!(sync_a.merge("a", "b", &to_a, 0).await.unwrap(), 1);
+
+        let title_a = agendas_a.get_agenda_by_id(id).await.unwrap().unwrap().title;
+        let title_b = agendas_b.get_agenda_by_id(id).await.unwrap().unwrap().title;
+        assert_eq!(title_a, "From b");
+        assert_eq!(title_b, "From b");
+
+        // A later local edit wins over what was merged, whatever the clock.
+        agendas_a
+            .update_agenda(id, &retitle("Final"))
+            .await
+            .unwrap();
+        let pending = sync_a.pending().await.unwrap();
+        assert!(pending.last().unwrap().at > 1000000000000000);
+        assert_eq!(send(&sync_a, "a", &sync_b, "b").await, 1);
+        let title_b = agendas_b.get_agenda_by_id(id).await.unwrap().unwrap().title;
+        assert_eq!(title_b, "Final");
+    }
+}
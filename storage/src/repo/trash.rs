@@ -0,0 +1,255 @@
+use domain::*;
+use jiff::Timestamp;
+use sqlx::{FromRow, SqlitePool};
+use uuid::Uuid;
+
+use crate::repo::agenda_repo::DbAgenda;
+use crate::repo::log_repo::DbLog;
+use crate::repo::repo_error::RepoError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrashedAgenda {
+    pub agenda: Agenda,
+    pub deleted_at: Timestamp,
+}
+
+/// A log trashed on its own; the logs of a trashed agenda go with it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrashedLog {
+    pub log: Log,
+    pub deleted_at: Timestamp,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Restored {
+    Agenda(Agenda),
+    Log(Log),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PurgeSummary {
+    pub agendas: u64,
+    pub logs: u64,
+}
+
+#[derive(FromRow)]
+struct DbTrashed<T> {
+    #[sqlx(flatten)]
+    item: T,
+    deleted_at: i64,
+}
+
+/// Agendas and logs that were deleted but not purged yet.
+pub struct SqliteTrash {
+    pub pool: SqlitePool,
+}
+
+impl SqliteTrash {
+    /// Trashed agendas, most recently deleted first.
+    pub async fn agendas(&self) -> Result<Vec<TrashedAgenda>, RepoError> {
+        let rows = sqlx::query_as::<_, DbTrashed<DbAgenda>>(
+            "SELECT id, title, agenda_status, initiate_at, terminate_at, deleted_at FROM agenda
+            WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(TrashedAgenda {
+                    agenda: row.item.to_agenda()?,
+                    deleted_at: Timestamp::from_millisecond(row.deleted_at)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Logs trashed on their own, most recently deleted first.
+    pub async fn logs(&self) -> Result<Vec<TrashedLog>, RepoError> {
+        let rows = sqlx::query_as::<_, DbTrashed<DbLog>>(
+            "SELECT id, create_at, content, log_type, agenda_id, deleted_at FROM log
+            WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(TrashedLog {
+                    log: row.item.to_log()?,
+                    deleted_at: Timestamp::from_millisecond(row.deleted_at)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Takes the agenda or log with this id out of the trash. A restored
+    /// agenda brings back its logs, except those trashed on their own.
+    pub async fn restore(&self, id: Uuid) -> Result<Option<Restored>, RepoError> {
+        let agenda = sqlx::query_as::<_, DbAgenda>(
+            "UPDATE agenda SET deleted_at = NULL WHERE id = ? AND deleted_at IS NOT NULL
+            RETURNING id, title, agenda_status, initiate_at, terminate_at",
+        )
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+        if let Some(agenda) = agenda {
+            return Ok(Some(Restored::Agenda(agenda.to_agenda()?)));
+        }
+        let log = sqlx::query_as::<_, DbLog>(
+            "UPDATE log SET deleted_at = NULL WHERE id = ? AND deleted_at IS NOT NULL
+            RETURNING id, create_at, content, log_type, agenda_id",
+        )
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+        log.map(|log| Ok(Restored::Log(log.to_log()?))).transpose()
+    }
+
+    /// Deletes for good everything trashed before `before`, or all of the
+    /// trash when no cutoff is given.
+    pub async fn purge(&self, before: Option<Timestamp>) -> Result<PurgeSummary, RepoError> {
+        let cutoff = before.map_or(i64::MAX, |at| at.as_millisecond());
+        let mut tx = self.pool.begin_with("BEGIN IMMEDIATE").await?;
+        // Children first, so no foreign key action rewrites them.
+        sqlx::query(
+            "DELETE FROM agenda_revision WHERE agenda_id IN
+                (SELECT id FROM agenda WHERE deleted_at < ?)",
+        )
+        .bind(cutoff)
+        .execute(&mut *tx)
+        .await?;
+        let logs = sqlx::query(
+            "DELETE FROM log WHERE deleted_at < ?1
+                OR agenda_id IN (SELECT id FROM agenda WHERE deleted_at < ?1)",
+        )
+        .bind(cutoff)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        let agendas = sqlx::query("DELETE FROM agenda WHERE deleted_at < ?")
+            .bind(cutoff)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        tx.commit().await?;
+        Ok(PurgeSummary { agendas, logs })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::agenda_repo::SqliteAgendaRepo;
+    use crate::repo::log_repo::SqliteLogRepo;
+    use jiff::ToSpan;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn setup_pool() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .expect("create in-memory sqlite pool");
+
+        let crate_dir = env!("CARGO_MANIFEST_DIR");
+        let migrations = std::path::Path::new(crate_dir).join("migrations");
+        sqlx::migrate::Migrator::new(migrations)
+            .await
+            .expect("load migrations")
+            .run(&pool)
+            .await
+            .expect("run migrations");
+
+        pool
+    }
+
+    async fn agenda_with_log(pool: &SqlitePool, title: &str) -> (Uuid, Uuid) {
+        let agenda_id = SqliteAgendaRepo { pool: pool.clone() }
+            .create_agenda(&AgendaCreate {
+                title: title.to_string(),
+                agenda_status: AgendaStatus::Pending,
+                terminate_at: Timestamp::now() + 1.hour(),
+            })
+            .await
+            .expect("create agenda");
+        let log_id = SqliteLogRepo { pool: pool.clone() }
+            .create_log(&LogCreate {
+                agenda_id,
+                content: "note".to_string(),
+                log_type: LogType::CommonLog,
+            })
+            .await
+            .expect("create log");
+        (agenda_id, log_id)
+    }
+
+    #[tokio::test]
+    async fn trashed_agendas_hide_their_logs_until_restored() {
+        let pool = setup_pool().await;
+        let agendas = SqliteAgendaRepo { pool: pool.clone() };
+        let logs = SqliteLogRepo { pool: pool.clone() };
+        let trash = SqliteTrash { pool: pool.clone() };
+        let (agenda_id, log_id) = agenda_with_log(&pool, "Oops").await;
+
+        agendas.delete_agenda_by_id(agenda_id).await.unwrap();
+        assert!(
+            logs.get_logs_by_agenda_id(agenda_id)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        let trashed = trash.agendas().await.unwrap();
+        assert_eq!(trashed.len(), 1);
+        assert_eq!(trashed[0].agenda.title, "Oops");
+        assert!(trash.logs().await.unwrap().is_empty());
+
+        let restored = trash.restore(agenda_id).await.unwrap();
+        assert!(matches!(restored, Some(Restored::Agenda(agenda)) if agenda.id == agenda_id));
+        assert!(agendas.get_agenda_by_id(agenda_id).await.unwrap().is_some());
+        assert_eq!(
+            logs.get_logs_by_agenda_id(agenda_id).await.unwrap()[0].id,
+            log_id
+        );
+
+        logs.delete_log(log_id).await.unwrap();
+        assert_eq!(trash.logs().await.unwrap()[0].log.id, log_id);
+        assert!(matches!(
+            trash.restore(log_id).await.unwrap(),
+            Some(Restored::Log(_))
+        ));
+        assert!(trash.restore(log_id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn purge_deletes_trashed_rows_for_good() {
+        let pool = setup_pool().await;
+        let agendas = SqliteAgendaRepo { pool: pool.clone() };
+        let logs = SqliteLogRepo { pool: pool.clone() };
+        let trash = SqliteTrash { pool: pool.clone() };
+        let (gone, _) = agenda_with_log(&pool, "Gone").await;
+        let (kept, kept_log) = agenda_with_log(&pool, "Kept").await;
+        agendas.delete_agenda_by_id(gone).await.unwrap();
+        logs.delete_log(kept_log).await.unwrap();
+
+        let none = trash
+            .purge(Some(Timestamp::now() - 1.hour()))
+            .await
+            .unwrap();
+        assert_eq!(none, PurgeSummary::default());
+
+        let summary = trash.purge(None).await.unwrap();
+        assert_eq!(
+            summary,
+            PurgeSummary {
+                agendas: 1,
+                logs: 2
+            }
+        );
+        let rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM agenda")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(rows, 1);
+        assert!(agendas.get_agenda_by_id(kept).await.unwrap().is_some());
+        assert!(trash.agendas().await.unwrap().is_empty());
+    }
+}